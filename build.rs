@@ -0,0 +1,26 @@
+fn main() {
+    #[cfg(feature = "capi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("set by cargo");
+
+        let config = cbindgen::Config {
+            language: cbindgen::Language::C,
+            include_guard: Some("RT_ONE_H".into()),
+            enumeration: cbindgen::EnumConfig {
+                prefix_with_name: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{crate_dir}/src/capi.rs"))
+            .generate()
+            .expect("generating C header")
+            .write_to_file(format!("{crate_dir}/include/rt_one.h"));
+
+        println!("cargo:rerun-if-changed=build.rs");
+        println!("cargo:rerun-if-changed=src/capi.rs");
+    }
+}
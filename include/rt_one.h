@@ -0,0 +1,132 @@
+#ifndef RT_ONE_H
+#define RT_ONE_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of fallible calls.
+ */
+typedef enum RtStatus {
+  RtStatus_Ok = 0,
+  RtStatus_NullPointer = 1,
+  RtStatus_BufferTooSmall = 2,
+} RtStatus;
+
+/**
+ * Opaque handle to a material. Objects keep their own reference,
+ * so materials may be freed right after use.
+ */
+typedef struct RtMaterial RtMaterial;
+
+/**
+ * Opaque handle to a camera and the world it looks at.
+ */
+typedef struct RtScene RtScene;
+
+/**
+ * Called with each tile as it finishes: where it is in the image and its size in pixels,
+ * then `width * height` linear RGBA pixels as floats, row-major within the tile.
+ * The pixels are only valid during the call.
+ */
+typedef void (*RtTileCallback)(void *user_data,
+                               uint32_t x,
+                               uint32_t y,
+                               uint32_t width,
+                               uint32_t height,
+                               const float *pixels);
+
+/**
+ * Create an empty scene. Free with [`rt_scene_free`].
+ */
+struct RtScene *rt_scene_new(uint32_t width,
+                             uint32_t height,
+                             uint32_t samples_per_pixel,
+                             uint32_t max_bounces);
+
+/**
+ * # Safety
+ *
+ * `scene` must come from [`rt_scene_new`] and not be used afterwards. Null is ignored.
+ */
+void rt_scene_free(struct RtScene *scene);
+
+/**
+ * Place the camera at the given origin, looking down -Z.
+ * The viewport is `viewport_height` tall at `focal_length` in front of the camera.
+ *
+ * # Safety
+ *
+ * `scene` must be null or a live pointer from [`rt_scene_new`].
+ */
+enum RtStatus rt_scene_set_camera(struct RtScene *scene,
+                                  float x,
+                                  float y,
+                                  float z,
+                                  float focal_length,
+                                  float viewport_height);
+
+/**
+ * # Safety
+ *
+ * `scene` must be null or a live pointer from [`rt_scene_new`],
+ * and `material` null or a live pointer from one of the `rt_material_*` functions.
+ */
+enum RtStatus rt_scene_add_sphere(struct RtScene *scene,
+                                  float x,
+                                  float y,
+                                  float z,
+                                  float radius,
+                                  const struct RtMaterial *material);
+
+/**
+ * Render into a caller-provided buffer of `width * height * 4` bytes,
+ * as row-major 8-bit sRGB RGBA starting at the top left.
+ *
+ * # Safety
+ *
+ * `scene` must be null or a live pointer from [`rt_scene_new`],
+ * and `buffer` must be null or valid for writes of `buffer_len` bytes.
+ */
+enum RtStatus rt_scene_render(const struct RtScene *scene, uint8_t *buffer, uintptr_t buffer_len);
+
+/**
+ * Render tile by tile, handing each tile to `callback` as soon as it's done.
+ * `user_data` is passed through untouched.
+ *
+ * # Safety
+ *
+ * `scene` must be null or a live pointer from [`rt_scene_new`],
+ * and `callback` must be safe to call with `user_data`.
+ */
+enum RtStatus rt_scene_render_tiles(const struct RtScene *scene,
+                                    uint32_t tile_size,
+                                    RtTileCallback callback,
+                                    void *user_data);
+
+/**
+ * A diffuse material with linear RGB albedo. Free with [`rt_material_free`].
+ */
+struct RtMaterial *rt_material_lambertian(float red, float green, float blue);
+
+/**
+ * A metal with linear RGB albedo and fuzz in [0, 1]. Free with [`rt_material_free`].
+ */
+struct RtMaterial *rt_material_metal(float red, float green, float blue, float fuzz);
+
+/**
+ * Clear glass-like material. Free with [`rt_material_free`].
+ */
+struct RtMaterial *rt_material_dielectric(float refractive_index);
+
+/**
+ * # Safety
+ *
+ * `material` must come from one of the `rt_material_*` functions and not be used afterwards.
+ * Null is ignored.
+ */
+void rt_material_free(struct RtMaterial *material);
+
+#endif  /* RT_ONE_H */
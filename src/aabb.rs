@@ -0,0 +1,84 @@
+use std::ops::Range;
+
+use bevy_math::Vec3;
+
+use crate::ray::Ray;
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl Aabb {
+    /// Contains nothing, and is the identity for [`Aabb::union`].
+    pub const EMPTY: Self = Self {
+        min: Vec3::INFINITY,
+        max: Vec3::NEG_INFINITY,
+    };
+
+    /// The box spanned by two corners, in any order.
+    pub fn new(a: Vec3, b: Vec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// The smallest box containing all the given points.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points
+            .into_iter()
+            .fold(Self::EMPTY, |aabb, point| aabb.union(&Self::new(point, point)))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// Total area of the six faces.
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let size = self.size();
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// The part of `t_range` where the ray is inside the box, or `None` if it misses.
+    pub fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Range<f32>> {
+        let origin = ray.origin();
+        let inverse = ray.direction().as_vec3().recip();
+
+        let t0 = (self.min - origin) * inverse;
+        let t1 = (self.max - origin) * inverse;
+
+        let near = t0.min(t1).max_element().max(t_range.start);
+        let far = t0.max(t1).min_element().min(t_range.end);
+
+        (near <= far).then_some(near..far)
+    }
+}
@@ -0,0 +1,540 @@
+//! Arbitrary output variables: extra images next to the beauty render,
+//! such as normals or depth, for compositing and denoising.
+
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::bail;
+use bevy_color::{ColorToComponents, Hsla, LinearRgba};
+use bevy_math::Vec3;
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec,
+    WritableImage,
+};
+use rayon::prelude::*;
+
+use crate::{camera::Camera, hittable::Hittable};
+
+/// An extra pass which can be rendered alongside the beauty image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    /// Shading normal in world space, facing the camera. Zero where nothing was hit.
+    Normal,
+    /// Distance along the camera ray, infinite where nothing was hit.
+    Depth,
+    /// Surface color without lighting, of the material override if there is one.
+    /// Zero where nothing was hit.
+    Albedo,
+    /// Which object was hit, as in [`crate::hittable::Hit::object_id`]. 0 is the background.
+    ObjectId,
+    /// Which material was hit, as in [`crate::material::DynMaterial::id`]. 0 is the background.
+    /// The material override is ignored.
+    MaterialId,
+    /// A flat color per object, to see at a glance which object made which pixels.
+    ObjectColor,
+    /// A flat color per material, see [`Aov::MaterialId`].
+    MaterialColor,
+    /// How much of each pixel the objects in it cover, like a Cryptomatte.
+    /// Up to [`MATTE_RANKS`] objects per pixel, as pairs of object ID and coverage,
+    /// most covering first. Unlike `ObjectId` this is anti-aliased, so objects can be
+    /// cut out cleanly along their edges.
+    Matte,
+}
+
+/// How many objects per pixel a [`Aov::Matte`] keeps.
+pub const MATTE_RANKS: usize = 4;
+/// At most this many of the camera's samples per pixel are averaged into the AOVs.
+/// They converge much faster than the beauty image.
+const AOV_SAMPLES: usize = 64;
+
+impl Aov {
+    /// The layer name, and the channel names within it.
+    fn channels(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            Aov::Normal => ("normal", &["X", "Y", "Z"]),
+            Aov::Depth => ("depth", &["Z"]),
+            Aov::Albedo => ("albedo", &["R", "G", "B"]),
+            Aov::ObjectId => ("id", &["R"]),
+            Aov::MaterialId => ("material_id", &["R"]),
+            Aov::ObjectColor => ("id_color", &["R", "G", "B"]),
+            Aov::MaterialColor => ("material_color", &["R", "G", "B"]),
+            Aov::Matte => (
+                "matte",
+                &[
+                    "id0",
+                    "coverage0",
+                    "id1",
+                    "coverage1",
+                    "id2",
+                    "coverage2",
+                    "id3",
+                    "coverage3",
+                ],
+            ),
+        }
+    }
+}
+
+impl FromStr for Aov {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "normal" => Ok(Aov::Normal),
+            "depth" => Ok(Aov::Depth),
+            "albedo" => Ok(Aov::Albedo),
+            "id" => Ok(Aov::ObjectId),
+            "material-id" => Ok(Aov::MaterialId),
+            "id-color" => Ok(Aov::ObjectColor),
+            "material-color" => Ok(Aov::MaterialColor),
+            "matte" => Ok(Aov::Matte),
+            other => bail!(
+                "unknown AOV {other:?}, expected normal, depth, albedo, id, material-id, \
+                 id-color, material-color or matte"
+            ),
+        }
+    }
+}
+
+/// A named group of channels, e.g. "normal" with X, Y and Z.
+#[derive(Debug)]
+pub struct AovLayer {
+    /// Empty for the beauty image.
+    pub name: &'static str,
+    pub channels: Vec<(&'static str, Vec<f32>)>,
+}
+
+impl AovLayer {
+    /// The beauty image as R, G, B and A channels.
+    pub fn beauty(image: &[LinearRgba]) -> Self {
+        Self {
+            name: "",
+            channels: ["R", "G", "B", "A"]
+                .into_iter()
+                .enumerate()
+                .map(|(index, name)| {
+                    (
+                        name,
+                        image
+                            .iter()
+                            .map(|color| color.to_f32_array()[index])
+                            .collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// The channels of each pixel one after the other, e.g. `[x0, y0, z0, x1, ...]`.
+    pub fn interleaved(&self) -> Vec<f32> {
+        let pixels = self.channels.first().map_or(0, |(_, values)| values.len());
+        (0..pixels)
+            .flat_map(|pixel| self.channels.iter().map(move |(_, values)| values[pixel]))
+            .collect()
+    }
+}
+
+/// The beauty image and any AOVs, all in linear floats.
+#[derive(Debug)]
+pub struct AovImage {
+    pub width: usize,
+    pub height: usize,
+    pub layers: Vec<AovLayer>,
+}
+
+impl AovImage {
+    /// Write every layer into a single EXR file, with channels named like `normal.X`.
+    /// The beauty image gets the plain R, G, B and A channels compositing tools look for first.
+    ///
+    /// A path of `-` writes to stdout.
+    pub fn write_exr(&self, pathlike: impl AsRef<Path>) -> anyhow::Result<()> {
+        let channels = self
+            .layers
+            .iter()
+            .flat_map(|layer| {
+                layer.channels.iter().map(|(channel, samples)| {
+                    let name = if layer.name.is_empty() {
+                        channel.to_string()
+                    } else {
+                        format!("{}.{channel}", layer.name)
+                    };
+                    AnyChannel::new(name.as_str(), FlatSamples::F32(samples.clone()))
+                })
+            })
+            .collect();
+
+        self.write_channels(channels, pathlike.as_ref())
+    }
+
+    /// Write each layer to its own EXR file with plain channel names, as denoisers want them.
+    /// The beauty image goes to the path, the others next to it, e.g. `out_normal.exr`.
+    pub fn write_separate_exrs(&self, pathlike: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = pathlike.as_ref();
+
+        for layer in &self.layers {
+            let channels = layer
+                .channels
+                .iter()
+                .map(|(channel, samples)| {
+                    AnyChannel::new(*channel, FlatSamples::F32(samples.clone()))
+                })
+                .collect();
+            let path = if layer.name.is_empty() {
+                path.to_path_buf()
+            } else {
+                layer_path(path, layer.name)
+            };
+            self.write_channels(channels, &path)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_channels(
+        &self,
+        channels: SmallVec<[AnyChannel<FlatSamples>; 4]>,
+        path: &Path,
+    ) -> anyhow::Result<()> {
+        let layer = Layer::new(
+            (self.width, self.height),
+            LayerAttributes::default(),
+            Encoding::SMALL_LOSSLESS,
+            AnyChannels::sort(channels),
+        );
+
+        // The encoder needs to seek, which stdout can't
+        let mut bytes = Cursor::new(vec![]);
+        Image::from_layer(layer).write().to_buffered(&mut bytes)?;
+
+        if path == Path::new("-") {
+            std::io::Write::write_all(&mut std::io::stdout().lock(), bytes.get_ref())?;
+        } else {
+            std::fs::write(path, bytes.get_ref())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A flat color telling the object or material `id` apart from its neighbors, black for 0.
+/// Consecutive ids are spread around the color wheel.
+pub fn id_color(id: usize) -> LinearRgba {
+    if id == 0 {
+        return LinearRgba::BLACK;
+    }
+
+    // The golden angle, so nearby ids never get similar hues
+    let hue = (id as f32 * 137.507_77) % 360.0;
+    let lightness = if id.is_multiple_of(2) { 0.45 } else { 0.6 };
+    Hsla::hsl(hue, 0.75, lightness).into()
+}
+
+/// Where a layer named `name` of the image at `path` goes when written on its own,
+/// e.g. `out_normal.exr` for `out.exr`.
+pub fn layer_path(path: &Path, name: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}_{name}.exr"))
+}
+
+/// Render the beauty image along with the given AOVs.
+pub fn render(camera: &Camera, world: &dyn Hittable, aovs: &[Aov]) -> AovImage {
+    let beauty = camera.render_linear(world);
+
+    let mut layers = vec![AovLayer::beauty(&beauty)];
+    layers.extend(render_aovs(camera, world, aovs));
+
+    AovImage {
+        width: camera.im_width,
+        height: camera.im_height,
+        layers,
+    }
+}
+
+/// Render only the given AOVs, one layer each.
+///
+/// They follow the same camera rays as the beauty image, averaged over up to [`AOV_SAMPLES`]
+/// of them, so their edges are anti-aliased and blurred by depth of field the same way.
+/// Only [`Aov::ObjectId`] looks through the center of each pixel, as IDs can't be averaged.
+pub fn render_aovs(camera: &Camera, world: &dyn Hittable, aovs: &[Aov]) -> Vec<AovLayer> {
+    let pixels: Vec<PixelAovs> = (0..camera.im_height)
+        .into_par_iter()
+        .flat_map_iter(|row| (0..camera.im_width).map(move |col| (row, col)))
+        .map(|(row, col)| PixelAovs::sample(camera, world, row, col))
+        .collect();
+
+    aovs.iter()
+        .map(|&aov| {
+            let (name, channel_names) = aov.channels();
+            let mut channels: Vec<Vec<f32>> =
+                vec![Vec::with_capacity(pixels.len()); channel_names.len()];
+
+            for pixel in &pixels {
+                let values: Vec<f32> = match aov {
+                    Aov::Normal => pixel.normal.to_array().to_vec(),
+                    Aov::Depth => vec![pixel.depth],
+                    Aov::Albedo => pixel.albedo.to_array().to_vec(),
+                    Aov::ObjectId => vec![pixel.object_id as f32],
+                    Aov::MaterialId => vec![pixel.material_id as f32],
+                    Aov::ObjectColor => id_color(pixel.object_id).to_f32_array_no_alpha().to_vec(),
+                    Aov::MaterialColor => {
+                        id_color(pixel.material_id).to_f32_array_no_alpha().to_vec()
+                    }
+                    Aov::Matte => {
+                        let mut coverage = pixel.coverage.clone();
+                        coverage.resize(MATTE_RANKS, (0.0, 0.0));
+                        coverage
+                            .into_iter()
+                            .flat_map(|(id, weight)| [id, weight])
+                            .collect()
+                    }
+                };
+
+                for (channel, value) in channels.iter_mut().zip(values) {
+                    channel.push(value);
+                }
+            }
+
+            AovLayer {
+                name,
+                channels: channel_names.iter().copied().zip(channels).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Everything the AOVs need to know about one pixel.
+struct PixelAovs {
+    normal: Vec3,
+    /// Averaged over the samples which hit something.
+    depth: f32,
+    albedo: Vec3,
+    /// Seen through the center of the pixel.
+    object_id: usize,
+    material_id: usize,
+    /// The object IDs (as in [`Aov::ObjectId`]) seen by the samples,
+    /// with the fraction of samples seeing each. Most covering first, the background is left out.
+    coverage: Vec<(f32, f32)>,
+}
+
+impl PixelAovs {
+    fn sample(camera: &Camera, world: &dyn Hittable, row: usize, col: usize) -> Self {
+        let samples = camera.samples_per_pixel.clamp(1, AOV_SAMPLES);
+        let min_dist = camera.ray_bias.t_min(camera.min_dist, 0.0);
+
+        let mut normal = Vec3::ZERO;
+        let mut albedo = Vec3::ZERO;
+        let mut depth = 0.0;
+        let mut counts: Vec<(usize, usize)> = vec![];
+        for sample in 0..samples {
+            let Some(ray) = camera.sample_ray(row, col, sample) else {
+                continue;
+            };
+            let Some(hit) = world.hit(&ray, min_dist..10_000_000.0) else {
+                continue;
+            };
+            let index = hit.object_id as usize;
+
+            let material = camera.material_override.as_ref().unwrap_or(&hit.material);
+            normal += Vec3::from(hit.normal);
+            albedo += LinearRgba::from(material.albedo(&hit)).to_vec3();
+            depth += hit.distance;
+
+            match counts.iter_mut().find(|(seen, _)| *seen == index) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((index, 1)),
+            }
+        }
+
+        let hits: usize = counts.iter().map(|(_, count)| count).sum();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let center = camera.inspect(world, row, col);
+
+        Self {
+            normal: normal / samples as f32,
+            depth: if hits == 0 {
+                f32::INFINITY
+            } else {
+                depth / hits as f32
+            },
+            albedo: albedo / samples as f32,
+            object_id: center.as_ref().map_or(0, |hit| hit.object_id as usize),
+            material_id: center.map_or(0, |hit| hit.material.id()),
+            coverage: counts
+                .into_iter()
+                .map(|(id, count)| (id as f32, count as f32 / samples as f32))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Hittables, material::Lambertian, objects::Sphere};
+
+    #[test]
+    fn layers_in_one_exr() -> anyhow::Result<()> {
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: Vec3::new(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material: Lambertian::linear_rgb(0.2, 0.4, 0.6).into(),
+        });
+
+        let camera = Camera::builder().resolution(9, 7).build().unwrap();
+
+        let image = render(&camera, &world, &[Aov::Depth, Aov::Albedo, Aov::ObjectId]);
+
+        let center = 3 * 9 + 4;
+        let depth = &image.layers[1];
+        assert_eq!(depth.name, "depth");
+        // The single sample is jittered within the pixel, off the sphere's closest point
+        assert!((depth.channels[0].1[center] - 1.5).abs() < 0.05);
+        assert_eq!(depth.channels[0].1[0], f32::INFINITY);
+        assert_eq!(image.layers[2].channels[2].1[center], 0.6);
+        assert_eq!(image.layers[3].channels[0].1[center], 1.0);
+
+        let path = std::env::temp_dir().join("rt_one_aovs.exr");
+        image.write_exr(&path)?;
+
+        let read = exr::prelude::read_all_flat_layers_from_file(&path)?;
+        let names: Vec<String> = read.layer_data[0]
+            .channel_data
+            .list
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect();
+        assert_eq!(
+            names,
+            ["A", "B", "G", "R", "albedo.B", "albedo.G", "albedo.R", "depth.Z", "id.R"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn matte_coverage_along_edges() {
+        let mut world = Hittables::default();
+        let gray = || Lambertian::linear_rgb(0.5, 0.5, 0.5);
+        // Two spheres side by side, touching in the middle column
+        world.add(Sphere {
+            center: Vec3::new(-1.0, 0.0, -4.0),
+            radius: 1.0,
+            material: gray().into(),
+        });
+        world.add(Sphere {
+            center: Vec3::new(1.0, 0.0, -4.0),
+            radius: 1.0,
+            material: gray().into(),
+        });
+
+        let camera = Camera::builder()
+            .resolution(9, 9)
+            .samples(32)
+            .build()
+            .unwrap();
+
+        let image = render(&camera, &world, &[Aov::Matte]);
+        let matte = &image.layers[1].channels;
+        let at = |channel: usize, row: usize, col: usize| matte[channel].1[row * 9 + col];
+
+        // Fully inside the left sphere
+        assert_eq!((at(0, 4, 3), at(1, 4, 3)), (1.0, 1.0));
+        assert_eq!(at(3, 4, 3), 0.0);
+
+        // Shared between both, and each pixel's coverage adds up to at most 1
+        let (first, second) = (at(1, 4, 4), at(3, 4, 4));
+        assert!(first > 0.0 && second > 0.0, "{first} {second}");
+        assert!(first + second <= 1.0);
+        assert_eq!(at(0, 4, 4) + at(2, 4, 4), 3.0);
+    }
+
+    #[test]
+    fn object_and_material_ids() {
+        let mut world = Hittables::default();
+        let shared: crate::material::DynMaterial = Lambertian::linear_rgb(0.5, 0.5, 0.5).into();
+        for (x, material) in [
+            (-4.0, shared.clone()),
+            (0.0, shared),
+            (4.0, Lambertian::linear_rgb(0.1, 0.2, 0.3).into()),
+        ] {
+            // Columns are two units apart at this distance, so these land on columns 2, 4 and 6
+            world.add(Sphere {
+                center: Vec3::new(x, 0.0, -3.0),
+                radius: 0.9,
+                material,
+            });
+        }
+
+        let camera = Camera::builder().resolution(9, 3).build().unwrap();
+        let image = render(
+            &camera,
+            &world,
+            &[Aov::ObjectId, Aov::MaterialId, Aov::ObjectColor],
+        );
+        let at = |layer: usize, col: usize| image.layers[layer].channels[0].1[9 + col];
+
+        let columns = [2, 4, 6];
+        let objects = columns.map(|col| at(1, col));
+        let materials = columns.map(|col| at(2, col));
+        assert_eq!(objects, [1.0, 2.0, 3.0]);
+        assert_eq!(materials[0], materials[1]);
+        assert!(materials[1] != materials[2] && materials[2] > 0.0);
+        assert_eq!((at(1, 0), at(2, 0), at(3, 0)), (0.0, 0.0, 0.0));
+
+        let colors: Vec<Vec3> = (1..=3).map(|id| id_color(id).to_vec3()).collect();
+        assert!(colors[0] != colors[1] && colors[1] != colors[2]);
+
+        // Accelerators number objects the same way
+        let bvh = crate::bvh::Bvh::new(&world, Default::default());
+        let through_bvh = render_aovs(&camera, &bvh, &[Aov::ObjectId]);
+        assert_eq!(through_bvh[0].channels, image.layers[1].channels);
+    }
+
+    #[test]
+    fn anti_aliased_passes_in_separate_files() -> anyhow::Result<()> {
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: Vec3::new(0.0, 0.0, -4.0),
+            radius: 1.0,
+            material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+        });
+
+        let camera = Camera::builder()
+            .resolution(9, 9)
+            .samples(32)
+            .build()
+            .unwrap();
+
+        let image = render(&camera, &world, &[Aov::Normal, Aov::Albedo]);
+        let albedo = &image.layers[2].channels[0].1;
+
+        // Inside, outside, and partly covering the sphere's silhouette
+        assert_eq!(albedo[4 * 9 + 4], 0.5);
+        assert_eq!(albedo[0], 0.0);
+        let row: Vec<f32> = albedo[4 * 9..5 * 9].to_vec();
+        assert!(
+            row.iter().any(|&value| value > 0.0 && value < 0.5),
+            "{row:?}"
+        );
+
+        let path = std::env::temp_dir().join("rt_one_aov_files.exr");
+        image.write_separate_exrs(&path)?;
+
+        let read = exr::prelude::read_all_flat_layers_from_file(layer_path(&path, "normal"))?;
+        let names: Vec<String> = read.layer_data[0]
+            .channel_data
+            .list
+            .iter()
+            .map(|channel| channel.name.to_string())
+            .collect();
+        assert_eq!(names, ["X", "Y", "Z"]);
+        assert!(path.exists());
+
+        Ok(())
+    }
+}
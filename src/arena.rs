@@ -0,0 +1,221 @@
+//! Contiguous storage for scenes with very many objects.
+
+use std::{ops::Range, sync::OnceLock};
+
+use bevy_math::Vec3;
+
+use crate::{
+    aabb::Aabb,
+    bvh::{BvhSplit, BvhTree},
+    hittable::{Hit, Hittable},
+    material::DynMaterial,
+    objects::{Cuboid, Sphere, Triangle},
+    ray::Ray,
+    stats::SceneStats,
+};
+
+/// Refers to a material in an [`Arena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialId(u32);
+
+/// Refers to an object in an [`Arena`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectId {
+    Sphere(u32),
+    Triangle(u32),
+    Cuboid(u32),
+}
+
+/// Objects stored by type in contiguous vectors, and materials shared by index.
+///
+/// Unlike [`crate::hittable::Hittables`] there is no allocation per object,
+/// so scenes with hundreds of thousands of them stay compact.
+/// Rays are traced through a BVH over the arena, built on first use after objects were added.
+#[derive(Debug, Default)]
+pub struct Arena {
+    materials: Vec<DynMaterial>,
+    spheres: Vec<Sphere>,
+    triangles: Vec<Triangle>,
+    cuboids: Vec<Cuboid>,
+    tree: OnceLock<BvhTree>,
+}
+
+fn index_u32(index: usize) -> u32 {
+    u32::try_from(index).expect("arenas hold at most u32::MAX of each kind")
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_material(&mut self, material: impl Into<DynMaterial>) -> MaterialId {
+        self.materials.push(material.into());
+        MaterialId(index_u32(self.materials.len() - 1))
+    }
+
+    pub fn material(&self, id: MaterialId) -> &DynMaterial {
+        &self.materials[id.0 as usize]
+    }
+
+    pub fn add_sphere(&mut self, center: Vec3, radius: f32, material: MaterialId) -> ObjectId {
+        self.tree.take();
+        self.spheres.push(Sphere {
+            center,
+            radius,
+            material: self.material(material).clone(),
+        });
+        ObjectId::Sphere(index_u32(self.spheres.len() - 1))
+    }
+
+    pub fn add_triangle(&mut self, a: Vec3, b: Vec3, c: Vec3, material: MaterialId) -> ObjectId {
+        self.tree.take();
+        self.triangles
+            .push(Triangle::new(a, b, c, self.material(material).clone()));
+        ObjectId::Triangle(index_u32(self.triangles.len() - 1))
+    }
+
+    /// See [`Cuboid::new`].
+    pub fn add_cuboid(&mut self, a: Vec3, b: Vec3, material: MaterialId) -> ObjectId {
+        self.tree.take();
+        self.cuboids
+            .push(Cuboid::new(a, b, self.material(material).clone()));
+        ObjectId::Cuboid(index_u32(self.cuboids.len() - 1))
+    }
+
+    pub fn get(&self, id: ObjectId) -> &dyn Hittable {
+        match id {
+            ObjectId::Sphere(index) => &self.spheres[index as usize],
+            ObjectId::Triangle(index) => &self.triangles[index as usize],
+            ObjectId::Cuboid(index) => &self.cuboids[index as usize],
+        }
+    }
+
+    /// Number of objects of all kinds.
+    pub fn len(&self) -> usize {
+        self.spheres.len() + self.triangles.len() + self.cuboids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Objects of all kinds numbered one after the other, spheres first.
+    fn object(&self, index: usize) -> &dyn Hittable {
+        let (spheres, triangles) = (self.spheres.len(), self.triangles.len());
+
+        if index < spheres {
+            &self.spheres[index]
+        } else if index < spheres + triangles {
+            &self.triangles[index - spheres]
+        } else {
+            &self.cuboids[index - spheres - triangles]
+        }
+    }
+
+    fn tree(&self) -> &BvhTree {
+        self.tree.get_or_init(|| {
+            let bounds: Vec<Aabb> = (0..self.len())
+                .map(|index| self.object(index).bounding_box())
+                .collect();
+            BvhTree::new(&bounds, BvhSplit::Sah)
+        })
+    }
+}
+
+impl Hittable for Arena {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        self.tree().hit(ray, t_range, |index, range| {
+            self.object(index).hit(ray, range)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree().bounds()
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        for index in 0..self.len() {
+            self.object(index).validate(problems);
+        }
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        // Objects count their own size below, this is what the arena adds
+        stats.memory_bytes += std::mem::size_of_val(self)
+            + self.materials.capacity() * std::mem::size_of::<DynMaterial>()
+            + self.tree.get().map_or(0, BvhTree::memory_bytes);
+
+        for index in 0..self.len() {
+            self.object(index).stats(stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::Color;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        hittable::Hittables,
+        material::{Lambertian, Metal},
+    };
+
+    #[test]
+    fn same_hits_as_list() {
+        let mut arena = Arena::new();
+        let mut list = Hittables::default();
+
+        let diffuse = arena.add_material(Lambertian::linear_rgb(0.5, 0.5, 0.5));
+        let metal = arena.add_material(Metal::new(Color::WHITE, 0.1));
+
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut point = || {
+            Vec3::new(
+                rng.gen_range(-5.0..5.0),
+                rng.gen_range(-5.0..5.0),
+                rng.gen_range(-5.0..5.0),
+            )
+        };
+
+        for _ in 0..300 {
+            let (center, a, b, c) = (point(), point(), point(), point());
+
+            arena.add_sphere(center, 0.3, diffuse);
+            list.add(Sphere {
+                center,
+                radius: 0.3,
+                material: arena.material(diffuse).clone(),
+            });
+
+            arena.add_triangle(a, a + 0.2 * b, a + 0.2 * c, metal);
+            list.add(Triangle::new(
+                a,
+                a + 0.2 * b,
+                a + 0.2 * c,
+                arena.material(metal).clone(),
+            ));
+        }
+        let cuboid = arena.add_cuboid(Vec3::splat(-0.5), Vec3::splat(0.5), metal);
+        list.add(Cuboid::new(
+            Vec3::splat(-0.5),
+            Vec3::splat(0.5),
+            arena.material(metal).clone(),
+        ));
+
+        assert_eq!(arena.len(), 601);
+        assert_eq!(
+            arena.get(cuboid).bounding_box(),
+            Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5))
+        );
+
+        for _ in 0..1000 {
+            let ray = Ray::new(point(), point());
+            let expected = list.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+            let actual = arena.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+            assert_eq!(expected, actual, "{ray:?}");
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Render on a background thread and receive tiles as they finish,
+//! either by blocking on an iterator or by awaiting a [`Stream`].
+
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use bevy_color::LinearRgba;
+use futures_core::Stream;
+
+use crate::{
+    camera::Camera,
+    cancel::{CancellationToken, RenderStatus},
+    hittable::Hittable,
+    render::TileScheduler,
+    tile::Tile,
+};
+
+/// Something which happened during a render started with [`render_async`].
+#[derive(Debug)]
+pub enum RenderEvent {
+    /// A tile finished. `done` of `total` tiles are finished so far.
+    Tile {
+        tile: Tile,
+        pixels: Vec<LinearRgba>,
+        done: usize,
+        total: usize,
+    },
+    /// The render ended. This is always the last event.
+    /// If cancelled, unrendered pixels are transparent black.
+    Finished {
+        status: RenderStatus,
+        image: Vec<LinearRgba>,
+    },
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<RenderEvent>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct Channel {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+impl Channel {
+    fn update(&self, f: impl FnOnce(&mut Queue)) {
+        let mut queue = self.queue.lock().unwrap();
+        f(&mut queue);
+
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+        self.ready.notify_all();
+    }
+}
+
+/// Closes the channel when the render ends, even by panicking, so receivers don't wait forever.
+struct CloseOnDrop(Arc<Channel>);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.update(|queue| queue.closed = true);
+    }
+}
+
+/// The events of a running render. Dropping this cancels the render.
+pub struct RenderEvents {
+    channel: Arc<Channel>,
+    token: CancellationToken,
+}
+
+impl RenderEvents {
+    /// Stop the render after the tiles in progress. A [`RenderEvent::Finished`] still follows.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Wait for the next event. Returns `None` once the render has ended and all events were received.
+    pub fn recv(&self) -> Option<RenderEvent> {
+        let mut queue = self.channel.queue.lock().unwrap();
+        loop {
+            if let Some(event) = queue.events.pop_front() {
+                return Some(event);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = self.channel.ready.wait(queue).unwrap();
+        }
+    }
+}
+
+impl Iterator for RenderEvents {
+    type Item = RenderEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl Stream for RenderEvents {
+    type Item = RenderEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut queue = self.channel.queue.lock().unwrap();
+
+        if let Some(event) = queue.events.pop_front() {
+            Poll::Ready(Some(event))
+        } else if queue.closed {
+            Poll::Ready(None)
+        } else {
+            queue.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for RenderEvents {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+/// Start rendering tile by tile on a background thread, and return right away.
+/// The tiles themselves render in parallel, see [`TileScheduler`].
+pub fn render_async(
+    camera: Camera,
+    world: impl Hittable + 'static,
+    tile_size: usize,
+) -> RenderEvents {
+    let channel = Arc::new(Channel::default());
+    let token = CancellationToken::new();
+
+    let events = RenderEvents {
+        channel: channel.clone(),
+        token: token.clone(),
+    };
+
+    // Not on the rayon pool, since the scheduler waits for tiles rendered on it
+    std::thread::spawn(move || {
+        let channel = CloseOnDrop(channel);
+
+        let result = TileScheduler::new(&camera, tile_size).run(&world, &token, |done| {
+            channel.0.update(|queue| {
+                queue.events.push_back(RenderEvent::Tile {
+                    tile: done.tile,
+                    pixels: done.pixels.to_vec(),
+                    done: done.progress.tiles_done,
+                    total: done.progress.tiles_total,
+                })
+            });
+            Ok::<_, Infallible>(())
+        });
+        let Ok((status, image)) = result;
+
+        channel.0.update(|queue| {
+            queue
+                .events
+                .push_back(RenderEvent::Finished { status, image })
+        });
+    });
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittables;
+
+    fn small_camera() -> Camera {
+        Camera::builder().resolution(10, 6).build().unwrap()
+    }
+
+    #[test]
+    fn events_in_order() {
+        let camera = small_camera();
+        let expected = camera.render_linear(&Hittables::default());
+
+        let events: Vec<_> = render_async(camera, Hittables::default(), 4).collect();
+
+        // 3 by 2 tiles, then the full image
+        assert_eq!(events.len(), 7);
+        for (index, event) in events[..6].iter().enumerate() {
+            assert!(
+                matches!(event, RenderEvent::Tile { done, total: 6, .. } if *done == index + 1)
+            );
+        }
+        let RenderEvent::Finished { status, image } = &events[6] else {
+            panic!("expected the render to finish last, got {:?}", events[6]);
+        };
+        assert_eq!(*status, RenderStatus::Complete);
+        assert_eq!(*image, expected);
+    }
+
+    #[test]
+    fn stream_ends_after_finishing() {
+        let mut events = render_async(small_camera(), Hittables::default(), 4);
+
+        let mut context = Context::from_waker(Waker::noop());
+        let mut received = vec![];
+        loop {
+            match Pin::new(&mut events).poll_next(&mut context) {
+                Poll::Ready(Some(event)) => received.push(event),
+                Poll::Ready(None) => break,
+                Poll::Pending => std::thread::yield_now(),
+            }
+        }
+
+        assert_eq!(received.len(), 7);
+        assert!(matches!(
+            received.last(),
+            Some(RenderEvent::Finished {
+                status: RenderStatus::Complete,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,145 @@
+//! What rays see when they miss everything.
+
+use std::{fmt::Debug, sync::Arc};
+
+use bevy_color::{Color, LinearRgba, Mix};
+
+use crate::{environment::EnvironmentMap, ray::Ray, sky::PhysicalSky};
+
+/// See [`Background::Custom`].
+pub type BackgroundFn = Arc<dyn Fn(&Ray) -> Color + Send + Sync>;
+
+/// The color in the direction of a ray which hit nothing.
+/// It also lights the scene unless the camera's `sky_visibility` says otherwise.
+#[derive(Clone)]
+pub enum Background {
+    /// The same color in every direction. Black for scenes lit only by emissive surfaces.
+    Solid(Color),
+
+    /// Blends from `bottom` looking straight down to `top` looking straight up.
+    Gradient { bottom: Color, top: Color },
+
+    /// A panorama such as an HDRI sky probe, or a baked [`PhysicalSky`].
+    /// Scattered rays are aimed at its bright spots.
+    Environment(Arc<EnvironmentMap>),
+
+    /// Anything else.
+    Custom(BackgroundFn),
+}
+
+impl Background {
+    /// Darkness, e.g. for a Cornell box.
+    pub const BLACK: Self = Self::Solid(Color::BLACK);
+
+    /// White near the horizon going blue overhead, like in the book.
+    pub const SKY: Self = Self::Gradient {
+        bottom: Color::WHITE,
+        top: Color::LinearRgba(LinearRgba::rgb(0.5, 0.7, 1.0)),
+    };
+
+    pub fn custom(color: impl Fn(&Ray) -> Color + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(color))
+    }
+
+    pub fn color(&self, ray: &Ray) -> Color {
+        match self {
+            Self::Solid(color) => *color,
+            Self::Gradient { bottom, top } => {
+                // Direction from straight down to straight up, remapped to [0, 1]
+                let a = (ray.direction().y + 1.0) * 0.5;
+                bottom.mix(top, a)
+            }
+            Self::Environment(map) => map.color(ray.direction()),
+            Self::Custom(color) => color(ray),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::SKY
+    }
+}
+
+impl Debug for Background {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Solid(color) => f.debug_tuple("Solid").field(color).finish(),
+            Self::Gradient { bottom, top } => f
+                .debug_struct("Gradient")
+                .field("bottom", bottom)
+                .field("top", top)
+                .finish(),
+            Self::Environment(map) => f
+                .debug_tuple("Environment")
+                .field(&format_args!("{}x{}", map.width(), map.height()))
+                .finish(),
+            Self::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl From<Color> for Background {
+    fn from(value: Color) -> Self {
+        Self::Solid(value)
+    }
+}
+
+impl From<EnvironmentMap> for Background {
+    fn from(value: EnvironmentMap) -> Self {
+        Self::Environment(Arc::new(value))
+    }
+}
+
+impl From<PhysicalSky> for Background {
+    /// Baked finely enough for the sun to be a few pixels across.
+    fn from(value: PhysicalSky) -> Self {
+        value.bake(2048, 1024).into()
+    }
+}
+
+impl From<LinearRgba> for Background {
+    fn from(value: LinearRgba) -> Self {
+        Self::Solid(value.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::Vec3;
+
+    use super::*;
+    use crate::{camera::Camera, hittable::Hittables};
+
+    #[test]
+    fn misses_see_the_background() {
+        let up = Ray::new(Vec3::ZERO, Vec3::Y);
+        let down = Ray::new(Vec3::ZERO, Vec3::NEG_Y);
+        assert_eq!(Background::SKY.color(&down), Color::WHITE);
+        assert_eq!(Background::SKY.color(&up), Color::linear_rgb(0.5, 0.7, 1.0));
+
+        let render = |background: Background| {
+            let camera = Camera::builder()
+                .resolution(3, 3)
+                .bounces(2)
+                .background(background)
+                .build()
+                .unwrap();
+            camera.render_pixel(&Hittables::default(), 1, 1)
+        };
+        assert_eq!(render(Background::BLACK), LinearRgba::BLACK);
+        assert_eq!(
+            render(LinearRgba::rgb(0.1, 0.2, 0.3).into()),
+            LinearRgba::rgb(0.1, 0.2, 0.3)
+        );
+        assert_eq!(
+            // The camera looks towards -Z
+            render(Background::custom(|ray| if ray.direction().z < 0.0 {
+                Color::linear_rgb(0.0, 0.0, 1.0)
+            } else {
+                Color::linear_rgb(1.0, 0.0, 0.0)
+            })),
+            LinearRgba::rgb(0.0, 0.0, 1.0)
+        );
+    }
+}
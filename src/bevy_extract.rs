@@ -0,0 +1,197 @@
+//! Path trace a Bevy ECS world as a still image.
+//!
+//! Entities with a [`GlobalTransform`], a mesh handle and a [`StandardMaterial`] handle are converted.
+//! Meshes whose vertices all lie on a sphere around their origin become exact [`Sphere`]s,
+//! everything else becomes triangles.
+
+use bevy_asset::{Assets, Handle};
+use bevy_ecs::world::World;
+use bevy_math::{Mat3, Vec2, Vec3};
+use bevy_pbr::StandardMaterial;
+use bevy_render::mesh::{Indices, Mesh, PrimitiveTopology, VertexAttributeValues};
+use bevy_transform::components::GlobalTransform;
+use tracing::warn;
+
+use crate::{
+    hittable::Hittables,
+    material::{Dielectric, DynMaterial, Lambertian, Metal},
+    objects::{Sphere, Triangle},
+};
+
+/// Approximate a standard PBR material with the materials available here.
+pub fn convert_material(material: &StandardMaterial) -> DynMaterial {
+    if material.specular_transmission > 0.5 {
+        let mut glass = Dielectric::refraction_index(material.ior);
+        glass.color = material.base_color;
+        glass.into()
+    } else if material.metallic > 0.5 {
+        Metal::new(material.base_color, material.perceptual_roughness).into()
+    } else {
+        Lambertian::new(material.base_color).into()
+    }
+}
+
+fn positions(mesh: &Mesh) -> Option<&[[f32; 3]]> {
+    match mesh.attribute(Mesh::ATTRIBUTE_POSITION)? {
+        VertexAttributeValues::Float32x3(positions) => Some(positions),
+        _ => None,
+    }
+}
+
+fn normals(mesh: &Mesh) -> Option<&[[f32; 3]]> {
+    match mesh.attribute(Mesh::ATTRIBUTE_NORMAL)? {
+        VertexAttributeValues::Float32x3(normals) => Some(normals),
+        _ => None,
+    }
+}
+
+fn uvs(mesh: &Mesh) -> Option<&[[f32; 2]]> {
+    match mesh.attribute(Mesh::ATTRIBUTE_UV_0)? {
+        VertexAttributeValues::Float32x2(uvs) => Some(uvs),
+        _ => None,
+    }
+}
+
+/// If the mesh is a finely tessellated sphere around its origin, the radius of that sphere.
+///
+/// All vertices must be equally far from the origin,
+/// and the faces must hug that sphere (which rules out e.g. cubes).
+fn sphere_radius(positions: &[[f32; 3]], indices: &[usize]) -> Option<f32> {
+    let radius = Vec3::from(*positions.first()?).length();
+
+    let on_sphere = positions
+        .iter()
+        .all(|&p| (Vec3::from(p).length() - radius).abs() <= radius * 1e-3);
+
+    let hugging = indices.chunks_exact(3).all(|triangle| {
+        let centroid = triangle
+            .iter()
+            .map(|&index| Vec3::from(positions[index]))
+            .sum::<Vec3>()
+            / 3.0;
+        centroid.length() >= radius * 0.95
+    });
+
+    (on_sphere && hugging).then_some(radius)
+}
+
+/// Convert the meshes in a Bevy world to hittables.
+pub fn extract_world(world: &mut World) -> Hittables {
+    let mut hittables = Hittables::default();
+
+    let mut query = world.query::<(&GlobalTransform, &Handle<Mesh>, &Handle<StandardMaterial>)>();
+
+    let (Some(meshes), Some(materials)) = (
+        world.get_resource::<Assets<Mesh>>(),
+        world.get_resource::<Assets<StandardMaterial>>(),
+    ) else {
+        warn!("world has no mesh or material assets");
+        return hittables;
+    };
+
+    for (transform, mesh, material) in query.iter(world) {
+        let (Some(mesh), Some(material)) = (meshes.get(mesh), materials.get(material)) else {
+            continue;
+        };
+
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            warn!(
+                "skipping mesh with topology {:?}",
+                mesh.primitive_topology()
+            );
+            continue;
+        }
+
+        let Some(positions) = positions(mesh) else {
+            continue;
+        };
+
+        let material = convert_material(material);
+
+        let indices: Vec<usize> = match mesh.indices() {
+            Some(indices @ (Indices::U16(_) | Indices::U32(_))) => indices.iter().collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        let (scale, _, translation) = transform.to_scale_rotation_translation();
+        let uniform_scale = (scale.x - scale.y).abs() < 1e-4 && (scale.x - scale.z).abs() < 1e-4;
+
+        if let Some(radius) = sphere_radius(positions, &indices).filter(|_| uniform_scale) {
+            hittables.add(Sphere {
+                center: translation,
+                radius: radius * scale.x,
+                material,
+            });
+            continue;
+        }
+
+        let vertex = |index: usize| transform.transform_point(Vec3::from(positions[index]));
+        // Normals stay perpendicular to the surface under non-uniform scaling
+        let normal_matrix = Mat3::from(transform.affine().matrix3).inverse().transpose();
+        let normals = normals(mesh).filter(|normals| normals.len() == positions.len());
+        let uvs = uvs(mesh).filter(|uvs| uvs.len() == positions.len());
+
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+            let mut hittable = Triangle::new(vertex(a), vertex(b), vertex(c), material.clone());
+
+            if let Some(normals) = normals {
+                hittable = hittable.with_normals(
+                    [a, b, c].map(|index| normal_matrix * Vec3::from(normals[index])),
+                );
+            }
+            if let Some(uvs) = uvs {
+                // Bevy's v goes down the image, ours goes up
+                hittable = hittable.with_uvs([a, b, c].map(|index| {
+                    let [u, v] = uvs[index];
+                    Vec2::new(u, 1.0 - v)
+                }));
+            }
+
+            hittables.add(hittable);
+        }
+    }
+
+    hittables
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::Color;
+    use bevy_math::primitives::{Cuboid, Sphere};
+    use bevy_render::mesh::Meshable;
+    use bevy_transform::components::Transform;
+
+    use super::*;
+
+    #[test]
+    fn spheres_and_meshes() {
+        let mut world = World::new();
+
+        let mut meshes = Assets::<Mesh>::default();
+        let sphere = meshes.add(Sphere::new(0.5).mesh().ico(3).unwrap());
+        let cuboid = meshes.add(Cuboid::new(1.0, 1.0, 1.0).mesh());
+
+        let mut materials = Assets::<StandardMaterial>::default();
+        let shiny = materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            metallic: 1.0,
+            ..Default::default()
+        });
+
+        world.insert_resource(meshes);
+        world.insert_resource(materials);
+
+        world.spawn((
+            GlobalTransform::from(Transform::from_xyz(0.0, 1.0, 0.0).with_scale(Vec3::splat(2.0))),
+            sphere,
+            shiny.clone(),
+        ));
+        world.spawn((GlobalTransform::default(), cuboid, shiny));
+
+        let hittables = extract_world(&mut world);
+
+        // One sphere plus 6 faces of 2 triangles
+        assert_eq!(hittables.objects.len(), 13);
+    }
+}
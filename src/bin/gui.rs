@@ -0,0 +1,660 @@
+//! Interactive look development: tweak the camera and materials and watch the image refine.
+//!
+//! Run with `cargo run --release --features gui --bin rt-gui`.
+//!
+//! In the viewport, drag to orbit, scroll to zoom and use WASD (plus Q/E for down/up) to move.
+//! Click a pixel to inspect what it sees.
+
+use std::time::{Duration, Instant};
+
+use bevy_color::{Color, LinearRgba};
+use bevy_math::{EulerRot, Quat, Vec3, VectorSpace};
+use eframe::egui;
+use rt_one::{
+    camera::Camera,
+    hittable::Hittables,
+    material::{Dielectric, DynMaterial, Lambertian, Metal},
+    objects::Sphere,
+    output_color::Transfer,
+};
+use tracing::info;
+
+/// How long each frame may spend path tracing before handing control back to the UI.
+const FRAME_BUDGET: Duration = Duration::from_millis(30);
+
+/// Radians per dragged point.
+const ORBIT_SPEED: f32 = 0.005;
+/// Keep just short of straight up or down, where yaw stops making sense.
+const MAX_PITCH: f32 = 1.55;
+/// Zoom factor per scrolled point, exponentially.
+const ZOOM_SPEED: f32 = 0.002;
+/// Scene units per second, scaled by the orbit distance.
+const MOVE_SPEED: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MaterialKind {
+    Lambertian,
+    Metal,
+    Dielectric,
+}
+
+/// Editable material parameters, turned into real materials when the world is rebuilt.
+#[derive(Debug, Clone, Copy)]
+struct MaterialParams {
+    kind: MaterialKind,
+    color: [f32; 3],
+    fuzz: f32,
+    refractive_index: f32,
+}
+
+impl MaterialParams {
+    fn lambertian(color: [f32; 3]) -> Self {
+        Self {
+            kind: MaterialKind::Lambertian,
+            color,
+            fuzz: 0.0,
+            refractive_index: 1.5,
+        }
+    }
+
+    fn build(&self) -> DynMaterial {
+        let [red, green, blue] = self.color;
+        match self.kind {
+            MaterialKind::Lambertian => Lambertian::linear_rgb(red, green, blue).into(),
+            MaterialKind::Metal => {
+                Metal::new(Color::linear_rgb(red, green, blue), self.fuzz).into()
+            }
+            MaterialKind::Dielectric => {
+                let mut glass = Dielectric::refraction_index(self.refractive_index);
+                glass.color = Color::linear_rgb(red, green, blue);
+                glass.into()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SphereParams {
+    name: String,
+    center: Vec3,
+    radius: f32,
+    material: MaterialParams,
+}
+
+/// The camera orbits around a target point.
+struct CameraParams {
+    width: usize,
+    height: usize,
+    target: Vec3,
+    distance: f32,
+    /// Radians around +Y.
+    yaw: f32,
+    /// Radians around the camera's horizontal axis, positive looks up.
+    pitch: f32,
+    focal_length: f32,
+    bounce: usize,
+    max_samples: usize,
+    srgb: bool,
+}
+
+impl CameraParams {
+    fn build(&self) -> Camera {
+        let mut camera = Camera::new();
+        camera.im_width = self.width.max(1);
+        camera.im_height = self.height.max(1);
+        camera.samples_per_pixel = 1;
+        camera.orientation = self.orientation();
+        camera.cam_origin = self.target + camera.orientation * Vec3::new(0.0, 0.0, self.distance);
+        camera.focal_length = self.focal_length;
+        camera.bounce = self.bounce;
+        camera.min_dist = 0.001;
+        camera.output_color.transfer = if self.srgb {
+            Transfer::Srgb
+        } else {
+            Transfer::Linear
+        };
+        camera.update_viewport();
+
+        camera
+    }
+
+    fn orientation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+
+    /// Returns true if the camera moved.
+    fn navigate(&mut self, ctx: &egui::Context, viewport: &egui::Response) -> bool {
+        let mut moved = false;
+
+        if viewport.dragged() {
+            let delta = viewport.drag_delta();
+            self.yaw -= delta.x * ORBIT_SPEED;
+            self.pitch = (self.pitch - delta.y * ORBIT_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+            moved = true;
+        }
+
+        if viewport.hovered() {
+            let scroll = ctx.input(|input| input.smooth_scroll_delta.y);
+            if scroll != 0.0 {
+                self.distance = (self.distance * (-scroll * ZOOM_SPEED).exp()).max(0.01);
+                moved = true;
+            }
+        }
+
+        if !ctx.wants_keyboard_input() {
+            let (step, dt) = ctx.input(|input| {
+                let axis = |negative, positive| {
+                    input.key_down(positive) as i32 as f32 - input.key_down(negative) as i32 as f32
+                };
+                let step = Vec3::new(
+                    axis(egui::Key::A, egui::Key::D),
+                    axis(egui::Key::Q, egui::Key::E),
+                    axis(egui::Key::W, egui::Key::S),
+                );
+                (step, input.stable_dt)
+            });
+
+            if step != Vec3::ZERO {
+                // Move relative to where the camera is looking, but keep WASD level with the ground
+                let heading = Quat::from_rotation_y(self.yaw);
+                let step = heading * Vec3::new(step.x, 0.0, step.z) + Vec3::new(0.0, step.y, 0.0);
+
+                self.target += step.normalize() * MOVE_SPEED * self.distance.max(1.0) * dt;
+                moved = true;
+            }
+        }
+
+        moved
+    }
+}
+
+/// The image so far, refined one sample per pixel per pass, a row at a time.
+struct Accumulation {
+    width: usize,
+    pixels: Vec<LinearRgba>,
+    /// Samples summed into each row. Mid-pass, the rows before `next_row` are one ahead.
+    row_samples: Vec<usize>,
+    next_row: usize,
+}
+
+impl Accumulation {
+    fn new(camera: &Camera) -> Self {
+        Self {
+            width: camera.im_width,
+            pixels: vec![LinearRgba::ZERO; camera.im_width * camera.im_height],
+            row_samples: vec![0; camera.im_height],
+            next_row: 0,
+        }
+    }
+
+    /// Passes every row has finished.
+    fn passes(&self) -> usize {
+        self.row_samples.iter().min().copied().unwrap_or_default()
+    }
+
+    /// Samples summed into the pixel at `index`, row-major.
+    fn samples(&self, index: usize) -> usize {
+        self.row_samples[index / self.width]
+    }
+
+    /// The average of the pixel's samples so far.
+    fn mean(&self, index: usize) -> LinearRgba {
+        self.pixels[index] / self.samples(index).max(1) as f32
+    }
+}
+
+struct App {
+    camera_params: CameraParams,
+    spheres: Vec<SphereParams>,
+    selected: Option<usize>,
+
+    camera: Camera,
+    world: Hittables,
+    accumulation: Accumulation,
+    texture: Option<egui::TextureHandle>,
+
+    /// The pixel (row, column) shown in the inspector.
+    inspected: Option<(usize, usize)>,
+}
+
+impl App {
+    fn new() -> Self {
+        let camera_params = CameraParams {
+            width: 400,
+            height: 225,
+            target: Vec3::new(0.0, 0.0, -1.0),
+            distance: 1.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            focal_length: 1.0,
+            bounce: 10,
+            max_samples: 256,
+            srgb: true,
+        };
+
+        let spheres = vec![
+            SphereParams {
+                name: "ground".into(),
+                center: Vec3::new(0.0, -100.5, -1.0),
+                radius: 100.0,
+                material: MaterialParams::lambertian([0.8, 0.8, 0.0]),
+            },
+            SphereParams {
+                name: "center".into(),
+                center: Vec3::new(0.0, 0.0, -1.2),
+                radius: 0.5,
+                material: MaterialParams::lambertian([0.1, 0.2, 0.5]),
+            },
+            SphereParams {
+                name: "left".into(),
+                center: Vec3::new(-1.0, 0.0, -1.0),
+                radius: 0.5,
+                material: MaterialParams {
+                    kind: MaterialKind::Dielectric,
+                    color: [1.0, 1.0, 1.0],
+                    fuzz: 0.0,
+                    refractive_index: 1.5,
+                },
+            },
+            SphereParams {
+                name: "right".into(),
+                center: Vec3::new(1.0, 0.0, -1.0),
+                radius: 0.5,
+                material: MaterialParams {
+                    kind: MaterialKind::Metal,
+                    color: [0.8, 0.6, 0.2],
+                    fuzz: 0.3,
+                    refractive_index: 1.5,
+                },
+            },
+        ];
+
+        let camera = camera_params.build();
+        let accumulation = Accumulation::new(&camera);
+
+        let mut app = Self {
+            camera_params,
+            spheres,
+            selected: None,
+            camera,
+            world: Hittables::default(),
+            accumulation,
+            texture: None,
+            inspected: None,
+        };
+        app.rebuild();
+
+        app
+    }
+
+    /// Apply edited camera parameters and start accumulating from scratch.
+    fn restart(&mut self) {
+        self.camera = self.camera_params.build();
+        self.accumulation = Accumulation::new(&self.camera);
+    }
+
+    /// Apply all edited parameters and start accumulating from scratch.
+    fn rebuild(&mut self) {
+        self.world = Hittables::default();
+        for sphere in &self.spheres {
+            self.world.add(Sphere {
+                center: sphere.center,
+                radius: sphere.radius,
+                material: sphere.material.build(),
+            });
+        }
+
+        self.restart();
+    }
+
+    fn done(&self) -> bool {
+        self.accumulation.passes() >= self.camera_params.max_samples
+    }
+
+    /// Trace rows until the frame budget is spent.
+    fn refine(&mut self) {
+        let start = Instant::now();
+        let width = self.camera.im_width;
+
+        while !self.done() && start.elapsed() < FRAME_BUDGET {
+            let row = self.accumulation.next_row;
+            // Each pass is the next sample, not the same one again
+            let sample = self.accumulation.row_samples[row];
+            for col in 0..width {
+                self.accumulation.pixels[row * width + col] +=
+                    self.camera.render_sample(&self.world, row, col, sample);
+            }
+
+            self.accumulation.row_samples[row] += 1;
+            self.accumulation.next_row = (row + 1) % self.camera.im_height;
+        }
+    }
+
+    fn image(&self) -> egui::ColorImage {
+        let rgb: Vec<u8> = (0..self.accumulation.pixels.len())
+            .flat_map(|index| self.camera.to_rgb8(self.accumulation.mean(index)))
+            .collect();
+
+        egui::ColorImage::from_rgb([self.camera.im_width, self.camera.im_height], &rgb)
+    }
+
+    /// Returns true if anything changed.
+    fn camera_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let params = &mut self.camera_params;
+        let mut changed = false;
+
+        egui::Grid::new("camera").show(ui, |ui| {
+            ui.label("Resolution");
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .add(egui::DragValue::new(&mut params.width).range(1..=4096))
+                    .changed();
+                changed |= ui
+                    .add(egui::DragValue::new(&mut params.height).range(1..=4096))
+                    .changed();
+            });
+            ui.end_row();
+
+            ui.label("Target");
+            changed |= vec3_ui(ui, &mut params.target);
+            ui.end_row();
+
+            ui.label("Distance");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut params.distance)
+                        .speed(0.01)
+                        .range(0.01..=10_000.0),
+                )
+                .changed();
+            ui.end_row();
+
+            ui.label("Yaw / pitch");
+            ui.horizontal(|ui| {
+                changed |= ui.drag_angle(&mut params.yaw).changed();
+                changed |= ui.drag_angle(&mut params.pitch).changed();
+            });
+            params.pitch = params.pitch.clamp(-MAX_PITCH, MAX_PITCH);
+            ui.end_row();
+
+            ui.label("Focal length");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut params.focal_length)
+                        .speed(0.01)
+                        .range(0.05..=10.0),
+                )
+                .changed();
+            ui.end_row();
+
+            ui.label("Bounces");
+            changed |= ui
+                .add(egui::DragValue::new(&mut params.bounce).range(0..=100))
+                .changed();
+            ui.end_row();
+
+            ui.label("Samples per pixel");
+            // More samples continue refining the current image, no need to restart
+            ui.add(egui::DragValue::new(&mut params.max_samples).range(1..=100_000));
+            ui.end_row();
+
+            ui.label("sRGB output");
+            changed |= ui.checkbox(&mut params.srgb, "").changed();
+            ui.end_row();
+        });
+
+        changed
+    }
+
+    fn objects_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        for (index, sphere) in self.spheres.iter().enumerate() {
+            let selected = self.selected == Some(index);
+            if ui.selectable_label(selected, &sphere.name).clicked() {
+                self.selected = if selected { None } else { Some(index) };
+            }
+        }
+
+        let Some(sphere) = self.selected.and_then(|index| self.spheres.get_mut(index)) else {
+            return false;
+        };
+
+        ui.separator();
+        let mut changed = false;
+        let material = &mut sphere.material;
+
+        egui::Grid::new("object").show(ui, |ui| {
+            ui.label("Center");
+            changed |= vec3_ui(ui, &mut sphere.center);
+            ui.end_row();
+
+            ui.label("Radius");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut sphere.radius)
+                        .speed(0.01)
+                        .range(0.001..=1000.0),
+                )
+                .changed();
+            ui.end_row();
+
+            ui.label("Material");
+            egui::ComboBox::from_id_salt("material")
+                .selected_text(format!("{:?}", material.kind))
+                .show_ui(ui, |ui| {
+                    for kind in [
+                        MaterialKind::Lambertian,
+                        MaterialKind::Metal,
+                        MaterialKind::Dielectric,
+                    ] {
+                        changed |= ui
+                            .selectable_value(&mut material.kind, kind, format!("{kind:?}"))
+                            .changed();
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Color");
+            changed |= ui.color_edit_button_rgb(&mut material.color).changed();
+            ui.end_row();
+
+            match material.kind {
+                MaterialKind::Lambertian => {}
+                MaterialKind::Metal => {
+                    ui.label("Fuzz");
+                    changed |= ui
+                        .add(egui::Slider::new(&mut material.fuzz, 0.0..=1.0))
+                        .changed();
+                    ui.end_row();
+                }
+                MaterialKind::Dielectric => {
+                    ui.label("Refractive index");
+                    changed |= ui
+                        .add(egui::Slider::new(&mut material.refractive_index, 0.5..=3.0))
+                        .changed();
+                    ui.end_row();
+                }
+            }
+        });
+
+        changed
+    }
+
+    /// Describe what the given pixel sees, one line per property.
+    fn inspect(&self, row: usize, col: usize) -> Vec<String> {
+        let mut lines = vec![format!("Pixel: row {row}, column {col}")];
+
+        match self.camera.inspect(&self.world, row, col) {
+            Some(hit) => {
+                // IDs count from 1, 0 is the background
+                let index = hit.object_id as usize - 1;
+                lines.push(format!("Object: {} (#{index})", self.spheres[index].name));
+                lines.push(format!("Material: {:?}", hit.material));
+                lines.push(format!("Distance: {:.4}", hit.distance));
+                lines.push(format!("Point: {:.4}", hit.point));
+                lines.push(format!(
+                    "Normal: {:.4} ({})",
+                    *hit.normal,
+                    if hit.front_face { "front" } else { "back" }
+                ));
+            }
+            None => lines.push("Object: none, sky".into()),
+        }
+
+        let index = row * self.camera.im_width + col;
+        let radiance = self.accumulation.mean(index);
+        lines.push(format!(
+            "Radiance: [{:.4}, {:.4}, {:.4}] over {} samples",
+            radiance.red,
+            radiance.green,
+            radiance.blue,
+            self.accumulation.samples(index)
+        ));
+
+        lines
+    }
+
+    fn inspector_ui(&mut self, ui: &mut egui::Ui) {
+        let Some((row, col)) = self.inspected else {
+            ui.label("Click the image to inspect a pixel");
+            return;
+        };
+
+        // The image may have been resized since
+        if row >= self.camera.im_height || col >= self.camera.im_width {
+            self.inspected = None;
+            return;
+        }
+
+        for line in self.inspect(row, col) {
+            ui.label(line);
+        }
+    }
+
+    /// Maps a position on the displayed image to a (row, column) pixel.
+    fn pixel_at(&self, rect: egui::Rect, position: egui::Pos2) -> Option<(usize, usize)> {
+        let uv = (position - rect.min) / rect.size();
+        if !(0.0..1.0).contains(&uv.x) || !(0.0..1.0).contains(&uv.y) {
+            return None;
+        }
+
+        let row = (uv.y * self.camera.im_height as f32) as usize;
+        let col = (uv.x * self.camera.im_width as f32) as usize;
+
+        Some((row, col))
+    }
+}
+
+fn vec3_ui(ui: &mut egui::Ui, v: &mut Vec3) -> bool {
+    ui.horizontal(|ui| {
+        let mut changed = false;
+        for component in [&mut v.x, &mut v.y, &mut v.z] {
+            changed |= ui
+                .add(egui::DragValue::new(component).speed(0.01))
+                .changed();
+        }
+        changed
+    })
+    .inner
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut changed = false;
+
+        egui::SidePanel::left("controls").show(ctx, |ui| {
+            ui.heading("Camera");
+            changed |= self.camera_ui(ui);
+
+            ui.separator();
+            ui.heading("Objects");
+            changed |= self.objects_ui(ui);
+
+            ui.separator();
+            ui.heading("Inspector");
+            self.inspector_ui(ui);
+
+            ui.separator();
+            ui.label(format!(
+                "{} / {} samples per pixel",
+                self.accumulation.passes(),
+                self.camera_params.max_samples
+            ));
+        });
+
+        if changed {
+            self.rebuild();
+        }
+
+        self.refine();
+
+        let image = self.image();
+        match &mut self.texture {
+            Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+            None => {
+                self.texture =
+                    Some(ctx.load_texture("render", image, egui::TextureOptions::NEAREST))
+            }
+        }
+
+        let viewport = egui::CentralPanel::default()
+            .show(ctx, |ui| {
+                self.texture.as_ref().map(|texture| {
+                    let response = ui.add(
+                        egui::Image::new(texture)
+                            .shrink_to_fit()
+                            .sense(egui::Sense::click_and_drag()),
+                    );
+
+                    if let Some((row, col)) = self.inspected {
+                        let rect = response.rect;
+                        let center = rect.min
+                            + egui::vec2(
+                                (col as f32 + 0.5) / self.camera.im_width as f32,
+                                (row as f32 + 0.5) / self.camera.im_height as f32,
+                            ) * rect.size();
+                        ui.painter().circle_stroke(
+                            center,
+                            5.0,
+                            egui::Stroke::new(1.5, egui::Color32::RED),
+                        );
+                    }
+
+                    response
+                })
+            })
+            .inner;
+
+        if let Some(viewport) = viewport {
+            if viewport.clicked() {
+                let pixel = viewport
+                    .interact_pointer_pos()
+                    .and_then(|position| self.pixel_at(viewport.rect, position));
+
+                if let Some((row, col)) = pixel {
+                    info!("{}", self.inspect(row, col).join("\n"));
+                }
+                self.inspected = pixel;
+            }
+
+            if self.camera_params.navigate(ctx, &viewport) {
+                self.restart();
+            }
+        }
+
+        if !self.done() {
+            ctx.request_repaint();
+        }
+    }
+}
+
+fn main() -> eframe::Result {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    eframe::run_native(
+        "rt",
+        eframe::NativeOptions::default(),
+        Box::new(|_cc| Ok(Box::new(App::new()))),
+    )
+}
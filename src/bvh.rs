@@ -0,0 +1,599 @@
+use std::{ops::Range, sync::Arc};
+
+use bevy_math::Vec3;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{Hit, Hittable, Hittables},
+    ray::Ray,
+    stats::{SceneStats, TreeStats},
+};
+
+// Relative costs for the surface area heuristic.
+// Testing a box is much cheaper than testing an object.
+const TRAVERSAL_COST: f32 = 0.125;
+const INTERSECTION_COST: f32 = 1.0;
+/// Candidate split planes per axis are placed between this many bins.
+const BINS: usize = 12;
+/// Nodes with more objects than this are always split.
+const MAX_LEAF_OBJECTS: usize = 4;
+/// Deeper nodes become leaves, which bounds the traversal stack.
+const MAX_DEPTH: usize = 60;
+/// Subtrees with at least this many objects are built on separate threads.
+const PARALLEL_OBJECTS: usize = 1024;
+
+/// How a BVH decides where to split a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BvhSplit {
+    /// Half the objects on each side, along the longest axis. Fast to build.
+    Median,
+    /// The split with the lowest surface area heuristic cost,
+    /// among planes between bins of object centers.
+    #[default]
+    Sah,
+}
+
+#[derive(Debug)]
+enum Node {
+    /// Refers to `count` entries of `object_indices`, starting at `first`.
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    /// The first child is the next node, the second is at `second`.
+    /// `axis` is the one the children were split along.
+    Interior {
+        bounds: Aabb,
+        second: usize,
+        axis: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// A bounding volume hierarchy over the objects of a [`Hittables`].
+///
+/// Objects are grouped into a tree of boxes, and rays only test the objects
+/// in boxes they pass through.
+#[derive(Debug)]
+pub struct Bvh {
+    objects: Vec<Arc<Box<dyn Hittable>>>,
+    tree: BvhTree,
+}
+
+/// The tree itself, referring to objects by index so it can be used for any storage.
+#[derive(Debug)]
+pub(crate) struct BvhTree {
+    /// Depth first, so the first child of a node follows it.
+    nodes: Vec<Node>,
+    object_indices: Vec<usize>,
+}
+
+/// Nodes while building, before they are flattened.
+enum BuildNode {
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: Aabb,
+        axis: usize,
+        children: Box<[BuildNode; 2]>,
+    },
+}
+
+/// What building needs to know about each object.
+struct Primitive {
+    bounds: Aabb,
+    center: Vec3,
+}
+
+impl Bvh {
+    pub fn new(world: &Hittables, split: BvhSplit) -> Self {
+        let objects = world.objects.clone();
+        let bounds: Vec<Aabb> = objects.iter().map(|object| object.bounding_box()).collect();
+
+        Self {
+            objects,
+            tree: BvhTree::new(&bounds, split),
+        }
+    }
+
+    /// The expected cost of a random ray hitting the root, by the surface area heuristic.
+    /// Lower is better. Useful to compare how well trees are built.
+    pub fn sah_cost(&self) -> f32 {
+        self.tree.sah_cost()
+    }
+}
+
+impl BvhTree {
+    /// Build over objects with the given bounds. Leaves refer to objects by their index in `bounds`.
+    pub(crate) fn new(bounds: &[Aabb], split: BvhSplit) -> Self {
+        let primitives: Vec<Primitive> = bounds
+            .iter()
+            .map(|&bounds| Primitive {
+                bounds,
+                center: bounds.center(),
+            })
+            .collect();
+
+        let mut object_indices: Vec<usize> = (0..bounds.len()).collect();
+        let root = build(&primitives, &mut object_indices, 0, 0, split);
+
+        let mut nodes = vec![];
+        flatten(root, &mut nodes);
+
+        Self {
+            nodes,
+            object_indices,
+        }
+    }
+
+    fn sah_cost(&self) -> f32 {
+        let root_area = self.nodes[0].bounds().surface_area();
+        if root_area <= 0.0 {
+            return 0.0;
+        }
+
+        self.nodes
+            .iter()
+            .map(|node| {
+                let cost = match node {
+                    Node::Leaf { count, .. } => INTERSECTION_COST * *count as f32,
+                    Node::Interior { .. } => TRAVERSAL_COST,
+                };
+                cost * node.bounds().surface_area() / root_area
+            })
+            .sum()
+    }
+
+    /// Find the closest hit, testing objects in the boxes the ray passes through
+    /// with `hit_object(index, range)`.
+    pub(crate) fn hit(
+        &self,
+        ray: &Ray,
+        t_range: Range<f32>,
+        hit_object: impl Fn(usize, Range<f32>) -> Option<Hit>,
+    ) -> Option<Hit> {
+        let mut closest_hit = None;
+        let mut closest = t_range.end;
+
+        let direction = ray.direction().as_vec3();
+
+        // Nodes still to visit, at most one per level of the tree plus the one being visited
+        let mut stack = [0; MAX_DEPTH + 2];
+        let mut len = 1;
+
+        while len > 0 {
+            len -= 1;
+            let index = stack[len];
+            let node = &self.nodes[index];
+            if node.bounds().hit(ray, t_range.start..closest).is_none() {
+                continue;
+            }
+
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    for &index in &self.object_indices[first..first + count] {
+                        if let Some(hit) = hit_object(index, t_range.start..closest) {
+                            closest = hit.distance;
+                            closest_hit = Some(hit);
+                        }
+                    }
+                }
+                Node::Interior { second, axis, .. } => {
+                    // Visit the child nearer the ray origin first, it's more likely to shorten the ray
+                    let (near, far) = if direction[axis] < 0.0 {
+                        (second, index + 1)
+                    } else {
+                        (index + 1, second)
+                    };
+                    stack[len] = far;
+                    stack[len + 1] = near;
+                    len += 2;
+                }
+            }
+        }
+
+        closest_hit
+    }
+
+    pub(crate) fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map_or(Aabb::EMPTY, |root| *root.bounds())
+    }
+
+    /// How deep the tree goes and how full its leaves are.
+    pub(crate) fn shape(&self) -> TreeStats {
+        let mut shape = TreeStats {
+            leaf_sizes: (usize::MAX, 0),
+            ..Default::default()
+        };
+
+        // Node index and depth
+        let mut stack = vec![(0, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            let Some(node) = self.nodes.get(index) else {
+                continue;
+            };
+            shape.depth = shape.depth.max(depth);
+            match *node {
+                Node::Leaf { count, .. } => {
+                    shape.leaves += 1;
+                    shape.leaf_objects += count;
+                    shape.leaf_sizes =
+                        (shape.leaf_sizes.0.min(count), shape.leaf_sizes.1.max(count));
+                }
+                Node::Interior { second, .. } => {
+                    stack.push((index + 1, depth + 1));
+                    stack.push((second, depth + 1));
+                }
+            }
+        }
+
+        if shape.leaves == 0 {
+            shape.leaf_sizes = (0, 0);
+        }
+        shape
+    }
+
+    /// Heap memory used by the tree.
+    pub(crate) fn memory_bytes(&self) -> usize {
+        self.nodes.capacity() * std::mem::size_of::<Node>()
+            + self.object_indices.capacity() * std::mem::size_of::<usize>()
+    }
+}
+
+fn union(primitives: &[Primitive], indices: &[usize]) -> Aabb {
+    indices.iter().fold(Aabb::EMPTY, |bounds, &i| {
+        bounds.union(&primitives[i].bounds)
+    })
+}
+
+/// Build the subtree over `indices`, reordering them so each leaf's objects are contiguous.
+/// `offset` is where `indices` starts within all of them.
+fn build(
+    primitives: &[Primitive],
+    indices: &mut [usize],
+    offset: usize,
+    depth: usize,
+    split: BvhSplit,
+) -> BuildNode {
+    let indices_len = indices.len();
+    let bounds = union(primitives, indices);
+    let leaf = BuildNode::Leaf {
+        bounds,
+        first: offset,
+        count: indices.len(),
+    };
+
+    if indices.len() <= 1 || depth == MAX_DEPTH {
+        return leaf;
+    }
+
+    let centers = indices.iter().fold(Aabb::EMPTY, |centers, &i| {
+        centers.union(&Aabb::new(primitives[i].center, primitives[i].center))
+    });
+    let size = centers.size();
+    let axis = if size.x >= size.y && size.x >= size.z {
+        0
+    } else if size.y >= size.z {
+        1
+    } else {
+        2
+    };
+
+    // All centers in one spot, no plane separates them
+    if size[axis] <= 0.0 || size[axis].is_nan() {
+        return leaf;
+    }
+
+    let mid = match split {
+        BvhSplit::Median => median_split(primitives, indices, axis),
+        BvhSplit::Sah => match sah_split(primitives, indices, &bounds, &centers, axis) {
+            Some(mid) => mid,
+            None => return leaf,
+        },
+    };
+
+    let (below, above) = indices.split_at_mut(mid);
+    let (below, above) = if indices_len >= PARALLEL_OBJECTS {
+        rayon::join(
+            || build(primitives, below, offset, depth + 1, split),
+            || build(primitives, above, offset + mid, depth + 1, split),
+        )
+    } else {
+        (
+            build(primitives, below, offset, depth + 1, split),
+            build(primitives, above, offset + mid, depth + 1, split),
+        )
+    };
+
+    BuildNode::Interior {
+        bounds,
+        axis,
+        children: Box::new([below, above]),
+    }
+}
+
+fn median_split(primitives: &[Primitive], indices: &mut [usize], axis: usize) -> usize {
+    let mid = indices.len() / 2;
+    indices.select_nth_unstable_by(mid, |&a, &b| {
+        primitives[a].center[axis].total_cmp(&primitives[b].center[axis])
+    });
+    mid
+}
+
+/// Partition the indices at the cheapest plane between bins of centers along `axis`.
+/// Returns where the second half starts, or `None` if a leaf is cheaper.
+fn sah_split(
+    primitives: &[Primitive],
+    indices: &mut [usize],
+    bounds: &Aabb,
+    centers: &Aabb,
+    axis: usize,
+) -> Option<usize> {
+    let bin_of = |i: usize| {
+        let t = (primitives[i].center[axis] - centers.min[axis]) / centers.size()[axis];
+        ((t * BINS as f32) as usize).min(BINS - 1)
+    };
+
+    let mut counts = [0; BINS];
+    let mut bin_bounds = [Aabb::EMPTY; BINS];
+    for &i in indices.iter() {
+        let bin = bin_of(i);
+        counts[bin] += 1;
+        bin_bounds[bin] = bin_bounds[bin].union(&primitives[i].bounds);
+    }
+
+    // Sweep from the right to get the area and count of everything above each plane
+    let mut above = [(0.0, 0); BINS - 1];
+    let (mut area, mut count) = (Aabb::EMPTY, 0);
+    for plane in (0..BINS - 1).rev() {
+        area = area.union(&bin_bounds[plane + 1]);
+        count += counts[plane + 1];
+        above[plane] = (area.surface_area(), count);
+    }
+
+    let mut best = None;
+    let mut best_cost = f32::INFINITY;
+    let (mut area, mut count) = (Aabb::EMPTY, 0);
+    for plane in 0..BINS - 1 {
+        area = area.union(&bin_bounds[plane]);
+        count += counts[plane];
+
+        let (above_area, above_count) = above[plane];
+        if count == 0 || above_count == 0 {
+            continue;
+        }
+
+        let cost = TRAVERSAL_COST
+            + INTERSECTION_COST
+                * (area.surface_area() * count as f32 + above_area * above_count as f32)
+                / bounds.surface_area();
+
+        if cost < best_cost {
+            best_cost = cost;
+            best = Some(plane);
+        }
+    }
+
+    let plane = best?;
+    let leaf_cost = INTERSECTION_COST * indices.len() as f32;
+    if indices.len() <= MAX_LEAF_OBJECTS && leaf_cost <= best_cost {
+        return None;
+    }
+
+    // Partition in place, objects in bins up to the plane first
+    let mut mid = 0;
+    for j in 0..indices.len() {
+        if bin_of(indices[j]) <= plane {
+            indices.swap(mid, j);
+            mid += 1;
+        }
+    }
+
+    Some(mid)
+}
+
+fn flatten(node: BuildNode, nodes: &mut Vec<Node>) {
+    match node {
+        BuildNode::Leaf {
+            bounds,
+            first,
+            count,
+        } => nodes.push(Node::Leaf {
+            bounds,
+            first,
+            count,
+        }),
+        BuildNode::Interior {
+            bounds,
+            axis,
+            children,
+        } => {
+            let index = nodes.len();
+            nodes.push(Node::Interior {
+                bounds,
+                second: 0,
+                axis,
+            });
+
+            let [first, second] = *children;
+            flatten(first, nodes);
+
+            let second_index = nodes.len();
+            if let Node::Interior { second, .. } = &mut nodes[index] {
+                *second = second_index;
+            }
+
+            flatten(second, nodes);
+        }
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        self.tree.hit(ray, t_range, |index, range| {
+            self.objects[index]
+                .hit(ray, range)
+                .map(|hit| hit.on_object(index))
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree.bounds()
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        for object in self.objects.iter() {
+            object.validate(problems);
+        }
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_bvh(self.tree.shape());
+        stats.memory_bytes += std::mem::size_of_val(self)
+            + self.tree.memory_bytes()
+            + self.objects.capacity() * std::mem::size_of::<Arc<Box<dyn Hittable>>>();
+
+        for object in self.objects.iter() {
+            object.stats(stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        objects::Sphere,
+        scenes::{self, MaterialWeights},
+    };
+
+    #[test]
+    fn camera_renders_the_same_through_a_bvh() {
+        use crate::camera::Camera;
+
+        let world = scenes::random_spheres(5, 3, MaterialWeights::default());
+        let camera = Camera::builder()
+            .resolution(16, 9)
+            .samples(2)
+            .bounces(4)
+            .min_dist(0.001)
+            .position(Vec3::new(0.0, 1.0, 6.0))
+            .build()
+            .unwrap();
+
+        let bvh = Bvh::new(&world, BvhSplit::default());
+        assert_eq!(camera.render_rgb8(&world), camera.render_rgb8(&bvh));
+    }
+
+    #[test]
+    fn same_hits_as_list() {
+        let world = scenes::random_spheres(3, 4, MaterialWeights::default());
+        let trees = [
+            Bvh::new(&world, BvhSplit::Median),
+            Bvh::new(&world, BvhSplit::Sah),
+        ];
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..2000 {
+            let origin = Vec3::new(
+                rng.gen_range(-6.0..6.0),
+                rng.gen_range(0.1..3.0),
+                rng.gen_range(-6.0..6.0),
+            );
+            let direction = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..0.2),
+                rng.gen_range(-1.0..1.0),
+            );
+            let ray = Ray::new(origin, direction);
+
+            let expected = world.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+            for tree in &trees {
+                let actual = tree.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+                assert_eq!(expected, actual, "{ray:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_build() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut world = Hittables::default();
+        for _ in 0..4 * PARALLEL_OBJECTS {
+            world.add(Sphere {
+                center: Vec3::new(
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                ),
+                radius: 0.1,
+                ..Default::default()
+            });
+        }
+        let tree = Bvh::new(&world, BvhSplit::Sah);
+
+        let mut leaf_objects: Vec<usize> = tree
+            .tree
+            .nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Leaf { first, count, .. } => Some(*first..first + count),
+                Node::Interior { .. } => None,
+            })
+            .flat_map(|range| tree.tree.object_indices[range].to_vec())
+            .collect();
+        leaf_objects.sort();
+        assert_eq!(leaf_objects, (0..world.objects.len()).collect::<Vec<_>>());
+
+        for _ in 0..500 {
+            let ray = Ray::new(
+                Vec3::new(0.0, 0.0, 20.0),
+                Vec3::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5), -1.0),
+            );
+            let expected = world.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+            let actual = tree.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+            assert_eq!(expected, actual, "{ray:?}");
+        }
+    }
+
+    #[test]
+    fn sah_is_cheaper_than_median() {
+        let world = scenes::random_spheres(3, 11, MaterialWeights::default());
+
+        let median = Bvh::new(&world, BvhSplit::Median).sah_cost();
+        let sah = Bvh::new(&world, BvhSplit::Sah).sah_cost();
+
+        assert!(sah < median, "SAH {sah} vs median {median}");
+    }
+
+    #[test]
+    fn stats_show_the_tree_shape() {
+        let world = scenes::random_spheres(3, 11, MaterialWeights::default());
+        let mut stats = SceneStats::default();
+        Bvh::new(&world, BvhSplit::Median).stats(&mut stats);
+
+        let bvh = stats.bvh.unwrap();
+        assert_eq!(bvh.leaf_objects, world.objects.len());
+        assert!(bvh.leaf_sizes.0 >= 1 && bvh.leaf_sizes.1 <= MAX_LEAF_OBJECTS);
+        // Halving each level needs at least this many levels to get down to full leaves
+        let min_depth = (world.objects.len() as f32 / MAX_LEAF_OBJECTS as f32)
+            .log2()
+            .ceil();
+        assert!(bvh.depth >= min_depth as usize, "{bvh:?}");
+    }
+}
@@ -1,11 +1,372 @@
-use std::{ops::Range, path::Path};
+use std::{
+    convert::Infallible,
+    ops::{ControlFlow, Range},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use bevy_color::{Color, ColorToComponents, ColorToPacked, LinearRgba, Mix, Srgba};
-use bevy_math::{vec3, Vec2, Vec3, VectorSpace};
-use rand::random;
+use anyhow::ensure;
+use bevy_color::{Alpha, Color, ColorToComponents, LinearRgba};
+use bevy_math::{vec3, Dir3, Mat3, Quat, UVec2, Vec2, Vec3, VectorSpace};
+use rand::{rngs::SmallRng, Rng};
+use rayon::prelude::*;
+use tracing::info;
 
-use crate::{hittable::Hittable, ppm, ray};
+use crate::{
+    background::Background,
+    cancel::{CancellationToken, RenderStatus},
+    environment::EnvironmentMap,
+    exposure::Exposure,
+    hittable::{Hit, Hittable, Hittables},
+    lens::LensSystem,
+    light::{Light, LightSample},
+    material::{DynMaterial, Lobe},
+    output::{self, ImageFormat, Quantized},
+    output_color::{BitDepth, Dither, OutputColor, Transfer},
+    pdf::{HittablePdf, MaterialPdf, MixturePdf, Pdf},
+    random,
+    ray::{self, RayKind, RayMask},
+    render::{Accumulation, Progress, Snapshot, TileScheduler},
+    sampler::PixelSampler,
+    stats,
+    tile::Tile,
+    tonemap::Tonemap,
+};
 
+/// Separate bounce budgets per kind of scattering.
+///
+/// Glass needs many transmission bounces to look right,
+/// while diffuse interreflection converges after a few.
+#[derive(Debug, Clone, Copy)]
+pub struct DepthLimits {
+    pub diffuse: usize,
+    pub specular: usize,
+    pub transmission: usize,
+}
+
+impl DepthLimits {
+    /// No per-lobe limits, only the total bounce count applies.
+    pub const UNLIMITED: Self = Self {
+        diffuse: usize::MAX,
+        specular: usize::MAX,
+        transmission: usize::MAX,
+    };
+
+    fn remaining(&mut self, lobe: Lobe) -> &mut usize {
+        match lobe {
+            Lobe::Diffuse | Lobe::Volume => &mut self.diffuse,
+            Lobe::Specular => &mut self.specular,
+            Lobe::Transmission => &mut self.transmission,
+        }
+    }
+}
+
+/// How far along a ray to start looking for hits,
+/// so that a ray leaving a surface does not hit that same surface due to floating point error.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum RayBias {
+    /// Always start at the camera's `min_dist`.
+    #[default]
+    Constant,
+
+    /// Start at this fraction of the distance the previous ray travelled,
+    /// but never closer than `min_dist`.
+    /// Hit points far away are less precise, so they need a larger margin.
+    DistanceScaled(f32),
+}
+
+impl RayBias {
+    /// The start of the hit range for a ray leaving a hit `travelled` away from the previous origin.
+    /// Rays leaving the camera have travelled nowhere.
+    pub fn t_min(&self, min_dist: f32, travelled: f32) -> f32 {
+        match self {
+            RayBias::Constant => min_dist,
+            RayBias::DistanceScaled(scale) => min_dist.max(scale * travelled),
+        }
+    }
+}
+
+/// Sets up a [`Camera`], checking the settings and deriving the viewport once at the end.
+///
+/// ```
+/// # use bevy_math::Vec3;
+/// let camera = rt_one::camera::Camera::builder()
+///     .resolution(400, 225)
+///     .samples(32)
+///     .bounces(50)
+///     .position(Vec3::new(0.0, 1.0, 3.0))
+///     .look_at(Vec3::ZERO, Vec3::Y)
+///     .vfov(40.0)
+///     .build()?;
+/// # anyhow::Ok(())
+/// ```
+#[must_use]
+pub struct CameraBuilder {
+    camera: Camera,
+    /// Target and up, applied once the viewport is known.
+    look_at: Option<(Vec3, Vec3)>,
+}
+
+impl CameraBuilder {
+    pub fn resolution(mut self, width: usize, height: usize) -> Self {
+        self.camera.im_width = width;
+        self.camera.im_height = height;
+        self
+    }
+
+    pub fn samples(mut self, samples_per_pixel: usize) -> Self {
+        self.camera.samples_per_pixel = samples_per_pixel;
+        self
+    }
+
+    pub fn bounces(mut self, bounces: usize) -> Self {
+        self.camera.bounce = bounces;
+        self
+    }
+
+    pub fn min_dist(mut self, min_dist: f32) -> Self {
+        self.camera.min_dist = min_dist;
+        self
+    }
+
+    /// Encode quantized images as sRGB, or else leave them linear.
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.camera.output_color.transfer = if srgb {
+            Transfer::Srgb
+        } else {
+            Transfer::Linear
+        };
+        self
+    }
+
+    /// See `Camera::output_color`.
+    pub fn output_color(mut self, output_color: OutputColor) -> Self {
+        self.camera.output_color = output_color;
+        self
+    }
+
+    /// See `Camera::tonemap`.
+    pub fn tonemap(mut self, tonemap: Tonemap) -> Self {
+        self.camera.tonemap = tonemap;
+        self
+    }
+
+    /// See `Camera::exposure_compensation`.
+    pub fn exposure_compensation(mut self, stops: f32) -> Self {
+        self.camera.exposure_compensation = stops;
+        self
+    }
+
+    pub fn position(mut self, position: Vec3) -> Self {
+        self.camera.cam_origin = position;
+        self
+    }
+
+    pub fn orientation(mut self, orientation: Quat) -> Self {
+        self.camera.orientation = orientation;
+        self
+    }
+
+    /// See [`Camera::look_at`].
+    pub fn look_at(mut self, target: Vec3, up: Vec3) -> Self {
+        self.look_at = Some((target, up));
+        self
+    }
+
+    pub fn focal_length(mut self, focal_length: f32) -> Self {
+        self.camera.focal_length = focal_length;
+        self
+    }
+
+    /// Vertical field of view in degrees.
+    pub fn vfov(mut self, degrees: f32) -> Self {
+        self.camera.vfov = Some(degrees);
+        self
+    }
+
+    /// See `Camera::defocus_angle`.
+    pub fn defocus(mut self, angle: f32, focus_distance: f32) -> Self {
+        self.camera.defocus_angle = angle;
+        self.camera.focus_distance = focus_distance;
+        self
+    }
+
+    pub fn lens(mut self, lens: LensSystem) -> Self {
+        self.camera.lens = Some(lens);
+        self
+    }
+
+    /// See `Camera::exposure`. Replaces the defocus angle and shutter when built.
+    pub fn exposure(mut self, exposure: Exposure) -> Self {
+        self.camera.exposure = Some(exposure);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.camera.seed = seed;
+        self
+    }
+
+    pub fn progress(mut self, progress: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.camera.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Where within each pixel the samples go, e.g. [`PixelSampler::Stratified`]
+    /// for less noise at the same sample count.
+    pub fn sampler(mut self, sampler: PixelSampler) -> Self {
+        self.camera.sampler = sampler;
+        self
+    }
+
+    pub fn tile_size(mut self, tile_size: usize) -> Self {
+        self.camera.tile_size = tile_size;
+        self
+    }
+
+    /// What rays which miss everything see, e.g. [`Background::BLACK`] for scenes lit by lamps.
+    pub fn background(mut self, background: impl Into<Background>) -> Self {
+        self.camera.background = background.into();
+        self
+    }
+
+    /// See `Camera::shutter`. E.g. `0.0..1.0` for the full motion of moving objects.
+    pub fn shutter(mut self, shutter: Range<f32>) -> Self {
+        self.camera.shutter = shutter;
+        self
+    }
+
+    /// See `Camera::transparent_background`.
+    pub fn transparent_background(mut self, transparent: bool) -> Self {
+        self.camera.transparent_background = transparent;
+        self
+    }
+
+    /// See `Camera::max_radiance`.
+    pub fn max_radiance(mut self, max_radiance: f32) -> Self {
+        self.camera.max_radiance = Some(max_radiance);
+        self
+    }
+
+    /// See `Camera::max_indirect`.
+    pub fn max_indirect(mut self, max_indirect: f32) -> Self {
+        self.camera.max_indirect = Some(max_indirect);
+        self
+    }
+
+    pub fn light(mut self, light: Light) -> Self {
+        self.camera.lights.push(light);
+        self
+    }
+
+    /// See `Camera::light_objects`.
+    pub fn light_object(mut self, object: impl Hittable + 'static) -> Self {
+        self.camera.light_objects.add(object);
+        self
+    }
+
+    pub fn reflectance_groups(mut self, reflectance_groups: bool) -> Self {
+        self.camera.reflectance_groups = reflectance_groups;
+        self
+    }
+
+    /// Check the settings and derive the viewport.
+    pub fn build(self) -> anyhow::Result<Camera> {
+        let Self {
+            mut camera,
+            look_at,
+        } = self;
+
+        ensure!(
+            camera.im_width > 0 && camera.im_height > 0,
+            "camera: image size {}x{} has no pixels",
+            camera.im_width,
+            camera.im_height
+        );
+        ensure!(
+            camera.samples_per_pixel > 0,
+            "camera: zero samples per pixel renders black"
+        );
+        ensure!(camera.tile_size > 0, "camera: tile size is zero");
+        ensure!(
+            camera.cam_origin.is_finite(),
+            "camera: position {} is not finite",
+            camera.cam_origin
+        );
+        ensure!(
+            camera.min_dist >= 0.0,
+            "camera: negative min_dist {}",
+            camera.min_dist
+        );
+        ensure!(
+            camera.focal_length > 0.0,
+            "camera: focal length {} is not positive",
+            camera.focal_length
+        );
+        for (name, clamp) in [
+            ("max radiance", camera.max_radiance),
+            ("max indirect", camera.max_indirect),
+        ] {
+            if let Some(clamp) = clamp {
+                ensure!(clamp > 0.0, "camera: {name} {clamp} is not positive");
+            }
+        }
+        if let Some(vfov) = camera.vfov {
+            ensure!(
+                vfov > 0.0 && vfov < 180.0,
+                "camera: vertical field of view {vfov} is not between 0 and 180 degrees"
+            );
+        }
+        if let Some(exposure) = &camera.exposure {
+            // Anything else makes the brightness infinite or NaN
+            for (name, value) in [
+                ("f-number", exposure.f_number),
+                ("shutter time", exposure.shutter_time),
+                ("ISO", exposure.iso),
+            ] {
+                ensure!(
+                    value > 0.0 && value.is_finite(),
+                    "camera: exposure {name} {value} is not positive"
+                );
+            }
+            // The aperture is the base of the cone of rays meeting at the focus distance
+            let aperture_radius = exposure.aperture_diameter(camera.focal_length) / 2.0;
+            camera.defocus_angle = 2.0
+                * (aperture_radius / camera.focus_distance)
+                    .atan()
+                    .to_degrees();
+            camera.shutter = exposure.shutter_interval();
+        }
+        ensure!(
+            camera.shutter.start <= camera.shutter.end,
+            "camera: shutter closes at {} before it opens at {}",
+            camera.shutter.end,
+            camera.shutter.start
+        );
+        ensure!(
+            camera.defocus_angle <= 0.0 || camera.focus_distance > 0.0,
+            "camera: focus distance {} is not in front of the camera",
+            camera.focus_distance
+        );
+
+        match look_at {
+            Some((target, up)) => {
+                camera.look_at(target, up)?;
+            }
+            None => camera.update_viewport(),
+        }
+
+        Ok(camera)
+    }
+}
+
+/// See [`Camera::progress`].
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// Prefer [`Camera::builder`] for setting one up.
+/// Fields can still be changed afterwards, e.g. to override settings,
+/// followed by [`Camera::update_viewport`] if they affect the viewport.
 #[allow(dead_code)]
 pub struct Camera {
     pub im_width: usize,
@@ -24,94 +385,408 @@ pub struct Camera {
     pub focal_length: f32,
     pub cam_origin: Vec3,
 
+    /// Depth of field: the cone angle in degrees of rays through each pixel,
+    /// with its tip at `focus_distance`. 0 keeps everything sharp like a pinhole.
+    /// Ignored when rendering through a `lens`.
+    pub defocus_angle: f32,
+
+    /// Distance from the camera to the plane in perfect focus.
+    pub focus_distance: f32,
+
+    /// If set, the vertical field of view in degrees, which then decides `viewport_height`
+    /// from `focal_length`. Small values zoom in like a telephoto lens, large ones go wide.
+    pub vfov: Option<f32>,
+
+    /// Rotation from the default view, which looks towards -Z with +Y up.
+    pub orientation: Quat,
+
     pub samples_per_pixel: usize,
     pub bounce: usize,
     pub min_dist: f32,
-    pub srgb_output: bool,
+    pub ray_bias: RayBias,
+
+    /// Scattered rays start this far off the surface along its normal.
+    /// Removes acne on grazing hits which `min_dist` alone misses,
+    /// which in turn allows a smaller `min_dist` for small scenes.
+    pub normal_offset: f32,
+
+    /// How 8- and 16-bit images are encoded, quantized and dithered.
+    /// Float images are left linear.
+    pub output_color: OutputColor,
+
+    /// How radiance is fit into the display's range when quantizing, before the `output_color`.
+    /// Float images are left linear.
+    pub tonemap: Tonemap,
+
+    /// Brightens quantized images by this many stops before the `tonemap`, or darkens if negative.
+    /// Unlike `exposure` it leaves float images alone.
+    pub exposure_compensation: f32,
 
     /// If true, change reflectance by column
     /// in 5 groups from 10% up to 90% (20% steps)
     pub reflectance_groups: bool,
+
+    /// If set, primary rays are traced through this lens system
+    /// instead of the ideal pinhole.
+    pub lens: Option<LensSystem>,
+
+    /// If set, aperture, shutter and ISO decide how bright the image is.
+    /// When built, the aperture also sets `defocus_angle` and the shutter time `shutter`.
+    pub exposure: Option<Exposure>,
+
+    /// If set, paths are also cut short when a single kind of bounce is exhausted.
+    /// The total is still capped by `bounce`.
+    pub depth_limits: Option<DepthLimits>,
+
+    /// If set, no sample brings more than this radiance to its pixel, so the rare bright
+    /// path doesn't show as a lone white pixel at low sample counts. Colors are scaled down
+    /// keeping their hue. Darkens what is truly that bright, e.g. lamps seen directly.
+    pub max_radiance: Option<f32>,
+
+    /// If set, light which has bounced off more than one surface is clamped like
+    /// `max_radiance`, which is where fireflies come from, e.g. caustics off glass.
+    /// Direct lighting is left alone.
+    pub max_indirect: Option<f32>,
+
+    /// If set, the image is rendered in passes of one sample per pixel,
+    /// stopping when the time is up. `samples_per_pixel` is then the most passes done.
+    /// The first pass always completes.
+    pub time_budget: Option<Duration>,
+
+    /// Decides the random numbers used for each sample, see [`crate::random`].
+    /// The same seed renders the same image.
+    pub seed: u64,
+
+    /// Where within each pixel the samples go.
+    pub sampler: PixelSampler,
+
+    /// If set, called as each tile of a render finishes, e.g. to show a progress bar.
+    /// Time budgeted renders go by passes rather than tiles, and don't report progress.
+    pub progress: Option<ProgressCallback>,
+
+    /// If set, renders use this many threads instead of one per core, e.g. for benchmarking.
+    pub threads: Option<usize>,
+
+    /// Renders are split into tiles this many pixels across, which threads take one at a time.
+    /// See [`TileScheduler`].
+    pub tile_size: usize,
+
+    /// Lights sampled at every diffuse hit, in addition to the sky.
+    pub lights: Vec<Light>,
+
+    /// Seen by rays which miss everything. The sky by default.
+    pub background: Background,
+
+    /// Emissive objects, e.g. the lamps of a room, which scattered rays are aimed at
+    /// half of the time instead of waiting to find them by chance. They must also be in the
+    /// world to be seen, share them with `Arc`. Empty by default.
+    /// Unlike `lights` this only changes the noise, not the image it converges to.
+    pub light_objects: Hittables,
+
+    /// Rays are sent at random times within this interval, so objects moving during it blur.
+    /// Moving objects such as [`crate::objects::MovingSphere`] go from where they are
+    /// at time 0 to where they are at time 1. Empty by default, freezing everything at 0.
+    pub shutter: Range<f32>,
+
+    /// The kinds of rays which see the `background`. With only [`RayMask::CAMERA`] it shows
+    /// as a backdrop but lights nothing, leaving that to `lights`.
+    pub sky_visibility: RayMask,
+
+    /// If set, camera rays which miss everything are transparent instead of showing the
+    /// `background`, for compositing over another. It still lights the scene.
+    /// PNG images then get an alpha channel, EXR images always have one.
+    pub transparent_background: bool,
+
+    /// If set, every surface is shaded with this instead of its own material,
+    /// e.g. [`Camera::CLAY`] to check lighting and form.
+    pub material_override: Option<DynMaterial>,
 }
 
-impl Camera {
-    pub fn new() -> Self {
-        Self::with_samples_per_pixel(1)
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    pub fn with_samples_per_pixel(samples: usize) -> Self {
+impl Camera {
+    /// The neutral gray of a clay render, see `material_override`.
+    pub const CLAY: LinearRgba = LinearRgba::rgb(0.5, 0.5, 0.5);
+
+    pub fn new() -> Self {
         let im_width = 600;
 
         // the width/height relationship
-        let mut aspect_ratio = 16. / 9.;
+        let aspect_ratio = 16. / 9.;
 
         let im_height = ((im_width as f32 / aspect_ratio) as usize).max(1);
 
-        // recalc since height might have been modified
-        aspect_ratio = im_width as f32 / im_height as f32;
+        let mut camera = Self {
+            im_width,
+            im_height,
+            aspect_ratio,
+            viewport_height: 2.0,
+            viewport_width: 0.0,
+            viewport_u: Vec3::ZERO,
+            viewport_v: Vec3::ZERO,
+            du: Vec3::ZERO,
+            dv: Vec3::ZERO,
+            viewport_origin: Vec3::ZERO,
+            pixel00_origin: Vec3::ZERO,
+            focal_length: 1.0,
+            cam_origin: Vec3::ZERO,
+            defocus_angle: 0.0,
+            focus_distance: 10.0,
+            vfov: None,
+            orientation: Quat::IDENTITY,
+            samples_per_pixel: 1,
+            bounce: 0,
+            min_dist: 0.0,
+            ray_bias: RayBias::default(),
+            normal_offset: 0.0,
+            output_color: OutputColor::default(),
+            tonemap: Tonemap::None,
+            exposure_compensation: 0.0,
+            reflectance_groups: false,
+            lens: None,
+            exposure: None,
+            depth_limits: None,
+            max_radiance: None,
+            max_indirect: None,
+            time_budget: None,
+            seed: 0,
+            sampler: PixelSampler::default(),
+            progress: None,
+            threads: None,
+            tile_size: 32,
+            lights: vec![],
+            background: Background::SKY,
+            light_objects: Hittables::default(),
+            shutter: 0.0..0.0,
+            sky_visibility: RayMask::ALL,
+            transparent_background: false,
+            material_override: None,
+        };
+        camera.update_viewport();
 
-        let viewport_height = 2.0;
-        let viewport_width = aspect_ratio * viewport_height;
+        camera
+    }
 
-        let focal_length = 1.0;
-        let cam_origin = Vec3::ZERO;
+    #[deprecated(note = "use `Camera::builder().samples(..)`")]
+    pub fn with_samples_per_pixel(samples: usize) -> Self {
+        Self {
+            samples_per_pixel: samples,
+            ..Self::new()
+        }
+    }
 
-        let viewport_u = vec3(viewport_width, 0.0, 0.0);
-        let viewport_v = vec3(0.0, -viewport_height, 0.0);
+    /// Configure a camera, starting from the defaults of [`Camera::new`].
+    pub fn builder() -> CameraBuilder {
+        CameraBuilder {
+            camera: Self::new(),
+            look_at: None,
+        }
+    }
+
+    /// Recalculate the viewport from the image size, viewport height (or field of view),
+    /// focal length, origin and orientation. Needed after changing any of those.
+    pub fn update_viewport(&mut self) {
+        if let Some(vfov) = self.vfov {
+            self.viewport_height = 2.0 * self.focal_length * (vfov.to_radians() / 2.0).tan();
+        }
+
+        // recalc since height might have been modified
+        self.aspect_ratio = self.im_width as f32 / self.im_height as f32;
+
+        self.viewport_width = self.aspect_ratio * self.viewport_height;
 
-        let du = viewport_u / im_width as f32;
-        let dv = viewport_v / im_height as f32;
+        self.viewport_u = self.orientation * vec3(self.viewport_width, 0.0, 0.0);
+        self.viewport_v = self.orientation * vec3(0.0, -self.viewport_height, 0.0);
+
+        self.du = self.viewport_u / self.im_width as f32;
+        self.dv = self.viewport_v / self.im_height as f32;
 
         // Viewport is at cam origin, then focal length in negative Z (forward) dir,
         // then we offset by the viewport horizontally and vertically since we'll iter over
         // that in parts.
-        let viewport_origin =
-            cam_origin - vec3(0.0, 0.0, focal_length) - viewport_u / 2. - viewport_v / 2.;
+        self.viewport_origin = self.cam_origin
+            - self.orientation * vec3(0.0, 0.0, self.focal_length)
+            - self.viewport_u / 2.
+            - self.viewport_v / 2.;
 
         // Make sure pixels are located in the middle of grid
-        let pixel00_origin = viewport_origin + 0.5 * (du + dv);
+        self.pixel00_origin = self.viewport_origin + 0.5 * (self.du + self.dv);
+    }
 
-        Self {
-            im_width,
-            im_height,
-            aspect_ratio,
-            viewport_height,
-            viewport_width,
-            viewport_u,
-            viewport_v,
-            du,
-            dv,
-            viewport_origin,
-            pixel00_origin,
-            focal_length,
-            cam_origin,
-            samples_per_pixel: samples,
-            bounce: 0,
-            min_dist: 0.0,
-            srgb_output: false,
-            reflectance_groups: false,
+    /// Point the camera at `target`, keeping `up` pointing upwards in the image.
+    /// Fails if `target` is the camera position or `up` is parallel to the view direction.
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) -> anyhow::Result<()> {
+        ensure!(
+            target != self.cam_origin,
+            "camera: can't look at its own position {target}"
+        );
+        // Else there is no telling which way is right, and the orientation comes out NaN
+        let forward = (target - self.cam_origin).normalize();
+        ensure!(
+            forward.cross(up.normalize_or_zero()).length_squared() > 1e-8,
+            "camera: up {up} is not at an angle to the view direction {forward}"
+        );
+        let right = forward.cross(up).normalize();
+        let up = right.cross(forward);
+
+        self.orientation = Quat::from_mat3(&Mat3::from_cols(right, up, -forward));
+        self.update_viewport();
+        Ok(())
+    }
+
+    /// Move the camera backwards until the whole scene is in view,
+    /// with `margin` (e.g. 1.1 for 10%) extra room around it.
+    pub fn frame(&mut self, scene: &dyn Hittable, margin: f32) {
+        let bounds = scene.bounding_box();
+        if bounds.is_empty() {
+            return;
         }
+
+        // Fit the bounding sphere of the box within the narrowest field of view
+        let radius = bounds.size().length() * 0.5 * margin;
+        let half_extent = self.viewport_height.min(self.viewport_width) * 0.5;
+        let half_fov = (half_extent / self.focal_length).atan();
+
+        let distance = radius / half_fov.sin();
+
+        self.cam_origin = bounds.center() + self.orientation * vec3(0.0, 0.0, distance);
+        self.update_viewport();
     }
 
-    // Range is +- 0.5 on both axes
-    fn sample_unit_square() -> Vec2 {
-        let r = || random::<f32>() - 1.;
-        Vec2::new(r(), r())
+    // Range is [-0.5, 0.5) on both axes
+    fn sample_unit_square(
+        &self,
+        row: usize,
+        col: usize,
+        sample: usize,
+        rng: &mut impl Rng,
+    ) -> Vec2 {
+        let pixel = UVec2::new(col as u32, row as u32);
+        self.sampler
+            .sample(self.seed, pixel, sample, self.samples_per_pixel, rng)
+            - 0.5
     }
 
-    fn get_ray(&self, row: usize, col: usize) -> ray::Ray {
-        let pixel = self.pixel00_origin + (row as f32 * self.dv) + (col as f32 * self.du);
+    /// A ray through the given pixel for the given sample.
+    /// Returns `None` if the ray did not make it out of the lens.
+    fn get_ray(
+        &self,
+        row: usize,
+        col: usize,
+        sample: usize,
+        rng: &mut impl Rng,
+    ) -> Option<ray::Ray> {
+        let ray = self.get_frozen_ray(row, col, sample, rng)?;
+
+        let Range { start, end } = self.shutter;
+        if end > start {
+            return Some(ray.with_time(rng.gen_range(start..end)));
+        }
+        Some(ray.with_time(start))
+    }
+
+    /// Like [`Camera::get_ray`], before the ray is given a time.
+    fn get_frozen_ray(
+        &self,
+        row: usize,
+        col: usize,
+        sample: usize,
+        rng: &mut impl Rng,
+    ) -> Option<ray::Ray> {
+        let perturb = self.sample_unit_square(row, col, sample, rng);
+
+        if let Some(lens) = &self.lens {
+            return self.get_lens_ray(lens, row, col, perturb, rng);
+        }
 
-        let perturb = Self::sample_unit_square();
+        let pixel = self.pixel00_origin + (row as f32 * self.dv) + (col as f32 * self.du);
 
         let mut pixel = pixel + perturb.x * self.du;
         pixel += perturb.y * self.dv;
 
         // Unit direction from camera to pixel
         let dir = -self.cam_origin + pixel;
-        ray::Ray::new(self.cam_origin, dir)
+        if self.defocus_angle <= 0.0 {
+            return Some(ray::Ray::new(self.cam_origin, dir));
+        }
+
+        // The pixel grid scaled out to the focus plane, where it stays sharp
+        let focus_point = self.cam_origin + dir * (self.focus_distance / self.focal_length);
+
+        let disk = random::random_in_disk(rng) * self.defocus_radius();
+        let origin = self.cam_origin + self.orientation * disk.extend(0.0);
+
+        Some(ray::Ray::new(origin, focus_point - origin))
     }
 
+    /// Radius of the disk primary rays start from, see `defocus_angle`.
+    pub fn defocus_radius(&self) -> f32 {
+        self.focus_distance * (self.defocus_angle.to_radians() / 2.0).tan()
+    }
+
+    fn get_lens_ray(
+        &self,
+        lens: &LensSystem,
+        row: usize,
+        col: usize,
+        perturb: Vec2,
+        rng: &mut impl Rng,
+    ) -> Option<ray::Ray> {
+        // range: [0.0, 1.0] across the image
+        let s = (col as f32 + 0.5 + perturb.x) / self.im_width as f32;
+        let t = (row as f32 + 0.5 + perturb.y) / self.im_height as f32;
+
+        // The lens flips the image, so the sensor is mirrored on both axes
+        let sensor_width = lens.sensor_height * self.aspect_ratio;
+        let sensor = Vec2::new(
+            (0.5 - s) * sensor_width,
+            (t - 0.5) * lens.sensor_height,
+        );
+
+        let (origin, direction) = lens.trace_from_sensor(sensor, rng)?;
+
+        Some(ray::Ray::new(
+            self.cam_origin + self.orientation * origin,
+            self.orientation * *direction,
+        ))
+    }
+
+    /// The camera ray of the given sample through a pixel, the same one the render traces.
+    /// Returns `None` if the ray did not make it out of the lens.
+    pub fn sample_ray(&self, row: usize, col: usize, sample: usize) -> Option<ray::Ray> {
+        self.get_ray(row, col, sample, &mut self.sample_rng(row, col, sample))
+    }
+
+    /// The random numbers for the given sample through a pixel. Also seeds this thread's
+    /// generator for the objects which use it, see [`crate::random`].
+    fn sample_rng(&self, row: usize, col: usize, sample: usize) -> SmallRng {
+        let pixel = row * self.im_width + col;
+        random::seed_sample(self.seed, pixel, sample);
+        random::sample_rng(self.seed, pixel, sample)
+    }
+
+    /// The ray through the exact center of a pixel.
+    /// Ignores the lens, so the result is the same every time.
+    pub fn pixel_center_ray(&self, row: usize, col: usize) -> ray::Ray {
+        let pixel = self.pixel00_origin + (row as f32 * self.dv) + (col as f32 * self.du);
+
+        ray::Ray::new(self.cam_origin, pixel - self.cam_origin)
+    }
+
+    /// What is seen through the center of a pixel, and where, see [`Hit::object_id`] for which object.
+    /// Useful for debugging a single pixel.
+    pub fn inspect(&self, world: &dyn Hittable, row: usize, col: usize) -> Option<Hit> {
+        let ray = self.pixel_center_ray(row, col);
+        let min_dist = self.ray_bias.t_min(self.min_dist, 0.0);
+
+        world.hit(&ray, min_dist..10_000_000.0)
+    }
+
+    #[allow(dead_code)]
     fn reflectance(&self, col: usize) -> f32 {
         if self.reflectance_groups {
             // range: [0.0, 1.0)
@@ -125,90 +800,457 @@ impl Camera {
             // steps: [0.0, 0.2, 0.4, 0.6, 0.8]
             let reflectance = reflectance * 2.0;
             // steps: [0.1, 0.3, 0.5, 0.7, 0.9]
-            let reflectance = reflectance + 0.1;
-
-            reflectance
+            reflectance + 0.1
         } else {
             0.5
         }
     }
 
-    pub fn render(
+    /// The radiance of the given sample through a pixel, before exposure.
+    fn sample_pixel(
         &self,
         world: &dyn Hittable,
-        output_file: impl AsRef<Path>,
-    ) -> anyhow::Result<()> {
-        let mut data = vec![];
-
-        for row in 0..self.im_height {
-            for col in 0..self.im_width {
-                let mut color: LinearRgba = LinearRgba::ZERO;
-
-                for _ in 0..self.samples_per_pixel {
-                    let ray = self.get_ray(row, col);
-
-                    let max_dist = 10_000_000.0;
-
-                    if self.bounce > 0 {
-                        color += self
-                            .world_color_bounce(
-                                &ray,
-                                world,
-                                self.min_dist..max_dist,
-                                self.bounce,
-                                // self.reflectance(col),
-                            )
-                            .to_linear();
-                    } else {
-                        color += self
-                            .world_color(&ray, world, self.min_dist..max_dist)
-                            .to_linear();
+        row: usize,
+        col: usize,
+        sample: usize,
+    ) -> LinearRgba {
+        // Vignetted by the lens, no light gets through
+        let mut rng = self.sample_rng(row, col, sample);
+        let Some(ray) = self.get_ray(row, col, sample, &mut rng) else {
+            return LinearRgba::BLACK;
+        };
+
+        let max_dist = 10_000_000.0;
+        let min_dist = self.ray_bias.t_min(self.min_dist, 0.0);
+
+        let color = if self.bounce > 0 {
+            self.world_color_bounce(
+                &ray,
+                world,
+                min_dist..max_dist,
+                self.bounce,
+                self.depth_limits.unwrap_or(DepthLimits::UNLIMITED),
+                &mut rng,
+                // self.reflectance(col),
+            )
+            .to_linear()
+        } else {
+            self.world_color(&ray, world, min_dist..max_dist)
+                .to_linear()
+        };
+
+        LinearRgba::from_vec3(clamp_radiance(color.to_vec3(), self.max_radiance))
+            .with_alpha(color.alpha)
+    }
+
+    fn expose(&self, color: LinearRgba) -> LinearRgba {
+        match &self.exposure {
+            Some(exposure) => (color * exposure.brightness()).with_alpha(color.alpha),
+            None => color,
+        }
+    }
+
+    /// The radiance of one sample through a pixel, exposed.
+    /// Samples are numbered, each number drawing its own random numbers,
+    /// so accumulating samples `0..n` one at a time gives what [`Camera::render_pixel`] averages.
+    pub fn render_sample(
+        &self,
+        world: &dyn Hittable,
+        row: usize,
+        col: usize,
+        sample: usize,
+    ) -> LinearRgba {
+        self.expose(self.sample_pixel(world, row, col, sample))
+    }
+
+    /// The averaged radiance of all samples through a single pixel.
+    pub fn render_pixel(&self, world: &dyn Hittable, row: usize, col: usize) -> LinearRgba {
+        let mut color: LinearRgba = LinearRgba::ZERO;
+
+        for sample in 0..self.samples_per_pixel {
+            color += self.sample_pixel(world, row, col, sample);
+        }
+
+        self.expose(color / self.samples_per_pixel as f32)
+    }
+
+    /// Render the full image without quantizing, tiles in parallel.
+    /// Pixels are row-major, starting at the top left.
+    pub fn render_linear(&self, world: &dyn Hittable) -> Vec<LinearRgba> {
+        if self.time_budget.is_some() {
+            return self.render_progressive(world, 0, |_| ControlFlow::Continue(()));
+        }
+
+        let result =
+            TileScheduler::new(self, self.tile_size).run(world, &CancellationToken::new(), |_| {
+                Ok::<_, Infallible>(())
+            });
+        let Ok((_, image)) = result;
+        image
+    }
+
+    /// Run `f` on a pool of `threads` threads if set, else on the global rayon pool.
+    pub(crate) fn in_thread_pool<T: Send>(&self, f: impl FnOnce() -> T + Send) -> T {
+        match self.threads {
+            Some(threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("creating a thread pool should succeed")
+                .install(f),
+            None => f(),
+        }
+    }
+
+    /// Render lazily, one pixel per item as `(x, y, color)`, row by row from the top left.
+    /// Nothing is rendered until the iterator is advanced, so it can be stopped at any point.
+    pub fn pixels<'a>(
+        &'a self,
+        world: &'a dyn Hittable,
+    ) -> impl Iterator<Item = (usize, usize, LinearRgba)> + 'a {
+        (0..self.im_height).flat_map(move |row| {
+            (0..self.im_width).map(move |col| (col, row, self.render_pixel(world, row, col)))
+        })
+    }
+
+    /// Render only the pixels within the tile, row-major.
+    pub fn render_tile(&self, world: &dyn Hittable, tile: &Tile) -> Vec<LinearRgba> {
+        tile.pixels()
+            .map(|(row, col)| self.render_pixel(world, row, col))
+            .collect()
+    }
+
+    /// Render tile by tile, calling `on_tile_complete` with the pixels of each tile as soon as it's done,
+    /// row-major within the tile. Tiles render in parallel and finish in any order.
+    /// Returning an error from the callback stops the render.
+    ///
+    /// Returns the full image, row-major.
+    pub fn render_tiles<E>(
+        &self,
+        world: &dyn Hittable,
+        tile_size: usize,
+        mut on_tile_complete: impl FnMut(&Tile, &[LinearRgba]) -> Result<(), E>,
+    ) -> Result<Vec<LinearRgba>, E> {
+        TileScheduler::new(self, tile_size)
+            .run(world, &CancellationToken::new(), |done| {
+                on_tile_complete(&done.tile, done.pixels)
+            })
+            .map(|(_, image)| image)
+    }
+
+    /// Render tile by tile, starting no new tiles once the token is cancelled.
+    /// If cancelled, the tiles not yet rendered are left transparent black.
+    pub fn render_cancellable(
+        &self,
+        world: &dyn Hittable,
+        tile_size: usize,
+        token: &CancellationToken,
+    ) -> (RenderStatus, Vec<LinearRgba>) {
+        let result =
+            TileScheduler::new(self, tile_size).run(world, token, |_| Ok::<_, Infallible>(()));
+        let Ok(rendered) = result;
+        rendered
+    }
+
+    /// Render in passes of one sample per pixel over the whole image, accumulating them,
+    /// until `samples_per_pixel` passes are done, the time budget runs out
+    /// or `on_snapshot` breaks. The first pass always finishes.
+    ///
+    /// Every `snapshot_every` passes (never if 0) `on_snapshot` is shown how far the render is,
+    /// e.g. for previews. The returned image is the same as [`Camera::render_linear`] gives
+    /// for the same number of samples.
+    pub fn render_progressive(
+        &self,
+        world: &dyn Hittable,
+        snapshot_every: usize,
+        on_snapshot: impl FnMut(&Snapshot) -> ControlFlow<()> + Send,
+    ) -> Vec<LinearRgba> {
+        let mut accumulation = Accumulation::new(self.im_width, self.im_height);
+        self.resume_progressive(world, &mut accumulation, snapshot_every, on_snapshot)
+    }
+
+    /// Like [`Camera::render_progressive`], continuing from the samples already accumulated,
+    /// e.g. from a [`crate::checkpoint::Checkpoint`].
+    ///
+    /// Each sample draws from its own random numbers, seeded by the camera's seed,
+    /// the pixel and which sample it is. So resuming renders the same image as not stopping.
+    pub fn resume_progressive(
+        &self,
+        world: &dyn Hittable,
+        accumulation: &mut Accumulation,
+        snapshot_every: usize,
+        mut on_snapshot: impl FnMut(&Snapshot) -> ControlFlow<()> + Send,
+    ) -> Vec<LinearRgba> {
+        assert_eq!(
+            (accumulation.width, accumulation.height()),
+            (self.im_width, self.im_height),
+            "the accumulation must match the camera's resolution"
+        );
+
+        let start = Instant::now();
+        let out_of_time = || {
+            self.time_budget
+                .is_some_and(|budget| start.elapsed() >= budget)
+        };
+
+        let first = accumulation.passes();
+        self.in_thread_pool(|| {
+            for pass in first..self.samples_per_pixel {
+                accumulation
+                    .sums
+                    .par_chunks_mut(self.im_width)
+                    .zip(accumulation.row_samples.par_iter_mut())
+                    .enumerate()
+                    .for_each(|(row, (sums, samples))| {
+                        // Rows ahead since a pass was cut short wait for the others
+                        if *samples > pass || (pass > first && out_of_time()) {
+                            return;
+                        }
+
+                        for (col, sum) in sums.iter_mut().enumerate() {
+                            *sum += self.sample_pixel(world, row, col, pass);
+                        }
+                        *samples += 1;
+                    });
+
+                if out_of_time() {
+                    break;
+                }
+
+                let passes = pass + 1;
+                if snapshot_every > 0 && passes.is_multiple_of(snapshot_every) {
+                    let snapshot = Snapshot {
+                        passes,
+                        elapsed: start.elapsed(),
+                        accumulation,
+                    };
+                    if on_snapshot(&snapshot).is_break() {
+                        break;
                     }
                 }
+            }
+        });
 
-                color /= self.samples_per_pixel as f32;
+        info!(
+            "Rendered {} samples per pixel in {:.1?}",
+            accumulation.passes(),
+            start.elapsed()
+        );
 
-                data.extend(if self.srgb_output {
-                    Srgba::from(color).to_u8_array_no_alpha()
-                } else {
-                    color.to_u8_array_no_alpha()
-                });
-            }
+        self.accumulated_image(accumulation)
+    }
+
+    /// The exposed image of the samples accumulated so far, row-major.
+    pub fn accumulated_image(&self, accumulation: &Accumulation) -> Vec<LinearRgba> {
+        accumulation
+            .sums
+            .iter()
+            .enumerate()
+            .map(|(index, &sum)| {
+                let samples = accumulation.row_samples[index / accumulation.width];
+                self.expose(sum / samples.max(1) as f32)
+            })
+            .collect()
+    }
+
+    /// Quantize a rendered pixel to 8-bit RGB for output,
+    /// after `exposure_compensation` and the `tonemap`.
+    /// Without its position it isn't dithered, see [`Camera::quantize`] for whole images.
+    pub fn to_rgb8(&self, color: LinearRgba) -> [u8; 3] {
+        let output_color = OutputColor {
+            dither: Dither::None,
+            ..self.output_color
+        };
+        output_color.to_rgb8(self.display(color), UVec2::ZERO)
+    }
+
+    /// Quantize a rendered image, row-major, to 8-bit RGB for output.
+    /// Like [`Camera::to_rgb8`], but dithered as the `output_color` says.
+    pub fn quantize(&self, image: &[LinearRgba]) -> Vec<u8> {
+        self.display_pixels(image, false)
+            .flat_map(|(pixel, color)| self.output_color.to_rgb8(color, pixel))
+            .collect()
+    }
+
+    /// Like [`Camera::quantize`], to 16-bit RGB.
+    pub fn quantize16(&self, image: &[LinearRgba]) -> Vec<u16> {
+        self.display_pixels(image, false)
+            .flat_map(|(pixel, color)| self.output_color.to_rgb16(color, pixel))
+            .collect()
+    }
+
+    /// Quantize a rendered image, row-major, at the `output_color`'s bit depth.
+    /// With a `transparent_background` it keeps the alpha channel, straight as PNG wants it.
+    pub fn quantize_image(&self, image: &[LinearRgba]) -> Quantized {
+        let output_color = &self.output_color;
+        if !self.transparent_background {
+            return match output_color.bit_depth {
+                BitDepth::Eight => Quantized::Rgb8(self.quantize(image)),
+                BitDepth::Sixteen => Quantized::Rgb16(self.quantize16(image)),
+            };
         }
 
-        ppm::write_pathlike(self.im_height, data, output_file)?;
+        let pixels = self.display_pixels(image, true);
+        match output_color.bit_depth {
+            BitDepth::Eight => Quantized::Rgba8(
+                pixels
+                    .flat_map(|(pixel, color)| output_color.to_rgba8(color, pixel))
+                    .collect(),
+            ),
+            BitDepth::Sixteen => Quantized::Rgba16(
+                pixels
+                    .flat_map(|(pixel, color)| output_color.to_rgba16(color, pixel))
+                    .collect(),
+            ),
+        }
+    }
 
-        Ok(())
+    /// Each pixel's column and row, with its color ready to quantize.
+    /// Rendered colors are premultiplied by their alpha, `straight` divides it back out.
+    fn display_pixels<'a>(
+        &'a self,
+        image: &'a [LinearRgba],
+        straight: bool,
+    ) -> impl Iterator<Item = (UVec2, LinearRgba)> + 'a {
+        let width = self.im_width.max(1);
+        image.iter().enumerate().map(move |(index, &color)| {
+            let pixel = UVec2::new((index % width) as u32, (index / width) as u32);
+            let color = if straight && color.alpha > 0.0 {
+                (color / color.alpha).with_alpha(color.alpha)
+            } else {
+                color
+            };
+            (pixel, self.display(color))
+        })
     }
 
-    pub fn sky_color(&self, ray: &ray::Ray) -> Color {
-        let y = ray.direction().y;
+    /// After `exposure_compensation` and the `tonemap`, mostly within `[0, 1]`.
+    fn display(&self, color: LinearRgba) -> LinearRgba {
+        let exposed = (color * self.exposure_compensation.exp2()).with_alpha(color.alpha);
+        self.tonemap.apply(exposed)
+    }
+
+    /// Render the full image quantized to 8-bit RGB, ready to be written out.
+    pub fn render_rgb8(&self, world: &dyn Hittable) -> Vec<u8> {
+        self.quantize(&self.render_linear(world))
+    }
+
+    /// Render and write the image. The format is picked from the file extension.
+    pub fn render(
+        &self,
+        world: &dyn Hittable,
+        output_file: impl AsRef<Path>,
+    ) -> anyhow::Result<()> {
+        let format = ImageFormat::from_path(&output_file);
+        let image = self.render_linear(world);
+        if format.is_float() {
+            return output::write_linear(format, self.im_width, &image, output_file);
+        }
+        let data = self.quantize_image(&image);
+        output::write_pathlike_quantized(self.im_height, &data, output_file, None)
+    }
 
-        // Range [-1.0, 1.0] rescaled to [0.0, 1.0].
-        // When looking down, we're looking more and more towards -1.0 (remapped to 0.0).
-        // In that case we want white. So that's the start value.
-        let a = (y + 1.0) * 0.5;
+    /// The `background` in the direction of the ray.
+    pub fn background_color(&self, ray: &ray::Ray) -> Color {
+        self.background.color(ray)
+    }
 
-        let white = Color::WHITE;
-        let blue: Color = LinearRgba::from_vec3(vec3(0.5, 0.7, 1.0)).into();
+    #[deprecated(note = "use `Camera::background_color`, or set `Camera::background`")]
+    pub fn sky_color(&self, ray: &ray::Ray) -> Color {
+        self.background_color(ray)
+    }
 
-        white.mix(&blue, a)
+    /// The environment map in the background, if diffuse rays see it and so can be aimed at it.
+    fn sampled_environment(&self) -> Option<&EnvironmentMap> {
+        match &self.background {
+            Background::Environment(map) if self.sky_visibility.contains(RayKind::Diffuse) => {
+                Some(map)
+            }
+            _ => None,
+        }
+    }
+
+    /// The background if this kind of ray sees it, else black.
+    /// Transparent for camera rays with a `transparent_background`.
+    fn miss_color(&self, ray: &ray::Ray) -> Color {
+        if self.transparent_background && ray.kind() == RayKind::Camera {
+            Color::NONE
+        } else if self.sky_visibility.contains(ray.kind()) {
+            self.background_color(ray)
+        } else {
+            Color::BLACK
+        }
     }
 
     pub fn world_color(&self, ray: &ray::Ray, world: &dyn Hittable, range: Range<f32>) -> Color {
+        stats::count_ray();
         match world.hit(ray, range) {
             // hit: remap the colors of the surface normal
             Some(hit) => LinearRgba::from_vec3(0.5 * (Vec3::from(hit.normal) + Vec3::ONE)).into(),
-            None => self.sky_color(ray),
+            None => self.miss_color(ray),
         }
     }
 
+    /// Light arriving at a diffuse hit straight from the camera's lights,
+    /// scaled by the Lambertian BRDF without its albedo.
+    /// In a `volume` it is scaled by the isotropic phase function instead, which ignores the normal.
+    fn direct_light(
+        &self,
+        world: &dyn Hittable,
+        hit: &Hit,
+        t_min: f32,
+        time: f32,
+        volume: bool,
+        rng: &mut impl Rng,
+    ) -> Vec3 {
+        let mut total = Vec3::ZERO;
+
+        for light in &self.lights {
+            let Some(LightSample {
+                direction,
+                distance,
+                radiance,
+            }) = light.sample(hit.point, rng)
+            else {
+                continue;
+            };
+
+            let (cos, phase) = if volume {
+                (1.0, 0.25 * std::f32::consts::FRAC_1_PI)
+            } else {
+                (
+                    hit.normal.dot(direction.as_vec3()),
+                    std::f32::consts::FRAC_1_PI,
+                )
+            };
+            if cos <= 0.0 || radiance == LinearRgba::BLACK {
+                continue;
+            }
+
+            let mut shadow = ray::Ray::new(hit.point, direction.as_vec3())
+                .with_kind(RayKind::Shadow)
+                .with_time(time);
+            if self.normal_offset > 0.0 {
+                shadow = shadow.offset_along_normal(hit.normal, self.normal_offset);
+            }
+            stats::count_ray();
+            if world.hit(&shadow, t_min..distance).is_some() {
+                continue;
+            }
+
+            total += radiance.to_vec3() * cos * phase;
+        }
+
+        total
+    }
+
     pub fn world_color_bounce(
         &self,
         ray: &ray::Ray,
         world: &dyn Hittable,
         range: Range<f32>,
         bounce: usize,
+        depth: DepthLimits,
+        rng: &mut impl Rng,
         // reflectance: f32,
     ) -> Color {
         // either exhaust the bounces (dark!)
@@ -218,22 +1260,714 @@ impl Camera {
             return Color::BLACK;
         }
 
+        stats::count_ray();
         match world.hit(ray, range.clone()) {
             Some(hit) => {
-                if let Some(scattered) = hit.material.scatter(ray, &hit) {
-                    LinearRgba::from_vec3(
-                        scattered.attenuation.to_linear().to_vec3()
-                            * self
-                                .world_color_bounce(&scattered.ray, world, range, bounce - 1)
-                                .to_linear()
-                                .to_vec3(),
-                    )
-                    .into()
+                // Lights keep shining through a material override, else there'd be nothing to see
+                let emitted = hit
+                    .material
+                    .emitted(hit.uv.x, hit.uv.y, hit.point)
+                    .to_linear()
+                    .to_vec3();
+
+                let material = self.material_override.as_ref().unwrap_or(&hit.material);
+                let Some(mut scattered) = material.scatter(ray, &hit, rng) else {
+                    return LinearRgba::from_vec3(emitted).into();
+                };
+
+                // Aim some rays at the lights and the environment's bright spots,
+                // weighting all by the chance of any of them picking the direction
+                if scattered.pdf.is_some() {
+                    let lights = (!self.light_objects.objects.is_empty())
+                        .then(|| HittablePdf::new(&self.light_objects, hit.point));
+                    let scattering = MaterialPdf::new(&**material, ray, &hit);
+                    let aimed = match (lights, self.sampled_environment()) {
+                        (Some(lights), Some(environment)) => Some(sample_pdf(
+                            MixturePdf::new(
+                                MixturePdf::new(lights, environment, 0.5),
+                                scattering,
+                                0.5,
+                            ),
+                            rng,
+                        )),
+                        (Some(lights), None) => {
+                            Some(sample_pdf(MixturePdf::new(lights, scattering, 0.5), rng))
+                        }
+                        (None, Some(environment)) => Some(sample_pdf(
+                            MixturePdf::new(environment, scattering, 0.5),
+                            rng,
+                        )),
+                        (None, None) => None,
+                    };
+                    if let Some((direction, pdf)) = aimed {
+                        scattered.ray = ray::Ray::new(hit.point, direction.as_vec3());
+                        scattered.pdf = Some(pdf);
+                    }
+                }
+
+                let kind = match scattered.lobe {
+                    Lobe::Diffuse | Lobe::Volume => RayKind::Diffuse,
+                    Lobe::Specular | Lobe::Transmission => RayKind::Specular,
+                };
+                scattered.ray = scattered.ray.with_kind(kind).with_time(ray.time());
+                if self.normal_offset > 0.0 {
+                    scattered.ray = scattered
+                        .ray
+                        .offset_along_normal(hit.normal, self.normal_offset);
+                }
+
+                let t_min = self.ray_bias.t_min(self.min_dist, hit.distance);
+                let mut attenuation = scattered.attenuation.to_linear().to_vec3();
+                if let Some(pdf) = scattered.pdf {
+                    if pdf <= 0.0 {
+                        return LinearRgba::from_vec3(emitted).into();
+                    }
+                    attenuation *= material.scattering_pdf(ray, &hit, &scattered.ray) / pdf;
+                }
+
+                // Lights can't be hit by chance, so diffuse surfaces look for them directly
+                let direct = match scattered.lobe {
+                    Lobe::Diffuse => self.direct_light(world, &hit, t_min, ray.time(), false, rng),
+                    Lobe::Volume => self.direct_light(world, &hit, t_min, ray.time(), true, rng),
+                    _ => Vec3::ZERO,
+                };
+
+                let mut depth = depth;
+                let remaining = depth.remaining(scattered.lobe);
+                let incoming = if *remaining == 0 {
+                    Vec3::ZERO
                 } else {
-                    Color::BLACK
+                    *remaining -= 1;
+                    let range = t_min..range.end;
+                    self.world_color_bounce(&scattered.ray, world, range, bounce - 1, depth, rng)
+                        .to_linear()
+                        .to_vec3()
+                };
+
+                // Past the first surface all the light reflected is indirect
+                let mut reflected = attenuation * (direct + incoming);
+                if ray.kind() != RayKind::Camera {
+                    reflected = clamp_radiance(reflected, self.max_indirect);
+                }
+
+                LinearRgba::from_vec3(emitted + reflected).into()
+            }
+            None => self.miss_color(ray),
+        }
+    }
+}
+
+/// A direction from `pdf`, with its density.
+fn sample_pdf(pdf: impl Pdf, rng: &mut impl Rng) -> (Dir3, f32) {
+    let direction = pdf.generate(rng);
+    (direction, pdf.value(direction))
+}
+
+/// Scale `radiance` down to at most `max` in every channel, keeping its hue.
+fn clamp_radiance(radiance: Vec3, max: Option<f32>) -> Vec3 {
+    match max {
+        Some(max) if radiance.max_element() > max => radiance * (max / radiance.max_element()),
+        _ => radiance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn look_at_centers_target() {
+        let mut camera = Camera::new();
+        camera.cam_origin = vec3(1.0, 2.0, 3.0);
+
+        let target = vec3(-4.0, 0.5, 1.0);
+        camera.look_at(target, Vec3::Y).unwrap();
+
+        // The middle of the viewport is straight ahead
+        let center = camera.viewport_origin + (camera.viewport_u + camera.viewport_v) / 2.;
+        let forward = (center - camera.cam_origin).normalize();
+        assert!(forward.dot((target - camera.cam_origin).normalize()) > 0.9999);
+
+        // Image rows go downwards
+        assert!(camera.viewport_v.y < 0.0);
+
+        // Looking straight down the up vector leaves no way to tell right from left
+        let orientation = camera.orientation;
+        assert!(camera
+            .look_at(camera.cam_origin - Vec3::Y, Vec3::Y)
+            .is_err());
+        assert!(camera.look_at(camera.cam_origin, Vec3::Y).is_err());
+        assert_eq!(camera.orientation, orientation);
+    }
+
+    #[test]
+    fn defocus_is_sharp_at_focus_distance() {
+        let camera = Camera::builder()
+            .resolution(8, 8)
+            .samples(16)
+            .defocus(10.0, 5.0)
+            .build()
+            .unwrap();
+
+        // Camera at the origin looking down -Z: the focus plane is at z = -5
+        let scale = camera.focus_distance / camera.focal_length;
+        let center = camera.pixel00_origin + 2.0 * camera.dv + 5.0 * camera.du;
+        let half_pixel = 0.5 * camera.du.x * scale + 1e-4;
+
+        let mut origins = vec![];
+        for sample in 0..16 {
+            let ray = camera.sample_ray(2, 5, sample).unwrap();
+            let t = -camera.focus_distance / ray.direction().z;
+            let on_plane = ray.at(t);
+
+            assert!((on_plane.x - center.x * scale).abs() <= half_pixel);
+            assert!((on_plane.y - center.y * scale).abs() <= half_pixel);
+            assert!(ray.origin().length() <= camera.defocus_radius() + 1e-5);
+            origins.push(ray.origin());
+        }
+        assert!(origins.iter().any(|origin| *origin != origins[0]));
+    }
+
+    #[test]
+    fn exposure_sets_aperture_and_shutter() {
+        let build = |f_number| {
+            Camera::builder()
+                .focal_length(2.0)
+                .defocus(0.0, 5.0)
+                .exposure(Exposure::new(f_number, 0.5, 100.0))
+                .build()
+                .unwrap()
+        };
+
+        let camera = build(4.0);
+        assert!((camera.defocus_radius() - 0.25).abs() < 1e-5);
+        assert_eq!(camera.shutter, 0.0..0.5);
+
+        // Opening up two stops doubles the blur
+        assert!((build(2.0).defocus_radius() - 0.5).abs() < 1e-5);
+        for exposure in [
+            Exposure::new(0.0, 0.5, 100.0),
+            Exposure::new(f32::NAN, 0.5, 100.0),
+            Exposure::new(4.0, 0.0, 100.0),
+            Exposure::new(4.0, f32::INFINITY, 100.0),
+            Exposure::new(4.0, 0.5, 0.0),
+            Exposure::new(4.0, 0.5, -100.0),
+        ] {
+            assert!(
+                Camera::builder().exposure(exposure).build().is_err(),
+                "{exposure:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn builder_checks_settings() {
+        let camera = Camera::builder()
+            .resolution(40, 20)
+            .position(vec3(1.0, 2.0, 3.0))
+            .look_at(Vec3::ZERO, Vec3::Y)
+            .build()
+            .unwrap();
+        assert_eq!(camera.aspect_ratio, 2.0);
+        assert!(camera.orientation != Quat::IDENTITY);
+
+        assert!(Camera::builder().samples(0).build().is_err());
+        assert!(Camera::builder().resolution(0, 10).build().is_err());
+        assert!(Camera::builder().vfov(180.0).build().is_err());
+        assert!(Camera::builder()
+            .look_at(Vec3::ZERO, Vec3::Y)
+            .build()
+            .is_err());
+        for up in [Vec3::Y, Vec3::NEG_Y, Vec3::ZERO] {
+            assert!(Camera::builder()
+                .position(Vec3::Y)
+                .look_at(Vec3::ZERO, up)
+                .build()
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn vfov_sets_viewport() {
+        let mut camera = Camera::new();
+        camera.vfov = Some(90.0);
+        camera.focal_length = 3.0;
+        camera.update_viewport();
+        assert!((camera.viewport_height - 6.0).abs() < 1e-5);
+
+        // Half the angle, less than half the height
+        camera.vfov = Some(45.0);
+        camera.update_viewport();
+        assert!(camera.viewport_height < 3.0);
+    }
+
+    #[test]
+    fn same_image_in_any_order_on_any_thread() {
+        use crate::scenes::{self, MaterialWeights};
+
+        let world = || scenes::random_spheres(1, 2, MaterialWeights::default());
+
+        let mut camera = Camera::builder()
+            .resolution(12, 8)
+            .samples(3)
+            .bounces(5)
+            .position(vec3(0.0, 1.0, 3.0))
+            .look_at(Vec3::ZERO, Vec3::Y)
+            .build()
+            .unwrap();
+
+        let image = camera.render_linear(&world());
+
+        // Tiles in reverse order, each on its own thread
+        let mut tiled = vec![LinearRgba::NONE; image.len()];
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = Tile::grid(12, 8, 5)
+                .into_iter()
+                .rev()
+                .map(|tile| {
+                    let camera = &camera;
+                    scope.spawn(move || (tile, camera.render_tile(&world(), &tile)))
+                })
+                .collect();
+
+            for handle in handles {
+                let (tile, pixels) = handle.join().unwrap();
+                for ((row, col), pixel) in tile.pixels().zip(pixels) {
+                    tiled[row * 12 + col] = pixel;
                 }
             }
-            None => self.sky_color(ray),
+        });
+        assert_eq!(image, tiled);
+
+        camera.threads = Some(1);
+        assert_eq!(image, camera.render_linear(&world()));
+
+        camera.seed = 1;
+        assert_ne!(image, camera.render_linear(&world()));
+    }
+
+    #[test]
+    fn pixels_are_lazy() {
+        let mut camera = Camera::new();
+        camera.im_width = 3;
+        camera.im_height = 2;
+        camera.update_viewport();
+
+        let world = Hittables::default();
+        let mut pixels = camera.pixels(&world);
+        assert_eq!(pixels.nth(4).map(|(x, y, _)| (x, y)), Some((1, 1)));
+
+        let image = camera.render_linear(&world);
+        assert_eq!(pixels.next().map(|(_, _, color)| color), Some(image[5]));
+        assert!(pixels.next().is_none());
+    }
+
+    #[test]
+    fn render_tiles_stops_on_error() {
+        let mut camera = Camera::new();
+        camera.im_width = 8;
+        camera.im_height = 4;
+        camera.update_viewport();
+
+        let mut tiles = vec![];
+        let result = camera.render_tiles(&Hittables::default(), 4, |tile, pixels| {
+            assert_eq!(pixels.len(), 16);
+            tiles.push(*tile);
+            Err("cancelled")
+        });
+
+        assert_eq!(result, Err("cancelled"));
+        assert_eq!(
+            tiles,
+            [Tile {
+                x: 0,
+                y: 0,
+                width: 4,
+                height: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn cancelled_render_is_partial() {
+        let mut camera = Camera::new();
+        camera.im_width = 8;
+        camera.im_height = 4;
+        camera.update_viewport();
+        let world = Hittables::default();
+
+        let token = CancellationToken::new();
+        let (status, image) = camera.render_cancellable(&world, 4, &token);
+        assert_eq!(status, RenderStatus::Complete);
+        assert!(image.iter().all(|color| color.alpha == 1.0));
+
+        token.clone().cancel();
+        let (status, image) = camera.render_cancellable(&world, 4, &token);
+        assert_eq!(status, RenderStatus::Cancelled);
+        assert!(image.iter().all(|color| *color == LinearRgba::NONE));
+    }
+
+    #[test]
+    fn inspect_finds_object() {
+        use crate::{material::Lambertian, objects::Sphere};
+
+        let mut world = Hittables::default();
+        for x in [-2.0, 0.0] {
+            world.add(Sphere {
+                center: vec3(x, 0.0, -2.0),
+                radius: 0.5,
+                material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+            });
         }
+
+        let mut camera = Camera::new();
+        camera.im_width = 9;
+        camera.im_height = 9;
+        camera.update_viewport();
+
+        let hit = camera.inspect(&world, 4, 4).unwrap();
+        assert_eq!(hit.object_id, 2);
+        assert!((hit.distance - 1.5).abs() < 1e-4);
+
+        assert!(camera.inspect(&world, 0, 4).is_none());
+    }
+
+    #[test]
+    fn time_budget_completes_first_pass() {
+        let mut camera = Camera::new();
+        camera.im_width = 8;
+        camera.im_height = 4;
+        camera.samples_per_pixel = usize::MAX;
+        camera.time_budget = Some(Duration::ZERO);
+        camera.update_viewport();
+
+        let pixels = camera.render_linear(&Hittables::default());
+        assert_eq!(pixels.len(), 32);
+        assert!(pixels
+            .iter()
+            .all(|pixel| pixel.red > 0.0 && pixel.red.is_finite()));
+    }
+
+    #[test]
+    fn progressive_matches_tiles() {
+        use crate::{material::Lambertian, objects::Sphere};
+
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: vec3(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+        });
+        let camera = Camera::builder()
+            .resolution(8, 6)
+            .samples(6)
+            .build()
+            .unwrap();
+
+        let mut snapshots = vec![];
+        let image = camera.render_progressive(&world, 2, |snapshot| {
+            snapshots.push(snapshot.passes);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(snapshots, [2, 4, 6]);
+        assert_eq!(image, camera.render_linear(&world));
+
+        // Stopping early keeps the samples so far
+        let early = camera.render_progressive(&world, 2, |_| ControlFlow::Break(()));
+        let mut two_samples = camera;
+        two_samples.samples_per_pixel = 2;
+        assert_eq!(early, two_samples.render_linear(&world));
+    }
+
+    #[test]
+    fn samples_differ() {
+        let camera = Camera::builder()
+            .resolution(8, 6)
+            .samples(1)
+            .build()
+            .unwrap();
+        let world = Hittables::default();
+
+        // Accumulating one sample at a time only converges if each draws new random numbers
+        let first = camera.render_sample(&world, 3, 4, 0);
+        let second = camera.render_sample(&world, 3, 4, 1);
+        assert_ne!(first, second);
+        assert_eq!(first, camera.render_pixel(&world, 3, 4));
+    }
+
+    #[test]
+    fn resuming_renders_the_same_image() {
+        let camera = Camera::builder()
+            .resolution(8, 6)
+            .samples(5)
+            .build()
+            .unwrap();
+        let world = Hittables::default();
+
+        let mut accumulation = Accumulation::new(8, 6);
+        camera.resume_progressive(&world, &mut accumulation, 3, |_| ControlFlow::Break(()));
+        assert_eq!(accumulation.passes(), 3);
+
+        let resumed =
+            camera.resume_progressive(&world, &mut accumulation, 0, |_| ControlFlow::Continue(()));
+        assert_eq!(accumulation.passes(), 5);
+        assert_eq!(resumed, camera.render_linear(&world));
+    }
+
+    #[test]
+    fn lights_brighten_diffuse_surfaces() {
+        use crate::{material::Lambertian, objects::Sphere};
+
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: vec3(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+        });
+
+        let mut camera = Camera::builder()
+            .resolution(5, 5)
+            .samples(4)
+            .bounces(3)
+            .min_dist(0.001)
+            .build()
+            .unwrap();
+
+        let unlit = camera.render_pixel(&world, 2, 2);
+
+        // Behind the sphere, so it's in its own shadow
+        camera.lights = vec![Light::point(vec3(0.0, 0.0, -4.0), Color::WHITE, 10.0)];
+        assert_eq!(camera.render_pixel(&world, 2, 2), unlit);
+
+        camera.lights = vec![Light::point(vec3(0.0, 0.0, 0.0), Color::WHITE, 10.0)];
+        assert!(camera.render_pixel(&world, 2, 2).red > unlit.red + 0.5);
+    }
+
+    #[test]
+    fn sky_only_seen_by_camera() {
+        use crate::{material::Lambertian, objects::Sphere};
+
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: vec3(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+        });
+
+        let mut camera = Camera::builder()
+            .resolution(5, 5)
+            .samples(4)
+            .bounces(3)
+            .build()
+            .unwrap();
+        camera.sky_visibility = RayMask::CAMERA;
+
+        // The backdrop is there, but the sphere is unlit
+        assert_ne!(camera.render_pixel(&world, 0, 0), LinearRgba::BLACK);
+        assert_eq!(camera.render_pixel(&world, 2, 2), LinearRgba::BLACK);
+    }
+
+    #[test]
+    fn transparent_background_keeps_the_light() {
+        use crate::{material::Lambertian, objects::Sphere};
+
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: vec3(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+        });
+
+        let mut camera = Camera::builder()
+            .resolution(5, 5)
+            .samples(4)
+            .bounces(3)
+            .min_dist(0.001)
+            .build()
+            .unwrap();
+        let opaque = camera.render_pixel(&world, 2, 2);
+
+        // The sky still lights the sphere, but it isn't seen around it
+        camera.transparent_background = true;
+        assert_eq!(camera.render_pixel(&world, 0, 0), LinearRgba::NONE);
+        assert_eq!(camera.render_pixel(&world, 2, 2), opaque);
+
+        let Quantized::Rgba8(data) = camera.quantize_image(&camera.render_linear(&world)) else {
+            panic!("expected 8-bit RGBA");
+        };
+        assert_eq!(data[3], 0);
+        assert_eq!(data[(2 * 5 + 2) * 4 + 3], u8::MAX);
+    }
+
+    #[test]
+    fn emissive_surfaces_light_the_dark() {
+        use crate::{
+            material::{DiffuseLight, Lambertian},
+            objects::Sphere,
+        };
+
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: vec3(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+        });
+
+        let mut camera = Camera::builder()
+            .resolution(5, 5)
+            .samples(16)
+            .bounces(3)
+            .min_dist(0.001)
+            .build()
+            .unwrap();
+        camera.sky_visibility = RayMask::NONE;
+        assert_eq!(camera.render_pixel(&world, 2, 2), LinearRgba::BLACK);
+
+        // A big lamp behind the camera
+        world.add(Sphere {
+            center: vec3(0.0, 0.0, 3.0),
+            radius: 2.0,
+            material: DiffuseLight::linear_rgb(4.0, 4.0, 4.0).into(),
+        });
+        assert!(camera.render_pixel(&world, 2, 2).red > 0.0);
+
+        // Seen directly, a lamp is its own color
+        let lamp = Camera::builder()
+            .resolution(5, 5)
+            .samples(4)
+            .bounces(3)
+            .position(vec3(0.0, 0.0, 6.0))
+            .build()
+            .unwrap();
+        assert_eq!(
+            lamp.render_pixel(&world, 2, 2),
+            LinearRgba::rgb(4.0, 4.0, 4.0)
+        );
+
+        // And stays lit in a clay render
+        camera.material_override = Some(Lambertian::new(Camera::CLAY).into());
+        assert!(camera.render_pixel(&world, 2, 2).red > 0.0);
+    }
+
+    #[test]
+    fn clamps_tame_bright_paths() {
+        use crate::{
+            material::{DiffuseLight, Lambertian},
+            objects::Sphere,
+        };
+
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: vec3(0.0, 0.0, -2.0),
+            radius: 0.5,
+            material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+        });
+        world.add(Sphere {
+            center: vec3(0.0, 0.0, 3.0),
+            radius: 2.0,
+            material: DiffuseLight::linear_rgb(4.0, 2.0, 1.0).into(),
+        });
+
+        let camera = |builder: CameraBuilder| {
+            let mut camera = builder
+                .resolution(5, 5)
+                .samples(16)
+                .bounces(4)
+                .min_dist(0.001)
+                .build()
+                .unwrap();
+            camera.sky_visibility = RayMask::NONE;
+            camera
+        };
+
+        // Seen directly, a lamp is as bright as allowed, keeping its hue
+        let lamp = camera(
+            Camera::builder()
+                .position(vec3(0.0, 0.0, 6.0))
+                .max_radiance(1.0),
+        );
+        assert_eq!(
+            lamp.render_pixel(&world, 2, 2),
+            LinearRgba::rgb(1.0, 0.5, 0.25)
+        );
+
+        // The sphere is convex, so all of its light comes straight from the lamp
+        let unclamped = camera(Camera::builder()).render_pixel(&world, 2, 2);
+        let indirect = camera(Camera::builder().max_indirect(1e-3));
+        assert_eq!(indirect.render_pixel(&world, 2, 2), unclamped);
+
+        // Until a floor reflects some more onto it
+        world.add(Sphere {
+            center: vec3(0.0, -100.5, -2.0),
+            radius: 100.0,
+            material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+        });
+        let unclamped = camera(Camera::builder()).render_pixel(&world, 2, 2);
+        assert!(indirect.render_pixel(&world, 2, 2).red < unclamped.red);
+
+        assert!(Camera::builder().max_radiance(0.0).build().is_err());
+    }
+
+    #[test]
+    fn aiming_at_lights_reduces_noise() {
+        use std::sync::Arc;
+
+        use crate::{
+            material::{DiffuseLight, Lambertian},
+            objects::Quad,
+        };
+
+        // A floor lit only by a small lamp high above it
+        let lamp = Arc::new(Quad::new(
+            vec3(-0.5, 3.0, -2.5),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+            DiffuseLight::linear_rgb(10.0, 10.0, 10.0),
+        ));
+        let mut world = Hittables::default();
+        world.add(Quad::new(
+            vec3(-5.0, -1.0, -7.0),
+            vec3(10.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 10.0),
+            Lambertian::linear_rgb(0.5, 0.5, 0.5),
+        ));
+        world.add(Arc::clone(&lamp));
+
+        let mut camera = Camera::builder()
+            .resolution(5, 5)
+            .samples(1)
+            .bounces(2)
+            .min_dist(0.001)
+            .build()
+            .unwrap();
+        camera.sky_visibility = RayMask::NONE;
+
+        let mean_and_variance = |camera: &mut Camera| {
+            let reds: Vec<f32> = (0..20_000)
+                .map(|seed| {
+                    camera.seed = seed;
+                    camera.render_pixel(&world, 4, 2).red
+                })
+                .collect();
+            let mean = reds.iter().sum::<f32>() / reds.len() as f32;
+            let variance =
+                reds.iter().map(|red| (red - mean).powi(2)).sum::<f32>() / reds.len() as f32;
+            (mean, variance)
+        };
+
+        let (blind_mean, blind_variance) = mean_and_variance(&mut camera);
+        camera.light_objects.add(lamp);
+        let (aimed_mean, aimed_variance) = mean_and_variance(&mut camera);
+
+        // The same brightness, found far more reliably
+        assert!(aimed_mean > 0.0);
+        assert!(
+            (aimed_mean - blind_mean).abs() < 0.1 * aimed_mean,
+            "{blind_mean} {aimed_mean}"
+        );
+        assert!(
+            aimed_variance < 0.1 * blind_variance,
+            "{blind_variance} {aimed_variance}"
+        );
     }
 }
@@ -0,0 +1,250 @@
+//! A C interface for embedding the renderer in non-Rust applications.
+//!
+//! Building with the `capi` feature writes the matching header to `include/rt_one.h`.
+//! The library is only an rlib by default, build a shared library to link against with
+//! `cargo rustc --release --lib --features capi --crate-type cdylib` (`just capi`) and:
+//!
+//! ```c
+//! RtScene *scene = rt_scene_new(400, 225, 10, 50);
+//! RtMaterial *ground = rt_material_lambertian(0.8f, 0.8f, 0.0f);
+//! rt_scene_add_sphere(scene, 0.0f, -100.5f, -1.0f, 100.0f, ground);
+//! rt_material_free(ground);
+//!
+//! uint8_t *pixels = malloc(400 * 225 * 4);
+//! rt_scene_render(scene, pixels, 400 * 225 * 4);
+//! rt_scene_free(scene);
+//! ```
+
+use std::{convert::Infallible, ffi::c_void};
+
+use bevy_color::Color;
+use bevy_math::Vec3;
+
+use crate::{
+    camera::Camera,
+    hittable::Hittables,
+    material::{Dielectric, DynMaterial, Lambertian, Metal},
+    objects::Sphere,
+    scene::Scene,
+};
+
+/// Result of fallible calls.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtStatus {
+    Ok = 0,
+    NullPointer = 1,
+    BufferTooSmall = 2,
+}
+
+/// Opaque handle to a camera and the world it looks at.
+pub struct RtScene(Scene);
+
+/// Opaque handle to a material. Objects keep their own reference,
+/// so materials may be freed right after use.
+pub struct RtMaterial(DynMaterial);
+
+/// Create an empty scene. Free with [`rt_scene_free`].
+#[no_mangle]
+pub extern "C" fn rt_scene_new(
+    width: u32,
+    height: u32,
+    samples_per_pixel: u32,
+    max_bounces: u32,
+) -> *mut RtScene {
+    let camera = Camera::builder()
+        .resolution(width.max(1) as usize, height.max(1) as usize)
+        .samples(samples_per_pixel.max(1) as usize)
+        .bounces(max_bounces as usize)
+        .min_dist(0.001)
+        .srgb(true)
+        .build()
+        .expect("sizes and samples were clamped to at least 1");
+
+    Box::into_raw(Box::new(RtScene(Scene::new(camera, Hittables::default()))))
+}
+
+/// # Safety
+///
+/// `scene` must come from [`rt_scene_new`] and not be used afterwards. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_free(scene: *mut RtScene) {
+    if !scene.is_null() {
+        drop(Box::from_raw(scene));
+    }
+}
+
+/// Place the camera at the given origin, looking down -Z.
+/// The viewport is `viewport_height` tall at `focal_length` in front of the camera.
+///
+/// # Safety
+///
+/// `scene` must be null or a live pointer from [`rt_scene_new`].
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_set_camera(
+    scene: *mut RtScene,
+    x: f32,
+    y: f32,
+    z: f32,
+    focal_length: f32,
+    viewport_height: f32,
+) -> RtStatus {
+    let Some(scene) = scene.as_mut() else {
+        return RtStatus::NullPointer;
+    };
+
+    let camera = &mut scene.0.camera;
+    camera.cam_origin = Vec3::new(x, y, z);
+    camera.focal_length = focal_length;
+    camera.viewport_height = viewport_height;
+    camera.update_viewport();
+
+    RtStatus::Ok
+}
+
+/// # Safety
+///
+/// `scene` must be null or a live pointer from [`rt_scene_new`],
+/// and `material` null or a live pointer from one of the `rt_material_*` functions.
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_add_sphere(
+    scene: *mut RtScene,
+    x: f32,
+    y: f32,
+    z: f32,
+    radius: f32,
+    material: *const RtMaterial,
+) -> RtStatus {
+    let (Some(scene), Some(material)) = (scene.as_mut(), material.as_ref()) else {
+        return RtStatus::NullPointer;
+    };
+
+    scene.0.world.add(Sphere {
+        center: Vec3::new(x, y, z),
+        radius,
+        material: material.0.clone(),
+    });
+
+    RtStatus::Ok
+}
+
+/// Render into a caller-provided buffer of `width * height * 4` bytes,
+/// as row-major 8-bit sRGB RGBA starting at the top left.
+///
+/// # Safety
+///
+/// `scene` must be null or a live pointer from [`rt_scene_new`],
+/// and `buffer` must be null or valid for writes of `buffer_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_render(
+    scene: *const RtScene,
+    buffer: *mut u8,
+    buffer_len: usize,
+) -> RtStatus {
+    let Some(scene) = scene.as_ref() else {
+        return RtStatus::NullPointer;
+    };
+    if buffer.is_null() {
+        return RtStatus::NullPointer;
+    }
+
+    let camera = &scene.0.camera;
+    if buffer_len < camera.im_width * camera.im_height * 4 {
+        return RtStatus::BufferTooSmall;
+    }
+
+    let buffer = std::slice::from_raw_parts_mut(buffer, buffer_len);
+    let pixels = camera.render_linear(&scene.0.world);
+
+    let data = camera.quantize(&pixels);
+    for (out, rgb) in buffer.chunks_exact_mut(4).zip(data.chunks_exact(3)) {
+        out.copy_from_slice(&[rgb[0], rgb[1], rgb[2], u8::MAX]);
+    }
+
+    RtStatus::Ok
+}
+
+/// Called with each tile as it finishes: where it is in the image and its size in pixels,
+/// then `width * height` linear RGBA pixels as floats, row-major within the tile.
+/// The pixels are only valid during the call.
+pub type RtTileCallback = Option<
+    unsafe extern "C" fn(
+        user_data: *mut c_void,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        pixels: *const f32,
+    ),
+>;
+
+/// Render tile by tile, handing each tile to `callback` as soon as it's done.
+/// `user_data` is passed through untouched.
+///
+/// # Safety
+///
+/// `scene` must be null or a live pointer from [`rt_scene_new`],
+/// and `callback` must be safe to call with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn rt_scene_render_tiles(
+    scene: *const RtScene,
+    tile_size: u32,
+    callback: RtTileCallback,
+    user_data: *mut c_void,
+) -> RtStatus {
+    let (Some(scene), Some(callback)) = (scene.as_ref(), callback) else {
+        return RtStatus::NullPointer;
+    };
+
+    let camera = &scene.0.camera;
+    let result: Result<_, Infallible> =
+        camera.render_tiles(&scene.0.world, tile_size as usize, |tile, pixels| {
+            callback(
+                user_data,
+                tile.x as u32,
+                tile.y as u32,
+                tile.width as u32,
+                tile.height as u32,
+                pixels.as_ptr().cast(),
+            );
+            Ok(())
+        });
+    let Ok(_) = result;
+
+    RtStatus::Ok
+}
+
+/// A diffuse material with linear RGB albedo. Free with [`rt_material_free`].
+#[no_mangle]
+pub extern "C" fn rt_material_lambertian(red: f32, green: f32, blue: f32) -> *mut RtMaterial {
+    Box::into_raw(Box::new(RtMaterial(
+        Lambertian::linear_rgb(red, green, blue).into(),
+    )))
+}
+
+/// A metal with linear RGB albedo and fuzz in [0, 1]. Free with [`rt_material_free`].
+#[no_mangle]
+pub extern "C" fn rt_material_metal(red: f32, green: f32, blue: f32, fuzz: f32) -> *mut RtMaterial {
+    Box::into_raw(Box::new(RtMaterial(
+        Metal::new(Color::linear_rgb(red, green, blue), fuzz).into(),
+    )))
+}
+
+/// Clear glass-like material. Free with [`rt_material_free`].
+#[no_mangle]
+pub extern "C" fn rt_material_dielectric(refractive_index: f32) -> *mut RtMaterial {
+    Box::into_raw(Box::new(RtMaterial(
+        Dielectric::refraction_index(refractive_index).into(),
+    )))
+}
+
+/// # Safety
+///
+/// `material` must come from one of the `rt_material_*` functions and not be used afterwards.
+/// Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn rt_material_free(material: *mut RtMaterial) {
+    if !material.is_null() {
+        drop(Box::from_raw(material));
+    }
+}
@@ -0,0 +1,179 @@
+//! Saving a progressive render part way, so it can be resumed after the process stops.
+//!
+//! A checkpoint file starts with the line `rt-one checkpoint 1`, then a line of JSON with the
+//! [`RenderCommand`], then the [`Accumulation`]: every pixel's sum as linear RGBA
+//! little-endian `f32`s row-major, then every row's sample count as a little-endian `u64`.
+//!
+//! The random numbers need no saving: each sample seeds its own from the camera's seed,
+//! the pixel and which sample it is, see [`crate::camera::Camera::resume_progressive`].
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Context};
+use bevy_color::{ColorToComponents, LinearRgba};
+use serde::{Deserialize, Serialize};
+
+use crate::{camera::Camera, render::Accumulation};
+
+const MAGIC: &str = "rt-one checkpoint 1\n";
+
+/// What was rendered, so the render can be set up again the same way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RenderCommand {
+    /// Command line arguments, starting with the program.
+    pub args: Vec<String>,
+    /// Where the program ran, which relative paths in `args` are relative to.
+    pub directory: PathBuf,
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+}
+
+/// A render stopped part way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub command: RenderCommand,
+    pub accumulation: Accumulation,
+}
+
+/// Where checkpoints of a render written to `output` go, e.g. `out.png.checkpoint`.
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".checkpoint");
+    PathBuf::from(path)
+}
+
+impl Checkpoint {
+    /// Write a checkpoint of a render, replacing any earlier one at `path` only once complete.
+    pub fn write(
+        command: &RenderCommand,
+        accumulation: &Accumulation,
+        path: impl AsRef<Path>,
+    ) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+
+        let mut out = BufWriter::new(
+            File::create(&partial).with_context(|| format!("Creating {}", partial.display()))?,
+        );
+        out.write_all(MAGIC.as_bytes())?;
+        serde_json::to_writer(&mut out, command)?;
+        out.write_all(b"\n")?;
+        for sum in &accumulation.sums {
+            for value in sum.to_f32_array() {
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+        for &samples in &accumulation.row_samples {
+            out.write_all(&(samples as u64).to_le_bytes())?;
+        }
+        out.into_inner()?.sync_all()?;
+
+        std::fs::rename(&partial, path)
+            .with_context(|| format!("Moving the checkpoint to {}", path.display()))
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut input = BufReader::new(
+            File::open(path).with_context(|| format!("Opening {}", path.display()))?,
+        );
+
+        let mut magic = String::new();
+        input.read_line(&mut magic)?;
+        if magic != MAGIC {
+            bail!("{} is not an rt-one checkpoint", path.display());
+        }
+
+        let mut header = String::new();
+        input.read_line(&mut header)?;
+        let command: RenderCommand =
+            serde_json::from_str(&header).context("Reading the checkpoint's command")?;
+
+        let mut accumulation = Accumulation::new(command.width, command.height);
+        let mut pixel = [0; 16];
+        for sum in &mut accumulation.sums {
+            input
+                .read_exact(&mut pixel)
+                .context("The checkpoint ends early")?;
+            *sum = LinearRgba::from_f32_array(std::array::from_fn(|channel| {
+                f32::from_le_bytes(pixel[channel * 4..][..4].try_into().unwrap())
+            }));
+        }
+        let mut samples = [0; 8];
+        for row in &mut accumulation.row_samples {
+            input
+                .read_exact(&mut samples)
+                .context("The checkpoint ends early")?;
+            *row = u64::from_le_bytes(samples) as usize;
+        }
+
+        ensure!(
+            input.read(&mut [0])? == 0,
+            "{} is longer than a {}x{} checkpoint",
+            path.display(),
+            command.width,
+            command.height
+        );
+
+        Ok(Self {
+            command,
+            accumulation,
+        })
+    }
+
+    /// Make sure the camera renders the same image the checkpoint was taken of.
+    pub fn check(&self, camera: &Camera) -> anyhow::Result<()> {
+        let RenderCommand {
+            width,
+            height,
+            seed,
+            ..
+        } = self.command;
+        ensure!(
+            (camera.im_width, camera.im_height, camera.seed) == (width, height, seed),
+            "the checkpoint is of a {width}x{height} render with seed {seed}, \
+             but the scene now renders {}x{} with seed {}",
+            camera.im_width,
+            camera.im_height,
+            camera.seed
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> anyhow::Result<()> {
+        let mut accumulation = Accumulation::new(3, 2);
+        accumulation.sums[4] = LinearRgba::new(1.5, 0.25, 8.0, 2.0);
+        accumulation.row_samples = vec![7, 6];
+
+        let command = RenderCommand {
+            args: vec!["rt-one".into(), "render".into(), "scene.ron".into()],
+            directory: "/scenes".into(),
+            width: 3,
+            height: 2,
+            seed: 42,
+        };
+
+        let path = std::env::temp_dir().join("rt_one_round_trip.checkpoint");
+        Checkpoint::write(&command, &accumulation, &path)?;
+        let read = Checkpoint::read(&path)?;
+
+        assert_eq!(read.command, command);
+        assert_eq!(read.accumulation, accumulation);
+        assert_eq!(read.accumulation.passes(), 6);
+
+        Ok(())
+    }
+}
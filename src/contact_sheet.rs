@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use crate::{camera::Camera, hittable::Hittable, output, text};
+
+const BACKGROUND: [u8; 3] = [32, 32, 32];
+const LABEL_COLOR: [u8; 3] = [230, 230, 230];
+
+/// Space around each image, in pixels.
+const PADDING: usize = 8;
+const LABEL_SCALE: usize = 2;
+
+struct Cell {
+    label: String,
+    width: usize,
+    height: usize,
+    /// RGB 8-bit
+    data: Vec<u8>,
+}
+
+/// Lays out several images in a grid, each labelled underneath.
+/// Handy for comparing the same scene rendered with different settings.
+pub struct ContactSheet {
+    /// Images per row.
+    pub columns: usize,
+    cells: Vec<Cell>,
+}
+
+impl ContactSheet {
+    pub fn new(columns: usize) -> Self {
+        Self {
+            columns: columns.max(1),
+            cells: vec![],
+        }
+    }
+
+    /// Add an already rendered image. Data is RGB 8-bit per channel.
+    pub fn add_image(&mut self, label: impl Into<String>, width: usize, data: Vec<u8>) {
+        let height = data.len() / 3 / width.max(1);
+
+        self.cells.push(Cell {
+            label: label.into(),
+            width,
+            height,
+            data,
+        });
+    }
+
+    /// Render the world and add the result.
+    pub fn add_render(&mut self, label: impl Into<String>, camera: &Camera, world: &dyn Hittable) {
+        self.add_image(label, camera.im_width, camera.render_rgb8(world));
+    }
+
+    /// The size of the composited image.
+    fn cell_size(&self) -> (usize, usize) {
+        let width = self
+            .cells
+            .iter()
+            .map(|cell| cell.width.max(text::size(&cell.label, LABEL_SCALE).0))
+            .max()
+            .unwrap_or_default();
+        let height = self
+            .cells
+            .iter()
+            .map(|cell| cell.height)
+            .max()
+            .unwrap_or_default();
+
+        let label_height = text::size("", LABEL_SCALE).1;
+
+        (width + PADDING, height + label_height + 2 * PADDING)
+    }
+
+    /// Composite all images into one.
+    /// Returns the number of rows and the RGB 8-bit data.
+    pub fn composite(&self) -> (usize, Vec<u8>) {
+        let (cell_width, cell_height) = self.cell_size();
+
+        let columns = self.columns.min(self.cells.len()).max(1);
+        let rows = self.cells.len().div_ceil(columns).max(1);
+
+        let width = columns * cell_width + PADDING;
+        let height = rows * cell_height + PADDING;
+
+        let mut data: Vec<u8> = BACKGROUND
+            .into_iter()
+            .cycle()
+            .take(width * height * 3)
+            .collect();
+
+        for (index, cell) in self.cells.iter().enumerate() {
+            let left = PADDING + (index % columns) * cell_width;
+            let top = PADDING + (index / columns) * cell_height;
+
+            for row in 0..cell.height {
+                let src = row * cell.width * 3;
+                let dst = ((top + row) * width + left) * 3;
+                data[dst..dst + cell.width * 3]
+                    .copy_from_slice(&cell.data[src..src + cell.width * 3]);
+            }
+
+            text::draw(
+                &mut data,
+                width,
+                left,
+                top + cell.height + PADDING,
+                &cell.label,
+                LABEL_SCALE,
+                LABEL_COLOR,
+            );
+        }
+
+        (height, data)
+    }
+
+    /// The format is picked from the file extension.
+    pub fn write(&self, output_file: impl AsRef<Path>) -> anyhow::Result<()> {
+        let (rows, data) = self.composite();
+
+        output::write_pathlike(rows, data, output_file, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_layout() {
+        let mut sheet = ContactSheet::new(2);
+        for value in 0..3 {
+            sheet.add_image(format!("{value}"), 4, vec![255; 4 * 2 * 3]);
+        }
+
+        let (rows, data) = sheet.composite();
+        let (cell_width, cell_height) = sheet.cell_size();
+        let width = data.len() / 3 / rows;
+
+        assert_eq!(width, 2 * cell_width + PADDING);
+        assert_eq!(rows, 2 * cell_height + PADDING);
+
+        // Top left corner of the third image, on the second row
+        let offset = ((PADDING + cell_height) * width + PADDING) * 3;
+        assert_eq!(data[offset..offset + 3], [255, 255, 255]);
+
+        // Nothing on the second row, second column
+        let offset = ((PADDING + cell_height) * width + PADDING + cell_width) * 3;
+        assert_eq!(data[offset..offset + 3], BACKGROUND);
+    }
+}
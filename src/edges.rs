@@ -0,0 +1,108 @@
+//! Object outlines drawn over a render, to explain the geometry in turntables and the like.
+
+use bevy_color::LinearRgba;
+
+use crate::{camera::Camera, hittable::Hittable};
+
+/// Finds edges where the object seen through neighboring pixel centers changes,
+/// or where the surface folds sharply, like the edges of a cube.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeOverlay {
+    pub color: LinearRgba,
+    /// Neighboring normals further apart than this, in radians, make an edge.
+    pub crease_angle: f32,
+}
+
+impl Default for EdgeOverlay {
+    fn default() -> Self {
+        Self {
+            color: LinearRgba::BLACK,
+            crease_angle: 30f32.to_radians(),
+        }
+    }
+}
+
+impl EdgeOverlay {
+    /// Which pixels are on an edge, row-major.
+    pub fn edges(&self, camera: &Camera, world: &dyn Hittable) -> Vec<bool> {
+        let (width, height) = (camera.im_width, camera.im_height);
+        let min_cos = self.crease_angle.cos();
+
+        let hits: Vec<_> = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (row, col)))
+            .map(|(row, col)| camera.inspect(world, row, col))
+            .collect();
+
+        let differ = |a: usize, b: usize| match (&hits[a], &hits[b]) {
+            (Some(a), Some(b)) => a.object_id != b.object_id || a.normal.dot(*b.normal) < min_cos,
+            (None, None) => false,
+            _ => true,
+        };
+
+        // Mark both pixels of a differing pair, so outlines are as thick on either side
+        let mut edges = vec![false; hits.len()];
+        for row in 0..height {
+            for col in 0..width {
+                let index = row * width + col;
+                let neighbors = [
+                    (col + 1 < width).then_some(index + 1),
+                    (row + 1 < height).then_some(index + width),
+                ];
+
+                for neighbor in neighbors.into_iter().flatten() {
+                    if differ(index, neighbor) {
+                        edges[index] = true;
+                        edges[neighbor] = true;
+                    }
+                }
+            }
+        }
+
+        edges
+    }
+
+    /// Draw the edges over a rendered image.
+    pub fn apply(&self, camera: &Camera, world: &dyn Hittable, image: &mut [LinearRgba]) {
+        for (pixel, edge) in image.iter_mut().zip(self.edges(camera, world)) {
+            if edge {
+                *pixel = self.color;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::Vec3;
+
+    use super::*;
+    use crate::{hittable::Hittables, material::Lambertian, objects::Cuboid};
+
+    #[test]
+    fn outlines_and_creases() {
+        let mut world = Hittables::default();
+        world.add(Cuboid::new(
+            Vec3::new(-0.6, -0.6, -3.0),
+            Vec3::new(0.6, 0.6, -2.0),
+            Lambertian::linear_rgb(0.5, 0.5, 0.5),
+        ));
+
+        let camera = Camera::builder()
+            .resolution(21, 21)
+            .position(Vec3::new(1.5, 1.5, 0.0))
+            .look_at(Vec3::new(0.0, 0.0, -2.5), Vec3::Y)
+            .build()
+            .unwrap();
+
+        let edges = EdgeOverlay::default().edges(&camera, &world);
+
+        // Background corners are plain, the outline and the crease through the middle are not
+        assert!(!edges[0] && !edges[20 * 21 + 20]);
+        assert!(edges.iter().any(|&edge| edge));
+        let center = 10 * 21 + 10;
+        let crease_near_center = [center, center - 1, center + 1, center - 21, center + 21]
+            .iter()
+            .any(|&index| edges[index]);
+        assert!(crease_near_center);
+    }
+}
@@ -0,0 +1,334 @@
+//! Equirectangular environment maps, e.g. an HDRI sky probe lighting the scene from all around.
+//!
+//! A small bright spot like the sun is rarely found by rays scattered at random,
+//! so the map keeps a distribution over its pixels by luminance to aim rays at it,
+//! see [`crate::pdf::MixturePdf`].
+
+use std::{f32::consts::PI, path::Path};
+
+use anyhow::Context;
+use bevy_color::{Color, LinearRgba, Luminance};
+use bevy_math::{Dir3, Vec2, Vec3};
+use rand::{Rng, RngCore};
+
+use crate::pdf::Pdf;
+
+/// A panorama around the scene, its middle straight ahead along -Z and its top straight up.
+#[derive(Debug)]
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    /// Row-major from the top left.
+    pixels: Vec<LinearRgba>,
+    /// The chance of picking each pixel, row-major.
+    chances: Vec<f32>,
+    /// Running sums of the rows' chances.
+    row_cdf: Vec<f32>,
+    /// Running sums of the chances within each row, row-major.
+    column_cdfs: Vec<f32>,
+}
+
+impl EnvironmentMap {
+    /// Load a Radiance HDR or OpenEXR image, or any other image `image` reads, taken as linear.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .with_context(|| format!("loading environment map {}", path.display()))?
+            .into_rgb32f();
+
+        let pixels = image
+            .pixels()
+            .map(|pixel| LinearRgba::rgb(pixel[0], pixel[1], pixel[2]))
+            .collect();
+        Ok(Self::new(
+            image.width() as usize,
+            image.height() as usize,
+            pixels,
+        ))
+    }
+
+    /// From linear pixels, row-major from the top left.
+    pub fn new(width: usize, height: usize, pixels: Vec<LinearRgba>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "pixels should be width * height"
+        );
+
+        // Rows near the poles are squeezed into less of the sphere
+        let row_area = |row: usize| ((row as f32 + 0.5) / height as f32 * PI).sin();
+        let mut weights: Vec<f32> = pixels
+            .iter()
+            .enumerate()
+            .map(|(index, pixel)| {
+                let luminance = pixel.luminance();
+                let luminance = if luminance.is_finite() {
+                    luminance
+                } else {
+                    0.0
+                };
+                luminance.max(0.0) * row_area(index / width.max(1))
+            })
+            .collect();
+        let mut total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            // Black, so any direction is as good as another
+            weights = (0..pixels.len())
+                .map(|index| row_area(index / width))
+                .collect();
+            total = weights.iter().sum();
+        }
+        let chances: Vec<f32> = weights.iter().map(|weight| weight / total).collect();
+
+        let mut row_cdf = Vec::with_capacity(height);
+        let mut column_cdfs = Vec::with_capacity(pixels.len());
+        let mut rows_sum = 0.0;
+        for row in chances.chunks(width.max(1)) {
+            let row_sum: f32 = row.iter().sum();
+            rows_sum += row_sum;
+            row_cdf.push(rows_sum);
+
+            let mut sum = 0.0;
+            for (column, chance) in row.iter().enumerate() {
+                sum += if row_sum > 0.0 { chance / row_sum } else { 0.0 };
+                // Rows never picked still need a valid distribution
+                column_cdfs.push(if row_sum > 0.0 {
+                    sum
+                } else {
+                    (column + 1) as f32 / width as f32
+                });
+            }
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+            chances,
+            row_cdf,
+            column_cdfs,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The color seen looking in `direction`.
+    pub fn color(&self, direction: Dir3) -> Color {
+        match self.pixel_index(direction) {
+            Some(index) => self.pixels[index].into(),
+            // Stands out, like a missing texture in a game
+            None => Color::linear_rgb(1.0, 0.0, 1.0),
+        }
+    }
+
+    /// Describe anything that would make this map render wrongly.
+    pub fn validate(&self, problems: &mut Vec<String>) {
+        if self.pixels.is_empty() {
+            problems.push("environment map: the image is empty".to_string());
+        }
+        if self.pixels.iter().any(|pixel| {
+            !(pixel.red.is_finite() && pixel.green.is_finite() && pixel.blue.is_finite())
+        }) {
+            problems.push("environment map: some pixels are not finite".to_string());
+        }
+    }
+
+    /// The index of the pixel seen looking in `direction`, row-major.
+    pub fn pixel_index(&self, direction: Dir3) -> Option<usize> {
+        if self.pixels.is_empty() {
+            return None;
+        }
+
+        let uv = direction_to_uv(direction);
+        let column = ((uv.x * self.width as f32) as usize).min(self.width - 1);
+        let row = ((uv.y * self.height as f32) as usize).min(self.height - 1);
+        Some(row * self.width + column)
+    }
+}
+
+impl Pdf for EnvironmentMap {
+    fn value(&self, direction: Dir3) -> f32 {
+        let Some(index) = self.pixel_index(direction) else {
+            return 0.0;
+        };
+
+        // From the density over the image to one over the sphere of directions,
+        // which the image stretches by 2 pi across and pi down, and by 1 / sin(theta) at each row
+        let sin_theta = (1.0 - direction.y * direction.y).max(0.0).sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let density = self.chances[index] * (self.width * self.height) as f32;
+        density / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Dir3 {
+        if self.pixels.is_empty() {
+            return Dir3::Y;
+        }
+
+        let pick = |cdf: &[f32], rng: &mut dyn RngCore| {
+            let target = rng.gen::<f32>() * cdf[cdf.len() - 1];
+            cdf.partition_point(|&sum| sum <= target).min(cdf.len() - 1)
+        };
+        let row = pick(&self.row_cdf, rng);
+        let column = pick(
+            &self.column_cdfs[row * self.width..(row + 1) * self.width],
+            rng,
+        );
+
+        let uv = Vec2::new(
+            (column as f32 + rng.gen::<f32>()) / self.width as f32,
+            (row as f32 + rng.gen::<f32>()) / self.height as f32,
+        );
+        uv_to_direction(uv)
+    }
+}
+
+/// Across the image as `u` turning right from -Z, down it as `v` from straight up.
+pub(crate) fn direction_to_uv(direction: Dir3) -> Vec2 {
+    let theta = direction.y.clamp(-1.0, 1.0).acos();
+    let phi = direction.x.atan2(-direction.z);
+
+    Vec2::new(0.5 + phi / std::f32::consts::TAU, theta / PI)
+}
+
+pub(crate) fn uv_to_direction(uv: Vec2) -> Dir3 {
+    let theta = uv.y * PI;
+    let phi = (uv.x - 0.5) * std::f32::consts::TAU;
+    let (sin_theta, cos_theta) = theta.sin_cos();
+    let (sin_phi, cos_phi) = phi.sin_cos();
+
+    Dir3::new(Vec3::new(
+        sin_theta * sin_phi,
+        cos_theta,
+        -sin_theta * cos_phi,
+    ))
+    .unwrap_or(Dir3::Y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        background::Background, camera::Camera, hittable::Hittables, material::Lambertian,
+        objects::Quad, pdf::SpherePdf, random::sample_rng,
+    };
+
+    /// A dim blue sky with a small bright sun high up ahead.
+    fn sunny_sky() -> EnvironmentMap {
+        let (width, height) = (64, 32);
+        let mut pixels = vec![LinearRgba::rgb(0.1, 0.2, 0.4); width * height];
+        for index in [
+            6 * width + 34,
+            6 * width + 35,
+            7 * width + 34,
+            7 * width + 35,
+        ] {
+            pixels[index] = LinearRgba::rgb(1000.0, 900.0, 800.0);
+        }
+        EnvironmentMap::new(width, height, pixels)
+    }
+
+    #[test]
+    fn directions_map_to_the_panorama() {
+        let ahead = Dir3::NEG_Z;
+        assert!(direction_to_uv(ahead).abs_diff_eq(Vec2::new(0.5, 0.5), 1e-6));
+        // Turning right goes right across the image
+        assert!(direction_to_uv(Dir3::X).abs_diff_eq(Vec2::new(0.75, 0.5), 1e-6));
+        assert!(direction_to_uv(Dir3::Y).y < 1e-3);
+
+        for uv in [
+            Vec2::new(0.1, 0.2),
+            Vec2::new(0.6, 0.9),
+            Vec2::new(0.95, 0.5),
+        ] {
+            let round_trip = direction_to_uv(uv_to_direction(uv));
+            assert!(round_trip.abs_diff_eq(uv, 1e-5), "{uv} {round_trip}");
+        }
+    }
+
+    #[test]
+    fn sampling_follows_the_light() {
+        let sky = sunny_sky();
+        let mut rng = sample_rng(0, 0, 0);
+
+        // A density over the sphere of directions
+        let samples = 400_000;
+        let integral = (0..samples)
+            .map(|_| sky.value(SpherePdf.generate(&mut rng)) / SpherePdf.value(Dir3::X))
+            .sum::<f32>()
+            / samples as f32;
+        assert!((integral - 1.0).abs() < 0.05, "{integral}");
+
+        // Mostly towards the sun, which outshines the rest of the sky
+        let sun = uv_to_direction(Vec2::new(35.0 / 64.0, 7.0 / 32.0));
+        let towards_sun = (0..1000)
+            .map(|_| sky.generate(&mut rng))
+            .inspect(|&direction| assert!(sky.value(direction) > 0.0))
+            .filter(|direction| direction.dot(sun.as_vec3()) > 0.98)
+            .count();
+        assert!(towards_sun > 800, "{towards_sun}");
+    }
+
+    #[test]
+    fn importance_sampling_reduces_noise() {
+        let sky = std::sync::Arc::new(sunny_sky());
+
+        let mut world = Hittables::default();
+        world.add(Quad::new(
+            Vec3::new(-10.0, -1.0, 10.0),
+            Vec3::new(20.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -20.0),
+            Lambertian::linear_rgb(0.5, 0.5, 0.5),
+        ));
+
+        // The same sky, but only found by scattered rays
+        let blind = {
+            let sky = sky.clone();
+            Background::custom(move |ray| sky.color(ray.direction()))
+        };
+
+        let pixel_values = |background: Background| {
+            let mut camera = Camera::builder()
+                .resolution(1, 1)
+                .samples(1)
+                .bounces(2)
+                .min_dist(0.001)
+                .position(Vec3::ZERO)
+                .look_at(Vec3::new(0.0, -1.0, -0.1), Vec3::Y)
+                .background(background)
+                .build()
+                .unwrap();
+            (0..100_000)
+                .map(|seed| {
+                    camera.seed = seed;
+                    camera.render_pixel(&world, 0, 0).red
+                })
+                .collect::<Vec<_>>()
+        };
+        let stats = |values: Vec<f32>| {
+            let mean = values.iter().sum::<f32>() / values.len() as f32;
+            let variance =
+                values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len() as f32;
+            (mean, variance)
+        };
+
+        let (aimed_mean, aimed_variance) = stats(pixel_values(Background::Environment(sky)));
+        let (blind_mean, blind_variance) = stats(pixel_values(blind));
+        assert!(
+            (aimed_mean - blind_mean).abs() < 0.1 * aimed_mean,
+            "{aimed_mean} {blind_mean}"
+        );
+        assert!(
+            aimed_variance < 0.1 * blind_variance,
+            "{aimed_variance} {blind_variance}"
+        );
+    }
+}
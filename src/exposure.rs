@@ -0,0 +1,91 @@
+use std::ops::Range;
+
+/// Camera settings as found on a real camera body.
+///
+/// The same three numbers decide how bright the image is,
+/// how much is out of focus (the aperture) and how much moving things smear (the shutter).
+#[derive(Debug, Clone, Copy)]
+pub struct Exposure {
+    /// Focal length divided by the aperture diameter, e.g. 8.0 for f/8.
+    pub f_number: f32,
+
+    /// How long the shutter is open, in seconds.
+    pub shutter_time: f32,
+
+    /// Sensor sensitivity.
+    pub iso: f32,
+}
+
+impl Default for Exposure {
+    /// f/8, 1/125 s, ISO 100.
+    /// This is the reference exposure, i.e. it leaves the image brightness untouched.
+    fn default() -> Self {
+        Self {
+            f_number: 8.0,
+            shutter_time: 1.0 / 125.0,
+            iso: 100.0,
+        }
+    }
+}
+
+impl Exposure {
+    pub fn new(f_number: f32, shutter_time: f32, iso: f32) -> Self {
+        Self {
+            f_number,
+            shutter_time,
+            iso,
+        }
+    }
+
+    /// The exposure value normalized to ISO 100.
+    /// Each step up halves the light hitting the sensor.
+    pub fn ev100(&self) -> f32 {
+        (self.f_number * self.f_number / self.shutter_time * 100.0 / self.iso).log2()
+    }
+
+    /// How much to scale radiance by, relative to the default exposure.
+    ///
+    /// Since the scene has no physical units the default exposure is defined to map
+    /// radiance as-is, and every stop away from it doubles or halves the result.
+    pub fn brightness(&self) -> f32 {
+        (Self::default().ev100() - self.ev100()).exp2()
+    }
+
+    /// Diameter of the aperture for a lens with the given focal length.
+    pub fn aperture_diameter(&self, focal_length: f32) -> f32 {
+        focal_length / self.f_number
+    }
+
+    /// The time span the shutter is open, starting at zero.
+    pub fn shutter_interval(&self) -> Range<f32> {
+        0.0..self.shutter_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_double_or_halve_the_brightness() {
+        assert_eq!(Exposure::default().brightness(), 1.0);
+
+        // Twice the time, twice the sensitivity, or one stop wider each let in twice the light
+        let brighter = [
+            Exposure::new(8.0, 2.0 / 125.0, 100.0),
+            Exposure::new(8.0, 1.0 / 125.0, 200.0),
+            Exposure::new(8.0 / 2f32.sqrt(), 1.0 / 125.0, 100.0),
+        ];
+        for exposure in brighter {
+            assert!((exposure.brightness() - 2.0).abs() < 1e-4, "{exposure:?}");
+        }
+        assert!((Exposure::new(16.0, 1.0 / 125.0, 100.0).brightness() - 0.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn aperture_and_shutter() {
+        let exposure = Exposure::new(4.0, 0.5, 100.0);
+        assert_eq!(exposure.aperture_diameter(2.0), 0.5);
+        assert_eq!(exposure.shutter_interval(), 0.0..0.5);
+    }
+}
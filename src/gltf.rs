@@ -0,0 +1,582 @@
+//! Loading glTF 2.0 scenes, either a `.gltf` with its buffers or a single `.glb`.
+//!
+//! Meshes are placed by the node hierarchy, and PBR materials are approximated
+//! with the materials available here. Textures, animations and skins are ignored.
+
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context};
+use bevy_color::{Color, LinearRgba};
+use bevy_math::{Affine3A, Mat4, Quat, Vec2, Vec3};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{
+    hittable::Hittables,
+    material::{Dielectric, DiffuseLight, DynMaterial, Lambertian, Metal},
+    mesh::Mesh,
+};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_JSON_CHUNK: u32 = 0x4E4F534A;
+const GLB_BIN_CHUNK: u32 = 0x004E4942;
+
+/// Triangle lists, the only primitive mode with surfaces to hit.
+const TRIANGLES: u64 = 4;
+
+/// The first camera in a glTF scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GltfCamera {
+    pub position: Vec3,
+    /// Like ours, glTF cameras look towards -Z with +Y up before turning.
+    pub orientation: Quat,
+    /// Vertical field of view in degrees, for perspective cameras.
+    pub vfov: Option<f32>,
+}
+
+#[derive(Debug)]
+pub struct GltfScene {
+    /// A [`Mesh`] per primitive.
+    pub world: Hittables,
+    pub camera: Option<GltfCamera>,
+}
+
+/// Load the default scene of a `.gltf` or `.glb` file.
+pub fn load(path: impl AsRef<Path>) -> anyhow::Result<GltfScene> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).with_context(|| format!("reading glTF {}", path.display()))?;
+
+    parse(&bytes, path.parent().unwrap_or(Path::new(".")))
+        .with_context(|| format!("loading glTF {}", path.display()))
+}
+
+/// Load from the contents of a `.gltf` or `.glb` file.
+/// External buffers are looked up relative to `directory`.
+pub fn parse(bytes: &[u8], directory: &Path) -> anyhow::Result<GltfScene> {
+    let (json, bin) = if bytes.starts_with(GLB_MAGIC) {
+        split_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+    let document: Value = serde_json::from_slice(json).context("parsing the JSON")?;
+
+    let buffers = document["buffers"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| {
+            load_buffer(buffer, bin, directory).with_context(|| format!("buffer {index}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let loader = Loader {
+        document: &document,
+        buffers,
+    };
+    let mut scene = GltfScene {
+        world: Hittables::default(),
+        camera: None,
+    };
+
+    for root in loader.root_nodes() {
+        loader.add_node(root, Affine3A::IDENTITY, &mut scene)?;
+    }
+
+    Ok(scene)
+}
+
+/// The JSON and binary chunks of a `.glb`.
+fn split_glb(bytes: &[u8]) -> anyhow::Result<(&[u8], Option<&[u8]>)> {
+    let u32_at = |offset: usize| -> anyhow::Result<u32> {
+        let word = bytes
+            .get(offset..offset + 4)
+            .context("the GLB ends early")?;
+        Ok(u32::from_le_bytes(word.try_into()?))
+    };
+
+    ensure!(u32_at(4)? == 2, "only glTF 2 is supported");
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset < bytes.len() {
+        let length = u32_at(offset)? as usize;
+        let kind = u32_at(offset + 4)?;
+        let data = bytes
+            .get(offset + 8..offset + 8 + length)
+            .context("a GLB chunk ends early")?;
+        match kind {
+            GLB_JSON_CHUNK => json = Some(data),
+            GLB_BIN_CHUNK => bin = Some(data),
+            _ => {}
+        }
+        offset += 8 + length;
+    }
+
+    Ok((json.context("the GLB has no JSON")?, bin))
+}
+
+fn load_buffer(buffer: &Value, bin: Option<&[u8]>, directory: &Path) -> anyhow::Result<Vec<u8>> {
+    let data = match buffer["uri"].as_str() {
+        Some(uri) if uri.starts_with("data:") => {
+            let (_, data) = uri
+                .split_once(";base64,")
+                .context("data URIs must be base64")?;
+            decode_base64(data)?
+        }
+        Some(uri) => {
+            let path = directory.join(uri);
+            std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?
+        }
+        None => bin.context("no URI and no GLB binary chunk")?.to_vec(),
+    };
+
+    let length = buffer["byteLength"].as_u64().unwrap_or_default() as usize;
+    ensure!(
+        data.len() >= length,
+        "{} bytes, but the buffer should have {length}",
+        data.len()
+    );
+    Ok(data)
+}
+
+fn decode_base64(text: &str) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for byte in text.bytes().take_while(|&byte| byte != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => bail!("{:?} is not base64", byte as char),
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            data.push((bits >> count) as u8);
+        }
+    }
+
+    Ok(data)
+}
+
+struct Loader<'a> {
+    document: &'a Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Loader<'_> {
+    fn get(&self, kind: &str, index: usize) -> anyhow::Result<&Value> {
+        self.document[kind]
+            .get(index)
+            .with_context(|| format!("there is no {kind} {index}"))
+    }
+
+    /// Nodes of the default scene, or every node without a parent if there are no scenes.
+    fn root_nodes(&self) -> Vec<usize> {
+        let indices = |value: &Value| -> Vec<usize> {
+            value
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|index| Some(index.as_u64()? as usize))
+                .collect()
+        };
+
+        let scene = self.document["scene"].as_u64().unwrap_or_default() as usize;
+        if let Some(scene) = self.document["scenes"].get(scene) {
+            return indices(&scene["nodes"]);
+        }
+
+        let nodes = self.document["nodes"].as_array().map_or(0, Vec::len);
+        let children: Vec<usize> = (0..nodes)
+            .flat_map(|node| indices(&self.document["nodes"][node]["children"]))
+            .collect();
+        (0..nodes).filter(|node| !children.contains(node)).collect()
+    }
+
+    fn add_node(
+        &self,
+        index: usize,
+        parent: Affine3A,
+        scene: &mut GltfScene,
+    ) -> anyhow::Result<()> {
+        let node = self.get("nodes", index)?;
+        let transform = parent * node_transform(node)?;
+
+        if let Some(mesh) = node["mesh"].as_u64() {
+            self.add_mesh(mesh as usize, transform, &mut scene.world)
+                .with_context(|| format!("mesh {mesh}"))?;
+        }
+
+        if let (Some(camera), None) = (node["camera"].as_u64(), scene.camera) {
+            let (_, orientation, position) = transform.to_scale_rotation_translation();
+            let yfov = self.get("cameras", camera as usize)?["perspective"]["yfov"].as_f64();
+            scene.camera = Some(GltfCamera {
+                position,
+                orientation,
+                vfov: yfov.map(|radians| (radians as f32).to_degrees()),
+            });
+        }
+
+        for child in node["children"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            let child = child.as_u64().context("child indices are numbers")? as usize;
+            self.add_node(child, transform, scene)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_mesh(
+        &self,
+        index: usize,
+        transform: Affine3A,
+        world: &mut Hittables,
+    ) -> anyhow::Result<()> {
+        let primitives = self.get("meshes", index)?["primitives"]
+            .as_array()
+            .context("a mesh needs primitives")?;
+
+        for primitive in primitives {
+            let mode = primitive["mode"].as_u64().unwrap_or(TRIANGLES);
+            if mode != TRIANGLES {
+                warn!("skipping a primitive of mode {mode}, only triangles are supported");
+                continue;
+            }
+
+            let attributes = &primitive["attributes"];
+            let accessor = |name: &str| attributes[name].as_u64().map(|index| index as usize);
+
+            let positions: Vec<Vec3> = self
+                .floats::<3>(accessor("POSITION").context("no positions")?)?
+                .into_iter()
+                .map(Vec3::from_array)
+                .collect();
+
+            let indices = match primitive["indices"].as_u64() {
+                Some(indices) => self.indices(indices as usize)?,
+                None => (0..positions.len() as u32).collect(),
+            };
+            let triangles = indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect();
+
+            let material = match primitive["material"].as_u64() {
+                Some(material) => convert_material(self.get("materials", material as usize)?),
+                None => Lambertian::linear_rgb(0.8, 0.8, 0.8).into(),
+            };
+
+            let mut mesh = Mesh::new(positions, triangles, material);
+            if let Some(normals) = accessor("NORMAL") {
+                mesh = mesh.with_normals(
+                    self.floats::<3>(normals)?
+                        .into_iter()
+                        .map(Vec3::from_array)
+                        .collect(),
+                );
+            }
+            if let Some(uvs) = accessor("TEXCOORD_0") {
+                // glTF's v goes down the image, ours goes up
+                mesh = mesh.with_uvs(
+                    self.floats::<2>(uvs)?
+                        .into_iter()
+                        .map(|[u, v]| Vec2::new(u, 1.0 - v))
+                        .collect(),
+                );
+            }
+            if transform != Affine3A::IDENTITY {
+                mesh.transform(transform);
+            }
+
+            world.add(mesh);
+        }
+
+        Ok(())
+    }
+
+    /// The raw bytes of each element of an accessor, and its component type.
+    fn elements(&self, index: usize) -> anyhow::Result<(Vec<&[u8]>, u64)> {
+        let accessor = self.get("accessors", index)?;
+        ensure!(
+            accessor.get("sparse").is_none(),
+            "accessor {index}: sparse accessors are not supported"
+        );
+
+        let component_type = accessor["componentType"]
+            .as_u64()
+            .context("no component type")?;
+        let component_size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            other => bail!("accessor {index}: unknown component type {other}"),
+        };
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            other => bail!("accessor {index}: unsupported type {other:?}"),
+        };
+        let element_size = component_size * components;
+        let count = accessor["count"].as_u64().context("no count")? as usize;
+
+        let view = self.get(
+            "bufferViews",
+            accessor["bufferView"]
+                .as_u64()
+                .context("accessors without buffer views are not supported")? as usize,
+        )?;
+        let buffer = &self.buffers[view["buffer"].as_u64().context("no buffer")? as usize];
+        let start = view["byteOffset"].as_u64().unwrap_or_default() as usize
+            + accessor["byteOffset"].as_u64().unwrap_or_default() as usize;
+        let stride = view["byteStride"]
+            .as_u64()
+            .map_or(element_size, |stride| stride as usize);
+
+        let elements = (0..count)
+            .map(|element| {
+                let offset = start + element * stride;
+                buffer
+                    .get(offset..offset + element_size)
+                    .with_context(|| format!("accessor {index} reaches past its buffer"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok((elements, component_type))
+    }
+
+    /// Vectors of floats, or of integers normalized to `[0, 1]`.
+    fn floats<const N: usize>(&self, index: usize) -> anyhow::Result<Vec<[f32; N]>> {
+        let (elements, component_type) = self.elements(index)?;
+
+        elements
+            .into_iter()
+            .map(|bytes| {
+                let mut values = [0.0; N];
+                for (component, value) in values.iter_mut().enumerate() {
+                    *value = match component_type {
+                        5126 => f32::from_le_bytes(bytes[4 * component..][..4].try_into()?),
+                        5121 => bytes[component] as f32 / 255.0,
+                        5123 => {
+                            u16::from_le_bytes(bytes[2 * component..][..2].try_into()?) as f32
+                                / 65535.0
+                        }
+                        other => bail!("accessor {index}: component type {other} is not a float"),
+                    };
+                }
+                Ok(values)
+            })
+            .collect()
+    }
+
+    fn indices(&self, index: usize) -> anyhow::Result<Vec<u32>> {
+        let (elements, component_type) = self.elements(index)?;
+
+        elements
+            .into_iter()
+            .map(|bytes| {
+                Ok(match component_type {
+                    5121 => bytes[0] as u32,
+                    5123 => u16::from_le_bytes(bytes.try_into()?) as u32,
+                    5125 => u32::from_le_bytes(bytes.try_into()?),
+                    other => bail!("accessor {index}: component type {other} is not an index"),
+                })
+            })
+            .collect()
+    }
+}
+
+fn node_transform(node: &Value) -> anyhow::Result<Affine3A> {
+    let floats = |value: &Value| -> Option<Vec<f32>> {
+        value
+            .as_array()?
+            .iter()
+            .map(|value| value.as_f64().map(|value| value as f32))
+            .collect()
+    };
+
+    if let Some(matrix) = floats(&node["matrix"]) {
+        ensure!(matrix.len() == 16, "a matrix has 16 numbers");
+        // Column-major, like glam
+        return Ok(Affine3A::from_mat4(Mat4::from_cols_slice(&matrix)));
+    }
+
+    let translation = floats(&node["translation"]).map_or(Vec3::ZERO, |t| Vec3::from_slice(&t));
+    let rotation = floats(&node["rotation"]).map_or(Quat::IDENTITY, |r| Quat::from_slice(&r));
+    let scale = floats(&node["scale"]).map_or(Vec3::ONE, |s| Vec3::from_slice(&s));
+
+    Ok(Affine3A::from_scale_rotation_translation(
+        scale,
+        rotation.normalize(),
+        translation,
+    ))
+}
+
+/// Approximate a glTF PBR material, the same way as Bevy's standard material.
+fn convert_material(material: &Value) -> DynMaterial {
+    let number = |value: &Value, default: f32| value.as_f64().map_or(default, |value| value as f32);
+    let color = |value: &Value, default: [f32; 3]| {
+        let mut rgb = default;
+        for (channel, value) in rgb.iter_mut().zip(value.as_array().into_iter().flatten()) {
+            *channel = number(value, *channel);
+        }
+        LinearRgba::rgb(rgb[0], rgb[1], rgb[2])
+    };
+
+    let pbr = &material["pbrMetallicRoughness"];
+    let extensions = &material["extensions"];
+    let base_color = color(&pbr["baseColorFactor"], [1.0; 3]);
+
+    let strength = number(
+        &extensions["KHR_materials_emissive_strength"]["emissiveStrength"],
+        1.0,
+    );
+    let emissive = color(&material["emissiveFactor"], [0.0; 3]) * strength;
+    if emissive != LinearRgba::rgb(0.0, 0.0, 0.0) {
+        return DiffuseLight::new(emissive).into();
+    }
+
+    let transmission = number(
+        &extensions["KHR_materials_transmission"]["transmissionFactor"],
+        0.0,
+    );
+    if transmission > 0.5 {
+        let mut glass =
+            Dielectric::refraction_index(number(&extensions["KHR_materials_ior"]["ior"], 1.5));
+        glass.color = base_color.into();
+        return glass.into();
+    }
+
+    if number(&pbr["metallicFactor"], 1.0) > 0.5 {
+        Metal::new(
+            Color::from(base_color),
+            number(&pbr["roughnessFactor"], 1.0),
+        )
+        .into()
+    } else {
+        Lambertian::new(base_color).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Hittable, ray::Ray};
+
+    fn base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut text = String::new();
+        for chunk in data.chunks(3) {
+            let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+                bits | (byte as u32) << (16 - 8 * i)
+            });
+            for i in 0..=chunk.len() {
+                text.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            }
+        }
+        text
+    }
+
+    /// One triangle in the z = 0 plane, moved to z = -3 by its node, and a camera at the origin.
+    fn triangle_gltf(buffer_uri: Option<&str>) -> (String, Vec<u8>) {
+        let mut bin = vec![];
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend(value.to_le_bytes());
+        }
+        for index in [0u16, 1, 2, 0] {
+            bin.extend(index.to_le_bytes());
+        }
+
+        let uri = buffer_uri.map_or(String::new(), |uri| format!(r#""uri": "{uri}","#));
+        let json = format!(
+            r#"{{
+  "asset": {{ "version": "2.0" }},
+  "scene": 0,
+  "scenes": [{{ "nodes": [0, 1] }}],
+  "nodes": [
+    {{ "children": [2], "translation": [0, 0, -3] }},
+    {{ "camera": 0, "rotation": [0, 0.7071068, 0, 0.7071068] }},
+    {{ "mesh": 0 }}
+  ],
+  "cameras": [{{ "type": "perspective", "perspective": {{ "yfov": 0.5, "znear": 0.1 }} }}],
+  "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0 }}] }}],
+  "materials": [{{ "pbrMetallicRoughness": {{ "baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0 }} }}],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+    {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+  ],
+  "bufferViews": [
+    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+    {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+  ],
+  "buffers": [{{ {uri} "byteLength": {} }}]
+}}"#,
+            bin.len()
+        );
+
+        (json, bin)
+    }
+
+    fn check(scene: &GltfScene) {
+        assert_eq!(scene.world.objects.len(), 1);
+        let ray = Ray::new(Vec3::new(0.2, 0.2, 0.0), Vec3::NEG_Z);
+        let hit = scene.world.hit(&ray, 0.0..f32::MAX).unwrap();
+        assert!((hit.distance - 3.0).abs() < 1e-5);
+        assert_eq!(
+            hit.material.type_name(),
+            std::any::type_name::<Lambertian>()
+        );
+
+        let camera = scene.camera.unwrap();
+        assert_eq!(camera.position, Vec3::ZERO);
+        assert!((camera.vfov.unwrap() - 0.5f32.to_degrees()).abs() < 1e-4);
+        assert!((camera.orientation * Vec3::NEG_Z).abs_diff_eq(Vec3::NEG_X, 1e-5));
+    }
+
+    #[test]
+    fn embedded_and_binary() -> anyhow::Result<()> {
+        // Buffer as a data URI
+        let (_, bin) = triangle_gltf(None);
+        let uri = format!("data:application/octet-stream;base64,{}", base64(&bin));
+        let (json, _) = triangle_gltf(Some(&uri));
+        check(&parse(json.as_bytes(), Path::new("."))?);
+
+        // The same as a GLB, with the buffer in the binary chunk
+        let (json, mut bin) = triangle_gltf(None);
+        let mut json = json.into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let mut glb = vec![];
+        glb.extend(GLB_MAGIC);
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes());
+        for (kind, chunk) in [(GLB_JSON_CHUNK, &json), (GLB_BIN_CHUNK, &bin)] {
+            glb.extend((chunk.len() as u32).to_le_bytes());
+            glb.extend(kind.to_le_bytes());
+            glb.extend(chunk);
+        }
+        check(&parse(&glb, Path::new("."))?);
+
+        // Missing buffer files are reported
+        let (json, _) = triangle_gltf(Some("missing.bin"));
+        let error = parse(json.as_bytes(), Path::new(".")).unwrap_err();
+        assert!(format!("{error:#}").contains("missing.bin"), "{error:#}");
+
+        Ok(())
+    }
+}
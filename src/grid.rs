@@ -0,0 +1,254 @@
+use std::{ops::Range, sync::Arc};
+
+use bevy_math::{IVec3, UVec3, Vec3};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{Hit, Hittable, Hittables},
+    ray::Ray,
+    stats::SceneStats,
+};
+
+/// Roughly how many cells there are per object.
+const DENSITY: f32 = 3.0;
+const MAX_RESOLUTION: u32 = 128;
+/// Objects this many times larger than the typical one are left out of the grid,
+/// else a ground sphere would stretch the cells over the whole horizon.
+const LARGE_FACTOR: f32 = 20.0;
+
+/// A uniform grid over the objects of a [`Hittables`].
+///
+/// Each cell lists the objects overlapping it, and rays step from cell to cell
+/// with a 3D-DDA until they hit something. Best for evenly spread objects of similar size.
+#[derive(Debug)]
+pub struct Grid {
+    objects: Vec<Arc<Box<dyn Hittable>>>,
+    /// Objects left out of the grid, which every ray is tested against.
+    large: Vec<usize>,
+    /// Of the objects in the grid.
+    bounds: Aabb,
+    resolution: UVec3,
+    cell_size: Vec3,
+    /// Where each cell's objects start in `object_indices`, and where the last one ends.
+    cell_starts: Vec<usize>,
+    object_indices: Vec<usize>,
+}
+
+impl Grid {
+    pub fn new(world: &Hittables) -> Self {
+        let objects = world.objects.clone();
+        let boxes: Vec<Aabb> = objects.iter().map(|object| object.bounding_box()).collect();
+
+        let mut extents: Vec<f32> = boxes.iter().map(|aabb| aabb.size().max_element()).collect();
+        extents.sort_by(f32::total_cmp);
+        let typical = extents.get(extents.len() / 2).copied().unwrap_or_default();
+
+        let (large, small): (Vec<usize>, Vec<usize>) = (0..boxes.len()).partition(|&i| {
+            let extent = boxes[i].size().max_element();
+            !extent.is_finite() || extent > LARGE_FACTOR * typical
+        });
+
+        let bounds = small
+            .iter()
+            .fold(Aabb::EMPTY, |bounds, &i| bounds.union(&boxes[i]));
+
+        let size = bounds.size();
+        let resolution = if small.is_empty() {
+            UVec3::ONE
+        } else {
+            let cells_per_unit = DENSITY * (small.len() as f32).cbrt() / size.max_element();
+            (size * cells_per_unit)
+                .round()
+                .as_uvec3()
+                .clamp(UVec3::ONE, UVec3::splat(MAX_RESOLUTION))
+        };
+        let cell_size = size / resolution.as_vec3();
+
+        let mut grid = Self {
+            objects,
+            large,
+            bounds,
+            resolution,
+            cell_size,
+            cell_starts: vec![],
+            object_indices: vec![],
+        };
+
+        let mut cells = vec![vec![]; resolution.element_product() as usize];
+        for index in small {
+            let first = grid.cell_of(boxes[index].min);
+            let last = grid.cell_of(boxes[index].max);
+
+            for z in first.z..=last.z {
+                for y in first.y..=last.y {
+                    for x in first.x..=last.x {
+                        cells[grid.cell_index(IVec3::new(x, y, z))].push(index);
+                    }
+                }
+            }
+        }
+
+        for cell in cells {
+            grid.cell_starts.push(grid.object_indices.len());
+            grid.object_indices.extend(cell);
+        }
+        grid.cell_starts.push(grid.object_indices.len());
+
+        grid
+    }
+
+    /// The cell containing the point, clamped to the grid.
+    fn cell_of(&self, point: Vec3) -> IVec3 {
+        let cell = ((point - self.bounds.min) / self.cell_size).floor();
+        // Flat grids divide by zero along the flat axis
+        let cell = Vec3::select(cell.is_nan_mask(), Vec3::ZERO, cell);
+
+        cell.as_ivec3()
+            .clamp(IVec3::ZERO, self.resolution.as_ivec3() - 1)
+    }
+
+    fn cell_index(&self, cell: IVec3) -> usize {
+        let resolution = self.resolution.as_ivec3();
+        ((cell.z * resolution.y + cell.y) * resolution.x + cell.x) as usize
+    }
+
+    fn cell_objects(&self, cell: IVec3) -> &[usize] {
+        let index = self.cell_index(cell);
+        &self.object_indices[self.cell_starts[index]..self.cell_starts[index + 1]]
+    }
+}
+
+impl Hittable for Grid {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        let mut closest_hit = None;
+        let mut closest = t_range.end;
+
+        let test = |indices: &[usize], closest_hit: &mut Option<Hit>, closest: &mut f32| {
+            for &index in indices {
+                if let Some(hit) = self.objects[index].hit(ray, t_range.start..*closest) {
+                    *closest = hit.distance;
+                    *closest_hit = Some(hit.on_object(index));
+                }
+            }
+        };
+
+        test(&self.large, &mut closest_hit, &mut closest);
+
+        let Some(Range { start, end }) = self.bounds.hit(ray, t_range.start..closest) else {
+            return closest_hit;
+        };
+
+        let origin = ray.origin();
+        let direction = ray.direction().as_vec3();
+        let inverse = direction.recip();
+
+        let mut cell = self.cell_of(ray.at(start));
+        let resolution = self.resolution.as_ivec3();
+
+        // Per axis: distance along the ray to the next cell boundary, between boundaries,
+        // which way to step and the cell index which means the ray has left the grid
+        let mut next_crossing = Vec3::INFINITY;
+        let mut delta = Vec3::INFINITY;
+        let mut step = IVec3::ZERO;
+        let mut out = IVec3::splat(-1);
+
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                let boundary =
+                    self.bounds.min[axis] + (cell[axis] + 1) as f32 * self.cell_size[axis];
+                next_crossing[axis] = (boundary - origin[axis]) * inverse[axis];
+                delta[axis] = self.cell_size[axis] * inverse[axis];
+                step[axis] = 1;
+                out[axis] = resolution[axis];
+            } else if direction[axis] < 0.0 {
+                let boundary = self.bounds.min[axis] + cell[axis] as f32 * self.cell_size[axis];
+                next_crossing[axis] = (boundary - origin[axis]) * inverse[axis];
+                delta[axis] = -self.cell_size[axis] * inverse[axis];
+                step[axis] = -1;
+            }
+        }
+
+        loop {
+            test(self.cell_objects(cell), &mut closest_hit, &mut closest);
+
+            let axis = (0..3)
+                .min_by(|&a, &b| next_crossing[a].total_cmp(&next_crossing[b]))
+                .unwrap();
+
+            // Anything in the cells ahead is further away than what was hit
+            if closest < next_crossing[axis] || next_crossing[axis] > end {
+                break;
+            }
+
+            cell[axis] += step[axis];
+            if cell[axis] == out[axis] {
+                break;
+            }
+            next_crossing[axis] += delta[axis];
+        }
+
+        closest_hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.large.iter().fold(self.bounds, |bounds, &i| {
+            bounds.union(&self.objects[i].bounding_box())
+        })
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        for object in self.objects.iter() {
+            object.validate(problems);
+        }
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.memory_bytes += std::mem::size_of_val(self)
+            + (self.large.capacity()
+                + self.cell_starts.capacity()
+                + self.object_indices.capacity())
+                * std::mem::size_of::<usize>()
+            + self.objects.capacity() * std::mem::size_of::<Arc<Box<dyn Hittable>>>();
+
+        for object in self.objects.iter() {
+            object.stats(stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::scenes::{self, MaterialWeights};
+
+    #[test]
+    fn same_hits_as_list() {
+        let world = scenes::random_spheres(3, 4, MaterialWeights::default());
+        let grid = Grid::new(&world);
+        assert!(
+            !grid.large.is_empty(),
+            "the ground should be left out of the grid"
+        );
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..2000 {
+            let origin = Vec3::new(
+                rng.gen_range(-6.0..6.0),
+                rng.gen_range(0.1..3.0),
+                rng.gen_range(-6.0..6.0),
+            );
+            let direction = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..0.2),
+                rng.gen_range(-1.0..1.0),
+            );
+            let ray = Ray::new(origin, direction);
+
+            let expected = world.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+            let actual = grid.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+            assert_eq!(expected, actual, "{ray:?}");
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Radiance RGBE (.hdr) images, which most HDR viewers and engines can load,
+//! e.g. to use a render as an environment map.
+
+use std::io::Write;
+
+use bevy_color::LinearRgba;
+
+/// Runs shorter than this are cheaper to store as literals.
+const MIN_RUN: usize = 4;
+
+/// Scanlines of these widths are run-length encoded, others are stored flat.
+const RLE_WIDTHS: std::ops::RangeInclusive<usize> = 8..=0x7fff;
+
+/// A shared exponent and a mantissa byte per channel. Alpha is dropped.
+fn rgbe(color: LinearRgba) -> [u8; 4] {
+    let [r, g, b] = [color.red, color.green, color.blue].map(|value| value.max(0.0));
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0; 4];
+    }
+
+    // max is mantissa * 2^exponent with the mantissa in [0.5, 1), like frexp
+    let exponent = ((max.to_bits() >> 23) & 0xff) as i32 - 126;
+    let scale = 256.0 * 2f32.powi(-exponent);
+
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+/// Append one channel of a scanline, with runs of the same byte stored as a count and the byte.
+fn write_rle(data: &[u8], out: &mut Vec<u8>) {
+    let mut index = 0;
+
+    while index < data.len() {
+        let mut run_start = index;
+        let mut run_length = 0;
+        while run_start < data.len() {
+            let value = data[run_start];
+            run_length = data[run_start..]
+                .iter()
+                .take(127)
+                .take_while(|&&byte| byte == value)
+                .count();
+            if run_length >= MIN_RUN {
+                break;
+            }
+            run_start += run_length;
+        }
+
+        // Literals up to the run, or to the end if there is none
+        for literals in data[index..run_start].chunks(128) {
+            out.push(literals.len() as u8);
+            out.extend_from_slice(literals);
+        }
+        index = run_start;
+
+        if run_start < data.len() {
+            out.extend([128 + run_length as u8, data[run_start]]);
+            index += run_length;
+        }
+    }
+}
+
+/// Write linear colors as RGBE, row-major with `width` pixels per row.
+pub fn write(width: usize, image: &[LinearRgba], writer: &mut impl Write) -> anyhow::Result<()> {
+    let height = image.len() / width;
+    assert_eq!(
+        width * height,
+        image.len(),
+        "width and height should fit exactly with no padding etc."
+    );
+
+    writer.write_all(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n")?;
+    writer.write_all(format!("-Y {height} +X {width}\n").as_bytes())?;
+
+    let mut out = vec![];
+    for row in image.chunks_exact(width) {
+        let pixels: Vec<[u8; 4]> = row.iter().map(|&color| rgbe(color)).collect();
+
+        if RLE_WIDTHS.contains(&width) {
+            out.extend([2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+            for channel in 0..4 {
+                let data: Vec<u8> = pixels.iter().map(|pixel| pixel[channel]).collect();
+                write_rle(&data, &mut out);
+            }
+        } else {
+            out.extend(pixels.into_flattened());
+        }
+
+        writer.write_all(&out)?;
+        out.clear();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Width, height and colors, for images written by [`write`].
+    fn decode(hdr: &[u8]) -> (usize, usize, Vec<[f32; 3]>) {
+        let header_end = hdr.windows(2).position(|bytes| bytes == b"\n\n").unwrap() + 2;
+        let resolution_end =
+            header_end + hdr[header_end..].iter().position(|&b| b == b'\n').unwrap();
+
+        let resolution = std::str::from_utf8(&hdr[header_end..resolution_end]).unwrap();
+        let fields: Vec<&str> = resolution.split_ascii_whitespace().collect();
+        let (height, width): (usize, usize) =
+            (fields[1].parse().unwrap(), fields[3].parse().unwrap());
+
+        let mut bytes = hdr[resolution_end + 1..].iter().copied();
+        let mut pixels = vec![];
+
+        for _ in 0..height {
+            let mut row = vec![[0u8; 4]; width];
+
+            if RLE_WIDTHS.contains(&width) {
+                let start: Vec<u8> = bytes.by_ref().take(4).collect();
+                assert_eq!(start, [2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+
+                for channel in 0..4 {
+                    let mut col = 0;
+                    while col < width {
+                        let count = bytes.next().unwrap() as usize;
+                        if count > 128 {
+                            let value = bytes.next().unwrap();
+                            for pixel in &mut row[col..col + count - 128] {
+                                pixel[channel] = value;
+                            }
+                            col += count - 128;
+                        } else {
+                            for pixel in &mut row[col..col + count] {
+                                pixel[channel] = bytes.next().unwrap();
+                            }
+                            col += count;
+                        }
+                    }
+                }
+            } else {
+                for pixel in &mut row {
+                    for byte in pixel.iter_mut() {
+                        *byte = bytes.next().unwrap();
+                    }
+                }
+            }
+
+            pixels.extend(row.into_iter().map(|[r, g, b, e]| {
+                if e == 0 {
+                    return [0.0; 3];
+                }
+                let scale = 2f32.powi(e as i32 - 128 - 8);
+                [r, g, b].map(|value| (value as f32 + 0.5) * scale)
+            }));
+        }
+        assert!(bytes.next().is_none(), "trailing bytes");
+
+        (width, height, pixels)
+    }
+
+    #[test]
+    fn roundtrip() -> anyhow::Result<()> {
+        for width in [3, 40] {
+            // A gradient over a wide range, with runs of the same color in between
+            let image: Vec<LinearRgba> = (0..width * 2)
+                .map(|index| match index % 10 {
+                    0..=5 => LinearRgba::rgb(0.5, 0.25, 0.0),
+                    _ => LinearRgba::rgb(index as f32 * 10.0, 1e-3, 1.0),
+                })
+                .collect();
+
+            let mut hdr = vec![];
+            write(width, &image, &mut hdr)?;
+            let (decoded_width, height, pixels) = decode(&hdr);
+            assert_eq!((decoded_width, height), (width, 2));
+
+            for (color, decoded) in image.iter().zip(pixels) {
+                let max = color.red.max(color.green).max(color.blue);
+                for (expected, actual) in [color.red, color.green, color.blue]
+                    .into_iter()
+                    .zip(decoded)
+                {
+                    // Mantissas have 8 bits relative to the brightest channel
+                    assert!((expected - actual).abs() <= max / 128.0, "{color:?}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn long_runs_are_compressed() {
+        let mut out = vec![];
+        write_rle(&[7; 300], &mut out);
+        assert_eq!(out, [255, 7, 255, 7, 128 + 46, 7]);
+
+        let mut out = vec![];
+        write_rle(&[1, 2, 3, 3, 3, 3, 3, 4], &mut out);
+        assert_eq!(out, [2, 1, 2, 128 + 5, 3, 1, 4]);
+    }
+}
@@ -1,8 +1,9 @@
 use std::{fmt::Debug, ops::Range, sync::Arc};
 
-use bevy_math::{Dir3, Vec3};
+use bevy_math::{Dir3, Vec2, Vec3};
+use rand::{Rng, RngCore};
 
-use crate::{material::DynMaterial, ray::Ray};
+use crate::{aabb::Aabb, material::DynMaterial, ray::Ray, stats::SceneStats};
 
 #[derive(Debug)]
 pub struct Hit {
@@ -25,15 +26,90 @@ pub struct Hit {
     /// Distance on the ray
     pub distance: f32,
 
-    /// The material hit
+    /// Texture coordinates of the point on the surface, both usually in `[0, 1]`.
+    pub uv: Vec2,
+
+    /// For triangles, the weights of the vertices `a`, `b` and `c` at the point, summing to one.
+    /// Useful to interpolate anything else stored per vertex.
+    pub barycentric: Option<Vec3>,
+
+    /// The material hit, see [`DynMaterial::id`] to tell materials apart.
     pub material: DynMaterial,
+
+    /// The object of the world which was hit, counting from one in the order they were added.
+    /// Primitives leave it at zero, the world or its accelerator fills it in.
+    pub object_id: u32,
+}
+
+impl Hit {
+    /// Mark the hit as being on the object at `index` of the world.
+    pub(crate) fn on_object(self, index: usize) -> Self {
+        Self {
+            object_id: u32::try_from(index + 1).unwrap_or(u32::MAX),
+            ..self
+        }
+    }
 }
 
-pub trait Hittable: std::fmt::Debug {
+pub trait Hittable: std::fmt::Debug + Send + Sync {
+    /// The closest hit within the range, if any.
+    /// Primitives fill in [`Hit::uv`] so textures can be mapped onto them.
     fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit>;
+
+    /// A box which fully contains the object.
+    fn bounding_box(&self) -> Aabb;
+
+    /// Describe anything that would make this object render wrongly, see [`crate::scene::Scene::validate`].
+    fn validate(&self, _problems: &mut Vec<String>) {}
+
+    /// Count this object (and whatever it's made of) into the stats.
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_object(std::any::type_name::<Self>(), std::mem::size_of_val(self));
+    }
+
+    /// The density of [`Hittable::random_direction`] picking `direction` from `origin`,
+    /// zero if the direction misses. Objects which can't be sampled, the default, always say zero.
+    /// See [`crate::pdf::HittablePdf`].
+    fn pdf_value(&self, _origin: Vec3, _direction: Dir3) -> f32 {
+        0.0
+    }
+
+    /// A direction from `origin` towards a random point of the object, e.g. to look for
+    /// light from a lamp instead of waiting for rays to find it by chance.
+    /// Objects which can't be sampled give an arbitrary direction.
+    fn random_direction(&self, _origin: Vec3, _rng: &mut dyn RngCore) -> Dir3 {
+        Dir3::X
+    }
+}
+
+/// Lets one object be shared, e.g. placed many times with [`crate::transform::Translate`].
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        (**self).hit(ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        (**self).validate(problems)
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        (**self).stats(stats)
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Dir3) -> f32 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random_direction(&self, origin: Vec3, rng: &mut dyn RngCore) -> Dir3 {
+        (**self).random_direction(origin, rng)
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Hittables {
     pub objects: Vec<Arc<Box<dyn Hittable>>>,
 }
@@ -42,14 +118,13 @@ impl Hittables {
     pub fn add(&mut self, object: impl Hittable + 'static) {
         self.objects.push(Arc::new(Box::new(object)));
     }
-}
 
-impl Hittable for Hittables {
-    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+    /// Like [`Hittable::hit`], but also returns the index of the object which was hit.
+    pub fn hit_object(&self, ray: &Ray, t_range: Range<f32>) -> Option<(usize, Hit)> {
         let mut range = t_range;
         let mut closest_hit = None;
 
-        for object in self.objects.iter() {
+        for (index, object) in self.objects.iter().enumerate() {
             if let Some(hit) = object.hit(ray, range.clone()) {
                 // We passed in a range [close, far). Since there was a hit,
                 // we shouldn't consider any hits beyond that since that would be
@@ -57,10 +132,61 @@ impl Hittable for Hittables {
                 // Therefore we shrink the far to be defined by this new hit.
                 range.end = hit.distance;
 
-                closest_hit = Some(hit);
+                closest_hit = Some((index, hit.on_object(index)));
             }
         }
 
         closest_hit
     }
 }
+
+impl Hittable for Hittables {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        self.hit_object(ray, t_range).map(|(_, hit)| hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::EMPTY, |aabb, object| {
+            aabb.union(&object.bounding_box())
+        })
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        for object in self.objects.iter() {
+            object.validate(problems);
+        }
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        // The list itself, then what's in it
+        stats.memory_bytes += std::mem::size_of_val(self)
+            + self.objects.capacity() * std::mem::size_of::<Arc<Box<dyn Hittable>>>();
+
+        for object in self.objects.iter() {
+            object.stats(stats);
+        }
+    }
+
+    /// Averaged over the objects, since [`Hittables::random_direction`] picks each equally often.
+    fn pdf_value(&self, origin: Vec3, direction: Dir3) -> f32 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+
+        let total: f32 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+        total / self.objects.len() as f32
+    }
+
+    fn random_direction(&self, origin: Vec3, rng: &mut dyn RngCore) -> Dir3 {
+        if self.objects.is_empty() {
+            return Dir3::X;
+        }
+
+        let object = &self.objects[rng.gen_range(0..self.objects.len())];
+        object.random_direction(origin, rng)
+    }
+}
@@ -0,0 +1,307 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{Hit, Hittable, Hittables},
+    ray::Ray,
+    stats::SceneStats,
+};
+
+// Relative costs for the surface area heuristic, the usual values from PBRT.
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSECTION_COST: f32 = 80.0;
+/// Splits which cut off empty space are favored by this fraction.
+const EMPTY_BONUS: f32 = 0.5;
+
+#[derive(Debug)]
+enum Node {
+    /// Refers to `count` entries of `object_indices`, starting at `first`.
+    Leaf { first: usize, count: usize },
+    /// The child below the split is the next node, the one above is at `above`.
+    Interior {
+        axis: usize,
+        split: f32,
+        above: usize,
+    },
+}
+
+/// A kd-tree over the objects of a [`Hittables`].
+///
+/// Space is divided by axis-aligned planes placed by the surface area heuristic.
+/// Objects straddling a plane are referenced from both sides, so unlike a BVH
+/// the nodes never overlap and rays visit them strictly front to back.
+#[derive(Debug)]
+pub struct KdTree {
+    objects: Vec<Arc<Box<dyn Hittable>>>,
+    bounds: Aabb,
+    nodes: Vec<Node>,
+    object_indices: Vec<usize>,
+}
+
+impl KdTree {
+    pub fn new(world: &Hittables) -> Self {
+        let objects = world.objects.clone();
+        let boxes: Vec<Aabb> = objects.iter().map(|object| object.bounding_box()).collect();
+        let bounds = boxes
+            .iter()
+            .fold(Aabb::EMPTY, |bounds, aabb| bounds.union(aabb));
+
+        let max_depth = (8.0 + 1.3 * (objects.len().max(1) as f32).log2()).round() as usize;
+
+        let mut tree = Self {
+            objects,
+            bounds,
+            nodes: vec![],
+            object_indices: vec![],
+        };
+        tree.build(&boxes, (0..boxes.len()).collect(), bounds, max_depth, 0);
+
+        tree
+    }
+
+    fn push_leaf(&mut self, indices: Vec<usize>) {
+        self.nodes.push(Node::Leaf {
+            first: self.object_indices.len(),
+            count: indices.len(),
+        });
+        self.object_indices.extend(indices);
+    }
+
+    fn build(
+        &mut self,
+        boxes: &[Aabb],
+        indices: Vec<usize>,
+        bounds: Aabb,
+        depth: usize,
+        bad_refines: usize,
+    ) {
+        if indices.len() <= 1 || depth == 0 {
+            return self.push_leaf(indices);
+        }
+
+        let Some((axis, split, cost)) = best_split(boxes, &indices, &bounds) else {
+            return self.push_leaf(indices);
+        };
+
+        // Allow a few splits which look worse than a leaf, in case they pay off further down
+        let leaf_cost = INTERSECTION_COST * indices.len() as f32;
+        let bad_refines = bad_refines + usize::from(cost > leaf_cost);
+        if (cost > 4.0 * leaf_cost && indices.len() < 16) || bad_refines == 3 {
+            return self.push_leaf(indices);
+        }
+
+        // Flat objects lying in the plane go on both sides
+        let below: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|&i| boxes[i].min[axis] < split || boxes[i].max[axis] <= split)
+            .collect();
+        let above: Vec<usize> = indices
+            .into_iter()
+            .filter(|&i| boxes[i].max[axis] > split || boxes[i].min[axis] >= split)
+            .collect();
+
+        let mut below_bounds = bounds;
+        below_bounds.max[axis] = split;
+        let mut above_bounds = bounds;
+        above_bounds.min[axis] = split;
+
+        let node = self.nodes.len();
+        self.nodes.push(Node::Interior {
+            axis,
+            split,
+            above: 0,
+        });
+
+        self.build(boxes, below, below_bounds, depth - 1, bad_refines);
+
+        let above_index = self.nodes.len();
+        if let Node::Interior { above, .. } = &mut self.nodes[node] {
+            *above = above_index;
+        }
+
+        self.build(boxes, above, above_bounds, depth - 1, bad_refines);
+    }
+}
+
+/// The cheapest split plane among the object bounds, as (axis, position, cost).
+fn best_split(boxes: &[Aabb], indices: &[usize], bounds: &Aabb) -> Option<(usize, f32, f32)> {
+    let total_area = bounds.surface_area();
+    if !(total_area > 0.0 && total_area.is_finite()) {
+        return None;
+    }
+
+    let size = bounds.size();
+    let mut best = None;
+    let mut best_cost = f32::INFINITY;
+
+    for axis in 0..3 {
+        // (position, is the end of a box)
+        let mut edges: Vec<(f32, bool)> = indices
+            .iter()
+            .flat_map(|&i| [(boxes[i].min[axis], false), (boxes[i].max[axis], true)])
+            .collect();
+        // Starts before ends at the same position
+        edges.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let cap_area = size[u] * size[v];
+        let perimeter = size[u] + size[v];
+
+        let mut below = 0;
+        let mut above = indices.len();
+
+        for (position, is_end) in edges {
+            if is_end {
+                above -= 1;
+            }
+
+            if position > bounds.min[axis] && position < bounds.max[axis] {
+                let below_area = 2.0 * (cap_area + (position - bounds.min[axis]) * perimeter);
+                let above_area = 2.0 * (cap_area + (bounds.max[axis] - position) * perimeter);
+
+                let bonus = if below == 0 || above == 0 {
+                    EMPTY_BONUS
+                } else {
+                    0.0
+                };
+
+                let cost = TRAVERSAL_COST
+                    + INTERSECTION_COST
+                        * (1.0 - bonus)
+                        * (below_area * below as f32 + above_area * above as f32)
+                        / total_area;
+
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = Some((axis, position));
+                }
+            }
+
+            if !is_end {
+                below += 1;
+            }
+        }
+    }
+
+    best.map(|(axis, split)| (axis, split, best_cost))
+}
+
+impl Hittable for KdTree {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        let Range { start, end } = self.bounds.hit(ray, t_range.clone())?;
+
+        let origin = ray.origin();
+        let direction = ray.direction().as_vec3();
+        let inverse = direction.recip();
+
+        let mut closest_hit = None;
+        let mut closest = t_range.end;
+
+        // Far children still to visit, with the part of the ray within them
+        let mut stack: Vec<(usize, f32, f32)> = vec![];
+        let (mut node, mut t_min, mut t_max) = (0, start, end);
+
+        loop {
+            // Whatever is left is behind what was already hit
+            if closest < t_min {
+                break;
+            }
+
+            match self.nodes[node] {
+                Node::Interior { axis, split, above } => {
+                    let t_plane = (split - origin[axis]) * inverse[axis];
+
+                    let below_first =
+                        origin[axis] < split || (origin[axis] == split && direction[axis] <= 0.0);
+                    let (first, second) = if below_first {
+                        (node + 1, above)
+                    } else {
+                        (above, node + 1)
+                    };
+
+                    if t_plane > t_max || t_plane <= 0.0 {
+                        node = first;
+                    } else if t_plane < t_min {
+                        node = second;
+                    } else {
+                        stack.push((second, t_plane, t_max));
+                        node = first;
+                        t_max = t_plane;
+                    }
+                    continue;
+                }
+                Node::Leaf { first, count } => {
+                    for &index in &self.object_indices[first..first + count] {
+                        if let Some(hit) = self.objects[index].hit(ray, t_range.start..closest) {
+                            closest = hit.distance;
+                            closest_hit = Some(hit.on_object(index));
+                        }
+                    }
+                }
+            }
+
+            let Some(next) = stack.pop() else {
+                break;
+            };
+            (node, t_min, t_max) = next;
+        }
+
+        closest_hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        for object in self.objects.iter() {
+            object.validate(problems);
+        }
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.memory_bytes += std::mem::size_of_val(self)
+            + self.nodes.capacity() * std::mem::size_of::<Node>()
+            + self.object_indices.capacity() * std::mem::size_of::<usize>()
+            + self.objects.capacity() * std::mem::size_of::<Arc<Box<dyn Hittable>>>();
+
+        for object in self.objects.iter() {
+            object.stats(stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::Vec3;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::scenes::{self, MaterialWeights};
+
+    #[test]
+    fn same_hits_as_list() {
+        let world = scenes::random_spheres(3, 4, MaterialWeights::default());
+        let tree = KdTree::new(&world);
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..2000 {
+            let origin = Vec3::new(
+                rng.gen_range(-6.0..6.0),
+                rng.gen_range(0.1..3.0),
+                rng.gen_range(-6.0..6.0),
+            );
+            let direction = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..0.2),
+                rng.gen_range(-1.0..1.0),
+            );
+            let ray = Ray::new(origin, direction);
+
+            let expected = world.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+            let actual = tree.hit(&ray, 0.001..f32::MAX).map(|hit| hit.distance);
+            assert_eq!(expected, actual, "{ray:?}");
+        }
+    }
+}
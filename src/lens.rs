@@ -0,0 +1,260 @@
+use bevy_math::{vec3, Dir3, Vec2, Vec3};
+
+use rand::Rng;
+
+use crate::material::Glam029;
+
+/// A single spherical (or planar) interface in a lens prescription.
+///
+/// Follows the usual lens table layout: each row describes a surface,
+/// the distance to the next surface and the medium found between them.
+#[derive(Debug, Clone)]
+pub struct LensElement {
+    /// Signed radius of curvature.
+    /// Positive means the center of curvature lies on the sensor side of the surface vertex.
+    /// Zero means a flat surface, which is also how an aperture stop is described.
+    pub curvature_radius: f32,
+
+    /// Distance along the optical axis from this surface to the next one towards the sensor.
+    /// For the last surface this is the distance to the sensor.
+    pub thickness: f32,
+
+    /// Refractive index of the medium between this surface and the next one towards the sensor.
+    pub ior: f32,
+
+    /// Radius of the clear aperture. Rays outside of it are blocked by the lens housing.
+    pub aperture_radius: f32,
+}
+
+/// A stack of lens elements in front of a sensor.
+///
+/// Rays are traced from the sensor through every interface before they
+/// enter the scene, so vignetting and aberrations come out of the geometry
+/// instead of being faked.
+///
+/// In the lens' local space the sensor is at z = 0 and the scene is towards -Z.
+#[derive(Debug, Clone)]
+pub struct LensSystem {
+    /// Ordered from the front (scene side) to the back (sensor side).
+    pub elements: Vec<LensElement>,
+
+    /// Physical height of the sensor, in scene units.
+    pub sensor_height: f32,
+}
+
+impl LensSystem {
+    pub fn new(elements: Vec<LensElement>, sensor_height: f32) -> Self {
+        Self {
+            elements,
+            sensor_height,
+        }
+    }
+
+    /// A symmetric biconvex glass lens, with the sensor placed where objects
+    /// at `focus_distance` are in focus (according to the thin lens equation).
+    ///
+    /// Spherical surfaces do not focus perfectly, so expect some softness
+    /// towards the edges of the image.
+    pub fn singlet(
+        focal_length: f32,
+        aperture_radius: f32,
+        focus_distance: f32,
+        sensor_height: f32,
+    ) -> Self {
+        let ior = 1.5;
+
+        // Lensmaker's equation for R1 = R, R2 = -R:
+        // 1/f = (n - 1) * 2/R
+        let radius = 2.0 * focal_length * (ior - 1.0);
+
+        // The lens must be thick enough for both caps to fit within the aperture
+        let sag = radius - (radius * radius - aperture_radius * aperture_radius).sqrt();
+        let thickness = 2.0 * sag + 0.1 * aperture_radius;
+
+        // 1/f = 1/d_o + 1/d_i
+        let image_distance = focal_length * focus_distance / (focus_distance - focal_length);
+
+        Self::new(
+            vec![
+                LensElement {
+                    curvature_radius: radius,
+                    thickness,
+                    ior,
+                    aperture_radius,
+                },
+                LensElement {
+                    curvature_radius: -radius,
+                    thickness: image_distance - thickness / 2.,
+                    ior: 1.0,
+                    aperture_radius,
+                },
+            ],
+            sensor_height,
+        )
+    }
+
+    /// Position of each surface vertex along the optical axis.
+    fn vertices(&self) -> impl Iterator<Item = f32> + '_ {
+        let mut z = -self.elements.iter().map(|e| e.thickness).sum::<f32>();
+
+        self.elements.iter().map(move |element| {
+            let vertex = z;
+            z += element.thickness;
+            vertex
+        })
+    }
+
+    /// Trace a ray leaving the given sensor point (in lens local space) out through the lens.
+    /// The target on the rear element is chosen at random from `rng`.
+    ///
+    /// Returns the ray origin and direction after the front element,
+    /// or `None` if the ray was blocked or totally internally reflected.
+    pub fn trace_from_sensor(
+        &self,
+        sensor: Vec2,
+        rng: &mut (impl Rng + ?Sized),
+    ) -> Option<(Vec3, Dir3)> {
+        let rear = self.elements.last()?;
+        let rear_z = self.vertices().last()?;
+
+        let target = loop {
+            let p = Vec2::new(rng.gen::<f32>() * 2. - 1., rng.gen::<f32>() * 2. - 1.);
+            if p.length_squared() < 1.0 {
+                break p * rear.aperture_radius;
+            }
+        };
+
+        let mut origin = sensor.extend(0.0);
+        let mut direction = Dir3::new(vec3(target.x, target.y, rear_z) - origin).ok()?;
+
+        let vertices: Vec<f32> = self.vertices().collect();
+
+        for (index, element) in self.elements.iter().enumerate().rev() {
+            let vertex = vertices[index];
+
+            let (t, outward) = if element.curvature_radius == 0.0 {
+                ((vertex - origin.z) / direction.z, Vec3::Z)
+            } else {
+                let center = vec3(0.0, 0.0, vertex + element.curvature_radius);
+                let oc = origin - center;
+
+                let b = oc.dot(*direction);
+                let c = oc.length_squared() - element.curvature_radius.powi(2);
+                let discriminant = b * b - c;
+
+                if discriminant < 0.0 {
+                    return None;
+                }
+
+                let discr_sqrt = discriminant.sqrt();
+
+                // Travelling towards the scene, the vertex cap is the near intersection
+                // only if the center of curvature is in front of it.
+                let t = if element.curvature_radius < 0.0 {
+                    -b - discr_sqrt
+                } else {
+                    -b + discr_sqrt
+                };
+
+                (t, (origin + t * direction - center).normalize())
+            };
+
+            if t <= 0.0 {
+                return None;
+            }
+
+            let hit = origin + t * direction;
+            if hit.truncate().length_squared() > element.aperture_radius.powi(2) {
+                return None;
+            }
+
+            let normal = if outward.dot(*direction) > 0.0 {
+                Dir3::new_unchecked(-outward)
+            } else {
+                Dir3::new_unchecked(outward)
+            };
+
+            let n_behind = element.ior;
+            let n_front = if index == 0 {
+                1.0
+            } else {
+                self.elements[index - 1].ior
+            };
+            let eta = n_behind / n_front;
+
+            let cos_theta = -direction.dot(*normal);
+            if (1.0 - cos_theta * cos_theta) * eta * eta > 1.0 {
+                return None;
+            }
+
+            origin = hit;
+            direction = direction.refract(normal, eta);
+        }
+
+        Some((origin, direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn singlet_focuses_at_focus_distance() {
+        let (focal_length, focus_distance) = (1.0, 4.0);
+        // A small aperture keeps rays paraxial, away from spherical aberration
+        let lens = LensSystem::singlet(focal_length, 0.02, focus_distance, 0.5);
+        // The thin lens equation measures from the middle of the lens
+        let vertices: Vec<f32> = lens.vertices().collect();
+        let center = (vertices[0] + vertices[1]) / 2.0;
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        // Light from the center of the sensor meets on the axis again at the focus distance,
+        // as near as a thick lens follows the thin lens equation
+        for _ in 0..16 {
+            let (origin, direction) = lens.trace_from_sensor(Vec2::ZERO, &mut rng).unwrap();
+            let across = direction.truncate();
+            let t = -origin.truncate().dot(across) / across.length_squared();
+            let focus = origin + t * direction;
+
+            assert!(
+                (center - focus.z - focus_distance).abs() < 0.01 * focus_distance,
+                "{focus}"
+            );
+        }
+    }
+
+    #[test]
+    fn stop_blocks_rays() {
+        let surface = |thickness, aperture_radius| LensElement {
+            curvature_radius: 0.0,
+            thickness,
+            ior: 1.0,
+            aperture_radius,
+        };
+        // A stop at z = -2, and a plain window at z = -1 which only picks the direction
+        let lens = LensSystem::new(vec![surface(1.0, 0.1), surface(1.0, 0.2)], 1.0);
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        // From off axis, every ray through the window passes outside the stop
+        for _ in 0..100 {
+            assert!(lens
+                .trace_from_sensor(Vec2::new(0.5, 0.0), &mut rng)
+                .is_none());
+        }
+
+        // From the center, those aimed near the axis get through unbent
+        let passed: Vec<_> = (0..200)
+            .filter_map(|_| lens.trace_from_sensor(Vec2::ZERO, &mut rng))
+            .collect();
+        assert!(!passed.is_empty());
+        for (origin, direction) in passed {
+            assert!((origin.z + 2.0).abs() < 1e-5);
+            assert!(origin.truncate().length() <= 0.1);
+            // Still on the line from the sensor center
+            assert!(direction.cross(origin.normalize()).length() < 1e-4);
+        }
+    }
+}
@@ -1,7 +1,58 @@
+pub mod aabb;
+pub mod aov;
+pub mod arena;
+pub mod async_render;
+pub mod background;
+#[cfg(feature = "bevy")]
+pub mod bevy_extract;
+pub mod bvh;
 pub mod camera;
+pub mod cancel;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod checkpoint;
+pub mod contact_sheet;
+pub mod edges;
+pub mod environment;
+pub mod exposure;
+pub mod gltf;
+pub mod grid;
+pub mod hdr;
 pub mod hittable;
+pub mod kdtree;
+pub mod lens;
+pub mod light;
 pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod obj;
 pub mod objects;
+#[cfg(feature = "oidn")]
+pub mod oidn;
+pub mod output;
+pub mod output_color;
+pub mod pdf;
+pub mod perlin;
+pub mod post;
 pub mod ppm;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod random;
 pub mod ray;
+pub mod render;
+pub mod sampler;
+pub mod scene;
+pub mod scene_file;
+pub mod scenes;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod sky;
+pub mod stats;
+pub mod stl;
+pub mod stream;
+pub mod text;
+pub mod texture;
+pub mod tile;
+pub mod tonemap;
+pub mod transform;
+pub mod visibility;
@@ -0,0 +1,259 @@
+//! Lights which are sampled directly, rather than found by scattered rays.
+
+use bevy_color::{Color, ColorToComponents, LinearRgba};
+use bevy_math::{Dir3, Vec3};
+
+use rand::Rng;
+
+use crate::random::{random_in_disk, random_on_sphere};
+
+#[derive(Debug, Clone, Copy)]
+pub enum LightKind {
+    /// Shines equally in all directions.
+    Point,
+    /// Shines in a cone, full strength within `inner_angle` of `direction`
+    /// and fading out towards `outer_angle`. Angles are in radians from the axis.
+    Spot {
+        direction: Dir3,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+    /// Shines along `direction` everywhere at once, like the sun.
+    /// It has no position and doesn't fall off with distance.
+    /// A source `angular_radius` in radians softens shadows, about 0.0047 for the sun.
+    Directional {
+        direction: Dir3,
+        angular_radius: f32,
+    },
+}
+
+/// Light arriving at a point from a [`Light`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    /// From the point towards the light.
+    pub direction: Dir3,
+    /// How far the light is, infinite for a directional light.
+    pub distance: f32,
+    pub radiance: LinearRgba,
+}
+
+/// A light at a point in space or infinitely far away, invisible to rays itself.
+///
+/// By default it behaves physically, falling off with the inverse square of the distance.
+/// `falloff` and `radius` bend that for art direction, e.g. to light a small scene
+/// evenly without blowing out whatever is closest.
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: Vec3,
+    pub color: Color,
+    /// Brightness at a distance of one.
+    pub intensity: f32,
+
+    /// Light falls off with the distance to this power. 2 is physically correct,
+    /// lower values reach further. Directional lights don't fall off.
+    pub falloff: f32,
+
+    /// If set, no light reaches further than this, fading out smoothly towards it.
+    pub radius: Option<f32>,
+
+    /// Light is emitted from a sphere this large, which softens shadows.
+    /// 0 gives a point with hard shadows.
+    pub source_radius: f32,
+}
+
+impl Light {
+    pub fn point(position: Vec3, color: Color, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Point,
+            position,
+            color,
+            intensity,
+            falloff: 2.0,
+            radius: None,
+            source_radius: 0.0,
+        }
+    }
+
+    /// A spotlight with a cone of `angle` radians from the axis, with a soft edge.
+    pub fn spot(position: Vec3, direction: Dir3, angle: f32, color: Color, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                direction,
+                inner_angle: angle * 0.8,
+                outer_angle: angle,
+            },
+            ..Self::point(position, color, intensity)
+        }
+    }
+
+    /// Shining along `direction` with `intensity` as the light falling on a surface facing it.
+    pub fn directional(direction: Dir3, color: Color, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional {
+                direction,
+                angular_radius: 0.0,
+            },
+            ..Self::point(Vec3::ZERO, color, intensity)
+        }
+    }
+
+    /// Pick a point on the light as seen from `point`, returning the light arriving from it.
+    /// With a source radius each call picks a different point from `rng`,
+    /// so shadows are soft on average.
+    pub fn sample(&self, point: Vec3, rng: &mut (impl Rng + ?Sized)) -> Option<LightSample> {
+        if let LightKind::Directional {
+            direction,
+            angular_radius,
+        } = self.kind
+        {
+            // Within a cone around the way back to the light
+            let towards =
+                -direction.as_vec3() + angular_radius.tan() * random_in_cone_base(direction, rng);
+            let color = LinearRgba::from(self.color).to_vec3() * self.intensity;
+            return Some(LightSample {
+                direction: Dir3::new(towards).ok()?,
+                distance: f32::INFINITY,
+                radiance: LinearRgba::from_vec3(color),
+            });
+        }
+
+        let source = if self.source_radius > 0.0 {
+            self.position + self.source_radius * random_on_sphere(rng).as_vec3()
+        } else {
+            self.position
+        };
+
+        let to_point = point - source;
+        let distance = to_point.length();
+
+        let mut strength = self.intensity / distance.max(1e-4).powf(self.falloff);
+
+        if let Some(radius) = self.radius {
+            // Smoothly down to zero at the radius, as in Unreal's lights
+            let window = (1.0 - (distance / radius).powi(4)).clamp(0.0, 1.0);
+            strength *= window * window;
+        }
+
+        if let LightKind::Spot {
+            direction,
+            inner_angle,
+            outer_angle,
+        } = self.kind
+        {
+            let cos = direction.dot(to_point / distance);
+            let (cos_inner, cos_outer) = (inner_angle.cos(), outer_angle.cos());
+            strength *= ((cos - cos_outer) / (cos_inner - cos_outer).max(1e-6)).clamp(0.0, 1.0);
+        }
+
+        let color = LinearRgba::from(self.color).to_vec3() * strength;
+        Some(LightSample {
+            direction: Dir3::new(-to_point).ok()?,
+            distance,
+            radiance: LinearRgba::from_vec3(color),
+        })
+    }
+
+    /// Describe anything that would make this light render wrongly.
+    pub fn validate(&self, problems: &mut Vec<String>) {
+        if self.falloff < 0.0 {
+            problems.push(format!(
+                "light: negative falloff {} brightens with distance",
+                self.falloff
+            ));
+        }
+        if self.radius.is_some_and(|radius| radius <= 0.0) {
+            problems.push(format!(
+                "light: radius {:?} lights nothing",
+                self.radius.unwrap_or_default()
+            ));
+        }
+        if self.source_radius < 0.0 {
+            problems.push(format!(
+                "light: negative source radius {}",
+                self.source_radius
+            ));
+        }
+        if let LightKind::Directional { angular_radius, .. } = self.kind {
+            if !(0.0..std::f32::consts::FRAC_PI_2).contains(&angular_radius) {
+                problems.push(format!(
+                    "light: angular radius {angular_radius} should be from 0 up to a right angle"
+                ));
+            }
+        }
+    }
+}
+
+/// A point in the unit disk across `axis`.
+fn random_in_cone_base(axis: Dir3, rng: &mut (impl Rng + ?Sized)) -> Vec3 {
+    let (u, v) = axis.any_orthonormal_pair();
+    let disk = random_in_disk(rng);
+    disk.x * u + disk.y * v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brightness(light: &Light, point: Vec3) -> f32 {
+        light
+            .sample(point, &mut crate::random::sample_rng(0, 0, 0))
+            .map_or(0.0, |sample| sample.radiance.red)
+    }
+
+    #[test]
+    fn falloff_and_radius() {
+        let mut light = Light::point(Vec3::ZERO, Color::WHITE, 4.0);
+        assert_eq!(brightness(&light, Vec3::new(2.0, 0.0, 0.0)), 1.0);
+
+        light.falloff = 1.0;
+        assert_eq!(brightness(&light, Vec3::new(2.0, 0.0, 0.0)), 2.0);
+
+        light.radius = Some(3.0);
+        let near = brightness(&light, Vec3::new(1.0, 0.0, 0.0));
+        assert!(near > 3.9 && near < 4.0);
+        assert!(brightness(&light, Vec3::new(2.9, 0.0, 0.0)) < 0.1);
+        assert_eq!(brightness(&light, Vec3::new(3.5, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn spot_cone() {
+        let light = Light::spot(Vec3::ZERO, Dir3::NEG_Y, 0.5, Color::WHITE, 1.0);
+
+        assert_eq!(brightness(&light, Vec3::NEG_Y), 1.0);
+        assert_eq!(brightness(&light, Vec3::new(1.0, -0.1, 0.0)), 0.0);
+        let edge = brightness(&light, Vec3::new(0.45f32.tan(), -1.0, 0.0).normalize());
+        assert!(edge > 0.0 && edge < 1.0);
+    }
+
+    #[test]
+    fn directional_everywhere() {
+        let mut light = Light::directional(Dir3::NEG_Y, Color::WHITE, 3.0);
+        let mut rng = crate::random::sample_rng(0, 0, 0);
+
+        // As bright anywhere, from straight up and infinitely far
+        let near = light.sample(Vec3::ZERO, &mut rng).unwrap();
+        let far = light
+            .sample(Vec3::new(100.0, -50.0, 7.0), &mut rng)
+            .unwrap();
+        assert_eq!(near, far);
+        assert_eq!(near.radiance, LinearRgba::rgb(3.0, 3.0, 3.0));
+        assert_eq!(near.direction, Dir3::Y);
+        assert_eq!(near.distance, f32::INFINITY);
+
+        // A wider source spreads the directions for soft shadows, within its angle
+        light.kind = LightKind::Directional {
+            direction: Dir3::NEG_Y,
+            angular_radius: 0.1,
+        };
+        let directions: Vec<Dir3> = (0..100)
+            .map(|_| light.sample(Vec3::ZERO, &mut rng).unwrap().direction)
+            .collect();
+        assert!(directions.iter().all(|d| d.y >= 0.1f32.cos() - 1e-6));
+        assert!(directions.iter().any(|d| d.y < 0.999));
+
+        let mut problems = vec![];
+        light.validate(&mut problems);
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+}
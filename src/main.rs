@@ -1,19 +1,677 @@
-use bevy_color::{palettes, Color};
+use anyhow::{bail, Context};
+use bevy_color::{palettes, Alpha, Color};
 use bevy_color::{ColorToPacked, LinearRgba};
-use bevy_math::Vec3;
-use clap::{Parser, Subcommand};
+use bevy_math::{Affine3A, Vec3};
+use clap::builder::RangedU64ValueParser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rt_one::aov::{self, Aov};
 use rt_one::camera::Camera;
-use rt_one::hittable::Hittables;
-use rt_one::material::{Dielectric, Lambertian, Metal};
+use rt_one::checkpoint::{self, Checkpoint, RenderCommand};
+use rt_one::contact_sheet::ContactSheet;
+use rt_one::edges::EdgeOverlay;
+use rt_one::gltf;
+use rt_one::hittable::{Hittable, Hittables};
+use rt_one::lens::LensSystem;
+use rt_one::material::{Dielectric, DynMaterial, Lambertian, Metal};
+use rt_one::obj;
 use rt_one::objects::Sphere;
-use rt_one::ppm;
+use rt_one::output::{self, ImageFormat, Quantized};
+use rt_one::output_color::{BitDepth, Dither, Transfer};
+use rt_one::post::Denoiser;
 use rt_one::ray;
-use tracing::info;
+use rt_one::render::Accumulation;
+use rt_one::render::Progress;
+use rt_one::sampler::PixelSampler;
+use rt_one::scene::{Accelerator, Scene};
+use rt_one::scene_file::{
+    BackgroundSettings, CameraSettings, MaterialSettings, Object, SceneFile, TextureSettings,
+};
+use rt_one::scenes::{self, MaterialWeights};
+use rt_one::stats::RenderTimings;
+use rt_one::stl;
+use rt_one::stream;
+use rt_one::text;
+use rt_one::texture::ImageTexture;
+use rt_one::tonemap::Tonemap;
+use std::io::IsTerminal;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
 
 #[derive(Parser)]
 struct Cli {
     #[command(subcommand)]
     command: Command,
+
+    #[command(flatten)]
+    options: RenderOptions,
+}
+
+// Settings for any scene which is path traced.
+// Each scene has its own defaults, these override them.
+// Not a doc comment, since clap would use it as the description of the whole program.
+#[derive(Args)]
+struct RenderOptions {
+    /// Set resolution, samples, bounces and denoising together.
+    /// The options below override the preset.
+    #[arg(long, global = true)]
+    quality: Option<Quality>,
+
+    /// Samples per pixel
+    #[arg(long, global = true)]
+    samples: Option<usize>,
+
+    /// Maximum number of bounces per path
+    #[arg(long, global = true)]
+    bounces: Option<usize>,
+
+    /// Render until this many seconds have passed instead of to a fixed number of samples,
+    /// then write the image. If `--samples` is given too, it stops there at the latest.
+    #[arg(long, global = true)]
+    max_seconds: Option<f64>,
+
+    /// Render in passes of one sample per pixel over the whole image, writing the image so far
+    /// every this many passes to preview it while the render goes on
+    #[arg(
+        long,
+        global = true,
+        conflicts_with_all = ["stream", "aovs"],
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    snapshot_every: Option<usize>,
+
+    /// Render in passes like `--snapshot-every`, saving a checkpoint next to the output
+    /// every this many passes, e.g. `out.png.checkpoint`. See the `resume` command
+    #[arg(
+        long,
+        global = true,
+        conflicts_with_all = ["stream", "aovs"],
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    checkpoint_every: Option<usize>,
+
+    /// The render to continue, set by the `resume` command
+    #[arg(skip)]
+    resume: Option<Checkpoint>,
+
+    /// Write the image here instead of the scene's default file. Use `-` for stdout
+    #[arg(short, long, global = true)]
+    out: Option<PathBuf>,
+
+    /// Image format: ppm, p6 (binary ppm), png, or exr or hdr for linear floats.
+    /// By default picked from the file extension, else ppm
+    #[arg(long, global = true)]
+    format: Option<ImageFormat>,
+
+    /// Send tiles to a viewer as they complete, see `rt_one::stream` for the format.
+    /// Either `host:port` for TCP or a Unix socket path
+    #[arg(long, global = true, conflicts_with = "max_seconds")]
+    stream: Option<String>,
+
+    /// Also render these passes (normal, depth, albedo, id, material-id, id-color,
+    /// material-color, matte) and write them as layers
+    /// of a single EXR next to the beauty image
+    #[arg(long, global = true, value_delimiter = ',', conflicts_with_all = ["stream", "format"])]
+    aovs: Vec<Aov>,
+
+    /// Write each of `--aovs` to its own EXR instead, e.g. `out_normal.exr` next to `out.exr`,
+    /// as denoisers expect them
+    #[arg(long, global = true, requires = "aovs")]
+    aov_files: bool,
+
+    /// Seed for the random numbers used while rendering. The same seed renders the same image
+    #[arg(long, global = true)]
+    sample_seed: Option<u64>,
+
+    /// Also write the image at these exposures, in stops relative to the render, e.g. `-2,0,2`.
+    /// Each is written next to the output, e.g. `out_+2ev.png`, all from the same render
+    #[arg(
+        long,
+        global = true,
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        conflicts_with = "aovs"
+    )]
+    bracket: Vec<f32>,
+
+    /// Stamp the scene name, seed, samples, bounces, resolution and render time below the image
+    #[arg(long, global = true, conflicts_with = "aovs")]
+    annotate: bool,
+
+    /// Denoise the render, guided by normal, depth and albedo passes.
+    /// `filter` is built in, `oidn` is Open Image Denoise and needs the oidn feature.
+    /// `--quality medium` and `final` use the filter unless told otherwise
+    #[arg(long, global = true, conflicts_with = "aovs")]
+    denoise: Option<Denoiser>,
+
+    /// Don't denoise, even if the `--quality` preset would
+    #[arg(long, global = true, conflicts_with = "denoise")]
+    no_denoise: bool,
+
+    /// Outline objects and sharp creases in black over the image
+    #[arg(long, global = true, conflicts_with = "aovs")]
+    edges: bool,
+
+    /// Shade every surface in neutral gray instead of its material, to check lighting and form
+    #[arg(long, global = true)]
+    clay: bool,
+
+    /// Leave the background transparent where nothing is hit, for compositing.
+    /// Kept in png and exr images
+    #[arg(long, global = true)]
+    transparent_background: bool,
+
+    /// Vertical field of view in degrees, overriding the scene's
+    #[arg(long, global = true)]
+    vfov: Option<f32>,
+
+    /// Depth of field: the cone angle in degrees of the rays through each pixel
+    #[arg(long, global = true)]
+    defocus_angle: Option<f32>,
+
+    /// Distance to the plane in focus when using `--defocus-angle`
+    #[arg(long, global = true)]
+    focus_distance: Option<f32>,
+
+    /// Clamp the radiance of each sample to this, removing fireflies at the cost of some energy
+    #[arg(long, global = true)]
+    max_radiance: Option<f32>,
+
+    /// Clamp only light which has bounced more than once, leaving direct lighting alone
+    #[arg(long, global = true)]
+    max_indirect: Option<f32>,
+
+    /// Fit bright light into 8-bit images instead of clipping it: none, reinhard, aces or filmic
+    #[arg(long, global = true)]
+    tonemap: Option<Tonemap>,
+
+    /// Brighten 8-bit images by this many stops before tonemapping, or darken if negative
+    #[arg(long, global = true, allow_hyphen_values = true)]
+    exposure: Option<f32>,
+
+    /// Encode 8- and 16-bit images as linear, srgb, or with a gamma such as 2.2
+    #[arg(long, global = true)]
+    transfer: Option<Transfer>,
+
+    /// Bits per channel of png and ppm images: 8 or 16
+    #[arg(long, global = true)]
+    bit_depth: Option<BitDepth>,
+
+    /// Dither before quantizing to hide banding in smooth gradients: none, ordered or blue-noise
+    #[arg(long, global = true)]
+    dither: Option<Dither>,
+
+    /// Render on this many threads instead of one per core
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+
+    /// Split renders into tiles this many pixels across
+    #[arg(long, global = true)]
+    tile_size: Option<usize>,
+
+    /// Don't show a progress bar. It's only shown when stderr is a terminal anyway
+    #[arg(long, global = true)]
+    no_progress: bool,
+
+    /// Where samples go within each pixel: random, stratified, halton, sobol or
+    /// blue-noise
+    #[arg(long, global = true)]
+    sampler: Option<PixelSampler>,
+
+    /// Render at this fraction of the resolution, e.g. 0.25 for quick composition checks
+    #[arg(long, global = true)]
+    scale: Option<f32>,
+
+    /// Upscale images rendered with `--scale` back to the full resolution when writing them
+    #[arg(long, global = true, requires = "scale", conflicts_with = "aovs")]
+    upscale: bool,
+
+    /// How rays find what they hit: bvh (the default), bvh-median, kd-tree, grid or list. Renders the same image either way
+    #[arg(long, global = true)]
+    accelerator: Option<Accelerator>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Quality {
+    /// Half resolution and few samples, to check the composition
+    Draft,
+    /// Full resolution, denoised but with little detail in the shadows
+    Medium,
+    /// Full resolution, denoised, with enough samples to publish
+    Final,
+}
+
+impl Quality {
+    /// Resolution scale, samples per pixel, bounces and denoiser.
+    fn settings(self) -> (f32, usize, usize, Option<Denoiser>) {
+        match self {
+            Quality::Draft => (0.5, 4, 8, None),
+            Quality::Medium => (1.0, 32, 20, Some(Denoiser::Filter)),
+            Quality::Final => (1.0, 500, 50, Some(Denoiser::Filter)),
+        }
+    }
+}
+
+/// Redraw a progress bar on the current line of stderr, ending the line when done.
+fn show_progress(progress: &Progress) {
+    const WIDTH: usize = 30;
+
+    let filled = (progress.fraction() * WIDTH as f64) as usize;
+    let eta = match progress.eta() {
+        Some(eta) if !progress.is_finished() => format!("ETA {}s", eta.as_secs()),
+        _ => format!("in {:.1?}", progress.elapsed),
+    };
+
+    eprint!(
+        "\r[{}{}] {:3.0}% {}/{} tiles, {:.1}M rays, {eta}   ",
+        "#".repeat(filled),
+        "-".repeat(WIDTH - filled),
+        100.0 * progress.fraction(),
+        progress.tiles_done,
+        progress.tiles_total,
+        progress.rays as f64 / 1e6,
+    );
+    if progress.is_finished() {
+        eprintln!();
+    }
+}
+
+fn scale_resolution(camera: &mut Camera, scale: f32) {
+    camera.im_width = ((camera.im_width as f32 * scale) as usize).max(1);
+    camera.im_height = ((camera.im_height as f32 * scale) as usize).max(1);
+    camera.update_viewport();
+}
+
+impl RenderOptions {
+    /// Returns the resolution to write images at, which is the camera's unless `--upscale` is given.
+    fn apply(&self, camera: &mut Camera) -> (usize, usize) {
+        let full_resolution = (camera.im_width, camera.im_height);
+        let mut scale = self.scale;
+
+        if let Some(quality) = self.quality {
+            let (preset_scale, samples, bounces, _) = quality.settings();
+
+            scale = scale.or(Some(preset_scale));
+            camera.samples_per_pixel = samples;
+            // The early chapters shade without bouncing, keep them that way
+            if camera.bounce > 0 {
+                camera.bounce = bounces;
+            }
+        }
+
+        if let Some(samples) = self.samples {
+            camera.samples_per_pixel = samples;
+        }
+        if let Some(bounces) = self.bounces {
+            camera.bounce = bounces;
+        }
+
+        if let Some(seconds) = self.max_seconds {
+            camera.time_budget = Some(Duration::from_secs_f64(seconds));
+            if self.samples.is_none() {
+                camera.samples_per_pixel = usize::MAX;
+            }
+        }
+
+        if let Some(seed) = self.sample_seed {
+            camera.seed = seed;
+        }
+        if self.max_radiance.is_some() {
+            camera.max_radiance = self.max_radiance;
+        }
+        if self.max_indirect.is_some() {
+            camera.max_indirect = self.max_indirect;
+        }
+        if let Some(tonemap) = self.tonemap {
+            camera.tonemap = tonemap;
+        }
+        if let Some(stops) = self.exposure {
+            camera.exposure_compensation = stops;
+        }
+        if let Some(transfer) = self.transfer {
+            camera.output_color.transfer = transfer;
+        }
+        if let Some(bit_depth) = self.bit_depth {
+            camera.output_color.bit_depth = bit_depth;
+        }
+        if let Some(dither) = self.dither {
+            camera.output_color.dither = dither;
+        }
+        if let Some(threads) = self.threads {
+            camera.threads = Some(threads);
+        }
+        if let Some(tile_size) = self.tile_size {
+            camera.tile_size = tile_size.max(1);
+        }
+        if !self.no_progress && std::io::stderr().is_terminal() {
+            camera.progress = Some(Arc::new(show_progress));
+        }
+        if let Some(sampler) = self.sampler {
+            camera.sampler = sampler;
+        }
+        if self.clay {
+            camera.material_override = Some(DynMaterial::from(Lambertian::new(Camera::CLAY)));
+        }
+        if self.transparent_background {
+            camera.transparent_background = true;
+        }
+
+        if let Some(vfov) = self.vfov {
+            camera.vfov = Some(vfov);
+            camera.update_viewport();
+        }
+
+        if let Some(angle) = self.defocus_angle {
+            camera.defocus_angle = angle;
+        }
+        if let Some(distance) = self.focus_distance {
+            camera.focus_distance = distance;
+        }
+
+        if let Some(scale) = scale {
+            scale_resolution(camera, scale);
+        }
+
+        if self.upscale {
+            full_resolution
+        } else {
+            (camera.im_width, camera.im_height)
+        }
+    }
+
+    /// `--denoise`, or else the `--quality` preset's, unless `--no-denoise`.
+    fn denoiser(&self) -> Option<Denoiser> {
+        if self.no_denoise {
+            return None;
+        }
+        self.denoise
+            .or_else(|| self.quality.and_then(|quality| quality.settings().3))
+    }
+
+    /// `--out`, or else `default_output`.
+    fn output_path<'a>(&'a self, default_output: &'a str) -> &'a Path {
+        self.out
+            .as_deref()
+            .unwrap_or_else(|| Path::new(default_output))
+    }
+
+    /// Write an 8-bit RGB image to `--out`, or else `default_output`.
+    fn write(&self, rows: usize, data: Vec<u8>, default_output: &str) -> anyhow::Result<()> {
+        output::write_pathlike(rows, data, self.output_path(default_output), self.format)
+    }
+
+    /// Validate and render the scene, logging any problems.
+    fn render(&self, scene: Scene, default_output: &str) -> anyhow::Result<()> {
+        self.render_timed(scene, default_output, RenderTimings::default())
+    }
+
+    /// Like `render`, then logs how long each phase took.
+    /// `timings` holds what was spent before rendering, e.g. building the scene.
+    fn render_timed(
+        &self,
+        mut scene: Scene,
+        default_output: &str,
+        mut timings: RenderTimings,
+    ) -> anyhow::Result<()> {
+        let (width, height) = self.apply(&mut scene.camera);
+        if let Some(accelerator) = self.accelerator {
+            scene.accelerator = accelerator;
+        }
+
+        for problem in scene.validate() {
+            warn!("{problem}");
+        }
+
+        let Scene {
+            camera,
+            world,
+            accelerator,
+        } = &scene;
+
+        let start = Instant::now();
+        let accelerated = accelerator.build(world);
+        timings.acceleration = start.elapsed();
+
+        if !self.aovs.is_empty() {
+            let path = match &self.out {
+                Some(path) => path.clone(),
+                None => Path::new(default_output).with_extension("exr"),
+            };
+
+            let start = Instant::now();
+            let image = aov::render(camera, accelerated.as_ref(), &self.aovs);
+            timings.tracing = start.elapsed();
+
+            let start = Instant::now();
+            if self.aov_files {
+                image.write_separate_exrs(path)?;
+            } else {
+                image.write_exr(path)?;
+            }
+            timings.encoding = start.elapsed();
+
+            info!("{timings}");
+            return Ok(());
+        }
+
+        let path = self.output_path(default_output);
+        let format = self.format.unwrap_or_else(|| ImageFormat::from_path(path));
+
+        // Time budgeted renders also go in passes, to know how many they got through
+        let progressive = self.snapshot_every.is_some()
+            || self.checkpoint_every.is_some()
+            || self.resume.is_some()
+            || camera.time_budget.is_some();
+
+        let start = Instant::now();
+        let (mut image, samples) = match &self.stream {
+            Some(address) => (
+                stream::render_streamed(
+                    camera,
+                    accelerated.as_ref(),
+                    stream::connect(address)?,
+                    32,
+                )?,
+                camera.samples_per_pixel,
+            ),
+            None if progressive => {
+                self.render_progressive(camera, accelerated.as_ref(), path, format)?
+            }
+            None => (
+                camera.render_linear(accelerated.as_ref()),
+                camera.samples_per_pixel,
+            ),
+        };
+        timings.tracing = start.elapsed();
+
+        let start = Instant::now();
+        if let Some(denoiser) = self.denoiser() {
+            denoiser.apply(camera, accelerated.as_ref(), &mut image)?;
+        }
+        if self.edges {
+            EdgeOverlay::default().apply(camera, accelerated.as_ref(), &mut image);
+        }
+        timings.post_processing = start.elapsed();
+
+        // The render itself is exposed at 0 EV
+        let stops: &[f32] = if self.bracket.is_empty() {
+            &[0.0]
+        } else {
+            &self.bracket
+        };
+
+        let eight_bit_rgb = camera.output_color.bit_depth == BitDepth::Eight
+            && !camera.transparent_background
+            && !format.is_float();
+        if self.annotate && !eight_bit_rgb {
+            warn!("--annotate is only drawn into 8-bit images without alpha");
+        }
+
+        for &stop in stops {
+            let path = if self.bracket.is_empty() {
+                path.to_path_buf()
+            } else {
+                output::bracketed_path(path, stop)
+            };
+
+            let start = Instant::now();
+            let exposed: Vec<LinearRgba> = image
+                .iter()
+                .map(|&color| (color * stop.exp2()).with_alpha(color.alpha))
+                .collect();
+
+            if format.is_float() {
+                let exposed =
+                    output::resize_pixels_nearest(&exposed, camera.im_width, width, height);
+                timings.post_processing += start.elapsed();
+
+                let start = Instant::now();
+                output::write_linear(format, width, &exposed, path)?;
+                timings.encoding += start.elapsed();
+                continue;
+            }
+
+            let mut data =
+                camera
+                    .quantize_image(&exposed)
+                    .resize_nearest(camera.im_width, width, height);
+            if let (true, Quantized::Rgb8(rgb)) = (self.annotate, &mut data) {
+                let mut annotation = annotation(camera, default_output, samples, timings.tracing);
+                if !self.bracket.is_empty() {
+                    annotation += &format!("  {stop:+}EV");
+                }
+                *rgb = text::with_footer(std::mem::take(rgb), width, &annotation);
+            }
+            let rows = data.pixel_count() / width;
+            timings.post_processing += start.elapsed();
+
+            let start = Instant::now();
+            output::write_pathlike_quantized(rows, &data, path, Some(format))?;
+            timings.encoding += start.elapsed();
+        }
+
+        // Finished, so there's nothing left to resume
+        let checkpoint = checkpoint::sidecar_path(path);
+        if self.checkpoint_every.is_some() && checkpoint.exists() {
+            std::fs::remove_file(&checkpoint)?;
+        }
+
+        info!("{timings}");
+        Ok(())
+    }
+
+    /// Render in passes, writing snapshots and checkpoints as asked to,
+    /// continuing from the checkpoint being resumed if any.
+    /// Returns the image and how many passes it got.
+    fn render_progressive(
+        &self,
+        camera: &Camera,
+        world: &dyn Hittable,
+        path: &Path,
+        format: ImageFormat,
+    ) -> anyhow::Result<(Vec<LinearRgba>, usize)> {
+        let (mut accumulation, command) = match &self.resume {
+            Some(resume) => {
+                resume.check(camera)?;
+                info!("Resuming after {} passes", resume.accumulation.passes());
+                (resume.accumulation.clone(), resume.command.clone())
+            }
+            None => (
+                Accumulation::new(camera.im_width, camera.im_height),
+                RenderCommand {
+                    args: std::env::args().collect(),
+                    directory: std::env::current_dir()?,
+                    width: camera.im_width,
+                    height: camera.im_height,
+                    seed: camera.seed,
+                },
+            ),
+        };
+
+        if self.checkpoint_every.is_some() && path == Path::new("-") {
+            bail!("--checkpoint-every needs an output file to save checkpoints next to");
+        }
+        let checkpoint = checkpoint::sidecar_path(path);
+        let due = |every: Option<usize>, passes: usize| {
+            every.is_some_and(|every| passes.is_multiple_of(every))
+        };
+
+        let image = camera.resume_progressive(world, &mut accumulation, 1, |snapshot| {
+            if due(self.snapshot_every, snapshot.passes) {
+                info!(
+                    "{} passes in {:.1?}, writing a snapshot to {}",
+                    snapshot.passes,
+                    snapshot.elapsed,
+                    path.display()
+                );
+                let image = camera.accumulated_image(snapshot.accumulation);
+                if let Err(error) = write_snapshot(camera, format, &image, path) {
+                    warn!("Could not write snapshot: {error:#}");
+                }
+            }
+
+            if due(self.checkpoint_every, snapshot.passes) {
+                info!(
+                    "{} passes, saving a checkpoint to {}",
+                    snapshot.passes,
+                    checkpoint.display()
+                );
+                if let Err(error) = Checkpoint::write(&command, snapshot.accumulation, &checkpoint)
+                {
+                    warn!("Could not save checkpoint: {error:#}");
+                }
+            }
+
+            ControlFlow::Continue(())
+        });
+
+        Ok((image, accumulation.passes()))
+    }
+}
+
+/// Write the image so far of a progressive render, at the render's resolution.
+fn write_snapshot(
+    camera: &Camera,
+    format: ImageFormat,
+    image: &[LinearRgba],
+    path: &Path,
+) -> anyhow::Result<()> {
+    if format.is_float() {
+        output::write_linear(format, camera.im_width, image, path)
+    } else {
+        output::write_pathlike_quantized(
+            camera.im_height,
+            &camera.quantize_image(image),
+            path,
+            Some(format),
+        )
+    }
+}
+
+/// The settings of a render in one line, for `--annotate`.
+/// `samples` is how many each pixel got, which for time budgeted renders is only known after.
+fn annotation(
+    camera: &Camera,
+    default_output: &str,
+    samples: usize,
+    render_time: Duration,
+) -> String {
+    let scene = Path::new(default_output)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .replace('_', " ");
+
+    format!(
+        "{scene}  SEED {}  SPP {samples}  BOUNCES {}  {}X{}  {:.2}S",
+        camera.seed,
+        camera.bounce,
+        camera.im_width,
+        camera.im_height,
+        render_time.as_secs_f32()
+    )
 }
 
 #[derive(Subcommand)]
@@ -59,32 +717,409 @@ enum Command {
 
     /// Air bubble in water. Chapter 11.3
     AirBubble,
+
+    /// The glass scene seen through a simple biconvex lens instead of a pinhole
+    RealisticLens,
+
+    /// A globe with an image wrapped around it. The Next Week, chapter 4.4
+    Earth {
+        /// Image to wrap around the sphere, ideally an equirectangular map of the earth
+        #[arg(long, default_value = "earthmap.jpg")]
+        texture: PathBuf,
+    },
+
+    /// A model from a file standing on the ground, scaled to fit the view
+    Model {
+        /// Wavefront OBJ or STL file to load
+        path: PathBuf,
+    },
+
+    /// The default scene of a glTF 2.0 file (.gltf or .glb), seen through its first camera
+    /// or from the front if it has none
+    RenderGltf {
+        /// The file to load
+        path: PathBuf,
+    },
+
+    /// A marble sphere on marble ground, textured with Perlin noise. The Next Week, chapter 5
+    PerlinSpheres {
+        /// Higher values give finer veins
+        #[arg(long, default_value_t = 4.0)]
+        noise_scale: f32,
+
+        /// Seeds the noise
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+
+    /// The marble spheres lit only by a glowing panel and sphere. The Next Week, chapter 7
+    SimpleLight {
+        /// Seeds the noise
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+
+    /// The Cornell box: red and green walls, a lamp in the ceiling and two turned boxes.
+    /// The Next Week, chapter 8
+    CornellBox,
+
+    /// A scene described in a RON or JSON file, see `rt_one::scene_file` for the format.
+    /// Written next to it as a .ppm by default
+    Render {
+        /// The scene file, read as JSON if it ends in .json
+        path: PathBuf,
+    },
+
+    /// Write one of the book's scenes as a scene file to start editing from, see `render`.
+    /// Written to e.g. "metal.ron" by default, or JSON if `--out` ends in .json
+    Export { chapter: Chapter },
+
+    #[command(flatten)]
+    Generated(Generated),
+
+    /// Print what a scene file or generated scene is made of instead of rendering it,
+    /// including the acceleration structure `--accelerator` would build
+    #[command(args_conflicts_with_subcommands = true)]
+    Stats {
+        /// The scene file, read as JSON if it ends in .json
+        path: Option<PathBuf>,
+
+        #[command(subcommand)]
+        scene: Option<Generated>,
+    },
+
+    /// Render a scene once per value of a parameter and lay the images out in a labelled grid
+    ContactSheet {
+        /// Images per row
+        #[arg(long, default_value_t = 4)]
+        columns: usize,
+
+        #[command(subcommand)]
+        sweep: Sweep,
+    },
+
+    /// Continue a render from a checkpoint saved by `--checkpoint-every`,
+    /// with the command line and in the directory it was started with
+    Resume { checkpoint: PathBuf },
+}
+
+/// The parameter to vary in a contact sheet
+#[derive(Subcommand)]
+enum Sweep {
+    /// Metal fuzz from 0 to 1 on a single sphere
+    Fuzz {
+        #[arg(long, default_value_t = 5)]
+        steps: usize,
+    },
+
+    /// Glass refractive index from 1 to 2 on a single sphere
+    RefractiveIndex {
+        #[arg(long, default_value_t = 5)]
+        steps: usize,
+    },
+
+    /// Samples per pixel in a generated scene
+    Samples {
+        #[arg(long, value_delimiter = ',', default_values_t = [1, 4, 16, 64])]
+        values: Vec<usize>,
+
+        #[command(subcommand)]
+        scene: Generated,
+    },
+
+    /// Maximum bounces in a generated scene
+    Bounces {
+        #[arg(long, value_delimiter = ',', default_values_t = [1, 2, 4, 8, 16])]
+        values: Vec<usize>,
+
+        #[command(subcommand)]
+        scene: Generated,
+    },
+}
+
+// Procedurally generated scenes
+#[derive(Clone, Subcommand)]
+enum Generated {
+    /// A deterministic field of small random spheres, for benchmarks and stress testing
+    RandomSpheres {
+        /// Same seed, same scene
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// Spheres are placed in a grid from -extent to extent along X and Z,
+        /// so there are (2 * extent)^2 of them
+        #[arg(long, default_value_t = 11)]
+        extent: i32,
+
+        /// Relative weight of diffuse spheres
+        #[arg(long, default_value_t = MaterialWeights::default().lambertian)]
+        lambertian: f32,
+
+        /// Relative weight of metal spheres
+        #[arg(long, default_value_t = MaterialWeights::default().metal)]
+        metal: f32,
+
+        /// Relative weight of glass spheres
+        #[arg(long, default_value_t = MaterialWeights::default().dielectric)]
+        dielectric: f32,
+    },
+
+    /// Book one's final scene, as on its cover: hundreds of small random spheres
+    /// around three big ones. Chapter 14
+    FinalScene {
+        /// Same seed, same scene
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+
+    /// Book one's cover with the diffuse spheres bouncing, blurred by the shutter.
+    /// The Next Week, chapter 2
+    BouncingSpheres {
+        /// Same seed, same scene
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+
+    /// A recursive sphere-flake fractal in metal
+    SphereFlake {
+        /// Levels of child spheres below the root
+        #[arg(long, default_value_t = 3)]
+        depth: usize,
+
+        /// Child spheres per sphere
+        #[arg(long, default_value_t = 9)]
+        branching: usize,
+    },
+
+    /// A Menger sponge fractal made of boxes
+    MengerSponge {
+        /// Each iteration multiplies the number of boxes by 20
+        #[arg(long, default_value_t = 2)]
+        iterations: usize,
+    },
+
+    /// A world built by a rhai script, seen through the script's camera if it sets one up
+    #[cfg(feature = "scripting")]
+    Script {
+        path: std::path::PathBuf,
+
+        /// Seeds the script's rand()
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
+    // Keep stdout free for images, see `--out`
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
 
-    let cli = Cli::parse();
+    run(Cli::parse())
+}
 
+fn run(cli: Cli) -> anyhow::Result<()> {
     match cli.command {
-        Command::FirstPpm => first_ppm(),
-        Command::Gradient => gradient(),
-        Command::RaySphere => ray_sphere(),
-        Command::RaySphereNormal => ray_sphere_normal_colors(),
-        Command::Hittables => hittables(),
-        Command::AntiAliasing => anti_aliasing(),
-        Command::FirstDiffuse => first_diffuse(),
-        Command::DiffuseNoAcne => diffuse_no_acne(),
-        Command::Lambertian => lambertian(),
-        Command::Gamma => gamma(),
-        Command::Metal => metal(),
-        Command::MetalFuzz => metal_fuzz(),
-        Command::GlassRefract => glass_refract(),
-        Command::AirBubble => air_bubble(),
+        Command::FirstPpm => first_ppm(&cli.options),
+        Command::Gradient => gradient(&cli.options),
+        Command::RaySphere => ray_sphere(&cli.options),
+        Command::RaySphereNormal => ray_sphere_normal_colors(&cli.options),
+        Command::Hittables => chapter(Chapter::Hittables, &cli.options),
+        Command::AntiAliasing => chapter(Chapter::AntiAliasing, &cli.options),
+        Command::FirstDiffuse => chapter(Chapter::FirstDiffuse, &cli.options),
+        Command::DiffuseNoAcne => chapter(Chapter::DiffuseNoAcne, &cli.options),
+        Command::Lambertian => chapter(Chapter::Lambertian, &cli.options),
+        Command::Gamma => gamma(&cli.options),
+        Command::Metal => chapter(Chapter::Metal, &cli.options),
+        Command::MetalFuzz => chapter(Chapter::MetalFuzz, &cli.options),
+        Command::GlassRefract => chapter(Chapter::GlassRefract, &cli.options),
+        Command::AirBubble => chapter(Chapter::AirBubble, &cli.options),
+        Command::RealisticLens => realistic_lens(&cli.options),
+        Command::Earth { texture } => earth(&texture, &cli.options),
+        Command::Model { path } => model(&path, &cli.options),
+        Command::RenderGltf { path } => render_gltf(&path, &cli.options),
+        Command::PerlinSpheres { noise_scale, seed } => render_chapter(
+            perlin_spheres(noise_scale, seed),
+            Chapter::PerlinSpheres.output(),
+            &cli.options,
+        ),
+        Command::SimpleLight { seed } => render_chapter(
+            simple_light(seed),
+            Chapter::SimpleLight.output(),
+            &cli.options,
+        ),
+        Command::CornellBox => chapter(Chapter::CornellBox, &cli.options),
+        Command::Render { path } => render_file(&path, &cli.options),
+        Command::Export { chapter } => export(chapter, &cli.options),
+        Command::Generated(generated) => {
+            let start = Instant::now();
+            let (scene, output) = generated_scene(generated)?;
+            let timings = RenderTimings {
+                scene: start.elapsed(),
+                ..Default::default()
+            };
+            cli.options.render_timed(scene, output, timings)
+        }
+        Command::Stats { path, scene } => stats(path.as_deref(), scene, &cli.options),
+        Command::ContactSheet { columns, sweep } => contact_sheet(columns, sweep, &cli.options),
+        Command::Resume { checkpoint } => resume(&checkpoint),
     }
 }
 
-fn first_ppm() -> anyhow::Result<()> {
+fn resume(path: &Path) -> anyhow::Result<()> {
+    let checkpoint = Checkpoint::read(path)?;
+
+    std::env::set_current_dir(&checkpoint.command.directory).with_context(|| {
+        format!(
+            "Changing to {}, where the render started",
+            checkpoint.command.directory.display()
+        )
+    })?;
+    let mut cli = Cli::try_parse_from(&checkpoint.command.args)?;
+    if matches!(cli.command, Command::Resume { .. }) {
+        bail!("{} would resume another checkpoint", path.display());
+    }
+
+    cli.options.resume = Some(checkpoint);
+    run(cli)
+}
+
+/// Print what a scene file or generated scene is made of, for `rt stats`.
+fn stats(
+    path: Option<&Path>,
+    generated: Option<Generated>,
+    options: &RenderOptions,
+) -> anyhow::Result<()> {
+    let mut scene = match (path, generated) {
+        (Some(path), _) => {
+            let directory = path.parent().unwrap_or(Path::new(""));
+            SceneFile::load(path)?.build(directory)?
+        }
+        (None, Some(generated)) => generated_scene(generated)?.0,
+        (None, None) => bail!("stats needs a scene file or a generated scene"),
+    };
+    if let Some(accelerator) = options.accelerator {
+        scene.accelerator = accelerator;
+    }
+
+    println!("{}", scene.stats());
+    Ok(())
+}
+
+/// Build one of the procedurally generated scenes, and where it should be rendered to.
+fn generated_scene(generated: Generated) -> anyhow::Result<(Scene, &'static str)> {
+    let scene = match generated {
+        Generated::RandomSpheres {
+            seed,
+            extent,
+            lambertian,
+            metal,
+            dielectric,
+        } => (
+            random_spheres(
+                seed,
+                extent,
+                MaterialWeights {
+                    lambertian,
+                    metal,
+                    dielectric,
+                },
+            )?,
+            "random_spheres.ppm",
+        ),
+        Generated::FinalScene { seed } => (final_scene(seed)?, "final_scene.ppm"),
+        Generated::BouncingSpheres { seed } => (bouncing_spheres(seed)?, "bouncing_spheres.ppm"),
+        Generated::SphereFlake { depth, branching } => {
+            (sphere_flake(depth, branching)?, "sphere_flake.ppm")
+        }
+        Generated::MengerSponge { iterations } => (menger_sponge(iterations)?, "menger_sponge.ppm"),
+        #[cfg(feature = "scripting")]
+        Generated::Script { path, seed } => (script(&path, seed)?, "script.ppm"),
+    };
+
+    Ok(scene)
+}
+
+fn contact_sheet(columns: usize, sweep: Sweep, options: &RenderOptions) -> anyhow::Result<()> {
+    let mut sheet = ContactSheet::new(columns);
+
+    match sweep {
+        Sweep::Fuzz { steps } => {
+            for fuzz in linspace(0.0, 1.0, steps) {
+                let mut scene =
+                    material_sweep(Metal::new(Color::linear_rgb(0.8, 0.6, 0.2), fuzz).into())?;
+                options.apply(&mut scene.camera);
+                sheet.add_render(format!("fuzz {fuzz:.2}"), &scene.camera, &scene.world);
+            }
+        }
+        Sweep::RefractiveIndex { steps } => {
+            for index in linspace(1.0, 2.0, steps) {
+                let mut scene = material_sweep(Dielectric::refraction_index(index).into())?;
+                options.apply(&mut scene.camera);
+                sheet.add_render(format!("ior {index:.2}"), &scene.camera, &scene.world);
+            }
+        }
+        Sweep::Samples { values, scene } => {
+            for samples in values {
+                let (mut scene, _) = generated_scene(scene.clone())?;
+                options.apply(&mut scene.camera);
+                scene.camera.samples_per_pixel = samples;
+                sheet.add_render(format!("{samples} spp"), &scene.camera, &scene.world);
+            }
+        }
+        Sweep::Bounces { values, scene } => {
+            for bounces in values {
+                let (mut scene, _) = generated_scene(scene.clone())?;
+                options.apply(&mut scene.camera);
+                scene.camera.bounce = bounces;
+                sheet.add_render(format!("{bounces} bounces"), &scene.camera, &scene.world);
+            }
+        }
+    }
+
+    let (rows, data) = sheet.composite();
+    options.write(rows, data, "contact_sheet.ppm")
+}
+
+/// `steps` evenly spaced values from `start` to `end`, both included.
+fn linspace(start: f32, end: f32, steps: usize) -> impl Iterator<Item = f32> {
+    let intervals = steps.saturating_sub(1).max(1) as f32;
+    (0..steps).map(move |step| start + (end - start) * step as f32 / intervals)
+}
+
+/// A small scene to show off a single material, with something behind it
+/// to see in reflections and through refraction.
+fn material_sweep(material: DynMaterial) -> anyhow::Result<Scene> {
+    let mut world = Hittables::default();
+
+    world.add(Sphere {
+        center: Vec3::new(0.0, -100.5, -1.0),
+        radius: 100.0,
+        material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+    });
+    world.add(Sphere {
+        center: Vec3::new(0.0, 0.0, -1.2),
+        radius: 0.5,
+        material,
+    });
+    world.add(Sphere {
+        center: Vec3::new(1.2, 0.0, -2.5),
+        radius: 0.5,
+        material: Lambertian::linear_rgb(0.7, 0.1, 0.1).into(),
+    });
+
+    let camera = Camera::builder()
+        .resolution(240, 160)
+        .samples(32)
+        .bounces(50)
+        .min_dist(0.001)
+        .srgb(true)
+        .build()?;
+
+    Ok(Scene::new(camera, world))
+}
+
+fn first_ppm(options: &RenderOptions) -> anyhow::Result<()> {
     let mut data = vec![];
     for row in 0..=255 {
         for col in 0..=255 {
@@ -93,10 +1128,10 @@ fn first_ppm() -> anyhow::Result<()> {
         }
     }
 
-    ppm::write_pathlike(256, data, "image.ppm")
+    options.write(256, data, "image.ppm")
 }
 
-fn gradient() -> anyhow::Result<()> {
+fn gradient(options: &RenderOptions) -> anyhow::Result<()> {
     let camera = Camera::new();
 
     let mut data = vec![];
@@ -107,15 +1142,15 @@ fn gradient() -> anyhow::Result<()> {
             let dir = -camera.cam_origin + pixel;
             let ray = ray::Ray::new(camera.cam_origin, dir);
 
-            let color = camera.sky_color(&ray);
+            let color = camera.background_color(&ray);
             data.extend(color.to_linear().to_u8_array_no_alpha());
         }
     }
 
-    ppm::write_pathlike(camera.im_height, data, "gradient.ppm")
+    options.write(camera.im_height, data, "gradient.ppm")
 }
 
-fn ray_sphere() -> anyhow::Result<()> {
+fn ray_sphere(options: &RenderOptions) -> anyhow::Result<()> {
     let camera = Camera::new();
 
     let mut data = vec![];
@@ -135,17 +1170,17 @@ fn ray_sphere() -> anyhow::Result<()> {
             let color: Color = if ray.hit_sphere(&sphere) >= 0.0 {
                 palettes::tailwind::RED_500.into()
             } else {
-                camera.sky_color(&ray)
+                camera.background_color(&ray)
             };
 
             data.extend(color.to_linear().to_u8_array_no_alpha());
         }
     }
 
-    ppm::write_pathlike(camera.im_height, data, "ray_sphere.ppm")
+    options.write(camera.im_height, data, "ray_sphere.ppm")
 }
 
-fn ray_sphere_normal_colors() -> anyhow::Result<()> {
+fn ray_sphere_normal_colors(options: &RenderOptions) -> anyhow::Result<()> {
     let c = Camera::new();
 
     let mut data = vec![];
@@ -186,110 +1221,294 @@ fn ray_sphere_normal_colors() -> anyhow::Result<()> {
 
                 LinearRgba::new(n.x, n.y, n.z, 1.0)
             } else {
-                c.sky_color(&ray).to_linear()
+                c.background_color(&ray).to_linear()
             };
 
             data.extend(color.to_u8_array_no_alpha());
         }
     }
 
-    ppm::write_pathlike(c.im_height, data, "ray_sphere_normal.ppm")
+    options.write(c.im_height, data, "ray_sphere_normal.ppm")
 }
 
-fn hittables() -> anyhow::Result<()> {
-    let mut world = Hittables::default();
+/// The book's scenes simple enough to be described by a scene file
+#[derive(Clone, Copy, ValueEnum)]
+enum Chapter {
+    Hittables,
+    AntiAliasing,
+    FirstDiffuse,
+    DiffuseNoAcne,
+    Lambertian,
+    Metal,
+    MetalFuzz,
+    GlassRefract,
+    AirBubble,
+    PerlinSpheres,
+    SimpleLight,
+    CornellBox,
+}
 
-    world.add(Sphere {
-        center: Vec3::new(0.0, 0.0, -1.0),
-        radius: 0.5,
-        ..Default::default()
-    });
-    world.add(Sphere {
-        center: Vec3::new(0.0, -100.5, -1.0),
-        radius: 100.0,
-        ..Default::default()
-    });
+impl Chapter {
+    fn file(self) -> SceneFile {
+        // Without bounces surfaces are shaded by their normals, like before materials
+        let shaded_by_normals = CameraSettings {
+            samples: 1,
+            bounces: 0,
+            min_dist: 0.0,
+            srgb: false,
+            ..Default::default()
+        };
+        let linear = CameraSettings {
+            samples: 10,
+            srgb: false,
+            ..Default::default()
+        };
+
+        match self {
+            Chapter::Hittables => sphere_on_ground(shaded_by_normals),
+            Chapter::AntiAliasing => sphere_on_ground(CameraSettings {
+                samples: 10,
+                ..shaded_by_normals
+            }),
+            Chapter::FirstDiffuse => sphere_on_ground(CameraSettings {
+                min_dist: 0.0,
+                ..linear
+            }),
+            Chapter::DiffuseNoAcne | Chapter::Lambertian => sphere_on_ground(linear),
+            Chapter::Metal => three_spheres(metal(0.8, 0.8, 0.8, 0.0), metal(0.8, 0.6, 0.2, 0.0)),
+            Chapter::MetalFuzz => {
+                three_spheres(metal(0.8, 0.8, 0.8, 0.3), metal(0.8, 0.6, 0.2, 1.0))
+            }
+            Chapter::GlassRefract => three_spheres(glass(1.5), metal(0.8, 0.6, 0.2, 1.0)),
+            Chapter::AirBubble => three_spheres(glass(1.0 / 1.33), metal(0.8, 0.6, 0.2, 1.0)),
+            Chapter::PerlinSpheres => perlin_spheres(4.0, 0),
+            Chapter::SimpleLight => simple_light(0),
+            Chapter::CornellBox => cornell_box(),
+        }
+    }
 
-    Camera::new().render(&world, "hittable.ppm")
+    /// Where the scene is rendered to by default.
+    fn output(self) -> &'static str {
+        match self {
+            Chapter::Hittables => "hittable.ppm",
+            Chapter::AntiAliasing => "anti_aliasing.ppm",
+            Chapter::FirstDiffuse => "first_diffuse.ppm",
+            Chapter::DiffuseNoAcne => "diffuse_no_acne.ppm",
+            Chapter::Lambertian => "lambertian.ppm",
+            Chapter::Metal => "metal.ppm",
+            Chapter::MetalFuzz => "metal_fuzz.ppm",
+            Chapter::GlassRefract => "glass_refract.ppm",
+            Chapter::AirBubble => "air_bubble.ppm",
+            Chapter::PerlinSpheres => "perlin_spheres.ppm",
+            Chapter::SimpleLight => "simple_light.ppm",
+            Chapter::CornellBox => "cornell_box.ppm",
+        }
+    }
 }
 
-fn anti_aliasing() -> anyhow::Result<()> {
-    let mut world = Hittables::default();
+fn solid(red: f32, green: f32, blue: f32) -> TextureSettings {
+    TextureSettings::Solid([red, green, blue])
+}
 
-    world.add(Sphere {
-        center: Vec3::new(0.0, 0.0, -1.0),
-        radius: 0.5,
-        ..Default::default()
-    });
-    world.add(Sphere {
-        center: Vec3::new(0.0, -100.5, -1.0),
-        radius: 100.0,
-        ..Default::default()
-    });
+fn metal(red: f32, green: f32, blue: f32, fuzz: f32) -> MaterialSettings {
+    MaterialSettings::Metal {
+        albedo: solid(red, green, blue),
+        fuzz,
+    }
+}
 
-    Camera::with_samples_per_pixel(10).render(&world, "anti_aliasing.ppm")
+fn glass(refraction_index: f32) -> MaterialSettings {
+    MaterialSettings::Dielectric { refraction_index }
 }
 
-fn first_diffuse() -> anyhow::Result<()> {
-    let mut world = Hittables::default();
+fn sphere(center: Vec3, radius: f32, material: MaterialSettings) -> Object {
+    Object::Sphere {
+        center,
+        radius,
+        material,
+    }
+}
 
-    world.add(Sphere {
-        center: Vec3::new(0.0, 0.0, -1.0),
-        radius: 0.5,
+/// A sphere on a huge sphere of ground, both in the default `Sphere` material.
+fn sphere_on_ground(camera: CameraSettings) -> SceneFile {
+    let material = MaterialSettings::Lambertian(solid(0.2, 0.4, 0.6));
+
+    SceneFile {
+        camera,
+        objects: vec![
+            sphere(Vec3::new(0.0, 0.0, -1.0), 0.5, material.clone()),
+            sphere(Vec3::new(0.0, -100.5, -1.0), 100.0, material),
+        ],
         ..Default::default()
-    });
-    world.add(Sphere {
-        center: Vec3::new(0.0, -100.5, -1.0),
-        radius: 100.0,
+    }
+}
+
+/// A blue diffuse sphere between two others on yellow ground.
+fn three_spheres(left: MaterialSettings, right: MaterialSettings) -> SceneFile {
+    let diffuse = |red, green, blue| MaterialSettings::Lambertian(solid(red, green, blue));
+
+    SceneFile {
+        camera: CameraSettings {
+            samples: 100,
+            ..Default::default()
+        },
+        objects: vec![
+            sphere(Vec3::new(0.0, -100.5, -1.0), 100.0, diffuse(0.8, 0.8, 0.0)),
+            sphere(Vec3::new(0.0, 0.0, -1.2), 0.5, diffuse(0.1, 0.2, 0.5)),
+            sphere(Vec3::new(-1.0, 0.0, -1.0), 0.5, left),
+            sphere(Vec3::new(1.0, 0.0, -1.0), 0.5, right),
+        ],
         ..Default::default()
+    }
+}
+
+fn perlin_spheres(noise_scale: f32, seed: u64) -> SceneFile {
+    let marble = MaterialSettings::Lambertian(TextureSettings::Marble {
+        seed,
+        scale: noise_scale,
     });
 
-    let mut camera = Camera::with_samples_per_pixel(10);
-    camera.bounce = 50;
-    camera.render(&world, "first_diffuse.ppm")
+    SceneFile {
+        camera: CameraSettings {
+            vfov: Some(20.0),
+            position: Vec3::new(13.0, 2.0, 3.0),
+            look_at: Some(Vec3::ZERO),
+            ..Default::default()
+        },
+        objects: vec![
+            sphere(Vec3::new(0.0, -1000.0, 0.0), 1000.0, marble.clone()),
+            sphere(Vec3::new(0.0, 2.0, 0.0), 2.0, marble),
+        ],
+        ..Default::default()
+    }
 }
 
-fn diffuse_no_acne() -> anyhow::Result<()> {
-    let mut world = Hittables::default();
-
-    world.add(Sphere {
-        center: Vec3::new(0.0, 0.0, -1.0),
-        radius: 0.5,
+fn simple_light(seed: u64) -> SceneFile {
+    let marble = MaterialSettings::Lambertian(TextureSettings::Marble { seed, scale: 4.0 });
+    let light = MaterialSettings::DiffuseLight(solid(4.0, 4.0, 4.0));
+
+    SceneFile {
+        camera: CameraSettings {
+            vfov: Some(20.0),
+            position: Vec3::new(26.0, 3.0, 6.0),
+            look_at: Some(Vec3::new(0.0, 2.0, 0.0)),
+            background: BackgroundSettings::Solid([0.0; 3]),
+            aim_at_lights: true,
+            ..Default::default()
+        },
+        objects: vec![
+            sphere(Vec3::new(0.0, -1000.0, 0.0), 1000.0, marble.clone()),
+            sphere(Vec3::new(0.0, 2.0, 0.0), 2.0, marble),
+            Object::Quad {
+                corner: Vec3::new(3.0, 1.0, -2.0),
+                u: Vec3::new(2.0, 0.0, 0.0),
+                v: Vec3::new(0.0, 2.0, 0.0),
+                material: light.clone(),
+            },
+            sphere(Vec3::new(0.0, 7.0, 0.0), 2.0, light),
+        ],
         ..Default::default()
-    });
-    world.add(Sphere {
-        center: Vec3::new(0.0, -100.5, -1.0),
-        radius: 100.0,
+    }
+}
+
+fn cornell_box() -> SceneFile {
+    let diffuse = |red, green, blue| MaterialSettings::Lambertian(solid(red, green, blue));
+    let red = diffuse(0.65, 0.05, 0.05);
+    let white = diffuse(0.73, 0.73, 0.73);
+    let green = diffuse(0.12, 0.45, 0.15);
+    let light = MaterialSettings::DiffuseLight(solid(15.0, 15.0, 15.0));
+
+    let quad = |corner, u, v, material| Object::Quad {
+        corner,
+        u,
+        v,
+        material,
+    };
+    // A box with a corner at the origin, turned and then moved into place
+    let turned_box = |size, degrees, offset| Object::Translate {
+        offset,
+        object: Box::new(Object::RotateY {
+            degrees,
+            object: Box::new(Object::Cuboid {
+                a: Vec3::ZERO,
+                b: size,
+                material: white.clone(),
+            }),
+        }),
+    };
+
+    SceneFile {
+        camera: CameraSettings {
+            resolution: (600, 600),
+            samples: 200,
+            vfov: Some(40.0),
+            position: Vec3::new(278.0, 278.0, -800.0),
+            look_at: Some(Vec3::new(278.0, 278.0, 0.0)),
+            background: BackgroundSettings::Solid([0.0; 3]),
+            aim_at_lights: true,
+            ..Default::default()
+        },
+        objects: vec![
+            quad(
+                Vec3::new(555.0, 0.0, 0.0),
+                Vec3::Y * 555.0,
+                Vec3::Z * 555.0,
+                green,
+            ),
+            quad(Vec3::ZERO, Vec3::Y * 555.0, Vec3::Z * 555.0, red),
+            quad(
+                Vec3::new(343.0, 554.0, 332.0),
+                Vec3::NEG_X * 130.0,
+                Vec3::NEG_Z * 105.0,
+                light,
+            ),
+            quad(Vec3::ZERO, Vec3::X * 555.0, Vec3::Z * 555.0, white.clone()),
+            quad(
+                Vec3::splat(555.0),
+                Vec3::NEG_X * 555.0,
+                Vec3::NEG_Z * 555.0,
+                white.clone(),
+            ),
+            quad(
+                Vec3::new(0.0, 0.0, 555.0),
+                Vec3::X * 555.0,
+                Vec3::Y * 555.0,
+                white.clone(),
+            ),
+            turned_box(
+                Vec3::new(165.0, 330.0, 165.0),
+                15.0,
+                Vec3::new(265.0, 0.0, 295.0),
+            ),
+            turned_box(Vec3::splat(165.0), -18.0, Vec3::new(130.0, 0.0, 65.0)),
+        ],
         ..Default::default()
-    });
+    }
+}
 
-    let mut camera = Camera::with_samples_per_pixel(10);
-    camera.bounce = 50;
-    camera.min_dist = 0.001;
-    camera.render(&world, "diffuse_no_acne.ppm")
+fn chapter(chapter: Chapter, options: &RenderOptions) -> anyhow::Result<()> {
+    render_chapter(chapter.file(), chapter.output(), options)
 }
 
-fn lambertian() -> anyhow::Result<()> {
-    let mut world = Hittables::default();
+fn render_chapter(
+    file: SceneFile,
+    default_output: &str,
+    options: &RenderOptions,
+) -> anyhow::Result<()> {
+    options.render(file.build(Path::new(""))?, default_output)
+}
 
-    world.add(Sphere {
-        center: Vec3::new(0.0, 0.0, -1.0),
-        radius: 0.5,
-        ..Default::default()
-    });
-    world.add(Sphere {
-        center: Vec3::new(0.0, -100.5, -1.0),
-        radius: 100.0,
-        ..Default::default()
-    });
+/// Write a chapter's scene file to `--out`, or else e.g. "metal.ron".
+fn export(chapter: Chapter, options: &RenderOptions) -> anyhow::Result<()> {
+    let default_output = Path::new(chapter.output()).with_extension("ron");
+    let path = options.out.as_deref().unwrap_or(&default_output);
 
-    let mut camera = Camera::with_samples_per_pixel(10);
-    camera.bounce = 50;
-    camera.min_dist = 0.001;
-    camera.render(&world, "lambertian.ppm")
+    chapter.file().save(path)?;
+    info!("Wrote {}", path.display());
+    Ok(())
 }
 
-fn gamma() -> anyhow::Result<()> {
+fn gamma(options: &RenderOptions) -> anyhow::Result<()> {
     let mut world = Hittables::default();
 
     world.add(Sphere {
@@ -303,15 +1522,17 @@ fn gamma() -> anyhow::Result<()> {
         ..Default::default()
     });
 
-    let mut camera = Camera::with_samples_per_pixel(10);
-    camera.bounce = 50;
-    camera.min_dist = 0.001;
-    camera.srgb_output = true;
-    camera.reflectance_groups = true;
-    camera.render(&world, "gamma.ppm")
+    let camera = Camera::builder()
+        .samples(10)
+        .bounces(50)
+        .min_dist(0.001)
+        .srgb(true)
+        .reflectance_groups(true)
+        .build()?;
+    options.render(Scene::new(camera, world), "gamma.ppm")
 }
 
-fn metal() -> anyhow::Result<()> {
+fn realistic_lens(options: &RenderOptions) -> anyhow::Result<()> {
     let mut world = Hittables::default();
 
     world.add(Sphere {
@@ -329,120 +1550,247 @@ fn metal() -> anyhow::Result<()> {
     world.add(Sphere {
         center: Vec3::new(-1.0, 0.0, -1.0),
         radius: 0.5,
-        material: Metal::linear_rgb(0.8, 0.8, 0.8).into(),
+        material: Dielectric::refraction_index(1.50).into(),
     });
 
     world.add(Sphere {
         center: Vec3::new(1.0, 0.0, -1.0),
         radius: 0.5,
-        material: Metal::linear_rgb(0.8, 0.6, 0.2).into(),
+        material: Metal::new(Color::linear_rgb(0.8, 0.6, 0.2), 1.0).into(),
     });
 
-    let mut camera = Camera::with_samples_per_pixel(100);
-    camera.bounce = 50;
-    camera.min_dist = 0.001;
-    camera.srgb_output = true;
-    camera.render(&world, "metal.ppm")
+    let camera = Camera::builder()
+        .samples(100)
+        .bounces(50)
+        .min_dist(0.001)
+        .srgb(true)
+        // 50mm at f/2, focused on the middle sphere, roughly the same field of view as the pinhole
+        .lens(LensSystem::singlet(0.05, 0.0125, 1.2, 0.1))
+        .build()?;
+    options.render(Scene::new(camera, world), "realistic_lens.ppm")
 }
 
-fn metal_fuzz() -> anyhow::Result<()> {
+fn earth(texture: &Path, options: &RenderOptions) -> anyhow::Result<()> {
     let mut world = Hittables::default();
 
     world.add(Sphere {
-        center: Vec3::new(0.0, -100.5, -1.0),
-        radius: 100.0,
-        material: Lambertian::linear_rgb(0.8, 0.8, 0.0).into(),
+        center: Vec3::ZERO,
+        radius: 2.0,
+        material: Lambertian::new(ImageTexture::load(texture)?).into(),
     });
 
-    world.add(Sphere {
-        center: Vec3::new(0.0, 0.0, -1.2),
-        radius: 0.5,
-        material: Lambertian::linear_rgb(0.1, 0.2, 0.5).into(),
-    });
+    let camera = Camera::builder()
+        .samples(100)
+        .bounces(50)
+        .min_dist(0.001)
+        .srgb(true)
+        .vfov(20.0)
+        .position(Vec3::new(0.0, 0.0, 12.0))
+        .look_at(Vec3::ZERO, Vec3::Y)
+        .build()?;
+    options.render(Scene::new(camera, world), "earth.ppm")
+}
 
-    world.add(Sphere {
-        center: Vec3::new(-1.0, 0.0, -1.0),
-        radius: 0.5,
-        material: Metal::new(Color::linear_rgb(0.8, 0.8, 0.8), 0.3).into(),
-    });
+fn model(path: &Path, options: &RenderOptions) -> anyhow::Result<()> {
+    let material = Lambertian::linear_rgb(0.7, 0.7, 0.7);
+    let extension = path.extension().unwrap_or_default().to_ascii_lowercase();
+    let mut mesh = if extension == "stl" {
+        stl::load(path, material)?
+    } else {
+        obj::load(path, material)?
+    };
+    info!(
+        "Loaded {} triangles from {}",
+        mesh.triangles.len(),
+        path.display()
+    );
+
+    // Two units tall or wide, standing on the ground at the origin
+    let bounds = mesh.bounding_box();
+    let scale = 2.0 / bounds.size().max_element().max(f32::MIN_POSITIVE);
+    let bottom = Vec3::new(bounds.center().x, bounds.min.y, bounds.center().z);
+    mesh.transform(Affine3A::from_scale(Vec3::splat(scale)) * Affine3A::from_translation(-bottom));
+    let height = scale * bounds.size().y;
 
+    let mut world = Hittables::default();
+    world.add(mesh);
     world.add(Sphere {
-        center: Vec3::new(1.0, 0.0, -1.0),
-        radius: 0.5,
-        material: Metal::new(Color::linear_rgb(0.8, 0.6, 0.2), 1.0).into(),
+        center: Vec3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
     });
 
-    let mut camera = Camera::with_samples_per_pixel(100);
-    camera.bounce = 50;
-    camera.min_dist = 0.001;
-    camera.srgb_output = true;
-    camera.render(&world, "metal_fuzz.ppm")
+    let camera = Camera::builder()
+        .samples(32)
+        .bounces(16)
+        .min_dist(0.001)
+        .srgb(true)
+        .vfov(40.0)
+        .position(Vec3::new(0.0, height * 0.75 + 0.5, 5.0))
+        .look_at(Vec3::new(0.0, height / 2.0, 0.0), Vec3::Y)
+        .build()?;
+    options.render(Scene::new(camera, world), "model.ppm")
 }
 
-fn glass_refract() -> anyhow::Result<()> {
-    let mut world = Hittables::default();
+fn render_file(path: &Path, options: &RenderOptions) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let scene = SceneFile::load(path)?.build(directory)?;
+    let timings = RenderTimings {
+        scene: start.elapsed(),
+        ..Default::default()
+    };
 
-    world.add(Sphere {
-        center: Vec3::new(0.0, -100.5, -1.0),
-        radius: 100.0,
-        material: Lambertian::linear_rgb(0.8, 0.8, 0.0).into(),
-    });
+    let output = path.with_extension("ppm");
+    options.render_timed(scene, &output.to_string_lossy(), timings)
+}
 
-    world.add(Sphere {
-        center: Vec3::new(0.0, 0.0, -1.2),
-        radius: 0.5,
-        material: Lambertian::linear_rgb(0.1, 0.2, 0.5).into(),
-    });
+fn render_gltf(path: &Path, options: &RenderOptions) -> anyhow::Result<()> {
+    let scene = gltf::load(path)?;
+    let bounds = scene.world.bounding_box();
+    info!(
+        "Loaded {} primitives from {}",
+        scene.world.objects.len(),
+        path.display()
+    );
+
+    let builder = Camera::builder()
+        .samples(32)
+        .bounces(16)
+        .min_dist(0.001)
+        .srgb(true);
+
+    let camera = match scene.camera {
+        Some(camera) => builder
+            .position(camera.position)
+            .orientation(camera.orientation)
+            .vfov(camera.vfov.unwrap_or(40.0)),
+        None => {
+            // Far enough back along +Z to see the whole bounding sphere
+            let vfov = 40.0f32;
+            let radius = (bounds.size().length() / 2.0).max(f32::MIN_POSITIVE);
+            let distance = radius / (vfov.to_radians() / 2.0).sin();
+            builder
+                .position(bounds.center() + Vec3::new(0.0, 0.0, distance))
+                .look_at(bounds.center(), Vec3::Y)
+                .vfov(vfov)
+        }
+    }
+    .build()?;
 
-    world.add(Sphere {
-        center: Vec3::new(-1.0, 0.0, -1.0),
-        radius: 0.5,
-        material: Dielectric::refraction_index(1.50).into(),
-    });
+    options.render(Scene::new(camera, scene.world), "gltf.ppm")
+}
 
-    world.add(Sphere {
-        center: Vec3::new(1.0, 0.0, -1.0),
-        radius: 0.5,
-        material: Metal::new(Color::linear_rgb(0.8, 0.6, 0.2), 1.0).into(),
-    });
+fn random_spheres(seed: u64, extent: i32, weights: MaterialWeights) -> anyhow::Result<Scene> {
+    let world = scenes::random_spheres(seed, extent, weights);
+
+    let camera = Camera::builder()
+        .samples(10)
+        .bounces(50)
+        .min_dist(0.001)
+        .srgb(true)
+        .position(Vec3::new(0.0, 1.0, extent as f32 + 3.0))
+        .build()?;
 
-    let mut camera = Camera::with_samples_per_pixel(100);
-    camera.bounce = 50;
-    camera.min_dist = 0.001;
-    camera.srgb_output = true;
-    camera.render(&world, "glass_refract.ppm")
+    Ok(Scene::new(camera, world))
 }
 
-fn air_bubble() -> anyhow::Result<()> {
-    let mut world = Hittables::default();
+fn final_scene(seed: u64) -> anyhow::Result<Scene> {
+    let world = scenes::final_scene(seed);
+
+    let camera = Camera::builder()
+        .samples(100)
+        .bounces(50)
+        .min_dist(0.001)
+        .srgb(true)
+        .vfov(20.0)
+        .position(Vec3::new(13.0, 2.0, 3.0))
+        .look_at(Vec3::ZERO, Vec3::Y)
+        .defocus(0.6, 10.0)
+        .build()?;
+
+    Ok(Scene::new(camera, world))
+}
 
-    world.add(Sphere {
-        center: Vec3::new(0.0, -100.5, -1.0),
-        radius: 100.0,
-        material: Lambertian::linear_rgb(0.8, 0.8, 0.0).into(),
-    });
+fn bouncing_spheres(seed: u64) -> anyhow::Result<Scene> {
+    let world = scenes::bouncing_spheres(seed);
+
+    let camera = Camera::builder()
+        .samples(100)
+        .bounces(50)
+        .min_dist(0.001)
+        .srgb(true)
+        .vfov(20.0)
+        .position(Vec3::new(13.0, 2.0, 3.0))
+        .look_at(Vec3::ZERO, Vec3::Y)
+        .defocus(0.6, 10.0)
+        .shutter(0.0..1.0)
+        .build()?;
+
+    Ok(Scene::new(camera, world))
+}
 
-    world.add(Sphere {
-        center: Vec3::new(0.0, 0.0, -1.2),
-        radius: 0.5,
-        material: Lambertian::linear_rgb(0.1, 0.2, 0.5).into(),
+fn sphere_flake(depth: usize, branching: usize) -> anyhow::Result<Scene> {
+    let world = scenes::sphere_flake(depth, branching, |level| {
+        // Shift from gold towards silver the smaller the spheres get
+        let t = level as f32 / depth.max(1) as f32;
+        Metal::new(Color::linear_rgb(0.8, 0.6 + 0.2 * t, 0.2 + 0.6 * t), 0.05).into()
     });
 
-    world.add(Sphere {
-        center: Vec3::new(-1.0, 0.0, -1.0),
-        radius: 0.5,
-        material: Dielectric::refraction_index(1.0 / 1.33).into(),
-    });
+    let mut camera = Camera::builder()
+        .samples(10)
+        .bounces(50)
+        .min_dist(0.001)
+        .srgb(true)
+        .build()?;
+    camera.frame(&world, 1.1);
+
+    Ok(Scene::new(camera, world))
+}
+
+fn menger_sponge(iterations: usize) -> anyhow::Result<Scene> {
+    let mut world = scenes::menger_sponge(
+        iterations,
+        1.0,
+        Lambertian::linear_rgb(0.7, 0.3, 0.2).into(),
+    );
+
+    let mut camera = Camera::builder()
+        .samples(10)
+        .bounces(50)
+        .min_dist(0.001)
+        .srgb(true)
+        .build()?;
+    camera.frame(&world, 1.2);
 
     world.add(Sphere {
-        center: Vec3::new(1.0, 0.0, -1.0),
-        radius: 0.5,
-        material: Metal::new(Color::linear_rgb(0.8, 0.6, 0.2), 1.0).into(),
+        center: Vec3::new(0.0, -100.5, 0.0),
+        radius: 100.0,
+        material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
     });
 
-    let mut camera = Camera::with_samples_per_pixel(100);
-    camera.bounce = 50;
-    camera.min_dist = 0.001;
-    camera.srgb_output = true;
-    camera.render(&world, "air_bubble.ppm")
+    Ok(Scene::new(camera, world))
+}
+
+#[cfg(feature = "scripting")]
+fn script(path: &std::path::Path, seed: u64) -> anyhow::Result<Scene> {
+    let source = std::fs::read_to_string(path)?;
+    let (world, camera) = rt_one::script::scene_from_script(&source, seed)?;
+
+    // Without a camera from the script, look at everything
+    let camera = match camera {
+        Some(camera) => camera,
+        None => {
+            let mut camera = Camera::builder()
+                .samples(10)
+                .bounces(50)
+                .min_dist(0.001)
+                .srgb(true)
+                .build()?;
+            camera.frame(&world, 1.1);
+            camera
+        }
+    };
+
+    Ok(Scene::new(camera, world))
 }
@@ -1,23 +1,50 @@
 use bevy_color::{Color, LinearRgba};
-use bevy_math::Dir3;
-use std::{fmt::Debug, ops::Deref, sync::Arc};
-
-use crate::{hittable::Hit, random::random_on_sphere, ray::Ray};
+use bevy_math::{Dir3, Vec3};
+use std::{
+    fmt::Debug,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use rand::RngCore;
+
+use crate::{
+    hittable::Hit,
+    pdf::{CosinePdf, Pdf, SpherePdf},
+    random::random_on_sphere,
+    ray::Ray,
+    texture::DynTexture,
+};
 
 #[derive(Debug, Clone)]
-pub struct DynMaterial(Arc<Box<dyn Material>>);
+pub struct DynMaterial(Arc<(Box<dyn Material>, usize)>);
+
+/// The id of the next material created.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
 
 impl Deref for DynMaterial {
     type Target = dyn Material;
 
     fn deref(&self) -> &Self::Target {
-        &**self.0
+        &*self.0 .0
     }
 }
 
 impl DynMaterial {
     pub fn new(material: impl Material + 'static) -> Self {
-        Self(Arc::new(Box::new(material)))
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        Self(Arc::new((Box::new(material), id)))
+    }
+
+    /// Identifies this material instance. Clones share the same id.
+    ///
+    /// Materials are numbered from one in the order they are created,
+    /// so a scene loaded the same way gets the same ids every time.
+    pub fn id(&self) -> usize {
+        self.0 .1
     }
 }
 
@@ -39,46 +66,126 @@ impl From<Dielectric> for DynMaterial {
     }
 }
 
-pub trait Material: Debug {
+impl From<DiffuseLight> for DynMaterial {
+    fn from(value: DiffuseLight) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Isotropic> for DynMaterial {
+    fn from(value: Isotropic) -> Self {
+        Self::new(value)
+    }
+}
+
+pub trait Material: Debug + Send + Sync {
     /// Given a ray and a [`Hit`] by that ray,
-    /// scatter by the material properties
-    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<Scattering>;
+    /// scatter by the material properties.
+    /// Any random choices are drawn from `rng`, the generator of the sample being traced.
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scattering>;
+
+    /// How much of the light arriving along `scattered` leaves along `ray` reversed,
+    /// as a density over directions. Only needed for materials which report a
+    /// [`Scattering::pdf`], see [`crate::pdf`].
+    fn scattering_pdf(&self, _ray: &Ray, _hit: &Hit, _scattered: &Ray) -> f32 {
+        0.0
+    }
+
+    /// Light given off by the surface at texture coordinates `u`, `v` and `point` in world space,
+    /// on top of any it scatters. Most materials give off none.
+    fn emitted(&self, _u: f32, _v: f32, _point: Vec3) -> Color {
+        Color::BLACK
+    }
+
+    /// Describe any parameters which are out of range, see [`crate::scene::Scene::validate`].
+    fn validate(&self, _problems: &mut Vec<String>) {}
+
+    /// The base color of the surface where it was hit, without any lighting.
+    /// Used for the albedo AOV.
+    fn albedo(&self, _hit: &Hit) -> Color {
+        Color::WHITE
+    }
+
+    /// The name of the implementing type.
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// The kind of scattering event, used to budget path depth separately per kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lobe {
+    Diffuse,
+    Specular,
+    Transmission,
+    /// Inside a participating medium, e.g. smoke. Counts as diffuse towards the depth limits.
+    Volume,
 }
 
 pub struct Scattering {
     /// The ray in the scatter direction
     pub ray: Ray,
     pub attenuation: Color,
+    pub lobe: Lobe,
+
+    /// The density the direction of `ray` was picked with, for importance sampled materials.
+    /// The light along it is then weighted by [`Material::scattering_pdf`] over this.
+    /// `None` for directions which aren't picked at random, e.g. mirror reflections,
+    /// which take `attenuation` as is.
+    pub pdf: Option<f32>,
+}
+
+/// The color of `texture` where it was hit.
+fn texture_at(texture: &DynTexture, hit: &Hit) -> Color {
+    texture.value(hit.uv.x, hit.uv.y, hit.point)
 }
 
 #[derive(Debug)]
 pub struct Lambertian {
-    pub color: Color,
+    pub texture: DynTexture,
 }
 
 impl Lambertian {
-    pub fn linear_rgb(red: f32, green: f32, blue: f32) -> Self {
+    /// From a texture, or a [`Color`] for the same color everywhere.
+    pub fn new(texture: impl Into<DynTexture>) -> Self {
         Self {
-            color: LinearRgba::rgb(red, green, blue).into(),
+            texture: texture.into(),
         }
     }
+
+    pub fn linear_rgb(red: f32, green: f32, blue: f32) -> Self {
+        Self::new(LinearRgba::rgb(red, green, blue))
+    }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit: &Hit) -> Option<Scattering> {
-        let scatter_dir = hit.normal.as_vec3() + random_on_sphere().as_vec3();
+    fn albedo(&self, hit: &Hit) -> Color {
+        texture_at(&self.texture, hit)
+    }
 
-        let scattered = Ray::new(hit.point, scatter_dir);
+    fn scatter(&self, _ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scattering> {
+        let pdf = CosinePdf::new(hit.normal);
+        let scatter_dir = pdf.generate(rng);
 
         Some(Scattering {
-            ray: scattered,
-            attenuation: self.color,
+            ray: Ray::new(hit.point, scatter_dir.as_vec3()),
+            attenuation: texture_at(&self.texture, hit),
+            lobe: Lobe::Diffuse,
+            pdf: Some(pdf.value(scatter_dir)),
         })
     }
+
+    fn scattering_pdf(&self, _ray: &Ray, hit: &Hit, scattered: &Ray) -> f32 {
+        CosinePdf::new(hit.normal).value(scattered.direction())
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        self.texture.validate(problems);
+    }
 }
 
 // todo: glam 0.29 has a builtin reflect and refract
-trait Glam029 {
+pub(crate) trait Glam029 {
     fn reflect(&self, normal: Dir3) -> Dir3;
 
     // Eta is n1/n2 where n1 is the refractive index we're coming from,
@@ -109,44 +216,54 @@ impl Glam029 for Dir3 {
 
 #[derive(Debug)]
 pub struct Metal {
-    pub color: Color,
+    pub texture: DynTexture,
     pub fuzz: f32,
 }
 
 impl Metal {
-    /// Create a metallic material with a given fuzz factor.
+    /// Create a metallic material with a given fuzz factor, from a texture or a [`Color`].
     /// The fuzz factor is clamped to the [0.0, 1.0] range.
-    pub fn new(color: Color, fuzz: f32) -> Self {
+    pub fn new(texture: impl Into<DynTexture>, fuzz: f32) -> Self {
         Self {
-            color,
+            texture: texture.into(),
             fuzz: fuzz.clamp(0.0, 1.0),
         }
     }
 
     pub fn linear_rgb(red: f32, green: f32, blue: f32) -> Self {
-        Self {
-            color: LinearRgba::rgb(red, green, blue).into(),
-            fuzz: 0.0,
-        }
+        Self::new(LinearRgba::rgb(red, green, blue), 0.0)
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<Scattering> {
+    fn albedo(&self, hit: &Hit) -> Color {
+        texture_at(&self.texture, hit)
+    }
+
+    fn scatter(&self, ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scattering> {
         let scatter_dir = ray.direction().reflect(hit.normal);
-        let fuzzed_dir = scatter_dir.as_vec3().normalize() + self.fuzz * random_on_sphere();
+        let fuzzed_dir = scatter_dir.as_vec3().normalize() + self.fuzz * random_on_sphere(rng);
 
         if hit.normal.dot(fuzzed_dir).is_sign_positive() {
             let scattered = Ray::new(hit.point, fuzzed_dir);
 
             Some(Scattering {
                 ray: scattered,
-                attenuation: self.color,
+                attenuation: texture_at(&self.texture, hit),
+                lobe: Lobe::Specular,
+                pdf: None,
             })
         } else {
             None
         }
     }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(0.0..=1.0).contains(&self.fuzz) {
+            problems.push(format!("metal: fuzz {} is outside [0, 1]", self.fuzz));
+        }
+        self.texture.validate(problems);
+    }
 }
 
 #[derive(Debug)]
@@ -172,7 +289,11 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<Scattering> {
+    fn albedo(&self, _hit: &Hit) -> Color {
+        self.color
+    }
+
+    fn scatter(&self, ray: &Ray, hit: &Hit, _rng: &mut dyn RngCore) -> Option<Scattering> {
         let n1 = 1.0; // air, ish
         let n2 = self.refractive_index;
 
@@ -194,12 +315,140 @@ impl Material for Dielectric {
             Some(Scattering {
                 ray: Ray::new(hit.point, *ray.direction().reflect(hit.normal)),
                 attenuation: self.color,
+                lobe: Lobe::Specular,
+                pdf: None,
             })
         } else {
             Some(Scattering {
                 ray: Ray::new(hit.point, *ray.direction().refract(hit.normal, eta)),
                 attenuation: self.color,
+                lobe: Lobe::Transmission,
+                pdf: None,
             })
         }
     }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if self.refractive_index <= 0.0 || self.refractive_index.is_nan() {
+            problems.push(format!(
+                "dielectric: refractive index {} is not positive",
+                self.refractive_index
+            ));
+        }
+    }
+}
+
+/// A surface which glows, e.g. a lamp or a window. It scatters no light.
+///
+/// Colors brighter than one light up the scene more, e.g. `Color::linear_rgb(4.0, 4.0, 4.0)`.
+#[derive(Debug)]
+pub struct DiffuseLight {
+    pub texture: DynTexture,
+}
+
+impl DiffuseLight {
+    /// From a texture, or a [`Color`] for the same color everywhere.
+    pub fn new(texture: impl Into<DynTexture>) -> Self {
+        Self {
+            texture: texture.into(),
+        }
+    }
+
+    pub fn linear_rgb(red: f32, green: f32, blue: f32) -> Self {
+        Self::new(LinearRgba::rgb(red, green, blue))
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &Hit, _rng: &mut dyn RngCore) -> Option<Scattering> {
+        None
+    }
+
+    fn emitted(&self, u: f32, v: f32, point: Vec3) -> Color {
+        self.texture.value(u, v, point)
+    }
+
+    fn albedo(&self, hit: &Hit) -> Color {
+        texture_at(&self.texture, hit)
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        self.texture.validate(problems);
+    }
+}
+
+/// Scatters equally in every direction, for the particles of a
+/// [`crate::medium::ConstantMedium`] such as smoke or fog.
+#[derive(Debug)]
+pub struct Isotropic {
+    pub texture: DynTexture,
+}
+
+impl Isotropic {
+    /// From a texture, or a [`Color`] for the same color everywhere.
+    pub fn new(texture: impl Into<DynTexture>) -> Self {
+        Self {
+            texture: texture.into(),
+        }
+    }
+
+    pub fn linear_rgb(red: f32, green: f32, blue: f32) -> Self {
+        Self::new(LinearRgba::rgb(red, green, blue))
+    }
+}
+
+impl Material for Isotropic {
+    fn albedo(&self, hit: &Hit) -> Color {
+        texture_at(&self.texture, hit)
+    }
+
+    fn scatter(&self, _ray: &Ray, hit: &Hit, rng: &mut dyn RngCore) -> Option<Scattering> {
+        let direction = SpherePdf.generate(rng);
+
+        Some(Scattering {
+            ray: Ray::new(hit.point, direction.as_vec3()),
+            attenuation: texture_at(&self.texture, hit),
+            lobe: Lobe::Volume,
+            pdf: Some(SpherePdf.value(direction)),
+        })
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, _hit: &Hit, scattered: &Ray) -> f32 {
+        SpherePdf.value(scattered.direction())
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        self.texture.validate(problems);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::Vec2;
+
+    use super::*;
+    use crate::random::sample_rng;
+
+    #[test]
+    fn scattering_follows_the_rng() {
+        let material = Metal::new(Color::WHITE, 0.5);
+        let hit = Hit {
+            point: Vec3::ZERO,
+            normal: Dir3::Y,
+            front_face: true,
+            distance: 1.0,
+            uv: Vec2::ZERO,
+            barycentric: None,
+            material: Lambertian::new(Color::WHITE).into(),
+            object_id: 0,
+        };
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let direction = |seed| {
+            let scattering = material.scatter(&ray, &hit, &mut sample_rng(seed, 0, 0));
+            scattering.unwrap().ray.direction()
+        };
+
+        assert_eq!(direction(1), direction(1));
+        assert_ne!(direction(1), direction(2));
+    }
 }
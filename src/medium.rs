@@ -0,0 +1,147 @@
+//! Participating media, e.g. smoke or fog, which scatter light throughout their volume
+//! instead of at a surface.
+
+use std::ops::Range;
+
+use bevy_math::{Dir3, Vec2};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{Hit, Hittable},
+    material::{DynMaterial, Isotropic},
+    random::random_f32,
+    ray::Ray,
+    stats::SceneStats,
+    texture::DynTexture,
+};
+
+/// A volume of the same density throughout, filling a closed `boundary` object.
+///
+/// Rays pass through it and get scattered at random depths, more likely the denser it is,
+/// so thin media look hazy and dense ones look like solid smoke.
+/// The boundary must be convex, since only the first stretch inside it is filled.
+#[derive(Debug)]
+pub struct ConstantMedium<H> {
+    pub boundary: H,
+    /// Chance of scattering per unit of distance travelled inside.
+    pub density: f32,
+    /// How light scatters inside, usually [`Isotropic`].
+    pub phase: DynMaterial,
+}
+
+impl<H: Hittable> ConstantMedium<H> {
+    /// Isotropic smoke of a texture, or a [`bevy_color::Color`] for the same color everywhere.
+    pub fn new(boundary: H, density: f32, texture: impl Into<DynTexture>) -> Self {
+        Self {
+            boundary,
+            density,
+            phase: Isotropic::new(texture).into(),
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for ConstantMedium<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        // Where the ray's line enters and leaves the boundary, which may be behind the ray
+        let enter = self.boundary.hit(ray, f32::NEG_INFINITY..f32::INFINITY)?;
+        let exit = self
+            .boundary
+            .hit(ray, enter.distance + 0.0001..f32::INFINITY)?;
+
+        let enter = enter.distance.max(t_range.start).max(0.0);
+        let exit = exit.distance.min(t_range.end);
+        if enter >= exit {
+            return None;
+        }
+
+        // Rays have unit directions, so distances along them are distances in space
+        let scatter_distance = -(1.0 - random_f32()).ln() / self.density;
+        if scatter_distance > exit - enter {
+            return None;
+        }
+        let distance = enter + scatter_distance;
+
+        Some(Hit {
+            point: ray.at(distance),
+            // Isotropic scattering doesn't look at the normal, so any will do
+            normal: Dir3::X,
+            front_face: true,
+            distance,
+            uv: Vec2::ZERO,
+            barycentric: None,
+            material: self.phase.clone(),
+            object_id: 0,
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(self.density > 0.0 && self.density.is_finite()) {
+            problems.push(format!(
+                "constant medium: density {} is not positive",
+                self.density
+            ));
+        }
+        self.boundary.validate(problems);
+        self.phase.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        self.boundary.stats(stats);
+        stats.add_material(&self.phase);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::Color;
+    use bevy_math::Vec3;
+
+    use super::*;
+    use crate::{material::Lambertian, objects::Cuboid};
+
+    #[test]
+    fn denser_scatters_sooner() {
+        let fog = |density| {
+            ConstantMedium::new(
+                Cuboid::new(Vec3::splat(-1.0), Vec3::ONE, Lambertian::new(Color::WHITE)),
+                density,
+                Color::WHITE,
+            )
+        };
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::NEG_Z);
+        crate::random::seed_sample(7, 0, 0);
+
+        // Mean free path 1/density, so over 2 units a density of 0.5 lets through e^-1 of rays
+        let thin = fog(0.5);
+        let hits: Vec<Hit> = (0..10_000)
+            .filter_map(|_| thin.hit(&ray, 0.0..f32::MAX))
+            .collect();
+        let scattered = hits.len() as f32 / 10_000.0;
+        assert!(
+            (scattered - (1.0 - (-1.0f32).exp())).abs() < 0.02,
+            "{scattered}"
+        );
+        assert!(hits
+            .iter()
+            .all(|hit| (4.0..=6.0).contains(&hit.distance) && hit.point.z.abs() <= 1.0));
+
+        let dense = fog(1000.0);
+        let hit = dense.hit(&ray, 0.0..f32::MAX).unwrap();
+        assert!((hit.distance - 4.0).abs() < 0.05, "{}", hit.distance);
+
+        // Starting inside, scattering starts right away
+        let inside = Ray::new(Vec3::ZERO, Vec3::NEG_Z);
+        assert!(dense.hit(&inside, 0.0..f32::MAX).unwrap().distance < 0.05);
+
+        // Nothing past the end of the range
+        assert!(dense.hit(&ray, 0.0..3.9).is_none());
+
+        let mut problems = vec![];
+        fog(0.0).validate(&mut problems);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+    }
+}
@@ -0,0 +1,225 @@
+//! Triangle meshes which share vertices between triangles, e.g. models loaded from files.
+
+use bevy_math::{Affine3A, Mat3, Vec2, Vec3};
+
+use crate::{
+    aabb::Aabb,
+    bvh::{BvhSplit, BvhTree},
+    hittable::{Hit, Hittable},
+    material::DynMaterial,
+    objects::hit_triangle,
+    ray::Ray,
+    stats::SceneStats,
+};
+
+/// Vertex buffers and triangles indexing into them, all of one material.
+///
+/// Much smaller than a [`crate::objects::Triangle`] per face,
+/// since each vertex is stored once however many triangles share it.
+///
+/// Has its own BVH over the triangles, so the scene's accelerator only sees one object
+/// and large models are still quick to hit. Call [`Mesh::rebuild_bvh`] after
+/// changing `positions` or `triangles` directly.
+#[derive(Debug)]
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    /// Empty, or one per position for smooth shading.
+    pub normals: Vec<Vec3>,
+    /// Empty, or texture coordinates for each position.
+    pub uvs: Vec<Vec2>,
+    /// Indices of three positions each, counter-clockwise seen from the front.
+    pub triangles: Vec<[u32; 3]>,
+    pub material: DynMaterial,
+    tree: BvhTree,
+}
+
+impl Mesh {
+    pub fn new(
+        positions: Vec<Vec3>,
+        triangles: Vec<[u32; 3]>,
+        material: impl Into<DynMaterial>,
+    ) -> Self {
+        let mut mesh = Self {
+            positions,
+            normals: vec![],
+            uvs: vec![],
+            triangles,
+            material: material.into(),
+            tree: BvhTree::new(&[], BvhSplit::Sah),
+        };
+        mesh.rebuild_bvh();
+        mesh
+    }
+
+    /// Build the BVH over the triangles again, after they moved or changed.
+    pub fn rebuild_bvh(&mut self) {
+        let bounds: Vec<Aabb> = (0..self.triangles.len())
+            .map(|triangle| Aabb::from_points(self.vertices(triangle)))
+            .collect();
+        self.tree = BvhTree::new(&bounds, BvhSplit::Sah);
+    }
+
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Self {
+        self.normals = normals;
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<Vec2>) -> Self {
+        self.uvs = uvs;
+        self
+    }
+
+    /// Move, turn or scale the mesh in place.
+    pub fn transform(&mut self, transform: Affine3A) {
+        for position in &mut self.positions {
+            *position = transform.transform_point3(*position);
+        }
+
+        // Normals stay perpendicular to the surface under non-uniform scaling
+        let normal_matrix = Mat3::from(transform.matrix3).inverse().transpose();
+        for normal in &mut self.normals {
+            *normal = (normal_matrix * *normal).normalize_or_zero();
+        }
+
+        self.rebuild_bvh();
+    }
+
+    /// Corners of a triangle, by its index in `triangles`.
+    pub fn vertices(&self, triangle: usize) -> [Vec3; 3] {
+        self.triangles[triangle].map(|index| self.positions[index as usize])
+    }
+
+    fn hit_triangle(
+        &self,
+        triangle: usize,
+        ray: &Ray,
+        t_range: std::ops::Range<f32>,
+    ) -> Option<Hit> {
+        let indices = self.triangles[triangle].map(|index| index as usize);
+        let normals = (!self.normals.is_empty()).then(|| indices.map(|index| self.normals[index]));
+        let uvs = (!self.uvs.is_empty()).then(|| indices.map(|index| self.uvs[index]));
+
+        hit_triangle(
+            self.vertices(triangle),
+            normals,
+            uvs,
+            &self.material,
+            ray,
+            t_range,
+        )
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, t_range: std::ops::Range<f32>) -> Option<Hit> {
+        self.tree.hit(ray, t_range, |triangle, range| {
+            self.hit_triangle(triangle, ray, range)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tree.bounds()
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !self.positions.iter().all(|position| position.is_finite()) {
+            problems.push("mesh: some positions are not finite".to_string());
+        }
+        for (name, len) in [("normals", self.normals.len()), ("uvs", self.uvs.len())] {
+            if len != 0 && len != self.positions.len() {
+                problems.push(format!(
+                    "mesh: {len} {name} for {} positions",
+                    self.positions.len()
+                ));
+            }
+        }
+        if let Some(index) = self
+            .triangles
+            .iter()
+            .flatten()
+            .find(|&&index| index as usize >= self.positions.len())
+        {
+            problems.push(format!(
+                "mesh: index {index} is past the {} positions",
+                self.positions.len()
+            ));
+        }
+        self.material.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        let buffers = self.positions.capacity() * std::mem::size_of::<Vec3>()
+            + self.normals.capacity() * std::mem::size_of::<Vec3>()
+            + self.uvs.capacity() * std::mem::size_of::<Vec2>()
+            + self.triangles.capacity() * std::mem::size_of::<[u32; 3]>()
+            + self.tree.memory_bytes();
+
+        stats.add_object(
+            std::any::type_name::<Self>(),
+            std::mem::size_of_val(self) + buffers,
+        );
+        stats.add_triangles(self.triangles.len());
+        stats.add_material(&self.material);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::Color;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::material::Lambertian;
+
+    /// A bumpy sheet of `size` by `size` quads.
+    fn terrain(size: u32) -> Mesh {
+        let positions = (0..=size)
+            .flat_map(|z| (0..=size).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                let (x, z) = (x as f32, z as f32);
+                Vec3::new(x, (x * 0.7).sin() + (z * 1.3).cos(), z)
+            })
+            .collect();
+        let corner = |x: u32, z: u32| z * (size + 1) + x;
+        let triangles = (0..size)
+            .flat_map(|z| (0..size).map(move |x| (x, z)))
+            .flat_map(|(x, z)| {
+                [
+                    [corner(x, z), corner(x, z + 1), corner(x + 1, z + 1)],
+                    [corner(x, z), corner(x + 1, z + 1), corner(x + 1, z)],
+                ]
+            })
+            .collect();
+
+        Mesh::new(positions, triangles, Lambertian::new(Color::WHITE))
+    }
+
+    #[test]
+    fn bvh_finds_the_closest_triangle() {
+        let mut mesh = terrain(20);
+        let mut rng = StdRng::seed_from_u64(4);
+
+        let mut check = |mesh: &Mesh| {
+            for _ in 0..500 {
+                let origin = Vec3::new(rng.gen_range(-5.0..25.0), 5.0, rng.gen_range(-5.0..25.0));
+                let target = Vec3::new(rng.gen_range(0.0..20.0), 0.0, rng.gen_range(0.0..20.0));
+                let ray = Ray::new(origin, target - origin);
+
+                let brute_force = (0..mesh.triangles.len())
+                    .filter_map(|triangle| mesh.hit_triangle(triangle, &ray, 0.0..f32::MAX))
+                    .map(|hit| hit.distance)
+                    .min_by(f32::total_cmp);
+                let hit = mesh.hit(&ray, 0.0..f32::MAX).map(|hit| hit.distance);
+                assert_eq!(hit, brute_force);
+            }
+        };
+
+        check(&mesh);
+        assert_eq!(mesh.bounding_box().min.x, 0.0);
+
+        // Moved, the tree moves along
+        mesh.transform(Affine3A::from_translation(Vec3::new(3.0, 0.0, 0.0)));
+        check(&mesh);
+        assert_eq!(mesh.bounding_box().min.x, 3.0);
+    }
+}
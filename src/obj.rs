@@ -0,0 +1,219 @@
+//! Loading triangle meshes from Wavefront OBJ files, the plain text format most
+//! modelling tools can export.
+//!
+//! Positions, texture coordinates, normals and faces are read.
+//! Faces with more than three corners are split into triangles.
+//! Everything else, such as groups and materials, is ignored.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{bail, ensure, Context};
+use bevy_math::{Vec2, Vec3};
+use tracing::warn;
+
+use crate::{material::DynMaterial, mesh::Mesh};
+
+/// Load the OBJ at `path` as one mesh of the given material.
+pub fn load(path: impl AsRef<Path>, material: impl Into<DynMaterial>) -> anyhow::Result<Mesh> {
+    let path = path.as_ref();
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("reading OBJ {}", path.display()))?;
+
+    parse(&text, material).with_context(|| format!("parsing OBJ {}", path.display()))
+}
+
+/// Parse the contents of an OBJ file.
+pub fn parse(text: &str, material: impl Into<DynMaterial>) -> anyhow::Result<Mesh> {
+    let mut positions = vec![];
+    let mut uvs = vec![];
+    let mut normals = vec![];
+
+    // Corners use separate indices for each attribute, meshes share one for all.
+    // So each distinct combination becomes a vertex.
+    let mut vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut vertices = vec![];
+    let mut triangles = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let mut parse_line = || -> anyhow::Result<()> {
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("v") => positions.push(Vec3::from_array(floats(fields)?)),
+                Some("vt") => {
+                    // An optional third coordinate is for 3D textures
+                    let [u, v] = floats(fields.take(2))?;
+                    uvs.push(Vec2::new(u, v));
+                }
+                Some("vn") => normals.push(Vec3::from_array(floats(fields)?)),
+                Some("f") => {
+                    let mut corners = vec![];
+                    for corner in fields {
+                        let key = parse_corner(corner, positions.len(), uvs.len(), normals.len())?;
+                        let index = *vertex_indices.entry(key).or_insert_with(|| {
+                            vertices.push(key);
+                            (vertices.len() - 1) as u32
+                        });
+                        corners.push(index);
+                    }
+
+                    ensure!(
+                        corners.len() >= 3,
+                        "a face needs at least 3 corners, got {}",
+                        corners.len()
+                    );
+                    for pair in corners[1..].windows(2) {
+                        triangles.push([corners[0], pair[0], pair[1]]);
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        };
+        parse_line().with_context(|| format!("line {}: {line}", number + 1))?;
+    }
+
+    let mesh_positions = vertices
+        .iter()
+        .map(|&(position, _, _)| positions[position])
+        .collect();
+    let mut mesh = Mesh::new(mesh_positions, triangles, material);
+
+    let mesh_uvs: Option<Vec<Vec2>> = vertices
+        .iter()
+        .map(|&(_, uv, _)| uv.map(|uv| uvs[uv]))
+        .collect();
+    let mesh_normals: Option<Vec<Vec3>> = vertices
+        .iter()
+        .map(|&(_, _, normal)| normal.map(|normal| normals[normal].normalize_or_zero()))
+        .collect();
+
+    // Attributes only some corners have can't be interpolated across the rest
+    match mesh_uvs {
+        Some(uvs) => mesh = mesh.with_uvs(uvs),
+        None if vertices.iter().any(|(_, uv, _)| uv.is_some()) => {
+            warn!("some faces have no texture coordinates, ignoring them all")
+        }
+        None => {}
+    }
+    match mesh_normals {
+        Some(normals) => mesh = mesh.with_normals(normals),
+        None if vertices.iter().any(|(_, _, normal)| normal.is_some()) => {
+            warn!("some faces have no normals, shading all flat")
+        }
+        None => {}
+    }
+
+    Ok(mesh)
+}
+
+/// Exactly `N` numbers, ignoring any after them.
+fn floats<'a, const N: usize>(
+    mut fields: impl Iterator<Item = &'a str>,
+) -> anyhow::Result<[f32; N]> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        let field = fields.next().context("too few numbers")?;
+        *value = field
+            .parse()
+            .with_context(|| format!("{field} is not a number"))?;
+    }
+    Ok(values)
+}
+
+/// A face corner such as `3`, `3/1`, `3//2` or `3/1/2`, as 0-based indices of
+/// its position and optional texture coordinate and normal.
+fn parse_corner(
+    corner: &str,
+    positions: usize,
+    uvs: usize,
+    normals: usize,
+) -> anyhow::Result<(usize, Option<usize>, Option<usize>)> {
+    let mut parts = corner.split('/');
+    let position = parts.next().unwrap_or_default();
+    let uv = parts.next().filter(|part| !part.is_empty());
+    let normal = parts.next().filter(|part| !part.is_empty());
+
+    Ok((
+        index(position, positions)?,
+        uv.map(|uv| index(uv, uvs)).transpose()?,
+        normal.map(|normal| index(normal, normals)).transpose()?,
+    ))
+}
+
+/// OBJ indices count from 1, or back from the latest with negative numbers.
+fn index(field: &str, count: usize) -> anyhow::Result<usize> {
+    let index: i64 = field
+        .parse()
+        .with_context(|| format!("{field} is not an index"))?;
+
+    let resolved = match index {
+        1.. => index - 1,
+        ..=-1 => count as i64 + index,
+        0 => bail!("indices start at 1"),
+    };
+    ensure!(
+        (0..count as i64).contains(&resolved),
+        "index {index} is out of range, there are {count}"
+    );
+
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::Color;
+
+    use super::*;
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray};
+
+    /// A unit square facing +Z as one quad face, with a normal and texture coordinates.
+    const SQUARE: &str = "
+# corners
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+o square
+f 1/1/1 2/2/1 -2/-2/-1 -1/-1/-1
+";
+
+    #[test]
+    fn quads_become_triangles() -> anyhow::Result<()> {
+        let mesh = parse(SQUARE, Lambertian::new(Color::WHITE))?;
+        assert_eq!(mesh.triangles, [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.normals, [Vec3::Z; 4]);
+
+        let ray = Ray::new(Vec3::new(0.25, 0.75, 2.0), Vec3::NEG_Z);
+        let hit = mesh.hit(&ray, 0.0..f32::MAX).unwrap();
+        assert_eq!(hit.distance, 2.0);
+        assert!(
+            hit.uv.abs_diff_eq(Vec2::new(0.25, 0.75), 1e-5),
+            "{}",
+            hit.uv
+        );
+
+        let mut problems = vec![];
+        mesh.validate(&mut problems);
+        assert!(problems.is_empty(), "{problems:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let material = || Lambertian::new(Color::WHITE);
+
+        let error = parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n", material()).unwrap_err();
+        assert!(format!("{error:#}").contains("line 3"), "{error:#}");
+
+        assert!(parse("v 0 0 0\nv 1 0 0\nf 1 2\n", material()).is_err());
+        assert!(parse("v 0 zero 0\n", material()).is_err());
+        assert!(parse("v 0 0 0\nf 0 1 1\n", material()).is_err());
+    }
+}
@@ -1,12 +1,27 @@
-use bevy_color::Color;
-use bevy_math::{Dir3, NormedVectorSpace, Vec3};
+use bevy_math::{Dir3, NormedVectorSpace, Vec2, Vec3};
+use rand::{Rng, RngCore};
 use tracing::debug;
 
 use crate::{
-    hittable::{Hit, Hittable},
+    aabb::Aabb,
+    hittable::{Hit, Hittable, Hittables},
     material::{DynMaterial, Lambertian},
+    pdf::Onb,
+    ray::Ray,
+    stats::SceneStats,
 };
 
+/// Rays towards sampled points start looking for the object this far out.
+const SAMPLING_MIN_DIST: f32 = 0.001;
+
+/// Longitude around the Y axis from -X as `u`, and latitude from the bottom as `v`.
+fn sphere_uv(outward_normal: Dir3) -> Vec2 {
+    let theta = (-outward_normal.y).acos();
+    let phi = (-outward_normal.z).atan2(outward_normal.x) + std::f32::consts::PI;
+
+    Vec2::new(phi / std::f32::consts::TAU, theta / std::f32::consts::PI)
+}
+
 #[derive(Debug)]
 pub struct Sphere {
     pub center: Vec3,
@@ -19,11 +34,86 @@ impl Default for Sphere {
         Self {
             center: Vec3::new(0.0, 0.0, -1.0),
             radius: 0.5,
-            material: Lambertian {
-                color: Color::linear_rgb(0.2, 0.4, 0.6),
-            }
-            .into(),
+            material: Lambertian::linear_rgb(0.2, 0.4, 0.6).into(),
+        }
+    }
+}
+
+/// Where a ray hits the sphere at `center`, shared by [`Sphere`] and [`MovingSphere`].
+fn hit_sphere(
+    center: Vec3,
+    radius: f32,
+    material: &DynMaterial,
+    ray: &crate::ray::Ray,
+    t_range: std::ops::Range<f32>,
+) -> Option<Hit> {
+    // We got (-b +- sqrt(b^2 - 4ac)) / 2a.
+    // If we substitute b = -2h:
+    // 2h +- sqrt(4h^2 - 4ac) / 2a = (2h +- 2 * sqrt(h^2 - ac)) / 2a =
+    // = (h +- sqrt(h^2 - ac) / a
+    // So then the discriminant is h^2 - ac.
+    //
+    // So if b = -2h = -2 * ray_dir.dot(-ray_origin + sphere_center)
+    // then h = ray_dir.dot(-ray_origin + sphere_center)
+
+    let d = ray.direction();
+    let q = -ray.origin() + center;
+
+    let h = d.dot(q);
+
+    let b = -2. * d.dot(q);
+    let c = q.length_squared() - radius.powi(2);
+
+    let discriminant = h.norm_squared() - c;
+
+    if discriminant < 0.0 {
+        None
+    } else {
+        debug!("b: {b:.2}, discriminant: {discriminant:.2}");
+        let discr_sqrt = discriminant.sqrt();
+
+        let t1 = h - discr_sqrt;
+        let t2 = h + discr_sqrt;
+
+        let t = if t_range.contains(&t1) {
+            t1
+        } else if t_range.contains(&t2) {
+            t2
+        } else {
+            return None;
+        };
+
+        let at = ray.at(t);
+        let outward_normal = Dir3::new_unchecked((-center + at).normalize());
+        let front_face = !ray.facing_same_general_direction(outward_normal);
+        let normal = if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+
+        Some(Hit {
+            point: at,
+            normal,
+            front_face,
+            distance: t,
+            uv: sphere_uv(outward_normal),
+            barycentric: None,
+            material: material.clone(),
+            object_id: 0,
+        })
+    }
+}
+
+impl Sphere {
+    /// Cosine of the half angle of the cone from `origin` around the sphere.
+    /// From inside, the cone covers every direction.
+    fn cos_theta_max(&self, origin: Vec3) -> f32 {
+        let distance_squared = (self.center - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return -1.0;
         }
+        (1.0 - self.radius * self.radius / distance_squared).sqrt()
     }
 }
 
@@ -33,58 +123,1301 @@ impl Hittable for Sphere {
         ray: &crate::ray::Ray,
         t_range: std::ops::Range<f32>,
     ) -> Option<crate::hittable::Hit> {
-        // We got (-b +- sqrt(b^2 - 4ac)) / 2a.
-        // If we substitute b = -2h:
-        // 2h +- sqrt(4h^2 - 4ac) / 2a = (2h +- 2 * sqrt(h^2 - ac)) / 2a =
-        // = (h +- sqrt(h^2 - ac) / a
-        // So then the discriminant is h^2 - ac.
-        //
-        // So if b = -2h = -2 * ray_dir.dot(-ray_origin + sphere_center)
-        // then h = ray_dir.dot(-ray_origin + sphere_center)
+        hit_sphere(self.center, self.radius, &self.material, ray, t_range)
+    }
 
-        let d = ray.direction();
-        let q = -ray.origin() + self.center;
+    /// One over the solid angle of the cone from `origin` around the sphere.
+    fn pdf_value(&self, origin: Vec3, direction: Dir3) -> f32 {
+        let ray = Ray::new(origin, direction.as_vec3());
+        if self.hit(&ray, SAMPLING_MIN_DIST..f32::INFINITY).is_none() {
+            return 0.0;
+        }
 
-        let h = d.dot(q);
+        let solid_angle = std::f32::consts::TAU * (1.0 - self.cos_theta_max(origin));
 
-        let b = -2. * d.dot(q);
-        let c = q.length_squared() - self.radius.powi(2);
+        1.0 / solid_angle
+    }
 
-        let discriminant = h.norm_squared() - c;
+    /// Uniform within the cone from `origin` around the sphere.
+    /// From inside, that cone covers every direction.
+    fn random_direction(&self, origin: Vec3, rng: &mut dyn RngCore) -> Dir3 {
+        let to_center = self.center - origin;
+        let cos_theta_max = self.cos_theta_max(origin);
 
-        if discriminant < 0.0 {
-            None
-        } else {
-            debug!("b: {b:.2}, discriminant: {discriminant:.2}");
-            let discr_sqrt = discriminant.sqrt();
+        let r1: f32 = rng.gen();
+        let r2: f32 = rng.gen();
+        let z = 1.0 + r2 * (cos_theta_max - 1.0);
+        let phi = std::f32::consts::TAU * r1;
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z);
 
-            let t1 = h - discr_sqrt;
-            let t2 = h + discr_sqrt;
+        match Dir3::new(to_center) {
+            Ok(axis) => Dir3::new_unchecked(Onb::new(axis).transform(local).normalize()),
+            Err(_) => Dir3::new_unchecked(local.normalize()),
+        }
+    }
 
-            let t = if t_range.contains(&t1) {
-                t1
-            } else if t_range.contains(&t2) {
-                t2
-            } else {
-                return None;
-            };
-
-            let at = ray.at(t);
-            let outward_normal = Dir3::new_unchecked((-self.center + at).normalize());
-            let front_face = !ray.facing_same_general_direction(outward_normal);
-            let normal = if front_face {
-                outward_normal
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(
+            self.center - Vec3::splat(self.radius),
+            self.center + Vec3::splat(self.radius),
+        )
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !self.center.is_finite() {
+            problems.push(format!("sphere: center {} is not finite", self.center));
+        }
+        if !(self.radius > 0.0 && self.radius.is_finite()) {
+            problems.push(format!(
+                "sphere at {}: radius {} is not positive",
+                self.center, self.radius
+            ));
+        }
+        self.material.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_object(std::any::type_name::<Self>(), std::mem::size_of_val(self));
+        stats.add_material(&self.material);
+    }
+}
+
+/// A sphere moving in a straight line, blurred by the camera's shutter.
+///
+/// Its center is at `from` at time 0 and at `to` at time 1, and keeps going
+/// along the same line at other times.
+#[derive(Debug)]
+pub struct MovingSphere {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub radius: f32,
+    pub material: DynMaterial,
+}
+
+impl MovingSphere {
+    pub fn center(&self, time: f32) -> Vec3 {
+        self.from.lerp(self.to, time)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &crate::ray::Ray, t_range: std::ops::Range<f32>) -> Option<Hit> {
+        hit_sphere(
+            self.center(ray.time()),
+            self.radius,
+            &self.material,
+            ray,
+            t_range,
+        )
+    }
+
+    /// Covers the motion from time 0 to 1, which is the most a shutter should span.
+    fn bounding_box(&self) -> Aabb {
+        let radius = Vec3::splat(self.radius);
+        Aabb::new(self.from - radius, self.from + radius)
+            .union(&Aabb::new(self.to - radius, self.to + radius))
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !self.from.is_finite() || !self.to.is_finite() {
+            problems.push(format!(
+                "moving sphere: path {} to {} is not finite",
+                self.from, self.to
+            ));
+        }
+        if !(self.radius > 0.0 && self.radius.is_finite()) {
+            problems.push(format!(
+                "moving sphere at {}: radius {} is not positive",
+                self.from, self.radius
+            ));
+        }
+        self.material.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_object(std::any::type_name::<Self>(), std::mem::size_of_val(self));
+        stats.add_material(&self.material);
+    }
+}
+
+#[derive(Debug)]
+pub struct Triangle {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+    pub material: DynMaterial,
+
+    /// If set, normals at `a`, `b` and `c` blended across the face for smooth shading.
+    /// Else the face is flat.
+    pub normals: Option<[Dir3; 3]>,
+
+    /// If set, texture coordinates at `a`, `b` and `c`.
+    /// Else `u` goes towards `b` and `v` towards `c`.
+    pub uvs: Option<[Vec2; 3]>,
+}
+
+impl Triangle {
+    pub fn new(a: Vec3, b: Vec3, c: Vec3, material: impl Into<DynMaterial>) -> Self {
+        Self {
+            a,
+            b,
+            c,
+            material: material.into(),
+            normals: None,
+            uvs: None,
+        }
+    }
+
+    /// Shade smoothly with these normals at `a`, `b` and `c`.
+    /// Ignored if any of them has no direction.
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = match normals.map(Dir3::new) {
+            [Ok(a), Ok(b), Ok(c)] => Some([a, b, c]),
+            _ => None,
+        };
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [Vec2; 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+/// Watertight ray/triangle intersection, see
+/// "Watertight Ray/Triangle Intersection" by Woop, Benthin and Wald (2013).
+///
+/// Triangles sharing an edge or a vertex agree exactly on which side of it a ray passes,
+/// so rays can't slip through the cracks of a mesh.
+///
+/// Normals and texture coordinates at the vertices are interpolated if given.
+pub(crate) fn hit_triangle(
+    vertices: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[Vec2; 3]>,
+    material: &DynMaterial,
+    ray: &crate::ray::Ray,
+    t_range: std::ops::Range<f32>,
+) -> Option<Hit> {
+    let dir = ray.direction().as_vec3();
+    let origin = ray.origin();
+
+    // Permute axes such that the ray direction is largest along z,
+    // and swap x/y if needed to keep the winding of the triangle
+    let abs = dir.abs();
+    let kz = if abs.x > abs.y && abs.x > abs.z {
+        0
+    } else if abs.y > abs.z {
+        1
+    } else {
+        2
+    };
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if dir[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // Shear such that the ray direction becomes (0, 0, 1)
+    let sz = 1.0 / dir[kz];
+    let sx = dir[kx] * sz;
+    let sy = dir[ky] * sz;
+
+    let [a, b, c] = vertices.map(|vertex| vertex - origin);
+
+    let ax = a[kx] - sx * a[kz];
+    let ay = a[ky] - sy * a[kz];
+    let bx = b[kx] - sx * b[kz];
+    let by = b[ky] - sy * b[kz];
+    let cx = c[kx] - sx * c[kz];
+    let cy = c[ky] - sy * c[kz];
+
+    // Scaled barycentric coordinates
+    let mut u = cx * by - cy * bx;
+    let mut v = ax * cy - ay * cx;
+    let mut w = bx * ay - by * ax;
+
+    // Exactly on an edge in single precision: fall back to double precision
+    // so that both triangles sharing the edge come to the same conclusion
+    if u == 0.0 || v == 0.0 || w == 0.0 {
+        let (ax, ay, bx, by, cx, cy) = (
+            ax as f64, ay as f64, bx as f64, by as f64, cx as f64, cy as f64,
+        );
+        u = (cx * by - cy * bx) as f32;
+        v = (ax * cy - ay * cx) as f32;
+        w = (bx * ay - by * ax) as f32;
+    }
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let az = sz * a[kz];
+    let bz = sz * b[kz];
+    let cz = sz * c[kz];
+
+    let t = (u * az + v * bz + w * cz) / det;
+    if !t_range.contains(&t) {
+        return None;
+    }
+
+    let (u, v, w) = (u / det, v / det, w / det);
+    let [a, b, c] = vertices;
+    let point = u * a + v * b + w * c;
+
+    let face_normal = Dir3::new((b - a).cross(c - a)).ok()?;
+    let front_face = !ray.facing_same_general_direction(face_normal);
+
+    // Vertex normals on the other side of the face would shade it from behind
+    let outward_normal = normals
+        .and_then(|[na, nb, nc]| Dir3::new(u * na + v * nb + w * nc).ok())
+        .map_or(face_normal, |smooth| {
+            if smooth.dot(*face_normal) < 0.0 {
+                -smooth
             } else {
-                -outward_normal
-            };
-
-            Some(Hit {
-                point: at,
-                normal,
-                front_face,
-                distance: t,
-                material: self.material.clone(),
-            })
+                smooth
+            }
+        });
+    let normal = if front_face {
+        outward_normal
+    } else {
+        -outward_normal
+    };
+
+    let uv = match uvs {
+        Some([ua, ub, uc]) => u * ua + v * ub + w * uc,
+        // How far towards b and c
+        None => Vec2::new(v, w),
+    };
+
+    Some(Hit {
+        point,
+        normal,
+        front_face,
+        distance: t,
+        uv,
+        barycentric: Some(Vec3::new(u, v, w)),
+        material: material.clone(),
+        object_id: 0,
+    })
+}
+
+impl Hittable for Triangle {
+    fn hit(
+        &self,
+        ray: &crate::ray::Ray,
+        t_range: std::ops::Range<f32>,
+    ) -> Option<crate::hittable::Hit> {
+        hit_triangle(
+            [self.a, self.b, self.c],
+            self.normals.map(|normals| normals.map(Vec3::from)),
+            self.uvs,
+            &self.material,
+            ray,
+            t_range,
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points([self.a, self.b, self.c])
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(self.a.is_finite() && self.b.is_finite() && self.c.is_finite()) {
+            problems.push(format!(
+                "triangle: vertices {}, {}, {} are not finite",
+                self.a, self.b, self.c
+            ));
+        } else if (self.b - self.a).cross(self.c - self.a).length_squared() == 0.0 {
+            problems.push(format!(
+                "triangle: vertices {}, {}, {} have no area",
+                self.a, self.b, self.c
+            ));
+        }
+        self.material.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_object(std::any::type_name::<Self>(), std::mem::size_of_val(self));
+        stats.add_triangles(1);
+        stats.add_material(&self.material);
+    }
+}
+
+/// An axis-aligned box.
+#[derive(Debug)]
+pub struct Cuboid {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub material: DynMaterial,
+}
+
+impl Cuboid {
+    /// The box spanned by two opposite corners, in any order.
+    pub fn new(a: Vec3, b: Vec3, material: impl Into<DynMaterial>) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+            material: material.into(),
+        }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(
+        &self,
+        ray: &crate::ray::Ray,
+        t_range: std::ops::Range<f32>,
+    ) -> Option<crate::hittable::Hit> {
+        let origin = ray.origin();
+        let dir = ray.direction().as_vec3();
+
+        // Slab test: the ray is inside the box where it's within all three pairs of planes.
+        // Keep track of which axis decided the entry and exit so we know the normal.
+        let (mut t_enter, mut enter_axis) = (f32::NEG_INFINITY, 0);
+        let (mut t_exit, mut exit_axis) = (f32::INFINITY, 0);
+
+        for axis in 0..3 {
+            let inv = 1.0 / dir[axis];
+            let t0 = (self.min[axis] - origin[axis]) * inv;
+            let t1 = (self.max[axis] - origin[axis]) * inv;
+            let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            if near > t_enter {
+                (t_enter, enter_axis) = (near, axis);
+            }
+            if far < t_exit {
+                (t_exit, exit_axis) = (far, axis);
+            }
+        }
+
+        if t_exit < t_enter {
+            return None;
+        }
+
+        // Entering the box the normal opposes the ray, leaving it the normal goes along with it
+        let (t, axis, sign) = if t_range.contains(&t_enter) {
+            (t_enter, enter_axis, -dir[enter_axis].signum())
+        } else if t_range.contains(&t_exit) {
+            (t_exit, exit_axis, dir[exit_axis].signum())
+        } else {
+            return None;
+        };
+
+        let mut outward_normal = Vec3::ZERO;
+        outward_normal[axis] = sign;
+        let outward_normal = Dir3::new_unchecked(outward_normal);
+
+        let front_face = !ray.facing_same_general_direction(outward_normal);
+        let normal = if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+
+        let point = ray.at(t);
+        // Position across the face along the next two axes. Flat boxes have no extent to divide by
+        let size = (self.max - self.min).max(Vec3::splat(f32::MIN_POSITIVE));
+        let across = (point - self.min) / size;
+        let uv = Vec2::new(across[(axis + 1) % 3], across[(axis + 2) % 3]);
+
+        Some(Hit {
+            point,
+            normal,
+            front_face,
+            distance: t,
+            uv: uv.clamp(Vec2::ZERO, Vec2::ONE),
+            barycentric: None,
+            material: self.material.clone(),
+            object_id: 0,
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.min, self.max)
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(self.min.is_finite() && self.max.is_finite()) {
+            problems.push(format!(
+                "cuboid: corners {}, {} are not finite",
+                self.min, self.max
+            ));
+        } else if self.min.cmpge(self.max).any() {
+            problems.push(format!(
+                "cuboid: corners {}, {} have no volume",
+                self.min, self.max
+            ));
+        }
+        self.material.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_object(std::any::type_name::<Self>(), std::mem::size_of_val(self));
+        stats.add_material(&self.material);
+    }
+}
+
+/// A flat parallelogram with a corner at `corner` and sides `u` and `v` from there,
+/// e.g. a wall or an area light.
+///
+/// The front is the side `u` cross `v` points to, i.e. where the corners go counter-clockwise.
+#[derive(Debug)]
+pub struct Quad {
+    pub corner: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: DynMaterial,
+}
+
+impl Quad {
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3, material: impl Into<DynMaterial>) -> Self {
+        Self {
+            corner,
+            u,
+            v,
+            material: material.into(),
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(
+        &self,
+        ray: &crate::ray::Ray,
+        t_range: std::ops::Range<f32>,
+    ) -> Option<crate::hittable::Hit> {
+        let n = self.u.cross(self.v);
+        let outward_normal = Dir3::new(n).ok()?;
+
+        // Parallel to the plane
+        let denominator = outward_normal.dot(ray.direction().as_vec3());
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = outward_normal.dot(self.corner - ray.origin()) / denominator;
+        if !t_range.contains(&t) {
+            return None;
+        }
+
+        // Position in the plane in multiples of u and v
+        let point = ray.at(t);
+        let planar = point - self.corner;
+        let w = n / n.length_squared();
+        let alpha = w.dot(planar.cross(self.v));
+        let beta = w.dot(self.u.cross(planar));
+        if !((0.0..=1.0).contains(&alpha) && (0.0..=1.0).contains(&beta)) {
+            return None;
+        }
+
+        let front_face = !ray.facing_same_general_direction(outward_normal);
+        let normal = if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+
+        Some(Hit {
+            point,
+            normal,
+            front_face,
+            distance: t,
+            uv: Vec2::new(alpha, beta),
+            barycentric: None,
+            material: self.material.clone(),
+            object_id: 0,
+        })
+    }
+
+    /// Points are picked uniformly over the area, so directions towards the quad
+    /// are denser where it's far away or seen edge on.
+    fn pdf_value(&self, origin: Vec3, direction: Dir3) -> f32 {
+        let ray = Ray::new(origin, direction.as_vec3());
+        let Some(hit) = self.hit(&ray, SAMPLING_MIN_DIST..f32::INFINITY) else {
+            return 0.0;
+        };
+
+        let area = self.u.cross(self.v).length();
+        let cos = direction.dot(hit.normal.as_vec3()).abs();
+        if cos < 1e-8 {
+            return 0.0;
+        }
+
+        hit.distance * hit.distance / (cos * area)
+    }
+
+    fn random_direction(&self, origin: Vec3, rng: &mut dyn RngCore) -> Dir3 {
+        let point = self.corner + rng.gen::<f32>() * self.u + rng.gen::<f32>() * self.v;
+        Dir3::new(point - origin).unwrap_or(Dir3::X)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points([
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.v,
+            self.corner + self.u + self.v,
+        ])
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(self.corner.is_finite() && self.u.is_finite() && self.v.is_finite()) {
+            problems.push(format!(
+                "quad: corner {} and sides {}, {} are not finite",
+                self.corner, self.u, self.v
+            ));
+        } else if self.u.cross(self.v).length_squared() == 0.0 {
+            problems.push(format!("quad: sides {}, {} have no area", self.u, self.v));
+        }
+        self.material.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_object(std::any::type_name::<Self>(), std::mem::size_of_val(self));
+        stats.add_material(&self.material);
+    }
+}
+
+/// A flat disk facing along `normal`, or a ring if `inner_radius` is more than zero,
+/// e.g. a round area light.
+#[derive(Debug)]
+pub struct Disk {
+    pub center: Vec3,
+    pub normal: Dir3,
+    pub radius: f32,
+    /// Radius of the hole in the middle.
+    pub inner_radius: f32,
+    pub material: DynMaterial,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Dir3, radius: f32, material: impl Into<DynMaterial>) -> Self {
+        Self {
+            center,
+            normal,
+            radius,
+            inner_radius: 0.0,
+            material: material.into(),
+        }
+    }
+
+    /// Cut a hole of this radius out of the middle.
+    pub fn with_inner_radius(mut self, inner_radius: f32) -> Self {
+        self.inner_radius = inner_radius;
+        self
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(
+        &self,
+        ray: &crate::ray::Ray,
+        t_range: std::ops::Range<f32>,
+    ) -> Option<crate::hittable::Hit> {
+        let denominator = self.normal.dot(ray.direction().as_vec3());
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = self.normal.dot(self.center - ray.origin()) / denominator;
+        if !t_range.contains(&t) {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let offset = point - self.center;
+        let distance = offset.length();
+        if !(self.inner_radius..=self.radius).contains(&distance) {
+            return None;
+        }
+
+        let front_face = !ray.facing_same_general_direction(self.normal);
+        let normal = if front_face {
+            self.normal
+        } else {
+            -self.normal
+        };
+
+        // Angle around the normal as u, distance out from the hole as v
+        let (tangent, bitangent) = self.normal.any_orthonormal_pair();
+        let angle = offset.dot(bitangent).atan2(offset.dot(tangent));
+        let u = angle / std::f32::consts::TAU + 0.5;
+        let v = (distance - self.inner_radius) / (self.radius - self.inner_radius);
+
+        Some(Hit {
+            point,
+            normal,
+            front_face,
+            distance: t,
+            uv: Vec2::new(u, v),
+            barycentric: None,
+            material: self.material.clone(),
+            object_id: 0,
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = rim_extent(self.normal.as_vec3(), self.radius);
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(self.center.is_finite() && self.radius.is_finite()) {
+            problems.push(format!(
+                "disk: center {} or radius {} is not finite",
+                self.center, self.radius
+            ));
+        } else if !(0.0 <= self.inner_radius && self.inner_radius < self.radius) {
+            problems.push(format!(
+                "disk: radii {} to {} leave nothing",
+                self.inner_radius, self.radius
+            ));
+        }
+        self.material.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_object(std::any::type_name::<Self>(), std::mem::size_of_val(self));
+        stats.add_material(&self.material);
+    }
+}
+
+/// How far a circle with this normal and radius reaches from its center along each axis.
+fn rim_extent(normal: Vec3, radius: f32) -> Vec3 {
+    radius * (Vec3::ONE - normal * normal).max(Vec3::ZERO).powf(0.5)
+}
+
+/// Coordinates with the axis of a round shape as +Y and its base at the origin.
+struct AxisFrame {
+    base: Vec3,
+    x: Vec3,
+    y: Vec3,
+    z: Vec3,
+}
+
+impl AxisFrame {
+    fn new(base: Vec3, axis: Vec3) -> Option<Self> {
+        let y = Dir3::new(axis).ok()?;
+        let (x, z) = y.any_orthonormal_pair();
+        Some(Self {
+            base,
+            x,
+            y: y.as_vec3(),
+            z,
+        })
+    }
+
+    fn to_local(&self, vector: Vec3) -> Vec3 {
+        Vec3::new(vector.dot(self.x), vector.dot(self.y), vector.dot(self.z))
+    }
+
+    fn to_world(&self, vector: Vec3) -> Vec3 {
+        vector.x * self.x + vector.y * self.y + vector.z * self.z
+    }
+}
+
+/// The closest hit within `t_range` on a capped shape around the local +Y axis from `y = 0`
+/// to `height`, whose radius goes in a straight line from `bottom` to `top`.
+///
+/// Gives the distance, the outward normal and texture coordinates.
+/// The side has `u` around the axis and `v` up it, the caps `v` out from the middle.
+fn hit_around_axis(
+    frame: &AxisFrame,
+    height: f32,
+    bottom: f32,
+    top: f32,
+    ray: &crate::ray::Ray,
+    t_range: std::ops::Range<f32>,
+) -> Option<(f32, Vec3, Vec2)> {
+    let origin = frame.to_local(ray.origin() - frame.base);
+    let dir = frame.to_local(ray.direction().as_vec3());
+    let around = |point: Vec3| point.z.atan2(point.x) / std::f32::consts::TAU + 0.5;
+
+    let mut closest: Option<(f32, Vec3, Vec2)> = None;
+    let mut consider = |t: f32, normal: Vec3, uv: Vec2| {
+        if t_range.contains(&t) && closest.is_none_or(|(best, ..)| t < best) {
+            closest = Some((t, normal, uv));
+        }
+    };
+
+    // Side: x² + z² = (bottom + slope * y)², within the height
+    let slope = (top - bottom) / height;
+    let radius_at_origin = bottom + slope * origin.y;
+    let a = dir.x * dir.x + dir.z * dir.z - slope * slope * dir.y * dir.y;
+    let half_b = origin.x * dir.x + origin.z * dir.z - slope * radius_at_origin * dir.y;
+    let c = origin.x * origin.x + origin.z * origin.z - radius_at_origin * radius_at_origin;
+
+    let roots = if a.abs() > 1e-12 {
+        let discriminant = half_b * half_b - a * c;
+        if discriminant >= 0.0 {
+            let root = discriminant.sqrt();
+            [(-half_b - root) / a, (-half_b + root) / a]
+        } else {
+            [f32::NAN; 2]
+        }
+    } else if half_b != 0.0 {
+        // Along the side of a cone, which it crosses once
+        [-c / (2.0 * half_b), f32::NAN]
+    } else {
+        [f32::NAN; 2]
+    };
+
+    for t in roots {
+        let point = origin + t * dir;
+        if (0.0..=height).contains(&point.y) {
+            let radius = bottom + slope * point.y;
+            let normal = Vec3::new(point.x, -slope * radius, point.z);
+            consider(t, normal, Vec2::new(around(point), point.y / height));
+        }
+    }
+
+    // Caps, where they have any size
+    for (y, radius, normal) in [(0.0, bottom, Vec3::NEG_Y), (height, top, Vec3::Y)] {
+        if radius <= 0.0 || dir.y == 0.0 {
+            continue;
+        }
+        let t = (y - origin.y) / dir.y;
+        let point = origin + t * dir;
+        let distance = Vec2::new(point.x, point.z).length();
+        if distance <= radius {
+            consider(t, normal, Vec2::new(around(point), distance / radius));
+        }
+    }
+
+    let (t, normal, uv) = closest?;
+    Some((t, frame.to_world(normal), uv))
+}
+
+/// Fill in a hit on a round shape from [`hit_around_axis`].
+fn around_axis_hit(
+    ray: &crate::ray::Ray,
+    (t, outward_normal, uv): (f32, Vec3, Vec2),
+    material: &DynMaterial,
+) -> Option<Hit> {
+    let outward_normal = Dir3::new(outward_normal).ok()?;
+    let front_face = !ray.facing_same_general_direction(outward_normal);
+    let normal = if front_face {
+        outward_normal
+    } else {
+        -outward_normal
+    };
+
+    Some(Hit {
+        point: ray.at(t),
+        normal,
+        front_face,
+        distance: t,
+        uv,
+        barycentric: None,
+        material: material.clone(),
+        object_id: 0,
+    })
+}
+
+/// A solid round cylinder with flat ends, from `base` to `base + axis`.
+#[derive(Debug)]
+pub struct Cylinder {
+    pub base: Vec3,
+    /// From the middle of the bottom to the middle of the top.
+    pub axis: Vec3,
+    pub radius: f32,
+    pub material: DynMaterial,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3, axis: Vec3, radius: f32, material: impl Into<DynMaterial>) -> Self {
+        Self {
+            base,
+            axis,
+            radius,
+            material: material.into(),
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(
+        &self,
+        ray: &crate::ray::Ray,
+        t_range: std::ops::Range<f32>,
+    ) -> Option<crate::hittable::Hit> {
+        let frame = AxisFrame::new(self.base, self.axis)?;
+        let height = self.axis.length();
+        let hit = hit_around_axis(&frame, height, self.radius, self.radius, ray, t_range)?;
+        around_axis_hit(ray, hit, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = rim_extent(self.axis.normalize_or_zero(), self.radius);
+        let top = self.base + self.axis;
+        Aabb::new(self.base - extent, self.base + extent)
+            .union(&Aabb::new(top - extent, top + extent))
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(self.base.is_finite() && self.axis.is_finite() && self.radius.is_finite()) {
+            problems.push(format!(
+                "cylinder: base {}, axis {} or radius {} is not finite",
+                self.base, self.axis, self.radius
+            ));
+        } else if self.axis.length_squared() == 0.0 || self.radius <= 0.0 {
+            problems.push(format!(
+                "cylinder: axis {} and radius {} have no volume",
+                self.axis, self.radius
+            ));
+        }
+        self.material.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_object(std::any::type_name::<Self>(), std::mem::size_of_val(self));
+        stats.add_material(&self.material);
+    }
+}
+
+/// A solid round cone standing on a flat base, with its tip at `base + axis`.
+#[derive(Debug)]
+pub struct Cone {
+    /// Middle of the flat end.
+    pub base: Vec3,
+    /// From the middle of the base to the tip.
+    pub axis: Vec3,
+    /// Of the base.
+    pub radius: f32,
+    pub material: DynMaterial,
+}
+
+impl Cone {
+    pub fn new(base: Vec3, axis: Vec3, radius: f32, material: impl Into<DynMaterial>) -> Self {
+        Self {
+            base,
+            axis,
+            radius,
+            material: material.into(),
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(
+        &self,
+        ray: &crate::ray::Ray,
+        t_range: std::ops::Range<f32>,
+    ) -> Option<crate::hittable::Hit> {
+        let frame = AxisFrame::new(self.base, self.axis)?;
+        let height = self.axis.length();
+        let hit = hit_around_axis(&frame, height, self.radius, 0.0, ray, t_range)?;
+        around_axis_hit(ray, hit, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = rim_extent(self.axis.normalize_or_zero(), self.radius);
+        Aabb::new(self.base - extent, self.base + extent)
+            .union(&Aabb::from_points([self.base + self.axis]))
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(self.base.is_finite() && self.axis.is_finite() && self.radius.is_finite()) {
+            problems.push(format!(
+                "cone: base {}, axis {} or radius {} is not finite",
+                self.base, self.axis, self.radius
+            ));
+        } else if self.axis.length_squared() == 0.0 || self.radius <= 0.0 {
+            problems.push(format!(
+                "cone: axis {} and radius {} have no volume",
+                self.axis, self.radius
+            ));
+        }
+        self.material.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        stats.add_object(std::any::type_name::<Self>(), std::mem::size_of_val(self));
+        stats.add_material(&self.material);
+    }
+}
+
+/// The box spanned by two opposite corners as six [`Quad`]s facing outwards.
+///
+/// Looks the same as a [`Cuboid`], but the faces are separate objects,
+/// e.g. for a room whose walls should be lit from the inside.
+pub fn quad_box(a: Vec3, b: Vec3, material: impl Into<DynMaterial>) -> Hittables {
+    let material = material.into();
+    let (min, max) = (a.min(b), a.max(b));
+    let size = max - min;
+    let (dx, dy, dz) = (size * Vec3::X, size * Vec3::Y, size * Vec3::Z);
+
+    let mut sides = Hittables::default();
+    for (corner, u, v) in [
+        (Vec3::new(min.x, min.y, max.z), dx, dy),  // front
+        (Vec3::new(max.x, min.y, max.z), -dz, dy), // right
+        (Vec3::new(max.x, min.y, min.z), -dx, dy), // back
+        (min, dz, dy),                             // left
+        (Vec3::new(min.x, max.y, max.z), dx, -dz), // top
+        (min, dx, dz),                             // bottom
+    ] {
+        sides.add(Quad::new(corner, u, v, material.clone()));
+    }
+
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::Color;
+
+    use super::*;
+    use crate::ray::Ray;
+
+    fn quad() -> [Triangle; 2] {
+        let material = Lambertian::new(Color::WHITE);
+        let material = DynMaterial::new(material);
+
+        let (a, b, c, d) = (
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(1.0, 1.0, -1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+        );
+
+        [
+            Triangle::new(a, b, c, material.clone()),
+            Triangle::new(a, c, d, material),
+        ]
+    }
+
+    #[test]
+    fn shared_edge_does_not_leak() {
+        let triangles = quad();
+
+        // Rays aimed exactly at the shared diagonal
+        for target in [
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.5, 0.5, -1.0),
+            Vec3::new(-0.3, -0.3, -1.0),
+            Vec3::new(0.9, 0.9, -1.0),
+            Vec3::new(-0.123, -0.123, -1.0),
+        ] {
+            for origin in [
+                Vec3::ZERO,
+                Vec3::new(0.1, -0.7, 0.3),
+                Vec3::new(-2.0, 3.0, 5.0),
+            ] {
+                let ray = Ray::new(origin, target - origin);
+                let hits = triangles
+                    .iter()
+                    .filter(|t| t.hit(&ray, 0.0..f32::MAX).is_some())
+                    .count();
+
+                assert!(hits > 0, "ray from {origin} towards {target} leaked");
+            }
+        }
+    }
+
+    #[test]
+    fn hit_from_behind_flips_normal() {
+        let [triangle, _] = quad();
+
+        let front = Ray::new(Vec3::new(0.5, -0.5, 0.0), Vec3::NEG_Z);
+        let hit = triangle.hit(&front, 0.0..f32::MAX).unwrap();
+        assert!(hit.front_face);
+        assert!((hit.distance - 1.0).abs() < 1e-6);
+
+        let back = Ray::new(Vec3::new(0.5, -0.5, -2.0), Vec3::Z);
+        let hit = triangle.hit(&back, 0.0..f32::MAX).unwrap();
+        assert!(!hit.front_face);
+        assert!(hit.normal.dot(Vec3::Z) < 0.0);
+    }
+
+    #[test]
+    fn texture_coordinates() {
+        let sphere = Sphere {
+            center: Vec3::ZERO,
+            radius: 2.0,
+            ..Default::default()
+        };
+        let uv_towards = |direction: Vec3| {
+            let ray = Ray::new(5.0 * direction, -direction);
+            sphere.hit(&ray, 0.0..f32::MAX).unwrap().uv
+        };
+
+        assert!(uv_towards(Vec3::Y).abs_diff_eq(Vec2::new(0.5, 1.0), 1e-5));
+        assert!(uv_towards(Vec3::NEG_X).abs_diff_eq(Vec2::new(0.0, 0.5), 1e-5));
+        assert!(uv_towards(Vec3::Z).abs_diff_eq(Vec2::new(0.25, 0.5), 1e-5));
+
+        let [triangle, _] = quad();
+        let ray = Ray::new(Vec3::new(0.5, -0.5, 0.0), Vec3::NEG_Z);
+        let uv = triangle.hit(&ray, 0.0..f32::MAX).unwrap().uv;
+        assert!(uv.abs_diff_eq(Vec2::new(0.5, 0.25), 1e-5), "{uv}");
+
+        let material = DynMaterial::new(Lambertian::new(Color::WHITE));
+        let cuboid = Cuboid::new(Vec3::ZERO, Vec3::new(2.0, 4.0, 1.0), material.clone());
+        let ray = Ray::new(Vec3::new(0.5, 1.0, 5.0), Vec3::NEG_Z);
+        let uv = cuboid.hit(&ray, 0.0..f32::MAX).unwrap().uv;
+        // On the +Z face, u goes along X and v along Y
+        assert!(uv.abs_diff_eq(Vec2::new(0.25, 0.25), 1e-5), "{uv}");
+
+        let flat = Cuboid::new(Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0), material);
+        let ray = Ray::new(Vec3::new(0.5, 0.5, 5.0), Vec3::NEG_Z);
+        let uv = flat.hit(&ray, 0.0..f32::MAX).unwrap().uv;
+        assert!(uv.is_finite(), "{uv}");
+    }
+
+    #[test]
+    fn quads_and_boxes() {
+        let material = DynMaterial::new(Lambertian::new(Color::WHITE));
+        let quad = Quad::new(Vec3::ZERO, 2.0 * Vec3::X, Vec3::Y, material.clone());
+
+        let hit = quad
+            .hit(
+                &Ray::new(Vec3::new(1.5, 0.25, 3.0), Vec3::NEG_Z),
+                0.0..f32::MAX,
+            )
+            .unwrap();
+        assert!(hit.front_face);
+        assert_eq!(hit.distance, 3.0);
+        assert!(
+            hit.uv.abs_diff_eq(Vec2::new(0.75, 0.25), 1e-5),
+            "{}",
+            hit.uv
+        );
+
+        // Beside it, and from behind
+        assert!(quad
+            .hit(
+                &Ray::new(Vec3::new(2.5, 0.5, 3.0), Vec3::NEG_Z),
+                0.0..f32::MAX
+            )
+            .is_none());
+        let back = quad
+            .hit(&Ray::new(Vec3::new(1.0, 0.5, -1.0), Vec3::Z), 0.0..f32::MAX)
+            .unwrap();
+        assert!(!back.front_face);
+        assert_eq!(*back.normal, Vec3::NEG_Z);
+
+        // Every face of the box points out, and it hits where the cuboid does
+        let (min, max) = (Vec3::new(-1.0, 0.0, 2.0), Vec3::new(3.0, 1.0, 4.0));
+        let sides = quad_box(max, min, material.clone());
+        let cuboid = Cuboid::new(min, max, material);
+        assert_eq!(sides.objects.len(), 6);
+
+        let center = (min + max) / 2.0;
+        for direction in [
+            Vec3::X,
+            Vec3::Y,
+            Vec3::Z,
+            Vec3::NEG_X,
+            Vec3::NEG_Y,
+            Vec3::NEG_Z,
+        ] {
+            let ray = Ray::new(center + 10.0 * direction, -direction);
+            let hit = sides.hit(&ray, 0.0..f32::MAX).unwrap();
+            assert!(hit.front_face, "{direction}");
+            assert_eq!(*hit.normal, direction);
+            assert_eq!(
+                hit.distance,
+                cuboid.hit(&ray, 0.0..f32::MAX).unwrap().distance
+            );
+        }
+    }
+
+    #[test]
+    fn disks_and_rings() {
+        let material = DynMaterial::new(Lambertian::new(Color::WHITE));
+        let disk = Disk::new(Vec3::new(0.0, 1.0, 0.0), Dir3::Y, 2.0, material);
+        let down_at = |x, z| Ray::new(Vec3::new(x, 5.0, z), Vec3::NEG_Y);
+
+        let hit = disk.hit(&down_at(0.0, 0.0), 0.0..f32::MAX).unwrap();
+        assert_eq!(hit.distance, 4.0);
+        assert!(hit.front_face);
+        assert_eq!(hit.uv.y, 0.0);
+
+        let rim = disk.hit(&down_at(1.99, 0.0), 0.0..f32::MAX).unwrap();
+        assert!(rim.uv.y > 0.99, "{}", rim.uv);
+        assert!(disk.hit(&down_at(1.5, 1.5), 0.0..f32::MAX).is_none());
+
+        let ring = disk.with_inner_radius(1.0);
+        assert!(ring.hit(&down_at(0.5, 0.0), 0.0..f32::MAX).is_none());
+        let hit = ring.hit(&down_at(0.0, 1.5), 0.0..f32::MAX).unwrap();
+        assert!((hit.uv.y - 0.5).abs() < 1e-5, "{}", hit.uv);
+
+        // Flat along Y, reaching out to the radius along X and Z
+        let bounds = ring.bounding_box();
+        assert!(bounds.size().abs_diff_eq(Vec3::new(4.0, 0.0, 4.0), 1e-5));
+    }
+
+    #[test]
+    fn cylinders_and_cones() {
+        let material = DynMaterial::new(Lambertian::new(Color::WHITE));
+        let cylinder = Cylinder::new(Vec3::ZERO, 2.0 * Vec3::Y, 1.0, material.clone());
+        let cone = Cone::new(Vec3::ZERO, 2.0 * Vec3::Y, 1.0, material);
+        let all = 0.0..f32::MAX;
+
+        // From the side at half height
+        let side = Ray::new(Vec3::new(5.0, 1.0, 0.0), Vec3::NEG_X);
+        let hit = cylinder.hit(&side, all.clone()).unwrap();
+        assert_eq!(hit.distance, 4.0);
+        assert!(hit.normal.abs_diff_eq(Vec3::X, 1e-5));
+        let hit = cone.hit(&side, all.clone()).unwrap();
+        assert!((hit.distance - 4.5).abs() < 1e-5);
+        // The slope leans the normal upwards
+        let expected = Vec3::new(2.0, 1.0, 0.0).normalize();
+        assert!(hit.normal.abs_diff_eq(expected, 1e-5), "{:?}", hit.normal);
+
+        // The caps, and the tip
+        let down = Ray::new(Vec3::new(0.5, 5.0, 0.0), Vec3::NEG_Y);
+        let hit = cylinder.hit(&down, all.clone()).unwrap();
+        assert_eq!((hit.distance, *hit.normal), (3.0, Vec3::Y));
+        let up = Ray::new(Vec3::new(0.5, -5.0, 0.0), Vec3::Y);
+        let hit = cone.hit(&up, all.clone()).unwrap();
+        assert_eq!((hit.distance, *hit.normal), (5.0, Vec3::NEG_Y));
+        assert!((cone.hit(&down, all.clone()).unwrap().distance - 4.0).abs() < 1e-5);
+
+        // Past the ends, and past the side of the cone above its base
+        assert!(cylinder
+            .hit(
+                &Ray::new(Vec3::new(5.0, 2.5, 0.0), Vec3::NEG_X),
+                all.clone()
+            )
+            .is_none());
+        assert!(cone
+            .hit(
+                &Ray::new(Vec3::new(5.0, 1.9, 0.6), Vec3::NEG_X),
+                all.clone()
+            )
+            .is_none());
+
+        // Inside, only the far wall is ahead
+        let inside = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::X);
+        let hit = cylinder.hit(&inside, 0.001..f32::MAX).unwrap();
+        assert_eq!(hit.distance, 1.0);
+        assert!(!hit.front_face);
+        assert!(cylinder.hit(&inside, 0.001..0.5).is_none());
+
+        // Tilted, the box still holds both ends
+        let tilted = Cylinder::new(
+            Vec3::ZERO,
+            Vec3::new(1.0, 1.0, 0.0),
+            0.5,
+            Lambertian::new(Color::WHITE),
+        );
+        let hit = tilted
+            .hit(&Ray::new(Vec3::new(0.5, 0.5, 5.0), Vec3::NEG_Z), all)
+            .unwrap();
+        assert!((hit.distance - 4.5).abs() < 1e-5);
+        let bounds = tilted.bounding_box();
+        assert!(bounds.size().abs_diff_eq(
+            Vec3::new(1.0 + 0.5f32.sqrt(), 1.0 + 0.5f32.sqrt(), 1.0),
+            1e-5
+        ));
+    }
+
+    #[test]
+    fn barycentric_interpolation() {
+        let [triangle, _] = quad();
+        let triangle = triangle
+            .with_normals([Vec3::NEG_X, Vec3::X, Vec3::Z])
+            .with_uvs([Vec2::ZERO, Vec2::X, Vec2::ONE]);
+
+        // Corners a, b, c are at (-1, -1), (1, -1), (1, 1) in the z = -1 plane
+        let hit = |x, y| {
+            let ray = Ray::new(Vec3::new(x, y, 0.0), Vec3::NEG_Z);
+            triangle.hit(&ray, 0.0..f32::MAX).unwrap()
+        };
+
+        let center = hit(1.0 / 3.0, -1.0 / 3.0);
+        let weights = center.barycentric.unwrap();
+        assert!(
+            weights.abs_diff_eq(Vec3::splat(1.0 / 3.0), 1e-5),
+            "{weights}"
+        );
+        assert!(
+            center.normal.abs_diff_eq(Vec3::Z, 1e-5),
+            "{:?}",
+            center.normal
+        );
+        assert!(center.uv.abs_diff_eq(Vec2::new(2.0, 1.0) / 3.0, 1e-5));
+
+        let near_b = hit(0.99, -0.99);
+        assert!(near_b.normal.x > 0.9, "{:?}", near_b.normal);
+        assert!(near_b.uv.abs_diff_eq(Vec2::X, 0.01));
+
+        // Seen from behind, the blended normal still faces the ray
+        let ray = Ray::new(Vec3::new(1.0 / 3.0, -1.0 / 3.0, -2.0), Vec3::Z);
+        let back = triangle.hit(&ray, 0.0..f32::MAX).unwrap();
+        assert!(!back.front_face);
+        assert!(
+            back.normal.abs_diff_eq(Vec3::NEG_Z, 1e-5),
+            "{:?}",
+            back.normal
+        );
+    }
+
+    #[test]
+    fn sphere_pdf_integrates_to_one() {
+        use rand::{rngs::SmallRng, SeedableRng};
+
+        let sphere = Sphere {
+            center: Vec3::ZERO,
+            radius: 1.0,
+            material: Lambertian::new(Color::WHITE).into(),
+        };
+        let mut rng = SmallRng::seed_from_u64(7);
+
+        for origin in [Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.3, -0.2, 0.1)] {
+            // Uniform directions over the whole sphere have a density of 1 / 4π
+            let count = 100_000;
+            let integral = (0..count)
+                .map(|_| sphere.pdf_value(origin, crate::random::random_on_sphere(&mut rng)))
+                .sum::<f32>()
+                * 2.0
+                * std::f32::consts::TAU
+                / count as f32;
+            assert!((integral - 1.0).abs() < 0.03, "{origin}: {integral}");
+
+            // Sampled directions all hit, with the density they're said to have
+            for _ in 0..100 {
+                let direction = sphere.random_direction(origin, &mut rng);
+                assert!(sphere.pdf_value(origin, direction) > 0.0, "{origin}");
+            }
+        }
+    }
+
+    #[test]
+    fn moving_spheres_follow_the_ray_time() {
+        let sphere = MovingSphere {
+            from: Vec3::ZERO,
+            to: Vec3::new(0.0, 2.0, 0.0),
+            radius: 0.5,
+            material: Lambertian::new(Color::WHITE).into(),
+        };
+        let ray = |time| Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::NEG_Z).with_time(time);
+
+        assert_eq!(sphere.hit(&ray(0.0), 0.0..f32::MAX).unwrap().distance, 4.5);
+        assert!(sphere.hit(&ray(1.0), 0.0..f32::MAX).is_none());
+        assert_eq!(sphere.center(0.5), Vec3::Y);
+
+        let bounds = sphere.bounding_box();
+        assert_eq!(bounds.min, Vec3::splat(-0.5));
+        assert_eq!(bounds.max, Vec3::new(0.5, 2.5, 0.5));
+
+        // Cameras send rays at times within their shutter
+        let camera = crate::camera::Camera::builder()
+            .resolution(4, 4)
+            .samples(16)
+            .shutter(0.25..0.5)
+            .build()
+            .unwrap();
+        for sample in 0..16 {
+            let time = camera.sample_ray(1, 1, sample).unwrap().time();
+            assert!((0.25..0.5).contains(&time), "{time}");
         }
     }
 }
@@ -0,0 +1,218 @@
+//! Denoising with [Intel Open Image Denoise](https://www.openimagedenoise.org/).
+//!
+//! The library is loaded when first needed rather than linked, so building doesn't need it installed.
+//! It is looked for by its usual names, or at the path in `RT_OIDN_LIBRARY`.
+
+use std::{
+    ffi::{c_char, c_void, CStr},
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+use bevy_color::LinearRgba;
+use libloading::Library;
+
+use crate::{
+    aov::{self, Aov},
+    camera::Camera,
+    hittable::Hittable,
+};
+
+/// Environment variable with the path of the library, if it's not found by name.
+pub const LIBRARY_VARIABLE: &str = "RT_OIDN_LIBRARY";
+
+const LIBRARY_NAMES: &[&str] = &[
+    "libOpenImageDenoise.so.2",
+    "libOpenImageDenoise.so",
+    "libOpenImageDenoise.2.dylib",
+    "libOpenImageDenoise.dylib",
+    "OpenImageDenoise.dll",
+];
+
+// From OpenImageDenoise/oidn.h
+type Handle = *mut c_void;
+const DEVICE_TYPE_CPU: i32 = 1;
+const FORMAT_FLOAT3: i32 = 3;
+const ERROR_NONE: i32 = 0;
+
+/// The functions used from the library, which must outlive them.
+pub struct Oidn {
+    new_device: unsafe extern "C" fn(i32) -> Handle,
+    commit_device: unsafe extern "C" fn(Handle),
+    get_device_error: unsafe extern "C" fn(Handle, *mut *const c_char) -> i32,
+    release_device: unsafe extern "C" fn(Handle),
+    new_filter: unsafe extern "C" fn(Handle, *const c_char) -> Handle,
+    #[allow(clippy::type_complexity)]
+    set_shared_filter_image: unsafe extern "C" fn(
+        Handle,
+        *const c_char,
+        *mut c_void,
+        i32,
+        usize,
+        usize,
+        usize,
+        usize,
+        usize,
+    ),
+    set_filter_bool: unsafe extern "C" fn(Handle, *const c_char, bool),
+    commit_filter: unsafe extern "C" fn(Handle),
+    execute_filter: unsafe extern "C" fn(Handle),
+    release_filter: unsafe extern "C" fn(Handle),
+    _library: Library,
+}
+
+impl Oidn {
+    /// Load the library from `RT_OIDN_LIBRARY`, or by its usual names.
+    pub fn load() -> anyhow::Result<Self> {
+        if let Some(path) = std::env::var_os(LIBRARY_VARIABLE) {
+            return Self::load_from(path);
+        }
+
+        LIBRARY_NAMES
+            .iter()
+            .find_map(|name| Self::load_from(name).ok())
+            .with_context(|| {
+                format!(
+                    "Open Image Denoise 2 was not found, install it or set {LIBRARY_VARIABLE} \
+                     to the path of the library"
+                )
+            })
+    }
+
+    /// Load the library from a path, or a name the system looks up.
+    pub fn load_from(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        // SAFETY: Runs the library's initializers, as linking against it would
+        let library = unsafe { Library::new(path) }
+            .with_context(|| format!("Could not load {}", path.display()))?;
+
+        // SAFETY: The signatures match oidn.h of OIDN 2
+        unsafe {
+            Ok(Self {
+                new_device: *library.get(b"oidnNewDevice\0")?,
+                commit_device: *library.get(b"oidnCommitDevice\0")?,
+                get_device_error: *library.get(b"oidnGetDeviceError\0")?,
+                release_device: *library.get(b"oidnReleaseDevice\0")?,
+                new_filter: *library.get(b"oidnNewFilter\0")?,
+                set_shared_filter_image: *library.get(b"oidnSetSharedFilterImage\0")?,
+                set_filter_bool: *library.get(b"oidnSetFilterBool\0")?,
+                commit_filter: *library.get(b"oidnCommitFilter\0")?,
+                execute_filter: *library.get(b"oidnExecuteFilter\0")?,
+                release_filter: *library.get(b"oidnReleaseFilter\0")?,
+                _library: library,
+            })
+        }
+    }
+
+    /// Denoise an HDR image of interleaved RGB floats in place, on the CPU.
+    /// Albedo and normals, also interleaved, tell the filter where edges and textures are.
+    pub fn denoise(
+        &self,
+        width: usize,
+        height: usize,
+        color: &mut [f32],
+        albedo: &[f32],
+        normal: &[f32],
+    ) -> anyhow::Result<()> {
+        let len = width * height * 3;
+        if color.len() != len || albedo.len() != len || normal.len() != len {
+            bail!("every image to denoise needs {len} floats, {width}x{height} RGB");
+        }
+
+        // SAFETY: The buffers are the size OIDN is told, and outlive the filter.
+        // The guides are only read, so passing them as mutable is fine.
+        unsafe {
+            let device = (self.new_device)(DEVICE_TYPE_CPU);
+            (self.commit_device)(device);
+            self.check(device)?;
+
+            let filter = (self.new_filter)(device, c"RT".as_ptr());
+            let images: [(&CStr, *mut f32); 4] = [
+                (c"color", color.as_mut_ptr()),
+                (c"albedo", albedo.as_ptr().cast_mut()),
+                (c"normal", normal.as_ptr().cast_mut()),
+                (c"output", color.as_mut_ptr()),
+            ];
+            for (name, data) in images {
+                (self.set_shared_filter_image)(
+                    filter,
+                    name.as_ptr(),
+                    data.cast(),
+                    FORMAT_FLOAT3,
+                    width,
+                    height,
+                    0,
+                    0,
+                    0,
+                );
+            }
+            (self.set_filter_bool)(filter, c"hdr".as_ptr(), true);
+            (self.commit_filter)(filter);
+            (self.execute_filter)(filter);
+
+            let result = self.check(device);
+            (self.release_filter)(filter);
+            (self.release_device)(device);
+            result
+        }
+    }
+
+    /// The device's last error, if any.
+    unsafe fn check(&self, device: Handle) -> anyhow::Result<()> {
+        let mut message = std::ptr::null();
+        let error = (self.get_device_error)(device, &mut message);
+        if error == ERROR_NONE {
+            return Ok(());
+        }
+
+        let message = if message.is_null() {
+            "no message".into()
+        } else {
+            CStr::from_ptr(message).to_string_lossy()
+        };
+        bail!("Open Image Denoise failed with error {error}: {message}")
+    }
+}
+
+/// Denoise a render of `world` in place, guided by albedo and normals rendered for it.
+/// Alpha is kept as it is.
+pub fn denoise(
+    camera: &Camera,
+    world: &dyn Hittable,
+    image: &mut [LinearRgba],
+) -> anyhow::Result<()> {
+    let oidn = Oidn::load()?;
+
+    let guides = aov::render_aovs(camera, world, &[Aov::Albedo, Aov::Normal]);
+    let mut color: Vec<f32> = image
+        .iter()
+        .flat_map(|color| [color.red, color.green, color.blue])
+        .collect();
+
+    oidn.denoise(
+        camera.im_width,
+        camera.im_height,
+        &mut color,
+        &guides[0].interleaved(),
+        &guides[1].interleaved(),
+    )?;
+
+    for (pixel, rgb) in image.iter_mut().zip(color.chunks_exact(3)) {
+        *pixel = LinearRgba::new(rgb[0], rgb[1], rgb[2], pixel.alpha);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_library_is_an_error() {
+        let error = Oidn::load_from("/nonexistent/libOpenImageDenoise.so")
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("/nonexistent"), "{error}");
+    }
+}
@@ -0,0 +1,480 @@
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::bail;
+use bevy_color::LinearRgba;
+
+use crate::{
+    aov::{AovImage, AovLayer},
+    hdr, ppm,
+};
+
+/// How to encode an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageFormat {
+    /// ASCII PPM (P3).
+    #[default]
+    Ppm,
+    /// Binary PPM (P6), much smaller and faster to write. Still uses the `.ppm` extension,
+    /// so it's only picked when asked for.
+    PpmBinary,
+    Png,
+    /// Linear floats, for grading in other tools without banding.
+    Exr,
+    /// Radiance RGBE, linear with 8-bit mantissas and a shared exponent.
+    /// Loads in most HDR viewers, e.g. to use a render as an environment map.
+    Hdr,
+}
+
+impl ImageFormat {
+    /// Pick the format from the file extension, falling back to PPM.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Whether images are written from linear floats with [`write_linear`]
+    /// instead of 8-bit data with [`write`].
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::Exr | Self::Hdr)
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ppm" => Ok(Self::Ppm),
+            "p6" => Ok(Self::PpmBinary),
+            "png" => Ok(Self::Png),
+            "exr" => Ok(Self::Exr),
+            "hdr" => Ok(Self::Hdr),
+            other => bail!("unknown image format {other:?}, expected ppm, p6, png, exr or hdr"),
+        }
+    }
+}
+
+/// Data is RGB 8-bit per channel.
+pub fn write(
+    format: ImageFormat,
+    rows: usize,
+    data: impl AsRef<[u8]>,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    match format {
+        ImageFormat::Ppm => ppm::write(rows, data, writer),
+        ImageFormat::PpmBinary => ppm::write_p6(rows, data, writer),
+        ImageFormat::Png => write_png(
+            rows,
+            data.as_ref(),
+            png::ColorType::Rgb,
+            png::BitDepth::Eight,
+            writer,
+        ),
+        ImageFormat::Exr | ImageFormat::Hdr => {
+            bail!("{format:?} images are written from linear floats, see `write_linear`")
+        }
+    }
+}
+
+/// Data is RGB 16-bit per channel, which only PNG and PPM hold.
+pub fn write16(
+    format: ImageFormat,
+    rows: usize,
+    data: &[u16],
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    match format {
+        ImageFormat::Ppm => ppm::write16(rows, data, writer),
+        ImageFormat::PpmBinary => ppm::write_p6_16(rows, data, writer),
+        ImageFormat::Png => write_png(
+            rows,
+            &big_endian(data),
+            png::ColorType::Rgb,
+            png::BitDepth::Sixteen,
+            writer,
+        ),
+        ImageFormat::Exr | ImageFormat::Hdr => {
+            bail!("{format:?} images are written from linear floats, see `write_linear`")
+        }
+    }
+}
+
+/// Quantized pixels, row-major from the top left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Quantized {
+    Rgb8(Vec<u8>),
+    Rgb16(Vec<u16>),
+    /// With straight alpha, i.e. not premultiplied.
+    Rgba8(Vec<u8>),
+    Rgba16(Vec<u16>),
+}
+
+impl Quantized {
+    /// Values per pixel.
+    pub fn channels(&self) -> usize {
+        match self {
+            Quantized::Rgb8(_) | Quantized::Rgb16(_) => 3,
+            Quantized::Rgba8(_) | Quantized::Rgba16(_) => 4,
+        }
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        let values = match self {
+            Quantized::Rgb8(data) | Quantized::Rgba8(data) => data.len(),
+            Quantized::Rgb16(data) | Quantized::Rgba16(data) => data.len(),
+        };
+        values / self.channels()
+    }
+
+    /// Like [`resize_nearest`], for any of them.
+    pub fn resize_nearest(self, width: usize, new_width: usize, new_height: usize) -> Self {
+        match self {
+            Quantized::Rgb8(data) => {
+                Quantized::Rgb8(resize_values::<_, 3>(&data, width, new_width, new_height))
+            }
+            Quantized::Rgb16(data) => {
+                Quantized::Rgb16(resize_values::<_, 3>(&data, width, new_width, new_height))
+            }
+            Quantized::Rgba8(data) => {
+                Quantized::Rgba8(resize_values::<_, 4>(&data, width, new_width, new_height))
+            }
+            Quantized::Rgba16(data) => {
+                Quantized::Rgba16(resize_values::<_, 4>(&data, width, new_width, new_height))
+            }
+        }
+    }
+}
+
+/// Write quantized pixels in any of the formats which aren't float.
+/// PPM has no alpha channel, so alpha is dropped there.
+pub fn write_quantized(
+    format: ImageFormat,
+    rows: usize,
+    data: &Quantized,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    match (format, data) {
+        (_, Quantized::Rgb8(data)) => write(format, rows, data, writer),
+        (_, Quantized::Rgb16(data)) => write16(format, rows, data, writer),
+        (ImageFormat::Png, Quantized::Rgba8(data)) => write_png(
+            rows,
+            data,
+            png::ColorType::Rgba,
+            png::BitDepth::Eight,
+            writer,
+        ),
+        (ImageFormat::Png, Quantized::Rgba16(data)) => write_png(
+            rows,
+            &big_endian(data),
+            png::ColorType::Rgba,
+            png::BitDepth::Sixteen,
+            writer,
+        ),
+        (_, Quantized::Rgba8(data)) => write(format, rows, without_alpha(data), writer),
+        (_, Quantized::Rgba16(data)) => write16(format, rows, &without_alpha(data), writer),
+    }
+}
+
+fn write_png(
+    rows: usize,
+    bytes: &[u8],
+    color: png::ColorType,
+    depth: png::BitDepth,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    let bytes_per_pixel = color.samples()
+        * if depth == png::BitDepth::Sixteen {
+            2
+        } else {
+            1
+        };
+    let cols = bytes.len() / rows / bytes_per_pixel;
+
+    let mut encoder = png::Encoder::new(writer, cols as u32, rows as u32);
+    encoder.set_color(color);
+    encoder.set_depth(depth);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(bytes)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// PNG and binary PPM want the most significant byte first.
+fn big_endian(data: &[u16]) -> Vec<u8> {
+    data.iter().flat_map(|value| value.to_be_bytes()).collect()
+}
+
+fn without_alpha<T: Copy>(data: &[T]) -> Vec<T> {
+    data.chunks_exact(4)
+        .flat_map(|rgba| [rgba[0], rgba[1], rgba[2]])
+        .collect()
+}
+
+/// Data is RGB 8-bit per channel.
+///
+/// A path of `-` writes to stdout.
+/// Unless a format is given it's picked from the file extension, so stdout gets PPM.
+pub fn write_pathlike(
+    rows: usize,
+    data: impl AsRef<[u8]>,
+    pathlike: impl AsRef<Path>,
+    format: Option<ImageFormat>,
+) -> anyhow::Result<()> {
+    let path = pathlike.as_ref();
+    let format = format.unwrap_or_else(|| ImageFormat::from_path(path));
+
+    write_to(path, |mut out| write(format, rows, data, &mut out))
+}
+
+/// Like [`write_pathlike`], for any [`Quantized`] pixels.
+pub fn write_pathlike_quantized(
+    rows: usize,
+    data: &Quantized,
+    pathlike: impl AsRef<Path>,
+    format: Option<ImageFormat>,
+) -> anyhow::Result<()> {
+    let path = pathlike.as_ref();
+    let format = format.unwrap_or_else(|| ImageFormat::from_path(path));
+
+    write_to(path, |mut out| {
+        write_quantized(format, rows, data, &mut out)
+    })
+}
+
+/// Buffered, to stdout for a path of `-`.
+fn write_to(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut out: BufWriter<Box<dyn Write>> = if path == Path::new("-") {
+        BufWriter::new(Box::new(std::io::stdout().lock()))
+    } else {
+        BufWriter::new(Box::new(std::fs::File::create(path)?))
+    };
+    write(&mut out)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Write linear colors in one of the float formats, row-major with `width` pixels per row.
+///
+/// A path of `-` writes to stdout.
+pub fn write_linear(
+    format: ImageFormat,
+    width: usize,
+    image: &[LinearRgba],
+    pathlike: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let path = pathlike.as_ref();
+
+    match format {
+        ImageFormat::Exr => write_exr(width, image, path),
+        ImageFormat::Hdr if path == Path::new("-") => {
+            let mut out = BufWriter::new(std::io::stdout().lock());
+            hdr::write(width, image, &mut out)?;
+            Ok(out.flush()?)
+        }
+        ImageFormat::Hdr => {
+            let mut out = BufWriter::new(std::fs::File::create(path)?);
+            hdr::write(width, image, &mut out)?;
+            Ok(out.flush()?)
+        }
+        other => bail!("{other:?} images are written from 8-bit data, see `write`"),
+    }
+}
+
+/// Write linear RGBA as a 32-bit float EXR, row-major with `width` pixels per row.
+///
+/// A path of `-` writes to stdout.
+pub fn write_exr(
+    width: usize,
+    image: &[LinearRgba],
+    pathlike: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    AovImage {
+        width,
+        height: image.len() / width,
+        layers: vec![AovLayer::beauty(image)],
+    }
+    .write_exr(pathlike)
+}
+
+/// The path of one image of an exposure bracket, e.g. `render_+2ev.png` for `render.png` at 2 stops up.
+pub fn bracketed_path(path: &Path, stops: f32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{stem}_{stops:+}ev");
+    if let Some(extension) = path.extension() {
+        name = format!("{name}.{}", extension.to_string_lossy());
+    }
+
+    path.with_file_name(name)
+}
+
+/// Resize RGB 8-bit data to the given size, repeating or skipping pixels as needed.
+pub fn resize_nearest(data: &[u8], width: usize, new_width: usize, new_height: usize) -> Vec<u8> {
+    resize_values::<_, 3>(data, width, new_width, new_height)
+}
+
+/// Like [`resize_nearest`], for `N` values per pixel.
+fn resize_values<T: Copy, const N: usize>(
+    data: &[T],
+    width: usize,
+    new_width: usize,
+    new_height: usize,
+) -> Vec<T> {
+    let pixels: Vec<[T; N]> = data
+        .chunks_exact(N)
+        .map(|values| std::array::from_fn(|index| values[index]))
+        .collect();
+
+    resize_pixels_nearest(&pixels, width, new_width, new_height).into_flattened()
+}
+
+/// Like [`resize_nearest`], for any kind of pixel.
+pub fn resize_pixels_nearest<T: Copy>(
+    pixels: &[T],
+    width: usize,
+    new_width: usize,
+    new_height: usize,
+) -> Vec<T> {
+    let height = pixels.len() / width;
+    if (width, height) == (new_width, new_height) {
+        return pixels.to_vec();
+    }
+
+    (0..new_height)
+        .flat_map(|row| (0..new_width).map(move |col| (row, col)))
+        .map(|(row, col)| pixels[row * height / new_height * width + col * width / new_width])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::ColorToComponents;
+
+    use super::*;
+
+    #[test]
+    fn png_signature() -> anyhow::Result<()> {
+        let data = [255, 0, 0, 0, 255, 0];
+
+        let mut writer = vec![];
+        write(ImageFormat::Png, 1, data, &mut writer)?;
+
+        assert_eq!(
+            writer[..8],
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']
+        );
+        assert_eq!(ImageFormat::from_path("out.PNG"), ImageFormat::Png);
+        assert_eq!(ImageFormat::from_path("-"), ImageFormat::Ppm);
+
+        Ok(())
+    }
+
+    #[test]
+    fn png_sixteen_bits() -> anyhow::Result<()> {
+        let data = [65535, 256, 1, 0, 1000, 40000];
+
+        let mut writer = vec![];
+        write16(ImageFormat::Png, 1, &data, &mut writer)?;
+
+        let mut reader = png::Decoder::new(std::io::Cursor::new(writer)).read_info()?;
+        let mut bytes = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut bytes)?;
+        assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+        assert_eq!((info.width, info.height), (2, 1));
+
+        let read: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(read, data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn png_alpha() -> anyhow::Result<()> {
+        let data = Quantized::Rgba8(vec![255, 0, 0, 255, 0, 0, 0, 0]);
+
+        let mut writer = vec![];
+        write_quantized(ImageFormat::Png, 1, &data, &mut writer)?;
+        let mut reader = png::Decoder::new(std::io::Cursor::new(writer)).read_info()?;
+        let mut bytes = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut bytes)?;
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(Quantized::Rgba8(bytes), data);
+
+        // PPM has no alpha
+        let mut writer = vec![];
+        write_quantized(ImageFormat::PpmBinary, 1, &data, &mut writer)?;
+        assert!(writer.ends_with(&[255, 0, 0, 0, 0, 0]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn exr_keeps_floats() -> anyhow::Result<()> {
+        let image = [
+            LinearRgba::new(0.25, 4.0, 100.0, 1.0),
+            LinearRgba::new(1e-4, 0.0, 0.5, 0.5),
+        ];
+        let path = std::env::temp_dir().join("rt_one_beauty.exr");
+        assert_eq!(ImageFormat::from_path(&path), ImageFormat::Exr);
+        write_exr(1, &image, &path)?;
+
+        let read = exr::prelude::read_all_flat_layers_from_file(&path)?;
+        let layer = &read.layer_data[0];
+        assert_eq!(layer.size.width(), 1);
+        assert_eq!(layer.size.height(), 2);
+
+        for channel in &layer.channel_data.list {
+            let index = ["R", "G", "B", "A"]
+                .iter()
+                .position(|name| channel.name.to_string() == *name)
+                .unwrap();
+            let expected: Vec<f32> = image
+                .iter()
+                .map(|color| color.to_f32_array()[index])
+                .collect();
+            assert_eq!(
+                channel.sample_data.values_as_f32().collect::<Vec<_>>(),
+                expected
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn upscale() {
+        let data = [1, 1, 1, 2, 2, 2];
+        let resized = resize_nearest(&data, 2, 4, 2);
+
+        assert_eq!(resized.len(), 4 * 2 * 3);
+        assert_eq!(resized[..12], [1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(resized[..12], resized[12..]);
+    }
+
+    #[test]
+    fn bracket_names() {
+        let path = Path::new("renders/out.png");
+        assert_eq!(bracketed_path(path, 2.0), Path::new("renders/out_+2ev.png"));
+        assert_eq!(
+            bracketed_path(path, -1.5),
+            Path::new("renders/out_-1.5ev.png")
+        );
+        assert_eq!(bracketed_path(Path::new("out"), 0.0), Path::new("out_+0ev"));
+    }
+}
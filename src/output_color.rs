@@ -0,0 +1,236 @@
+//! Turning display values into integers for 8- and 16-bit images.
+
+use std::str::FromStr;
+
+use anyhow::bail;
+use bevy_color::{LinearRgba, Srgba};
+use bevy_math::UVec2;
+use serde::{Deserialize, Serialize};
+
+use crate::sampler;
+
+/// How linear values are encoded before quantizing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Transfer {
+    /// Written as is, which looks dark on most displays.
+    #[default]
+    Linear,
+    /// The sRGB curve, what displays and image viewers expect.
+    Srgb,
+    /// A plain power curve, raising values to one over this.
+    Gamma(f32),
+}
+
+impl FromStr for Transfer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "linear" => Ok(Transfer::Linear),
+            "srgb" => Ok(Transfer::Srgb),
+            other => match other.parse::<f32>() {
+                Ok(gamma) if gamma > 0.0 => Ok(Transfer::Gamma(gamma)),
+                _ => bail!("unknown transfer {other:?}, expected linear, srgb or a gamma like 2.2"),
+            },
+        }
+    }
+}
+
+impl Transfer {
+    /// Encode a linear value in `[0, 1]`.
+    pub fn encode(self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            Transfer::Linear => value,
+            Transfer::Srgb => Srgba::gamma_function_inverse(value),
+            Transfer::Gamma(gamma) => value.powf(1.0 / gamma),
+        }
+    }
+}
+
+/// Bits per channel of quantized images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    /// Only PNG and PPM images hold this many.
+    Sixteen,
+}
+
+impl FromStr for BitDepth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(BitDepth::Eight),
+            "16" => Ok(BitDepth::Sixteen),
+            other => bail!("unknown bit depth {other:?}, expected 8 or 16"),
+        }
+    }
+}
+
+/// Noise added before rounding, so smooth gradients such as skies don't show bands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dither {
+    /// Round to the nearest level.
+    #[default]
+    None,
+    /// An 8x8 Bayer matrix. Cheap, but its cross-hatching can show.
+    Ordered,
+    /// A tile of blue noise, which looks like fine grain.
+    BlueNoise,
+}
+
+impl FromStr for Dither {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Dither::None),
+            "ordered" => Ok(Dither::Ordered),
+            "blue-noise" => Ok(Dither::BlueNoise),
+            other => bail!("unknown dither {other:?}, expected none, ordered or blue-noise"),
+        }
+    }
+}
+
+impl Dither {
+    /// Where in `[0, 1)` the given pixel rounds up.
+    pub fn threshold(self, pixel: UVec2) -> f32 {
+        match self {
+            Dither::None => 0.5,
+            Dither::Ordered => {
+                // Interleaves the bits of x ^ y and y, the lowest ones first
+                let (x, y) = (pixel.x % 8, pixel.y % 8);
+                let mut rank = 0;
+                for bit in 0..3 {
+                    rank = (rank << 2) | ((((x ^ y) >> bit) & 1) << 1) | ((y >> bit) & 1);
+                }
+                (rank as f32 + 0.5) / 64.0
+            }
+            Dither::BlueNoise => sampler::blue_noise(pixel),
+        }
+    }
+}
+
+/// How display values become the integers written to 8- and 16-bit images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputColor {
+    pub transfer: Transfer,
+    pub bit_depth: BitDepth,
+    pub dither: Dither,
+}
+
+impl OutputColor {
+    /// Quantize a color in `[0, 1]` at the given pixel to 8 bits, whatever the `bit_depth`.
+    pub fn to_rgb8(&self, color: LinearRgba, pixel: UVec2) -> [u8; 3] {
+        self.quantize(color, pixel, u8::MAX.into())
+            .map(|value| value as u8)
+    }
+
+    /// Quantize a color in `[0, 1]` at the given pixel to 16 bits, whatever the `bit_depth`.
+    pub fn to_rgb16(&self, color: LinearRgba, pixel: UVec2) -> [u16; 3] {
+        self.quantize(color, pixel, u16::MAX.into())
+            .map(|value| value as u16)
+    }
+
+    /// Like [`OutputColor::to_rgb8`], followed by alpha, which is neither encoded nor dithered.
+    pub fn to_rgba8(&self, color: LinearRgba, pixel: UVec2) -> [u8; 4] {
+        let [red, green, blue] = self.to_rgb8(color, pixel);
+        [
+            red,
+            green,
+            blue,
+            quantize_alpha(color, u8::MAX.into()) as u8,
+        ]
+    }
+
+    /// Like [`OutputColor::to_rgb16`], followed by alpha, which is neither encoded nor dithered.
+    pub fn to_rgba16(&self, color: LinearRgba, pixel: UVec2) -> [u16; 4] {
+        let [red, green, blue] = self.to_rgb16(color, pixel);
+        [
+            red,
+            green,
+            blue,
+            quantize_alpha(color, u16::MAX.into()) as u16,
+        ]
+    }
+
+    fn quantize(&self, color: LinearRgba, pixel: UVec2, max: f32) -> [f32; 3] {
+        let offset = self.dither.threshold(pixel) - 0.5;
+        [color.red, color.green, color.blue].map(|value| {
+            (self.transfer.encode(value) * max + offset)
+                .round()
+                .clamp(0.0, max)
+        })
+    }
+}
+
+fn quantize_alpha(color: LinearRgba, max: f32) -> f32 {
+    (color.alpha.clamp(0.0, 1.0) * max).round()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::ColorToPacked;
+
+    use super::*;
+
+    #[test]
+    fn transfers_and_depths() {
+        let gray = LinearRgba::rgb(0.5, 0.5, 0.5);
+        let at = |transfer, bit_depth| OutputColor {
+            transfer,
+            bit_depth,
+            dither: Dither::None,
+        };
+
+        assert_eq!(
+            at(Transfer::Linear, BitDepth::Eight).to_rgb8(gray, UVec2::ZERO),
+            [128; 3]
+        );
+        assert_eq!(
+            at(Transfer::Srgb, BitDepth::Eight).to_rgb8(gray, UVec2::ZERO),
+            Srgba::from(gray).to_u8_array_no_alpha()
+        );
+        assert_eq!(
+            at(Transfer::Gamma(2.2), BitDepth::Eight).to_rgb8(gray, UVec2::ZERO),
+            [186; 3]
+        );
+        assert_eq!(
+            at(Transfer::Linear, BitDepth::Sixteen)
+                .to_rgb16(LinearRgba::rgb(0.5, 1.0, 4.0), UVec2::ZERO),
+            [32768, 65535, 65535]
+        );
+
+        assert_eq!("2.2".parse::<Transfer>().unwrap(), Transfer::Gamma(2.2));
+        assert!("-1".parse::<Transfer>().is_err());
+    }
+
+    #[test]
+    fn dithering_keeps_the_average() {
+        // Between two 8-bit levels, which plain rounding flattens into a band
+        let value = 76.3 / 255.0;
+        let color = LinearRgba::rgb(value, value, value);
+
+        for (dither, size) in [(Dither::Ordered, 8), (Dither::BlueNoise, 64)] {
+            let output = OutputColor {
+                dither,
+                ..Default::default()
+            };
+            let levels: Vec<u8> = (0..size * size)
+                .map(|index| output.to_rgb8(color, UVec2::new(index % size, index / size))[0])
+                .collect();
+
+            assert!(levels.iter().all(|&level| level == 76 || level == 77));
+            let mean = levels.iter().map(|&level| level as f32).sum::<f32>() / levels.len() as f32;
+            assert!((mean - 76.3).abs() < 0.02, "{dither:?} {mean}");
+        }
+
+        assert_eq!(
+            OutputColor::default().to_rgb8(color, UVec2::new(3, 5)),
+            [76; 3]
+        );
+    }
+}
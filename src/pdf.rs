@@ -0,0 +1,275 @@
+//! Probability densities over directions, for importance sampling as in
+//! "Ray Tracing: The Rest of Your Life".
+//!
+//! Sending more rays where more light comes from, and weighting each by how likely
+//! its direction was, gives the same image with less noise.
+
+use bevy_math::{Dir3, Vec3};
+use rand::{Rng, RngCore};
+
+use crate::{
+    hittable::{Hit, Hittable},
+    material::Material,
+    random::random_on_sphere,
+    ray::Ray,
+};
+
+/// An orthonormal basis around a direction `w`, e.g. a surface normal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(w: Dir3) -> Self {
+        let w = w.as_vec3();
+        let (u, v) = w.any_orthonormal_pair();
+
+        Self { u, v, w }
+    }
+
+    /// From coordinates in this basis to the world.
+    pub fn transform(&self, local: Vec3) -> Vec3 {
+        local.x * self.u + local.y * self.v + local.z * self.w
+    }
+}
+
+/// A distribution of directions which can be sampled and evaluated.
+pub trait Pdf {
+    /// The probability density of picking `direction`.
+    fn value(&self, direction: Dir3) -> f32;
+
+    /// Pick a direction with this distribution.
+    fn generate(&self, rng: &mut dyn RngCore) -> Dir3;
+}
+
+impl<P: Pdf + ?Sized> Pdf for &P {
+    fn value(&self, direction: Dir3) -> f32 {
+        (**self).value(direction)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Dir3 {
+        (**self).generate(rng)
+    }
+}
+
+/// Every direction equally likely.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: Dir3) -> f32 {
+        0.25 * std::f32::consts::FRAC_1_PI
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Dir3 {
+        random_on_sphere(rng)
+    }
+}
+
+/// Directions around a normal, more likely the closer they are to it,
+/// like the light a Lambertian surface reflects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CosinePdf {
+    pub onb: Onb,
+}
+
+impl CosinePdf {
+    pub fn new(normal: Dir3) -> Self {
+        Self {
+            onb: Onb::new(normal),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Dir3) -> f32 {
+        let cos = direction.dot(self.onb.w);
+        cos.max(0.0) * std::f32::consts::FRAC_1_PI
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Dir3 {
+        Dir3::new(self.onb.transform(random_cosine_direction(rng)))
+            .unwrap_or(Dir3::new_unchecked(self.onb.w))
+    }
+}
+
+/// Directions from `origin` towards random points of an object, e.g. a lamp,
+/// see [`Hittable::random_direction`].
+#[derive(Debug, Clone, Copy)]
+pub struct HittablePdf<'a> {
+    pub object: &'a dyn Hittable,
+    pub origin: Vec3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(object: &'a dyn Hittable, origin: Vec3) -> Self {
+        Self { object, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: Dir3) -> f32 {
+        self.object.pdf_value(self.origin, direction)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Dir3 {
+        self.object.random_direction(self.origin, rng)
+    }
+}
+
+/// The directions a material scatters into at a hit, by [`Material::scattering_pdf`].
+/// Only meaningful for materials which report a [`crate::material::Scattering::pdf`].
+#[derive(Clone, Copy)]
+pub struct MaterialPdf<'a> {
+    pub material: &'a dyn Material,
+    pub ray: &'a Ray,
+    pub hit: &'a Hit,
+}
+
+impl<'a> MaterialPdf<'a> {
+    pub fn new(material: &'a dyn Material, ray: &'a Ray, hit: &'a Hit) -> Self {
+        Self { material, ray, hit }
+    }
+}
+
+impl Pdf for MaterialPdf<'_> {
+    fn value(&self, direction: Dir3) -> f32 {
+        let scattered = Ray::new(self.hit.point, direction.as_vec3());
+        self.material.scattering_pdf(self.ray, self.hit, &scattered)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Dir3 {
+        self.material
+            .scatter(self.ray, self.hit, rng)
+            .map_or(self.hit.normal, |scattering| scattering.ray.direction())
+    }
+}
+
+/// Picks from `a` with the chance `weight`, else from `b`.
+///
+/// Mixing a light's distribution with a material's finds the light often
+/// without losing the directions the material favours.
+#[derive(Debug, Clone, Copy)]
+pub struct MixturePdf<A, B> {
+    pub a: A,
+    pub b: B,
+    pub weight: f32,
+}
+
+impl<A: Pdf, B: Pdf> MixturePdf<A, B> {
+    pub fn new(a: A, b: B, weight: f32) -> Self {
+        Self { a, b, weight }
+    }
+}
+
+impl<A: Pdf, B: Pdf> Pdf for MixturePdf<A, B> {
+    fn value(&self, direction: Dir3) -> f32 {
+        self.weight * self.a.value(direction) + (1.0 - self.weight) * self.b.value(direction)
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> Dir3 {
+        if rng.gen::<f32>() < self.weight {
+            self.a.generate(rng)
+        } else {
+            self.b.generate(rng)
+        }
+    }
+}
+
+/// A direction around +Z, distributed by the cosine of its angle to it.
+pub fn random_cosine_direction(rng: &mut (impl Rng + ?Sized)) -> Vec3 {
+    let r1: f32 = rng.gen();
+    let r2: f32 = rng.gen();
+
+    let phi = std::f32::consts::TAU * r1;
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let radius = r2.sqrt();
+
+    Vec3::new(cos_phi * radius, sin_phi * radius, (1.0 - r2).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::Color;
+
+    use super::*;
+    use crate::{
+        hittable::Hittables,
+        material::Lambertian,
+        objects::{Quad, Sphere},
+        random::sample_rng,
+    };
+
+    #[test]
+    fn cosine_pdf_is_a_density() {
+        let normal = Dir3::new(Vec3::new(1.0, 2.0, -0.5)).unwrap();
+        let onb = Onb::new(normal);
+        assert!(onb.u.dot(onb.v).abs() < 1e-6 && onb.u.dot(onb.w).abs() < 1e-6);
+        assert!(onb.u.cross(onb.v).abs_diff_eq(onb.w, 1e-5));
+
+        let pdf = CosinePdf::new(normal);
+        let mut rng = sample_rng(0, 0, 0);
+
+        // The mean of value / density of uniform directions integrates the pdf over the sphere
+        let samples = 100_000;
+        let integral = (0..samples)
+            .map(|_| pdf.value(SpherePdf.generate(&mut rng)) / SpherePdf.value(Dir3::X))
+            .sum::<f32>()
+            / samples as f32;
+        assert!((integral - 1.0).abs() < 0.02, "{integral}");
+
+        // Generated directions stay above the surface, averaging 2/3 for the cosine
+        let mean_cos = (0..samples)
+            .map(|_| pdf.generate(&mut rng).dot(normal.as_vec3()))
+            .inspect(|cos| assert!(*cos >= -1e-6))
+            .sum::<f32>()
+            / samples as f32;
+        assert!((mean_cos - 2.0 / 3.0).abs() < 0.01, "{mean_cos}");
+    }
+
+    #[test]
+    fn hittable_pdfs_are_densities() {
+        let material = || Lambertian::new(Color::WHITE);
+        let mut lights = Hittables::default();
+        lights.add(Quad::new(
+            Vec3::new(-1.0, 3.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0),
+            material(),
+        ));
+        lights.add(Sphere {
+            center: Vec3::new(2.0, 1.0, 0.0),
+            radius: 1.0,
+            material: material().into(),
+        });
+        let mut rng = sample_rng(0, 0, 0);
+
+        for object in [
+            &**lights.objects[0] as &dyn Hittable,
+            &**lights.objects[1],
+            &lights,
+        ] {
+            let pdf = HittablePdf::new(object, Vec3::ZERO);
+
+            // Generated directions all hit the object
+            for _ in 0..1000 {
+                let direction = pdf.generate(&mut rng);
+                assert!(pdf.value(direction) > 0.0, "{object:?} {direction:?}");
+            }
+
+            // Integrating over the sphere of directions gives one
+            let samples = 400_000;
+            let integral = (0..samples)
+                .map(|_| pdf.value(SpherePdf.generate(&mut rng)) / SpherePdf.value(Dir3::X))
+                .sum::<f32>()
+                / samples as f32;
+            assert!((integral - 1.0).abs() < 0.03, "{object:?} {integral}");
+        }
+
+        // Directions away from the lights are never picked
+        assert_eq!(lights.pdf_value(Vec3::ZERO, Dir3::NEG_Y), 0.0);
+    }
+}
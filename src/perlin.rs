@@ -0,0 +1,130 @@
+//! Perlin noise: smooth pseudo-random values over space, for procedural textures.
+
+use bevy_math::Vec3;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+/// Lattice points repeat after this many along each axis.
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise on a lattice of random unit vectors.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    vectors: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    /// The same seed gives the same noise.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let vectors = (0..POINT_COUNT)
+            .map(|_| {
+                let vector = Vec3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                );
+                vector.try_normalize().unwrap_or(Vec3::X)
+            })
+            .collect();
+
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            perm.shuffle(&mut rng);
+            perm
+        };
+
+        Self {
+            vectors,
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+        }
+    }
+
+    /// Noise at the point, roughly in `[-1, 1]` and changing over distances of about one.
+    pub fn noise(&self, point: Vec3) -> f32 {
+        let cell = point.floor();
+        let fraction = point - cell;
+        let [i, j, k] = cell.as_ivec3().to_array();
+
+        let mut corners = [[[Vec3::ZERO; 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let wrap = |index: i32, offset: usize| {
+                        (index + offset as i32) as usize & (POINT_COUNT - 1)
+                    };
+                    *corner = self.vectors[self.perm_x[wrap(i, di)]
+                        ^ self.perm_y[wrap(j, dj)]
+                        ^ self.perm_z[wrap(k, dk)]];
+                }
+            }
+        }
+
+        interpolate(&corners, fraction)
+    }
+
+    /// Noise summed over `octaves` frequencies, each twice the last at half the weight.
+    /// Always positive, with sharp creases where the sum changes sign.
+    pub fn turbulence(&self, point: Vec3, octaves: usize) -> f32 {
+        let mut sum = 0.0;
+        let mut point = point;
+        let mut weight = 1.0;
+
+        for _ in 0..octaves {
+            sum += weight * self.noise(point);
+            weight *= 0.5;
+            point *= 2.0;
+        }
+
+        sum.abs()
+    }
+}
+
+/// Blend the gradients at the corners of a cell, eased so there are no visible grid lines.
+fn interpolate(corners: &[[[Vec3; 2]; 2]; 2], fraction: Vec3) -> f32 {
+    let eased = fraction * fraction * (3.0 - 2.0 * fraction);
+    let mut sum = 0.0;
+
+    for (i, plane) in corners.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, gradient) in row.iter().enumerate() {
+                let corner = Vec3::new(i as f32, j as f32, k as f32);
+                let weight = corner * eased + (1.0 - corner) * (1.0 - eased);
+                sum += weight.element_product() * gradient.dot(fraction - corner);
+            }
+        }
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooth_and_repeatable() {
+        let perlin = Perlin::new(3);
+        assert_eq!(
+            perlin.noise(Vec3::new(1.3, 2.7, -4.1)),
+            Perlin::new(3).noise(Vec3::new(1.3, 2.7, -4.1))
+        );
+
+        let mut previous = perlin.noise(Vec3::ZERO);
+        for step in 1..1000 {
+            let value = perlin.noise(Vec3::splat(step as f32 * 0.01));
+            assert!((-1.0..=1.0).contains(&value));
+            assert!((value - previous).abs() < 0.05, "jump at step {step}");
+            previous = value;
+        }
+
+        // Zero on the lattice points themselves
+        assert_eq!(perlin.noise(Vec3::new(5.0, -2.0, 7.0)), 0.0);
+        assert!(perlin.turbulence(Vec3::new(0.5, 0.5, 0.5), 7) >= 0.0);
+    }
+}
@@ -0,0 +1,258 @@
+//! Post-processing of finished renders.
+
+use std::str::FromStr;
+
+use anyhow::bail;
+use bevy_color::{Alpha, ColorToComponents, LinearRgba};
+use bevy_math::Vec3;
+use rayon::prelude::*;
+
+use crate::{
+    aov::{self, Aov},
+    camera::Camera,
+    hittable::Hittable,
+};
+
+/// Ways to take the noise out of a render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denoiser {
+    /// The built-in [`AtrousFilter`].
+    Filter,
+    /// Open Image Denoise, needs the `oidn` feature. See [`crate::oidn`].
+    Oidn,
+}
+
+impl FromStr for Denoiser {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "filter" => Ok(Denoiser::Filter),
+            "oidn" => Ok(Denoiser::Oidn),
+            other => bail!("unknown denoiser {other:?}, expected filter or oidn"),
+        }
+    }
+}
+
+impl Denoiser {
+    /// Denoise a render of `world` in place.
+    pub fn apply(
+        self,
+        camera: &Camera,
+        world: &dyn Hittable,
+        image: &mut [LinearRgba],
+    ) -> anyhow::Result<()> {
+        match self {
+            Denoiser::Filter => {
+                let guides = Guides::render(camera, world);
+                AtrousFilter::default().apply(camera.im_width, image, &guides);
+                Ok(())
+            }
+            #[cfg(feature = "oidn")]
+            Denoiser::Oidn => crate::oidn::denoise(camera, world, image),
+            #[cfg(not(feature = "oidn"))]
+            Denoiser::Oidn => bail!("built without the oidn feature, use the filter denoiser"),
+        }
+    }
+}
+
+/// Per pixel surface information which tells noise apart from detail.
+#[derive(Debug, Clone)]
+pub struct Guides {
+    pub normal: Vec<Vec3>,
+    /// Infinite where nothing was hit.
+    pub depth: Vec<f32>,
+    pub albedo: Vec<Vec3>,
+}
+
+impl Guides {
+    /// Render the guides from the same camera rays as the image, see [`aov::render_aovs`].
+    pub fn render(camera: &Camera, world: &dyn Hittable) -> Self {
+        let layers = aov::render_aovs(camera, world, &[Aov::Normal, Aov::Depth, Aov::Albedo]);
+        let vectors = |layer: &aov::AovLayer| {
+            layer
+                .interleaved()
+                .chunks_exact(3)
+                .map(Vec3::from_slice)
+                .collect()
+        };
+
+        Self {
+            normal: vectors(&layers[0]),
+            depth: layers[1].channels[0].1.clone(),
+            albedo: vectors(&layers[2]),
+        }
+    }
+}
+
+/// An edge-avoiding À-trous wavelet filter, after Dammertz et al. 2010.
+///
+/// Repeatedly blurs with a 5x5 kernel whose taps spread twice as far each time,
+/// leaving out neighbors whose normal, depth or color differ too much.
+/// Lighting is filtered with the albedo divided out, so textures stay sharp.
+#[derive(Debug, Clone, Copy)]
+pub struct AtrousFilter {
+    /// Each one doubles the reach, five cover 31 pixels across.
+    pub iterations: u32,
+    /// How different colors may be and still blend, relative to their brightness.
+    pub color_sigma: f32,
+    /// Raises the cosine between normals, higher keeps creases sharper.
+    pub normal_power: f32,
+    /// How different depths may be and still blend, relative to the depth.
+    pub depth_sigma: f32,
+}
+
+impl Default for AtrousFilter {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            color_sigma: 1.0,
+            normal_power: 64.0,
+            depth_sigma: 0.05,
+        }
+    }
+}
+
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+impl AtrousFilter {
+    /// Filter a row-major image `width` pixels wide in place. Alpha is kept as it is.
+    pub fn apply(&self, width: usize, image: &mut [LinearRgba], guides: &Guides) {
+        if width == 0 {
+            return;
+        }
+        let height = image.len() / width;
+
+        // Lighting alone, where there is an albedo to divide out
+        let demodulate = |albedo: Vec3| albedo.max(Vec3::splat(1e-3));
+        let mut lighting: Vec<Vec3> = image
+            .iter()
+            .zip(&guides.albedo)
+            .map(|(color, &albedo)| color.to_vec3() / demodulate(albedo))
+            .collect();
+
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+
+            lighting = (0..height)
+                .into_par_iter()
+                .flat_map_iter(|row| (0..width).map(move |col| (row, col)))
+                .map(|(row, col)| {
+                    let center = row * width + col;
+                    let mut sum = Vec3::ZERO;
+                    let mut total = 0.0;
+
+                    for (dy, ky) in KERNEL.iter().enumerate() {
+                        let y = row as isize + (dy as isize - 2) * step;
+                        if y < 0 || y >= height as isize {
+                            continue;
+                        }
+                        for (dx, kx) in KERNEL.iter().enumerate() {
+                            let x = col as isize + (dx as isize - 2) * step;
+                            if x < 0 || x >= width as isize {
+                                continue;
+                            }
+
+                            let other = y as usize * width + x as usize;
+                            let weight = if other == center {
+                                kx * ky
+                            } else {
+                                kx * ky
+                                    * self.geometry_weight(guides, center, other)
+                                    * self.color_weight(lighting[center], lighting[other])
+                            };
+                            sum += lighting[other] * weight;
+                            total += weight;
+                        }
+                    }
+
+                    // The center always fully weighs in, so the total is never zero
+                    sum / total
+                })
+                .collect();
+        }
+
+        for ((color, light), &albedo) in image.iter_mut().zip(lighting).zip(&guides.albedo) {
+            *color = LinearRgba::from_vec3(light * demodulate(albedo)).with_alpha(color.alpha);
+        }
+    }
+
+    /// How alike two colors are, in `[0, 1]`. Relative, so bright and dark areas blend alike.
+    fn color_weight(&self, a: Vec3, b: Vec3) -> f32 {
+        let brightness = (a.length_squared() + b.length_squared()) / 2.0;
+        let sigma = self.color_sigma * self.color_sigma * brightness;
+        (-a.distance_squared(b) / sigma.max(1e-8)).exp()
+    }
+
+    /// How alike the surfaces seen through two pixels are, in `[0, 1]`.
+    fn geometry_weight(&self, guides: &Guides, a: usize, b: usize) -> f32 {
+        let (depth_a, depth_b) = (guides.depth[a], guides.depth[b]);
+        match (depth_a.is_finite(), depth_b.is_finite()) {
+            // Both background
+            (false, false) => return 1.0,
+            (true, true) => {}
+            _ => return 0.0,
+        }
+
+        // Averaged normals along silhouettes are shorter than one
+        let normal = guides.normal[a]
+            .normalize_or_zero()
+            .dot(guides.normal[b].normalize_or_zero())
+            .max(0.0)
+            .powf(self.normal_power);
+        let depth = (-(depth_a - depth_b).abs() / (self.depth_sigma * depth_a).max(1e-4)).exp();
+
+        normal * depth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn smooths_noise_but_keeps_edges() {
+        let (width, height) = (32, 16);
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        // Left half near, right half far, both evenly lit with noise on top
+        let near = |index: usize| index % width < width / 2;
+        let guides = Guides {
+            normal: vec![Vec3::Z; width * height],
+            depth: (0..width * height)
+                .map(|index| if near(index) { 1.0 } else { 5.0 })
+                .collect(),
+            albedo: vec![Vec3::splat(0.5); width * height],
+        };
+        let mut image: Vec<LinearRgba> = (0..width * height)
+            .map(|index| {
+                let level = if near(index) { 0.2 } else { 0.8 };
+                let value = level * rng.gen_range(0.5..1.5);
+                LinearRgba::new(value, value, value, 1.0)
+            })
+            .collect();
+
+        let error = |image: &[LinearRgba]| {
+            image
+                .iter()
+                .enumerate()
+                .map(|(index, color)| {
+                    let level = if near(index) { 0.2 } else { 0.8 };
+                    (color.red - level).powi(2)
+                })
+                .sum::<f32>()
+        };
+
+        let before = error(&image);
+        AtrousFilter::default().apply(width, &mut image, &guides);
+        let after = error(&image);
+
+        assert!(after < before / 10.0, "{before} {after}");
+        // Right at the edge, each side keeps its own level
+        assert!((image[width / 2 - 1].red - 0.2).abs() < 0.05);
+        assert!((image[width / 2].red - 0.8).abs() < 0.1);
+        assert!(image.iter().all(|color| color.alpha == 1.0));
+    }
+}
@@ -1,13 +1,13 @@
 use std::{
+    fmt::Display,
     io::{BufWriter, Write},
     path::Path,
 };
 
 use tracing::debug;
 
-/// Data is RGB 8-bit per channel.
-pub fn write(rows: usize, data: impl AsRef<[u8]>, writer: &mut impl Write) -> anyhow::Result<()> {
-    let data = data.as_ref();
+/// Columns in RGB data with this many rows.
+fn columns<T>(rows: usize, data: &[T]) -> usize {
     let num_bytes = data.len();
     let cols = num_bytes / rows / 3;
 
@@ -17,9 +17,30 @@ pub fn write(rows: usize, data: impl AsRef<[u8]>, writer: &mut impl Write) -> an
         "cols and rows should fit exactly with no padding etc."
     );
 
+    cols
+}
+
+/// Data is RGB 8-bit per channel. Written as ASCII (P3), which is readable but large.
+pub fn write(rows: usize, data: impl AsRef<[u8]>, writer: &mut impl Write) -> anyhow::Result<()> {
+    write_ascii(rows, data.as_ref(), u8::MAX.into(), writer)
+}
+
+/// Like [`write`], for RGB 16-bit per channel.
+pub fn write16(rows: usize, data: &[u16], writer: &mut impl Write) -> anyhow::Result<()> {
+    write_ascii(rows, data, u16::MAX, writer)
+}
+
+fn write_ascii<T: Display + Copy>(
+    rows: usize,
+    data: &[T],
+    max: u16,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    let cols = columns(rows, data);
+
     writer.write_all(b"P3\n")?;
     writer.write_all(format!("{cols} {rows}\n").as_bytes())?;
-    writer.write_all(format!("255\n").as_bytes())?;
+    writer.write_all(format!("{max}\n").as_bytes())?;
 
     let rows: Vec<_> = data.chunks_exact(3 * cols).collect();
 
@@ -35,6 +56,33 @@ pub fn write(rows: usize, data: impl AsRef<[u8]>, writer: &mut impl Write) -> an
     Ok(())
 }
 
+/// Data is RGB 8-bit per channel. Written as binary (P6), which is a third of
+/// the size of [`write`] at most and much faster to write.
+pub fn write_p6(
+    rows: usize,
+    data: impl AsRef<[u8]>,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    let data = data.as_ref();
+    let cols = columns(rows, data);
+
+    writer.write_all(format!("P6\n{cols} {rows}\n255\n").as_bytes())?;
+    writer.write_all(data)?;
+
+    Ok(())
+}
+
+/// Like [`write_p6`], for RGB 16-bit per channel. Each value is written most significant byte first.
+pub fn write_p6_16(rows: usize, data: &[u16], writer: &mut impl Write) -> anyhow::Result<()> {
+    let cols = columns(rows, data);
+
+    writer.write_all(format!("P6\n{cols} {rows}\n65535\n").as_bytes())?;
+    let bytes: Vec<u8> = data.iter().flat_map(|value| value.to_be_bytes()).collect();
+    writer.write_all(&bytes)?;
+
+    Ok(())
+}
+
 /// Data is RGB 8-bit per channel.
 pub fn write_pathlike(
     rows: usize,
@@ -50,6 +98,72 @@ pub fn write_pathlike(
 mod tests {
     use super::*;
 
+    /// Width, height and RGB data of either encoding.
+    fn decode(ppm: &[u8]) -> (usize, usize, Vec<u8>) {
+        // The header is the magic number and three numbers, each followed by one whitespace
+        let mut fields = 0;
+        let mut header_end = 0;
+        for (index, byte) in ppm.iter().enumerate() {
+            if byte.is_ascii_whitespace() {
+                fields += 1;
+                if fields == 4 {
+                    header_end = index + 1;
+                    break;
+                }
+            }
+        }
+
+        let header = std::str::from_utf8(&ppm[..header_end]).unwrap();
+        let header: Vec<&str> = header.split_ascii_whitespace().collect();
+        let (cols, rows) = (header[1].parse().unwrap(), header[2].parse().unwrap());
+        assert_eq!(header[3], "255");
+
+        let data = match header[0] {
+            "P3" => std::str::from_utf8(&ppm[header_end..])
+                .unwrap()
+                .split_ascii_whitespace()
+                .map(|value| value.parse().unwrap())
+                .collect(),
+            "P6" => ppm[header_end..].to_vec(),
+            other => panic!("not a PPM: {other}"),
+        };
+
+        (cols, rows, data)
+    }
+
+    #[test]
+    fn ascii_and_binary_agree() -> anyhow::Result<()> {
+        // Includes bytes which are whitespace in ASCII, which P6 must not mangle
+        let data: Vec<u8> = (0..=255).cycle().step_by(7).take(4 * 3 * 3).collect();
+
+        let mut ascii = vec![];
+        write(3, &data, &mut ascii)?;
+        let mut binary = vec![];
+        write_p6(3, &data, &mut binary)?;
+
+        assert!(binary.starts_with(b"P6\n4 3\n255\n"));
+        assert!(binary.len() < ascii.len());
+        assert_eq!(decode(&ascii), (4, 3, data.clone()));
+        assert_eq!(decode(&binary), decode(&ascii));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sixteen_bits() -> anyhow::Result<()> {
+        let data = [65535, 256, 1];
+
+        let mut ascii = vec![];
+        write16(1, &data, &mut ascii)?;
+        assert_eq!(ascii, b"P3\n1 1\n65535\n65535 256 1 \n");
+
+        let mut binary = vec![];
+        write_p6_16(1, &data, &mut binary)?;
+        assert_eq!(binary, b"P6\n1 1\n65535\n\xff\xff\x01\x00\x00\x01");
+
+        Ok(())
+    }
+
     #[test]
     fn simple() -> anyhow::Result<()> {
         let data = [100, 0, 0, 0, 100, 0, 0, 0, 0, 100, 100, 100];
@@ -57,13 +171,13 @@ mod tests {
         // to mem
         let mut writer = vec![];
 
-        write(2, &data, &mut writer)?;
+        write(2, data, &mut writer)?;
 
         let s = String::from_utf8(writer)?;
         dbg!(s);
 
         // to file
-        write_pathlike(2, &data, "simple.ppm")?;
+        write_pathlike(2, data, "simple.ppm")?;
 
         Ok(())
     }
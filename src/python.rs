@@ -0,0 +1,130 @@
+//! Python bindings, for driving the renderer from notebooks.
+//!
+//! Build and install into the active virtualenv with
+//! `maturin develop --release --features python` (`just python`), then:
+//!
+//! ```python
+//! import rt_one
+//!
+//! scene = rt_one.Scene(width=400, height=225, samples=10)
+//! scene.add_sphere((0, -100.5, -1), 100, rt_one.lambertian(0.8, 0.8, 0.0))
+//! scene.add_sphere((0, 0, -1.2), 0.5, rt_one.metal(0.8, 0.6, 0.2, 0.1))
+//! image = scene.render_to_numpy()  # float32, (height, width, 3), linear
+//! ```
+
+use bevy_color::Color;
+use bevy_math::Vec3;
+use numpy::{ndarray::Array3, IntoPyArray, PyArray3};
+use pyo3::prelude::*;
+
+use crate::{
+    camera::Camera,
+    hittable::Hittables,
+    material::{Dielectric, DynMaterial, Lambertian, Metal},
+    objects::{Cuboid, Sphere, Triangle},
+    scene,
+};
+
+type Point = (f32, f32, f32);
+
+fn vec3((x, y, z): Point) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+#[pyclass(name = "Material", unsendable)]
+#[derive(Clone)]
+pub struct PyMaterial(DynMaterial);
+
+#[pyfunction]
+fn lambertian(red: f32, green: f32, blue: f32) -> PyMaterial {
+    PyMaterial(Lambertian::linear_rgb(red, green, blue).into())
+}
+
+#[pyfunction]
+fn metal(red: f32, green: f32, blue: f32, fuzz: f32) -> PyMaterial {
+    PyMaterial(Metal::new(Color::linear_rgb(red, green, blue), fuzz).into())
+}
+
+#[pyfunction]
+fn dielectric(refractive_index: f32) -> PyMaterial {
+    PyMaterial(Dielectric::refraction_index(refractive_index).into())
+}
+
+#[pyclass(name = "Scene", unsendable)]
+pub struct PyScene(scene::Scene);
+
+#[pymethods]
+impl PyScene {
+    #[new]
+    #[pyo3(signature = (width = 600, height = 337, samples = 10, bounce = 50))]
+    fn new(width: usize, height: usize, samples: usize, bounce: usize) -> Self {
+        let camera = Camera::builder()
+            .resolution(width.max(1), height.max(1))
+            .samples(samples.max(1))
+            .bounces(bounce)
+            .min_dist(0.001)
+            .build()
+            .expect("sizes and samples were clamped to at least 1");
+
+        Self(scene::Scene::new(camera, Hittables::default()))
+    }
+
+    fn add_sphere(&mut self, center: Point, radius: f32, material: PyMaterial) {
+        self.0.world.add(Sphere {
+            center: vec3(center),
+            radius,
+            material: material.0,
+        });
+    }
+
+    fn add_cuboid(&mut self, a: Point, b: Point, material: PyMaterial) {
+        self.0.world.add(Cuboid::new(vec3(a), vec3(b), material.0));
+    }
+
+    fn add_triangle(&mut self, a: Point, b: Point, c: Point, material: PyMaterial) {
+        self.0
+            .world
+            .add(Triangle::new(vec3(a), vec3(b), vec3(c), material.0));
+    }
+
+    /// Move the camera such that everything added so far is in view.
+    #[pyo3(signature = (margin = 1.1))]
+    fn frame(&mut self, margin: f32) {
+        let scene::Scene { camera, world, .. } = &mut self.0;
+        camera.frame(world, margin);
+    }
+
+    /// Problems which would make the render wrong, see `Scene::validate`.
+    fn validate(&self) -> Vec<String> {
+        self.0.validate()
+    }
+
+    /// Render to a linear float32 array of shape (height, width, 3).
+    fn render_to_numpy<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<f32>>> {
+        let camera = &self.0.camera;
+
+        let data: Vec<f32> = self
+            .0
+            .camera
+            .render_linear(&self.0.world)
+            .into_iter()
+            .flat_map(|color| [color.red, color.green, color.blue])
+            .collect();
+
+        let array = Array3::from_shape_vec((camera.im_height, camera.im_width, 3), data)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+
+        Ok(array.into_pyarray(py))
+    }
+}
+
+#[pymodule]
+fn rt_one(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyScene>()?;
+    module.add_class::<PyMaterial>()?;
+    module.add_function(wrap_pyfunction!(lambertian, module)?)?;
+    module.add_function(wrap_pyfunction!(metal, module)?)?;
+    module.add_function(wrap_pyfunction!(dielectric, module)?)?;
+
+    Ok(())
+}
@@ -1,14 +1,78 @@
-use bevy_math::{Dir3, ShapeSample};
+//! Random numbers for rendering.
+//!
+//! Every sample has its own generator, seeded from the camera seed, the pixel and the sample
+//! index by [`sample_rng`] and passed down to whatever needs random numbers.
+//! A sample is then the same whichever thread renders it and in whatever order,
+//! so images only depend on the seed.
+//!
+//! Code without a generator at hand, such as [`crate::hittable::Hittable::hit`],
+//! uses [`random_f32`] instead, which draws from a per-thread generator that
+//! [`seed_sample`] reseeds the same way.
 
-pub fn random_on_sphere() -> Dir3 {
-    let mut rng = rand::thread_rng();
-    let unit_sphere = bevy_math::prelude::Sphere::new(0.5).sample_boundary(&mut rng);
+use std::cell::RefCell;
+
+use bevy_math::{Dir3, ShapeSample, UVec2, Vec2};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// Mix bits thoroughly, the finalizer of SplitMix64.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// The random numbers for one sample of one pixel.
+pub fn sample_rng(seed: u64, pixel: usize, sample: usize) -> SmallRng {
+    SmallRng::seed_from_u64(mix(mix(seed ^ mix(pixel as u64)) ^ sample as u64))
+}
+
+/// Start this thread's random numbers for one sample of one pixel, see [`random_f32`].
+/// They differ from those of [`sample_rng`], so the two don't repeat each other.
+pub fn seed_sample(seed: u64, pixel: usize, sample: usize) {
+    let rng = sample_rng(seed, pixel, !sample);
+    RNG.with_borrow_mut(|thread_rng| *thread_rng = rng);
+}
+
+/// A random offset for the pixel at column `pixel.x` and row `pixel.y`, the same for all
+/// its samples.
+/// Used to decorrelate structured sample patterns between pixels.
+pub fn pixel_rotation(seed: u64, pixel: UVec2) -> Vec2 {
+    let pixel = (pixel.y as u64) << 32 | pixel.x as u64;
+    let bits = mix(mix(seed ^ 0x9e3779b97f4a7c15) ^ pixel);
+    // The top 24 bits of each half, which is all an f32 in [0, 1) can hold
+    let unit = |bits: u64| (bits >> 40) as f32 / (1u64 << 24) as f32;
+
+    Vec2::new(unit(bits), unit(bits << 32))
+}
+
+/// Use this thread's generator.
+pub fn with_rng<T>(f: impl FnOnce(&mut SmallRng) -> T) -> T {
+    RNG.with_borrow_mut(f)
+}
+
+/// Uniform in `[0, 1)` from this thread's generator.
+/// Prefer drawing from a generator passed in where there is one.
+pub fn random_f32() -> f32 {
+    with_rng(|rng| rng.gen())
+}
+
+/// Uniform within the unit disk.
+pub fn random_in_disk(rng: &mut (impl Rng + ?Sized)) -> Vec2 {
+    bevy_math::prelude::Circle::new(1.0).sample_interior(rng)
+}
+
+pub fn random_on_sphere(rng: &mut (impl Rng + ?Sized)) -> Dir3 {
+    let unit_sphere = bevy_math::prelude::Sphere::new(0.5).sample_boundary(rng);
 
     Dir3::new(unit_sphere).expect("unit sphere boundary should have unit length")
 }
 
-pub fn random_on_hemisphere(normal: Dir3) -> Dir3 {
-    let unit_sphere = random_on_sphere();
+pub fn random_on_hemisphere(normal: Dir3, rng: &mut (impl Rng + ?Sized)) -> Dir3 {
+    let unit_sphere = random_on_sphere(rng);
 
     if unit_sphere.dot(*normal) > 0.0 {
         unit_sphere
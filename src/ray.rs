@@ -3,21 +3,99 @@ use tracing::debug;
 
 use crate::objects::Sphere;
 
+/// What a ray is traced for, so objects can choose which rays see them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RayKind {
+    /// Leaving the camera.
+    #[default]
+    Camera,
+    /// Looking for a light, any hit means the point is in shadow.
+    Shadow,
+    /// Scattered diffusely off a surface.
+    Diffuse,
+    /// Reflected or refracted off a smooth surface.
+    Specular,
+}
+
+/// A set of [`RayKind`]s, e.g. those an object is visible to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RayMask(u8);
+
+impl RayMask {
+    pub const NONE: Self = Self(0);
+    pub const CAMERA: Self = Self::only(RayKind::Camera);
+    pub const SHADOW: Self = Self::only(RayKind::Shadow);
+    pub const DIFFUSE: Self = Self::only(RayKind::Diffuse);
+    pub const SPECULAR: Self = Self::only(RayKind::Specular);
+    /// Rays scattered off surfaces, of any kind.
+    pub const INDIRECT: Self = Self(Self::DIFFUSE.0 | Self::SPECULAR.0);
+    pub const ALL: Self = Self(Self::CAMERA.0 | Self::SHADOW.0 | Self::INDIRECT.0);
+
+    pub const fn only(kind: RayKind) -> Self {
+        Self(1 << kind as u8)
+    }
+
+    pub fn contains(self, kind: RayKind) -> bool {
+        self.0 & Self::only(kind).0 != 0
+    }
+
+    pub fn without(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl Default for RayMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl std::ops::BitOr for RayMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
 #[derive(Debug)]
 pub struct Ray {
     inner: Ray3d,
+    kind: RayKind,
+    /// When the ray was sent, within the camera's shutter interval. Moving objects are hit
+    /// where they were at this time, which blurs them.
+    time: f32,
 }
 
 impl Ray {
+    /// A camera ray, see [`Ray::with_kind`] for others.
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
         Self {
             inner: Ray3d {
                 origin,
                 direction: Dir3::new_unchecked(direction.normalize()),
             },
+            kind: RayKind::Camera,
+            time: 0.0,
         }
     }
 
+    pub fn with_kind(self, kind: RayKind) -> Self {
+        Self { kind, ..self }
+    }
+
+    pub fn kind(&self) -> RayKind {
+        self.kind
+    }
+
+    pub fn with_time(self, time: f32) -> Self {
+        Self { time, ..self }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
     pub fn direction(&self) -> Dir3 {
         self.inner.direction
     }
@@ -32,6 +110,28 @@ impl Ray {
         self.direction().dot(normal.into()) > 0.0
     }
 
+    /// Move the origin `distance` along the given surface normal,
+    /// onto the side of the surface the ray is heading towards.
+    ///
+    /// Reflected rays are pushed out along the normal and transmitted rays are pushed through,
+    /// so neither starts out inside the surface they left.
+    pub fn offset_along_normal(&self, normal: Dir3, distance: f32) -> Self {
+        let side = if self.facing_same_general_direction(normal) {
+            1.0
+        } else {
+            -1.0
+        };
+
+        Self {
+            inner: Ray3d {
+                origin: self.origin() + side * distance * normal.as_vec3(),
+                direction: self.direction(),
+            },
+            kind: self.kind,
+            time: self.time,
+        }
+    }
+
     /// A position some distance along the ray
     pub fn at(&self, t: f32) -> Vec3 {
         self.inner.get_point(t)
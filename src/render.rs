@@ -0,0 +1,286 @@
+//! Rendering an image as tiles spread over worker threads,
+//! or progressively in passes over the whole image, see [`Camera::render_progressive`].
+
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use bevy_color::LinearRgba;
+use rayon::prelude::*;
+
+use crate::{
+    camera::Camera,
+    cancel::{CancellationToken, RenderStatus},
+    hittable::Hittable,
+    stats,
+    tile::Tile,
+};
+
+/// How far along a render is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    pub pixels_done: usize,
+    pub pixels_total: usize,
+    /// Rays traced for the finished tiles, including shadow rays.
+    pub rays: u64,
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Between 0 and 1, by pixels.
+    pub fn fraction(&self) -> f64 {
+        if self.pixels_total == 0 {
+            1.0
+        } else {
+            self.pixels_done as f64 / self.pixels_total as f64
+        }
+    }
+
+    /// Time left if the remaining pixels go as fast as the ones done so far.
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        (fraction > 0.0).then(|| self.elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tiles_done == self.tiles_total
+    }
+}
+
+/// Per pixel sums of the samples of a progressive render so far, before exposure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulation {
+    pub width: usize,
+    /// Row-major.
+    pub sums: Vec<LinearRgba>,
+    /// Samples summed into each row. A pass cut short leaves some rows one sample behind.
+    pub row_samples: Vec<usize>,
+}
+
+impl Accumulation {
+    /// Nothing summed yet.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            sums: vec![LinearRgba::NONE; width * height],
+            row_samples: vec![0; height],
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.row_samples.len()
+    }
+
+    /// Passes every row has finished.
+    pub fn passes(&self) -> usize {
+        self.row_samples.iter().min().copied().unwrap_or_default()
+    }
+}
+
+/// How far a progressive render has come, see [`Camera::render_progressive`].
+#[derive(Debug)]
+pub struct Snapshot<'a> {
+    /// Passes done so far, each one sample per pixel.
+    pub passes: usize,
+    /// Since this render started, not counting renders it was resumed from.
+    pub elapsed: Duration,
+    /// See [`Camera::accumulated_image`] for the image so far.
+    pub accumulation: &'a Accumulation,
+}
+
+/// A tile which just finished, see [`TileScheduler::run`].
+#[derive(Debug)]
+pub struct TileDone<'a> {
+    pub tile: Tile,
+    /// Row-major within the tile.
+    pub pixels: &'a [LinearRgba],
+    /// Including this tile.
+    pub progress: Progress,
+}
+
+/// Splits an image into tiles and renders them on the camera's worker threads.
+///
+/// Tiles are handed out to whichever thread is free, so they finish in no particular order.
+/// Each finished tile is passed back to the thread which called [`TileScheduler::run`],
+/// which is where progress is reported, cancellation is noticed and the image is filled in.
+#[derive(Clone)]
+pub struct TileScheduler<'a> {
+    camera: &'a Camera,
+    tiles: Vec<Tile>,
+}
+
+impl<'a> TileScheduler<'a> {
+    /// Cover the camera's image with tiles of at most `tile_size` by `tile_size` pixels.
+    pub fn new(camera: &'a Camera, tile_size: usize) -> Self {
+        Self {
+            camera,
+            tiles: Tile::grid(camera.im_width, camera.im_height, tile_size),
+        }
+    }
+
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    /// Render every tile, calling `on_tile` for each as soon as it's done,
+    /// after the camera's progress callback. Returning an error from it stops the render.
+    ///
+    /// No new tiles are started once the token is cancelled.
+    /// Tiles not rendered by then are left transparent black in the returned image, row-major.
+    ///
+    /// The calling thread only waits for tiles, the rendering happens on
+    /// the camera's thread pool. So don't call this from a task on that pool.
+    pub fn run<E>(
+        &self,
+        world: &dyn Hittable,
+        token: &CancellationToken,
+        mut on_tile: impl FnMut(TileDone) -> Result<(), E>,
+    ) -> Result<(RenderStatus, Vec<LinearRgba>), E> {
+        let camera = self.camera;
+        let start = Instant::now();
+        let mut progress = Progress {
+            tiles_done: 0,
+            tiles_total: self.tiles.len(),
+            pixels_done: 0,
+            pixels_total: camera.im_width * camera.im_height,
+            rays: 0,
+            elapsed: Duration::ZERO,
+        };
+        let mut image = vec![LinearRgba::NONE; camera.im_width * camera.im_height];
+
+        // Cancelled when `on_tile` fails, without cancelling the caller's token
+        let stop = CancellationToken::new();
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                camera.in_thread_pool(|| {
+                    self.tiles.par_iter().for_each_with(sender, |sender, tile| {
+                        if token.is_cancelled() || stop.is_cancelled() {
+                            return;
+                        }
+
+                        let rays = stats::rays_traced_on_thread();
+                        let pixels = camera.render_tile(world, tile);
+                        let rays = stats::rays_traced_on_thread() - rays;
+                        // Fails only if the receiving end gave up
+                        let _ = sender.send((*tile, pixels, rays));
+                    })
+                })
+            });
+
+            for (tile, pixels, rays) in receiver {
+                for ((row, col), pixel) in tile.pixels().zip(&pixels) {
+                    image[row * camera.im_width + col] = *pixel;
+                }
+
+                progress.tiles_done += 1;
+                progress.pixels_done += pixels.len();
+                progress.rays += rays;
+                progress.elapsed = start.elapsed();
+
+                if let Some(report) = &camera.progress {
+                    report(&progress);
+                }
+                let result = on_tile(TileDone {
+                    tile,
+                    pixels: &pixels,
+                    progress,
+                });
+                if let Err(error) = result {
+                    stop.cancel();
+                    return Err(error);
+                }
+            }
+
+            let status = if progress.is_finished() {
+                RenderStatus::Complete
+            } else {
+                RenderStatus::Cancelled
+            };
+            Ok((status, image))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::{
+        hittable::Hittables,
+        scenes::{self, MaterialWeights},
+    };
+
+    #[test]
+    fn every_tile_once_on_any_thread() {
+        let mut camera = Camera::builder()
+            .resolution(20, 12)
+            .samples(2)
+            .build()
+            .unwrap();
+        let world = scenes::random_spheres(1, 2, MaterialWeights::default());
+
+        let expected: Vec<LinearRgba> = (0..camera.im_height)
+            .flat_map(|row| (0..camera.im_width).map(move |col| (row, col)))
+            .map(|(row, col)| camera.render_pixel(&world, row, col))
+            .collect();
+
+        for threads in [1, 3] {
+            camera.threads = Some(threads);
+            let scheduler = TileScheduler::new(&camera, 8);
+            assert_eq!(scheduler.tiles().len(), 6);
+
+            let mut finished = vec![];
+            let (status, image) = scheduler
+                .run(&world, &CancellationToken::new(), |done| {
+                    assert_eq!(done.progress.tiles_total, 6);
+                    assert_eq!(done.pixels.len(), done.tile.width * done.tile.height);
+                    finished.push((done.progress.tiles_done, done.tile));
+                    Ok::<_, Infallible>(())
+                })
+                .unwrap();
+
+            assert_eq!(status, RenderStatus::Complete);
+            assert_eq!(image, expected);
+
+            let counts: Vec<usize> = finished.iter().map(|(done, _)| *done).collect();
+            assert_eq!(counts, [1, 2, 3, 4, 5, 6]);
+            let mut tiles: Vec<Tile> = finished.iter().map(|(_, tile)| *tile).collect();
+            tiles.sort_by_key(|tile| (tile.y, tile.x));
+            assert_eq!(tiles, scheduler.tiles());
+        }
+    }
+
+    #[test]
+    fn progress_per_tile() {
+        let reports = Arc::new(Mutex::new(vec![]));
+        let camera = {
+            let reports = reports.clone();
+            Camera::builder()
+                .resolution(10, 6)
+                .samples(2)
+                .tile_size(4)
+                .progress(move |progress| reports.lock().unwrap().push(*progress))
+                .build()
+                .unwrap()
+        };
+        camera.render_linear(&Hittables::default());
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 6);
+        let last = reports.last().unwrap();
+        assert!(last.is_finished());
+        assert_eq!(last.pixels_done, 60);
+        // One camera ray per sample, which sees the sky
+        assert_eq!(last.rays, 120);
+        assert_eq!(last.eta(), Some(Duration::ZERO));
+    }
+}
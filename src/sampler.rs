@@ -0,0 +1,360 @@
+//! Where within a pixel each sample goes.
+
+use std::{str::FromStr, sync::OnceLock};
+
+use anyhow::bail;
+use bevy_math::{UVec2, Vec2};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::random;
+
+/// Structured samples per pixel are limited to this many strata per axis,
+/// so time budgeted renders (which have no sample count) still cover the pixel early.
+const MAX_STRATA: usize = 16;
+
+/// The blue noise offsets repeat every this many pixels on both axes.
+const BLUE_NOISE_SIZE: usize = 64;
+
+/// How sample positions within a pixel are chosen.
+///
+/// The structured samplers use the same pattern in every pixel, shifted by a random
+/// per-pixel offset (a Cranley-Patterson rotation). Otherwise neighboring pixels would
+/// make the same errors, which shows up as patterns instead of noise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PixelSampler {
+    /// Independent uniform samples.
+    #[default]
+    Random,
+    /// One jittered sample per cell of a grid over the pixel.
+    /// Samples past the largest square grid which fits, e.g. the tenth of ten, are uniform,
+    /// so no cell gets more than its share.
+    Stratified,
+    /// The Halton sequence in bases 2 and 3.
+    Halton,
+    /// The first two dimensions of the Sobol sequence. Any power of two samples
+    /// puts exactly one in each cell of every such grid of that many cells, not only squares.
+    Sobol,
+    /// Sobol, rotated by blue noise instead of white noise. Neighboring pixels then err
+    /// in opposite directions, so what noise is left is fine grained instead of blotchy.
+    BlueNoise,
+}
+
+impl FromStr for PixelSampler {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "random" => Ok(PixelSampler::Random),
+            "stratified" => Ok(PixelSampler::Stratified),
+            "halton" => Ok(PixelSampler::Halton),
+            "sobol" => Ok(PixelSampler::Sobol),
+            "blue-noise" => Ok(PixelSampler::BlueNoise),
+            other => bail!(
+                "unknown sampler {other:?}, expected random, stratified, halton, sobol or blue-noise"
+            ),
+        }
+    }
+}
+
+impl PixelSampler {
+    /// The position of the given sample within a pixel, in `[0, 1)` on both axes.
+    /// `seed` and `pixel`, its column and row, decide the rotation of structured patterns,
+    /// `rng` any jitter.
+    pub fn sample(
+        self,
+        seed: u64,
+        pixel: UVec2,
+        sample: usize,
+        samples: usize,
+        rng: &mut (impl Rng + ?Sized),
+    ) -> Vec2 {
+        let point = match self {
+            PixelSampler::Random => return Vec2::new(rng.gen(), rng.gen()),
+            PixelSampler::Stratified => {
+                let strata = (samples as f64).sqrt().floor() as usize;
+                let strata = strata.clamp(1, MAX_STRATA);
+
+                if sample >= strata * strata {
+                    return Vec2::new(rng.gen(), rng.gen());
+                }
+                let (x, y) = (sample % strata, sample / strata);
+                let jitter = Vec2::new(rng.gen(), rng.gen());
+
+                (Vec2::new(x as f32, y as f32) + jitter) / strata as f32
+            }
+            PixelSampler::Halton => {
+                Vec2::new(radical_inverse(2, sample), radical_inverse(3, sample))
+            }
+            PixelSampler::Sobol => Vec2::new(sobol(0, sample), sobol(1, sample)),
+            PixelSampler::BlueNoise => {
+                let point = Vec2::new(sobol(0, sample), sobol(1, sample));
+                return rotate(point, blue_noise_rotation(seed, pixel));
+            }
+        };
+
+        rotate(point, random::pixel_rotation(seed, pixel))
+    }
+}
+
+/// An offset for the given pixel from a tile of blue noise,
+/// which the seed shifts around so different seeds don't repeat the same errors.
+fn blue_noise_rotation(seed: u64, pixel: UVec2) -> Vec2 {
+    let shift = (random::pixel_rotation(seed, UVec2::ZERO) * BLUE_NOISE_SIZE as f32).as_uvec2();
+    blue_noise_tile()[tile_cell(pixel + shift)]
+}
+
+/// A value in `(0, 1)` for the given pixel from a tile of blue noise, e.g. to dither with.
+pub(crate) fn blue_noise(pixel: UVec2) -> f32 {
+    // Centered in its rank, so the tile averages to one half
+    blue_noise_tile()[tile_cell(pixel)].x + 0.5 / (BLUE_NOISE_SIZE * BLUE_NOISE_SIZE) as f32
+}
+
+fn blue_noise_tile() -> &'static [Vec2] {
+    static TILE: OnceLock<Vec<Vec2>> = OnceLock::new();
+    TILE.get_or_init(|| {
+        let (x, y) = (blue_noise_mask(0), blue_noise_mask(1));
+        x.into_iter().zip(y).map(|(x, y)| Vec2::new(x, y)).collect()
+    })
+}
+
+/// Where the pixel falls in the repeating tile, row-major.
+fn tile_cell(pixel: UVec2) -> usize {
+    let cell = pixel % BLUE_NOISE_SIZE as u32;
+    cell.y as usize * BLUE_NOISE_SIZE + cell.x as usize
+}
+
+/// A square of values in `[0, 1)`, each once, where close cells have distant values.
+///
+/// Made by the void-and-cluster idea: cells are ranked in the order they're picked,
+/// each time picking the one furthest from all picked so far as measured by the
+/// sum of a Gaussian of the distance to them, wrapping around the edges.
+fn blue_noise_mask(seed: u64) -> Vec<f32> {
+    const SIZE: usize = BLUE_NOISE_SIZE;
+    const SIGMA: f32 = 1.5;
+    // Past this many cells away the Gaussian is negligible
+    const REACH: isize = 6;
+
+    // Tiny random energies decide between the many equally empty cells
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut energy: Vec<f32> = (0..SIZE * SIZE).map(|_| rng.gen::<f32>() * 1e-6).collect();
+    let mut ranks = vec![0.0; SIZE * SIZE];
+
+    for rank in 0..SIZE * SIZE {
+        let (picked, _) = energy
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("the mask has cells");
+        ranks[picked] = rank as f32 / (SIZE * SIZE) as f32;
+        // Never pick it again
+        energy[picked] = f32::INFINITY;
+
+        let (px, py) = ((picked % SIZE) as isize, (picked / SIZE) as isize);
+        for dy in -REACH..=REACH {
+            for dx in -REACH..=REACH {
+                let x = (px + dx).rem_euclid(SIZE as isize) as usize;
+                let y = (py + dy).rem_euclid(SIZE as isize) as usize;
+                let distance_squared = (dx * dx + dy * dy) as f32;
+                energy[y * SIZE + x] += (-distance_squared / (2.0 * SIGMA * SIGMA)).exp();
+            }
+        }
+    }
+
+    ranks
+}
+
+/// Mirror the digits of `index` in `base` around the decimal point.
+fn radical_inverse(base: usize, mut index: usize) -> f32 {
+    let mut result = 0.0;
+    let mut scale = 1.0 / base as f64;
+
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale /= base as f64;
+    }
+
+    result as f32
+}
+
+/// A dimension of the Sobol sequence, only the first two are supported.
+///
+/// Each set bit of `index` flips the bits of a direction number, which for the first
+/// dimension gives the same points as base 2 Halton and for the second comes from the
+/// primitive polynomial x + 1.
+fn sobol(dimension: usize, index: usize) -> f32 {
+    debug_assert!(
+        dimension < 2,
+        "sobol: dimension {dimension} isn't supported"
+    );
+
+    let mut direction = 1u32 << 31;
+    let mut result = 0u32;
+    // Indices past 2^32 repeat, but no pixel gets that many samples
+    let mut index = index as u32;
+
+    while index > 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        direction = match dimension {
+            0 => direction >> 1,
+            _ => direction ^ (direction >> 1),
+        };
+        index >>= 1;
+    }
+
+    // Only the top 24 bits fit an f32 without rounding up to 1
+    (result >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Shift by `offset`, wrapping around within `[0, 1)`.
+fn rotate(point: Vec2, offset: Vec2) -> Vec2 {
+    let rotated = (point + offset).fract();
+    // fract can round up to exactly 1
+    rotated.min(Vec2::splat(1.0 - f32::EPSILON))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_differs_per_pixel() {
+        for sampler in [
+            PixelSampler::Stratified,
+            PixelSampler::Halton,
+            PixelSampler::Sobol,
+            PixelSampler::BlueNoise,
+        ] {
+            let mut rng = random::sample_rng(0, 0, 0);
+            let mut sample = |pixel, i| sampler.sample(0, pixel, i, 16, &mut rng);
+            let first: Vec<Vec2> = (0..16).map(|i| sample(UVec2::ZERO, i)).collect();
+            let second: Vec<Vec2> = (0..16).map(|i| sample(UVec2::X, i)).collect();
+
+            assert_ne!(first, second, "{sampler:?}");
+            for point in first.iter().chain(&second) {
+                assert!(point.cmpge(Vec2::ZERO).all() && point.cmplt(Vec2::ONE).all());
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_covers_every_cell() {
+        let pixel = UVec2::new(5, 0);
+        let rotation = random::pixel_rotation(3, pixel);
+        let mut rng = random::sample_rng(3, 5, 0);
+        let mut cells = [0; 16];
+
+        for sample in 0..16 {
+            // Undo the rotation to find the cell
+            let point = PixelSampler::Stratified.sample(3, pixel, sample, 16, &mut rng);
+            let point = (point - rotation + Vec2::ONE).fract();
+            let cell = (point * 4.0).floor();
+            cells[cell.y as usize * 4 + cell.x as usize] += 1;
+        }
+
+        assert_eq!(cells, [1; 16]);
+    }
+
+    #[test]
+    fn stratified_leftovers_are_uniform() {
+        let pixel = UVec2::new(5, 0);
+        let rotation = random::pixel_rotation(3, pixel);
+        let cell = |point: Vec2| {
+            let point = ((point - rotation + Vec2::ONE).fract() * 3.0).floor();
+            point.y as usize * 3 + point.x as usize
+        };
+
+        // Ten samples: one in each cell of a 3x3 grid, and the tenth anywhere
+        let mut cells = [0; 9];
+        let mut leftovers = [0; 9];
+        for draw in 0..900 {
+            let mut rng = random::sample_rng(3, draw, 0);
+            let mut sample = |i| PixelSampler::Stratified.sample(3, pixel, i, 10, &mut rng);
+            if draw == 0 {
+                for i in 0..9 {
+                    cells[cell(sample(i))] += 1;
+                }
+            }
+            leftovers[cell(sample(9))] += 1;
+        }
+
+        assert_eq!(cells, [1; 9]);
+        assert!(leftovers.iter().all(|&count| count > 50), "{leftovers:?}");
+    }
+
+    #[test]
+    fn sobol_is_stratified_at_powers_of_two() {
+        let first_points: Vec<(f32, f32)> = (0..4).map(|i| (sobol(0, i), sobol(1, i))).collect();
+        assert_eq!(
+            first_points,
+            [(0.0, 0.0), (0.5, 0.5), (0.25, 0.75), (0.75, 0.25)]
+        );
+
+        // 16 points, one in each of 1x16, 2x8, 4x4, 8x2 and 16x1 grids of cells
+        for columns in [1, 2, 4, 8, 16] {
+            let rows = 16 / columns;
+            let mut cells = [0; 16];
+            for i in 0..16 {
+                let x = (sobol(0, i) * columns as f32) as usize;
+                let y = (sobol(1, i) * rows as f32) as usize;
+                cells[y * columns + x] += 1;
+            }
+            assert_eq!(cells, [1; 16], "{columns}x{rows}");
+        }
+    }
+
+    #[test]
+    fn low_discrepancy_converges_faster() {
+        // The area of a disk within the pixel, averaged over many pixels and their rotations
+        let error = |sampler: PixelSampler| {
+            let mut total = 0.0;
+            for pixel in 0..64 {
+                let mut rng = random::sample_rng(1, pixel, 0);
+                let position = UVec2::new(pixel as u32, 0);
+                let inside = (0..256)
+                    .map(|i| sampler.sample(1, position, i, 256, &mut rng))
+                    .filter(|point| point.distance_squared(Vec2::splat(0.5)) < 0.16)
+                    .count();
+                total += (inside as f32 / 256.0 - std::f32::consts::PI * 0.16).abs();
+            }
+            total / 64.0
+        };
+
+        let random = error(PixelSampler::Random);
+        for sampler in [
+            PixelSampler::Halton,
+            PixelSampler::Sobol,
+            PixelSampler::BlueNoise,
+        ] {
+            let error = error(sampler);
+            assert!(error < 0.5 * random, "{sampler:?} {error} {random}");
+        }
+    }
+
+    #[test]
+    fn blue_noise_has_no_clumps() {
+        let mask = blue_noise_mask(0);
+
+        // Every value once
+        let mut ranks: Vec<usize> = mask
+            .iter()
+            .map(|value| (value * mask.len() as f32) as usize)
+            .collect();
+        ranks.sort();
+        assert!(ranks.iter().enumerate().all(|(i, rank)| i == *rank));
+
+        // Blurred, the highs and lows cancel out, leaving much less variance than white noise
+        let size = BLUE_NOISE_SIZE;
+        let blurred = (0..mask.len()).map(|cell| {
+            let (x, y) = (cell % size, cell / size);
+            let neighbors = (0..9).map(|i| mask[(y + i / 3) % size * size + (x + i % 3) % size]);
+            neighbors.sum::<f32>() / 9.0
+        });
+        let variance = blurred.map(|mean| (mean - 0.5).powi(2)).sum::<f32>() / mask.len() as f32;
+        let white = 1.0 / 12.0 / 9.0;
+        assert!(variance < 0.3 * white, "{variance}");
+    }
+}
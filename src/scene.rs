@@ -0,0 +1,269 @@
+use std::{path::Path, str::FromStr, time::Instant};
+
+use anyhow::bail;
+use bevy_color::LinearRgba;
+use bevy_math::{Dir3, Vec3};
+use tracing::{info, warn};
+
+use crate::{
+    background::Background,
+    bvh::{Bvh, BvhSplit},
+    camera::Camera,
+    grid::Grid,
+    hittable::{Hittable, Hittables},
+    kdtree::KdTree,
+    output::{self, ImageFormat},
+    ray::Ray,
+    stats::{RenderTimings, SceneStats},
+};
+
+/// How rays find what they hit in a scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Accelerator {
+    /// Test every object. Fine for a handful of them.
+    List,
+    /// See [`Bvh`].
+    Bvh(BvhSplit),
+    /// See [`KdTree`].
+    KdTree,
+    /// See [`Grid`].
+    Grid,
+}
+
+impl Default for Accelerator {
+    fn default() -> Self {
+        Self::Bvh(BvhSplit::Sah)
+    }
+}
+
+impl Accelerator {
+    /// Build the structure over the objects of the world.
+    pub fn build(self, world: &Hittables) -> Box<dyn Hittable> {
+        let start = Instant::now();
+
+        let accelerated: Box<dyn Hittable> = match self {
+            Accelerator::List => Box::new(world.clone()),
+            Accelerator::Bvh(split) => Box::new(Bvh::new(world, split)),
+            Accelerator::KdTree => Box::new(KdTree::new(world)),
+            Accelerator::Grid => Box::new(Grid::new(world)),
+        };
+
+        info!(
+            "Built {self:?} over {} objects in {:.1?}",
+            world.objects.len(),
+            start.elapsed()
+        );
+
+        accelerated
+    }
+}
+
+impl FromStr for Accelerator {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "list" => Ok(Self::List),
+            "bvh" => Ok(Self::Bvh(BvhSplit::Sah)),
+            "bvh-median" => Ok(Self::Bvh(BvhSplit::Median)),
+            "kd-tree" | "kdtree" => Ok(Self::KdTree),
+            "grid" => Ok(Self::Grid),
+            other => bail!(
+                "unknown accelerator {other:?}, expected list, bvh, bvh-median, kd-tree or grid"
+            ),
+        }
+    }
+}
+
+/// Everything needed to render an image.
+pub struct Scene {
+    pub camera: Camera,
+    pub world: Hittables,
+    pub accelerator: Accelerator,
+}
+
+impl Scene {
+    pub fn new(camera: Camera, world: Hittables) -> Self {
+        Self {
+            camera,
+            world,
+            accelerator: Accelerator::default(),
+        }
+    }
+
+    /// Look for things which would make the render silently wrong,
+    /// such as NaN positions or out of range material parameters.
+    ///
+    /// Returns a description of each problem found.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        let camera = &self.camera;
+        if camera.im_width == 0 || camera.im_height == 0 {
+            problems.push(format!(
+                "camera: image size {}x{} has no pixels",
+                camera.im_width, camera.im_height
+            ));
+        }
+        if camera.samples_per_pixel == 0 {
+            problems.push("camera: zero samples per pixel renders black".into());
+        }
+        if !camera.cam_origin.is_finite() {
+            problems.push(format!("camera: origin {} is not finite", camera.cam_origin));
+        }
+        if camera.min_dist < 0.0 {
+            problems.push(format!("camera: negative min_dist {}", camera.min_dist));
+        }
+        if camera.defocus_angle > 0.0 && camera.focus_distance <= 0.0 {
+            problems.push(format!(
+                "camera: focus distance {} is not in front of the camera",
+                camera.focus_distance
+            ));
+        }
+        if self.camera_inside_geometry() {
+            problems.push(format!(
+                "camera: origin {} appears to be inside geometry",
+                camera.cam_origin
+            ));
+        }
+
+        for light in &camera.lights {
+            light.validate(&mut problems);
+        }
+        if let Background::Environment(map) = &camera.background {
+            map.validate(&mut problems);
+        }
+        self.world.validate(&mut problems);
+
+        problems
+    }
+
+    /// Inside a closed object every direction hits the inside of a surface.
+    fn camera_inside_geometry(&self) -> bool {
+        let origin = self.camera.cam_origin;
+        if !origin.is_finite() {
+            return false;
+        }
+
+        [Dir3::X, Dir3::NEG_X, Dir3::Y, Dir3::NEG_Y, Dir3::Z, Dir3::NEG_Z]
+            .into_iter()
+            .all(|dir| {
+                let ray = Ray::new(origin, Vec3::from(dir));
+                self.world
+                    .hit(&ray, self.camera.min_dist..f32::MAX)
+                    .is_some_and(|hit| !hit.front_face)
+            })
+    }
+
+    /// Count what the world is made of, as built by the `accelerator`, and what lights it.
+    pub fn stats(&self) -> SceneStats {
+        let mut stats = SceneStats::default();
+        self.accelerator.build(&self.world).stats(&mut stats);
+        stats.lights = self.camera.lights.len();
+        stats.light_objects = self.camera.light_objects.objects.len();
+        stats
+    }
+
+    /// Validate the scene, logging any problems, then render it quantized to 8-bit RGB.
+    pub fn render_rgb8(&self) -> Vec<u8> {
+        self.render_rgb8_timed(&mut RenderTimings::default())
+    }
+
+    fn render_linear_timed(&self, timings: &mut RenderTimings) -> Vec<LinearRgba> {
+        for problem in self.validate() {
+            warn!("{problem}");
+        }
+
+        let start = Instant::now();
+        let world = self.accelerator.build(&self.world);
+        timings.acceleration = start.elapsed();
+
+        let start = Instant::now();
+        let image = self.camera.render_linear(world.as_ref());
+        timings.tracing = start.elapsed();
+
+        image
+    }
+
+    fn render_rgb8_timed(&self, timings: &mut RenderTimings) -> Vec<u8> {
+        let image = self.render_linear_timed(timings);
+
+        let start = Instant::now();
+        let data = self.camera.quantize(&image);
+        timings.post_processing = start.elapsed();
+
+        data
+    }
+
+    /// Validate the scene, logging any problems, then render it.
+    /// The format is picked from the file extension. EXR and HDR files get the unquantized floats.
+    ///
+    /// Logs and returns how long each phase took.
+    pub fn render(&self, output_file: impl AsRef<Path>) -> anyhow::Result<RenderTimings> {
+        let mut timings = RenderTimings::default();
+
+        let format = ImageFormat::from_path(&output_file);
+        if format.is_float() {
+            let image = self.render_linear_timed(&mut timings);
+
+            let start = Instant::now();
+            output::write_linear(format, self.camera.im_width, &image, output_file)?;
+            timings.encoding = start.elapsed();
+        } else {
+            let image = self.render_linear_timed(&mut timings);
+
+            let start = Instant::now();
+            let data = self.camera.quantize_image(&image);
+            timings.post_processing = start.elapsed();
+
+            let start = Instant::now();
+            output::write_pathlike_quantized(self.camera.im_height, &data, output_file, None)?;
+            timings.encoding = start.elapsed();
+        }
+
+        info!("{timings}");
+
+        Ok(timings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::Color;
+
+    use super::*;
+    use crate::{material::Metal, objects::Sphere};
+
+    #[test]
+    fn reports_problems() {
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: Vec3::new(0.0, 0.0, -1.0),
+            radius: -0.5,
+            material: Metal {
+                texture: Color::WHITE.into(),
+                fuzz: 2.0,
+            }
+            .into(),
+        });
+
+        let problems = Scene::new(Camera::new(), world).validate();
+
+        assert_eq!(problems.len(), 2, "{problems:?}");
+    }
+
+    #[test]
+    fn camera_inside_sphere() {
+        let mut world = Hittables::default();
+        world.add(Sphere {
+            center: Vec3::ZERO,
+            radius: 10.0,
+            ..Default::default()
+        });
+
+        let problems = Scene::new(Camera::new(), world).validate();
+
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].contains("inside"));
+    }
+}
@@ -0,0 +1,683 @@
+//! Scenes described in RON or JSON files, so they can be written without recompiling.
+//!
+//! A file holds the camera settings and a list of objects, each with its material inline:
+//!
+//! ```ron
+//! (
+//!     camera: (
+//!         position: (13, 2, 3),
+//!         look_at: (0, 0, 0),
+//!         vfov: 20,
+//!     ),
+//!     objects: [
+//!         Sphere(center: (0, -1000, 0), radius: 1000, material: Lambertian(Solid((0.5, 0.5, 0.5)))),
+//!         Sphere(center: (0, 1, 0), radius: 1, material: Dielectric(refraction_index: 1.5)),
+//!         RotateY(degrees: 15, object: Cuboid(a: (3, 0, 0), b: (4, 1, 1), material: Metal(albedo: Solid((0.7, 0.6, 0.5)), fuzz: 0))),
+//!     ],
+//! )
+//! ```
+//!
+//! Optional settings may be left out, and given without `Some(..)`.
+//! Paths, e.g. of images and models, are relative to the scene file.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context};
+use bevy_color::{Color, LinearRgba};
+use bevy_math::{Dir3, Vec3};
+use ron::extensions::Extensions;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    background::Background,
+    camera::Camera,
+    environment::EnvironmentMap,
+    hittable::{Hittable, Hittables},
+    light::{Light, LightKind},
+    material::{Dielectric, DiffuseLight, DynMaterial, Isotropic, Lambertian, Metal},
+    medium::ConstantMedium,
+    obj,
+    objects::{Cone, Cuboid, Cylinder, Disk, MovingSphere, Quad, Sphere, Triangle},
+    output_color::OutputColor,
+    scene::Scene,
+    sky::PhysicalSky,
+    stl,
+    texture::{DynTexture, ImageTexture, NoiseTexture},
+    tonemap::Tonemap,
+    transform::{RotateY, Translate},
+};
+
+/// A linear RGB color.
+pub type Rgb = [f32; 3];
+
+/// How a scene file is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneFormat {
+    Ron,
+    Json,
+}
+
+impl SceneFormat {
+    /// JSON for `.json` files, else RON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Ron,
+        }
+    }
+}
+
+/// Everything in a scene file.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneFile {
+    pub camera: CameraSettings,
+    pub objects: Vec<Object>,
+    /// Lights without geometry, found by shadow rays, see [`Camera::lights`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<LightSettings>,
+}
+
+/// What [`Camera::builder`] is given.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraSettings {
+    /// Width and height in pixels.
+    pub resolution: (usize, usize),
+    pub samples: usize,
+    pub bounces: usize,
+    pub min_dist: f32,
+    pub srgb: bool,
+    /// See [`Camera::output_color`]. Its transfer replaces `srgb` if set.
+    pub output_color: Option<OutputColor>,
+    /// See [`Camera::tonemap`].
+    pub tonemap: Tonemap,
+    /// See [`Camera::exposure_compensation`], in stops.
+    pub exposure_compensation: f32,
+    pub position: Vec3,
+    /// If set, the camera turns towards this point, keeping `up` upwards.
+    pub look_at: Option<Vec3>,
+    pub up: Vec3,
+    /// Vertical field of view in degrees.
+    pub vfov: Option<f32>,
+    /// See [`Camera::defocus_angle`].
+    pub defocus_angle: f32,
+    pub focus_distance: f32,
+    /// See [`Camera::shutter`].
+    pub shutter: (f32, f32),
+    pub background: BackgroundSettings,
+    /// See [`Camera::transparent_background`].
+    pub transparent_background: bool,
+    /// Aim scattered rays at emissive spheres and quads, see [`Camera::light_objects`].
+    pub aim_at_lights: bool,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        let camera = Camera::new();
+
+        Self {
+            resolution: (camera.im_width, camera.im_height),
+            samples: 100,
+            bounces: 50,
+            min_dist: 0.001,
+            srgb: true,
+            output_color: None,
+            tonemap: camera.tonemap,
+            exposure_compensation: camera.exposure_compensation,
+            position: camera.cam_origin,
+            look_at: None,
+            up: Vec3::Y,
+            vfov: None,
+            defocus_angle: camera.defocus_angle,
+            focus_distance: camera.focus_distance,
+            shutter: (camera.shutter.start, camera.shutter.end),
+            background: BackgroundSettings::Sky,
+            transparent_background: false,
+            aim_at_lights: false,
+        }
+    }
+}
+
+/// See [`Background`].
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum BackgroundSettings {
+    /// The book's blue sky.
+    #[default]
+    Sky,
+    Solid(Rgb),
+    Gradient {
+        bottom: Rgb,
+        top: Rgb,
+    },
+    /// An equirectangular HDR or EXR image, see [`EnvironmentMap`].
+    Environment(PathBuf),
+    /// Daylight with the sun towards `sun`, see [`PhysicalSky`].
+    PhysicalSky {
+        sun: Vec3,
+        turbidity: f32,
+    },
+}
+
+/// An object in the world.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Object {
+    Sphere {
+        center: Vec3,
+        radius: f32,
+        material: MaterialSettings,
+    },
+    /// See [`MovingSphere`].
+    MovingSphere {
+        from: Vec3,
+        to: Vec3,
+        radius: f32,
+        material: MaterialSettings,
+    },
+    Quad {
+        corner: Vec3,
+        u: Vec3,
+        v: Vec3,
+        material: MaterialSettings,
+    },
+    /// An axis-aligned box between two opposite corners.
+    Cuboid {
+        a: Vec3,
+        b: Vec3,
+        material: MaterialSettings,
+    },
+    Triangle {
+        a: Vec3,
+        b: Vec3,
+        c: Vec3,
+        material: MaterialSettings,
+    },
+    Disk {
+        center: Vec3,
+        normal: Vec3,
+        radius: f32,
+        material: MaterialSettings,
+    },
+    Cylinder {
+        base: Vec3,
+        axis: Vec3,
+        radius: f32,
+        material: MaterialSettings,
+    },
+    Cone {
+        base: Vec3,
+        axis: Vec3,
+        radius: f32,
+        material: MaterialSettings,
+    },
+    /// A Wavefront OBJ or STL file, all in one material.
+    Model {
+        path: PathBuf,
+        material: MaterialSettings,
+    },
+    Translate {
+        offset: Vec3,
+        object: Box<Object>,
+    },
+    /// Turned counter-clockwise seen from above, around the Y axis through the origin.
+    RotateY {
+        degrees: f32,
+        object: Box<Object>,
+    },
+    /// Smoke or fog filling a convex `boundary`, see [`ConstantMedium`].
+    ConstantMedium {
+        boundary: Box<Object>,
+        density: f32,
+        texture: TextureSettings,
+    },
+}
+
+/// See [`Light`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightSettings {
+    pub kind: LightKindSettings,
+    /// Ignored by directional lights.
+    pub position: Vec3,
+    pub color: Rgb,
+    pub intensity: f32,
+    pub falloff: f32,
+    pub radius: Option<f32>,
+    pub source_radius: f32,
+}
+
+impl Default for LightSettings {
+    fn default() -> Self {
+        let light = Light::point(Vec3::ZERO, Color::WHITE, 1.0);
+
+        Self {
+            kind: LightKindSettings::Point,
+            position: light.position,
+            color: [1.0; 3],
+            intensity: light.intensity,
+            falloff: light.falloff,
+            radius: light.radius,
+            source_radius: light.source_radius,
+        }
+    }
+}
+
+/// See [`LightKind`]. Angles are in degrees.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum LightKindSettings {
+    #[default]
+    Point,
+    /// Shining along `direction` in a cone `angle` degrees from it.
+    Spot { direction: Vec3, angle: f32 },
+    /// Shining along `direction`, from a source `angular_radius` degrees across.
+    Directional {
+        direction: Vec3,
+        #[serde(default)]
+        angular_radius: f32,
+    },
+}
+
+/// See [`crate::material`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MaterialSettings {
+    Lambertian(TextureSettings),
+    Metal { albedo: TextureSettings, fuzz: f32 },
+    Dielectric { refraction_index: f32 },
+    DiffuseLight(TextureSettings),
+    Isotropic(TextureSettings),
+}
+
+/// See [`crate::texture`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TextureSettings {
+    Solid(Rgb),
+    /// White marble from Perlin noise, see [`NoiseTexture::marble`].
+    Marble {
+        seed: u64,
+        scale: f32,
+    },
+    Image(PathBuf),
+}
+
+impl SceneFile {
+    /// Read a scene file, RON or JSON by its extension.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading scene file {}", path.display()))?;
+
+        Self::parse(&text, SceneFormat::from_path(path))
+            .with_context(|| format!("parsing scene file {}", path.display()))
+    }
+
+    pub fn parse(text: &str, format: SceneFormat) -> anyhow::Result<Self> {
+        Ok(match format {
+            SceneFormat::Ron => ron_options().from_str(text)?,
+            SceneFormat::Json => serde_json::from_str(text)?,
+        })
+    }
+
+    /// The text of the file, pretty printed.
+    pub fn to_string(&self, format: SceneFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            SceneFormat::Ron => {
+                let pretty = ron::ser::PrettyConfig::default()
+                    .extensions(Extensions::IMPLICIT_SOME)
+                    .indentor("    ".to_string());
+                ron_options().to_string_pretty(self, pretty)?
+            }
+            SceneFormat::Json => serde_json::to_string_pretty(self)?,
+        })
+    }
+
+    /// Write the scene file, RON or JSON by its extension.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let text = self.to_string(SceneFormat::from_path(path))?;
+        std::fs::write(path, text).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Build the camera and world. Relative paths are looked up in `directory`,
+    /// usually the one the file is in.
+    pub fn build(&self, directory: &Path) -> anyhow::Result<Scene> {
+        let mut camera = self.camera.build(directory)?;
+
+        let mut world = Hittables::default();
+        for object in &self.objects {
+            let built: Arc<Box<dyn Hittable>> = Arc::new(object.build(directory)?);
+            if self.camera.aim_at_lights && object.is_sampleable_light() {
+                camera.light_objects.objects.push(Arc::clone(&built));
+            }
+            world.objects.push(built);
+        }
+
+        for light in &self.lights {
+            camera.lights.push(light.build()?);
+        }
+
+        Ok(Scene::new(camera, world))
+    }
+}
+
+fn ron_options() -> ron::Options {
+    ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME)
+}
+
+fn color([red, green, blue]: Rgb) -> Color {
+    Color::linear_rgb(red, green, blue)
+}
+
+impl CameraSettings {
+    /// An environment map is looked up in `directory` if its path is relative.
+    pub fn build(&self, directory: &Path) -> anyhow::Result<Camera> {
+        let (width, height) = self.resolution;
+        let background = match &self.background {
+            BackgroundSettings::Sky => Background::SKY,
+            BackgroundSettings::Solid(rgb) => Background::Solid(color(*rgb)),
+            BackgroundSettings::Gradient { bottom, top } => Background::Gradient {
+                bottom: color(*bottom),
+                top: color(*top),
+            },
+            BackgroundSettings::Environment(path) => {
+                EnvironmentMap::load(directory.join(path))?.into()
+            }
+            BackgroundSettings::PhysicalSky { sun, turbidity } => {
+                let Ok(sun) = Dir3::new(*sun) else {
+                    bail!("physical sky: sun {sun} has no direction");
+                };
+                PhysicalSky::new(sun, *turbidity).into()
+            }
+        };
+
+        let mut builder = Camera::builder()
+            .resolution(width, height)
+            .samples(self.samples)
+            .bounces(self.bounces)
+            .min_dist(self.min_dist)
+            .srgb(self.srgb)
+            .tonemap(self.tonemap)
+            .exposure_compensation(self.exposure_compensation)
+            .position(self.position)
+            .defocus(self.defocus_angle, self.focus_distance)
+            .shutter(self.shutter.0..self.shutter.1)
+            .background(background)
+            .transparent_background(self.transparent_background);
+        if let Some(target) = self.look_at {
+            builder = builder.look_at(target, self.up);
+        }
+        if let Some(output_color) = self.output_color {
+            builder = builder.output_color(output_color);
+        }
+        if let Some(vfov) = self.vfov {
+            builder = builder.vfov(vfov);
+        }
+
+        builder.build()
+    }
+}
+
+impl Object {
+    pub fn build(&self, directory: &Path) -> anyhow::Result<Box<dyn Hittable>> {
+        let material = |material: &MaterialSettings| material.build(directory);
+
+        Ok(match self {
+            Object::Sphere {
+                center,
+                radius,
+                material: m,
+            } => Box::new(Sphere {
+                center: *center,
+                radius: *radius,
+                material: material(m)?,
+            }),
+            Object::MovingSphere {
+                from,
+                to,
+                radius,
+                material: m,
+            } => Box::new(MovingSphere {
+                from: *from,
+                to: *to,
+                radius: *radius,
+                material: material(m)?,
+            }),
+            Object::Quad {
+                corner,
+                u,
+                v,
+                material: m,
+            } => Box::new(Quad::new(*corner, *u, *v, material(m)?)),
+            Object::Cuboid { a, b, material: m } => Box::new(Cuboid::new(*a, *b, material(m)?)),
+            Object::Triangle {
+                a,
+                b,
+                c,
+                material: m,
+            } => Box::new(Triangle::new(*a, *b, *c, material(m)?)),
+            Object::Disk {
+                center,
+                normal,
+                radius,
+                material: m,
+            } => {
+                let Ok(normal) = Dir3::new(*normal) else {
+                    bail!("disk: normal {normal} has no direction");
+                };
+                Box::new(Disk::new(*center, normal, *radius, material(m)?))
+            }
+            Object::Cylinder {
+                base,
+                axis,
+                radius,
+                material: m,
+            } => Box::new(Cylinder::new(*base, *axis, *radius, material(m)?)),
+            Object::Cone {
+                base,
+                axis,
+                radius,
+                material: m,
+            } => Box::new(Cone::new(*base, *axis, *radius, material(m)?)),
+            Object::Model { path, material: m } => {
+                let path = directory.join(path);
+                let extension = path.extension().unwrap_or_default().to_ascii_lowercase();
+                if extension == "stl" {
+                    Box::new(stl::load(&path, material(m)?)?)
+                } else {
+                    Box::new(obj::load(&path, material(m)?)?)
+                }
+            }
+            Object::Translate { offset, object } => {
+                Box::new(Translate::new(shared(object, directory)?, *offset))
+            }
+            Object::RotateY { degrees, object } => {
+                Box::new(RotateY::new(shared(object, directory)?, *degrees))
+            }
+            Object::ConstantMedium {
+                boundary,
+                density,
+                texture,
+            } => Box::new(ConstantMedium::new(
+                shared(boundary, directory)?,
+                *density,
+                texture.build(directory)?,
+            )),
+        })
+    }
+
+    /// Whether this is a shape [`Camera::light_objects`] can aim at, lit by its material.
+    fn is_sampleable_light(&self) -> bool {
+        match self {
+            Object::Sphere { material, .. } | Object::Quad { material, .. } => {
+                matches!(material, MaterialSettings::DiffuseLight(_))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Wrappers need a sized object.
+fn shared(object: &Object, directory: &Path) -> anyhow::Result<Arc<dyn Hittable>> {
+    Ok(Arc::from(object.build(directory)?))
+}
+
+impl LightSettings {
+    pub fn build(&self) -> anyhow::Result<Light> {
+        let direction = |direction: Vec3| {
+            Dir3::new(direction)
+                .map_err(|_| anyhow::anyhow!("light: direction {direction} has no direction"))
+        };
+        let color = color(self.color);
+
+        let light = match self.kind {
+            LightKindSettings::Point => Light::point(self.position, color, self.intensity),
+            LightKindSettings::Spot {
+                direction: axis,
+                angle,
+            } => Light::spot(
+                self.position,
+                direction(axis)?,
+                angle.to_radians(),
+                color,
+                self.intensity,
+            ),
+            LightKindSettings::Directional {
+                direction: along,
+                angular_radius,
+            } => Light {
+                kind: LightKind::Directional {
+                    direction: direction(along)?,
+                    angular_radius: angular_radius.to_radians(),
+                },
+                ..Light::point(self.position, color, self.intensity)
+            },
+        };
+
+        Ok(Light {
+            falloff: self.falloff,
+            radius: self.radius,
+            source_radius: self.source_radius,
+            ..light
+        })
+    }
+}
+
+impl MaterialSettings {
+    pub fn build(&self, directory: &Path) -> anyhow::Result<DynMaterial> {
+        Ok(match self {
+            MaterialSettings::Lambertian(texture) => {
+                Lambertian::new(texture.build(directory)?).into()
+            }
+            MaterialSettings::Metal { albedo, fuzz } => {
+                Metal::new(albedo.build(directory)?, *fuzz).into()
+            }
+            MaterialSettings::Dielectric { refraction_index } => {
+                Dielectric::refraction_index(*refraction_index).into()
+            }
+            MaterialSettings::DiffuseLight(texture) => {
+                DiffuseLight::new(texture.build(directory)?).into()
+            }
+            MaterialSettings::Isotropic(texture) => {
+                Isotropic::new(texture.build(directory)?).into()
+            }
+        })
+    }
+}
+
+impl TextureSettings {
+    pub fn build(&self, directory: &Path) -> anyhow::Result<DynTexture> {
+        Ok(match self {
+            TextureSettings::Solid([red, green, blue]) => {
+                LinearRgba::rgb(*red, *green, *blue).into()
+            }
+            TextureSettings::Marble { seed, scale } => NoiseTexture::marble(*seed, *scale).into(),
+            TextureSettings::Image(path) => ImageTexture::load(directory.join(path))?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{output_color::Transfer, ray::Ray};
+
+    const GLASS_AND_BOX: &str = r#"
+        (
+            camera: (
+                resolution: (40, 30),
+                position: (0, 1, 5),
+                look_at: (0, 1, 0),
+                vfov: 40,
+                output_color: (transfer: Gamma(2.2), bit_depth: Sixteen, dither: BlueNoise),
+                background: Solid((0, 0, 0)),
+                aim_at_lights: true,
+            ),
+            objects: [
+                Sphere(center: (0, 1, 0), radius: 1, material: Dielectric(refraction_index: 1.5)),
+                Translate(
+                    offset: (3, 0, 0),
+                    object: RotateY(
+                        degrees: 45,
+                        object: Cuboid(a: (-0.5, 0, -0.5), b: (0.5, 1, 0.5), material: Lambertian(Solid((0.2, 0.3, 0.4)))),
+                    ),
+                ),
+                Quad(corner: (-1, 4, -1), u: (2, 0, 0), v: (0, 0, 2), material: DiffuseLight(Solid((4, 4, 4)))),
+            ],
+            lights: [
+                (kind: Spot(direction: (0, -1, 0), angle: 30), position: (0, 5, 0), intensity: 20),
+                (kind: Directional(direction: (1, -1, 0)), color: (1, 0.9, 0.8), intensity: 2),
+            ],
+        )
+    "#;
+
+    #[test]
+    fn ron_and_json_build_the_same_scene() -> anyhow::Result<()> {
+        let file = SceneFile::parse(GLASS_AND_BOX, SceneFormat::Ron)?;
+        assert_eq!(file.camera.samples, CameraSettings::default().samples);
+        assert_eq!(file.camera.vfov, Some(40.0));
+
+        let scene = file.build(Path::new("."))?;
+        assert_eq!(scene.camera.im_width, 40);
+        assert_eq!(scene.camera.output_color.transfer, Transfer::Gamma(2.2));
+        assert_eq!(scene.world.objects.len(), 3);
+        assert_eq!(scene.camera.light_objects.objects.len(), 1);
+        assert_eq!(scene.camera.lights.len(), 2);
+        assert!(matches!(
+            scene.camera.lights[0].kind,
+            LightKind::Spot { .. }
+        ));
+        assert_eq!(scene.camera.lights[1].falloff, 2.0);
+
+        // The box was moved and turned
+        let ray = Ray::new(Vec3::new(3.0, 0.5, 5.0), Vec3::NEG_Z);
+        let hit = scene.world.hit(&ray, 0.0..f32::MAX).unwrap();
+        let half_diagonal = 2f32.sqrt() / 2.0;
+        assert!((hit.point.z - half_diagonal).abs() < 1e-4, "{}", hit.point);
+
+        // Through JSON and back, nothing changes
+        let json = file.to_string(SceneFormat::Json)?;
+        assert_eq!(SceneFile::parse(&json, SceneFormat::Json)?, file);
+        let ron = file.to_string(SceneFormat::Ron)?;
+        assert_eq!(SceneFile::parse(&ron, SceneFormat::Ron)?, file);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        let typo = "(camera: (samples: 4, bounce: 2))";
+        let error = SceneFile::parse(typo, SceneFormat::Ron).unwrap_err();
+        assert!(format!("{error:#}").contains("bounce"), "{error:#}");
+
+        let missing = r#"{"objects": [{"Sphere": {"center": [0, 0, 0], "radius": 1}}]}"#;
+        let error = SceneFile::parse(missing, SceneFormat::Json).unwrap_err();
+        assert!(format!("{error:#}").contains("material"), "{error:#}");
+
+        let flat_disk = "(objects: [Disk(center: (0, 0, 0), normal: (0, 0, 0), radius: 1, \
+                         material: Lambertian(Solid((1, 1, 1))))])";
+        let file = SceneFile::parse(flat_disk, SceneFormat::Ron).unwrap();
+        assert!(file.build(Path::new(".")).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+use bevy_color::Color;
+use bevy_math::Vec3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    hittable::Hittables,
+    material::{Dielectric, DynMaterial, Lambertian, Metal},
+    objects::{Cuboid, MovingSphere, Sphere},
+};
+
+/// Relative likelihood of each material being picked.
+/// They don't need to sum to one.
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialWeights {
+    pub lambertian: f32,
+    pub metal: f32,
+    pub dielectric: f32,
+}
+
+impl Default for MaterialWeights {
+    /// Same distribution as the final scene of book one.
+    fn default() -> Self {
+        Self {
+            lambertian: 0.8,
+            metal: 0.15,
+            dielectric: 0.05,
+        }
+    }
+}
+
+impl MaterialWeights {
+    fn pick(&self, rng: &mut impl Rng) -> DynMaterial {
+        let total = self.lambertian + self.metal + self.dielectric;
+        let choice = rng.gen::<f32>() * total;
+
+        if choice < self.lambertian {
+            let mut albedo = || rng.gen::<f32>() * rng.gen::<f32>();
+            let (red, green, blue) = (albedo(), albedo(), albedo());
+            Lambertian::linear_rgb(red, green, blue).into()
+        } else if choice < self.lambertian + self.metal {
+            let mut albedo = || rng.gen_range(0.5..1.0);
+            let color = Color::linear_rgb(albedo(), albedo(), albedo());
+            Metal::new(color, rng.gen_range(0.0..0.5)).into()
+        } else {
+            Dielectric::refraction_index(1.5).into()
+        }
+    }
+}
+
+/// A ground sphere with a grid of small random spheres on top of it.
+///
+/// The grid spans `-grid_extent..grid_extent` on both the X and Z axes with one sphere per cell,
+/// so there are `(2 * grid_extent)^2` small spheres.
+/// The same seed always produces the same scene.
+pub fn random_spheres(seed: u64, grid_extent: i32, material_weights: MaterialWeights) -> Hittables {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut world = Hittables::default();
+
+    world.add(Sphere {
+        center: Vec3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+    });
+
+    for a in -grid_extent..grid_extent {
+        for b in -grid_extent..grid_extent {
+            let center = Vec3::new(
+                a as f32 + 0.9 * rng.gen::<f32>(),
+                0.2,
+                b as f32 + 0.9 * rng.gen::<f32>(),
+            );
+
+            world.add(Sphere {
+                center,
+                radius: 0.2,
+                material: material_weights.pick(&mut rng),
+            });
+        }
+    }
+
+    world
+}
+
+/// The final scene of book one, on its cover: a field of small spheres in random materials
+/// around three big ones of glass, diffuse brown and polished metal.
+/// The seed decides where the small spheres go and what they're made of.
+pub fn final_scene(seed: u64) -> Hittables {
+    book_cover(seed, false)
+}
+
+/// The opening scene of The Next Week: the cover of book one, with the diffuse spheres
+/// bouncing upwards while the shutter is open so they blur.
+///
+/// Render with a camera shutter of `0.0..1.0` to see the whole bounce.
+pub fn bouncing_spheres(seed: u64) -> Hittables {
+    book_cover(seed, true)
+}
+
+fn book_cover(seed: u64, bouncing: bool) -> Hittables {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut world = Hittables::default();
+
+    world.add(Sphere {
+        center: Vec3::new(0.0, -1000.0, 0.0),
+        radius: 1000.0,
+        material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+    });
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_material = rng.gen::<f32>();
+            let center = Vec3::new(
+                a as f32 + 0.9 * rng.gen::<f32>(),
+                0.2,
+                b as f32 + 0.9 * rng.gen::<f32>(),
+            );
+
+            // Keep clear of the big metal sphere
+            if (center - Vec3::new(4.0, 0.2, 0.0)).length() <= 0.9 {
+                continue;
+            }
+
+            if choose_material < 0.8 {
+                let mut albedo = || rng.gen::<f32>() * rng.gen::<f32>();
+                let material = Lambertian::linear_rgb(albedo(), albedo(), albedo()).into();
+                if bouncing {
+                    world.add(MovingSphere {
+                        from: center,
+                        to: center + Vec3::new(0.0, rng.gen_range(0.0..0.5), 0.0),
+                        radius: 0.2,
+                        material,
+                    });
+                } else {
+                    world.add(Sphere {
+                        center,
+                        radius: 0.2,
+                        material,
+                    });
+                }
+            } else if choose_material < 0.95 {
+                let mut albedo = || rng.gen_range(0.5..1.0);
+                let color = Color::linear_rgb(albedo(), albedo(), albedo());
+                world.add(Sphere {
+                    center,
+                    radius: 0.2,
+                    material: Metal::new(color, rng.gen_range(0.0..0.5)).into(),
+                });
+            } else {
+                world.add(Sphere {
+                    center,
+                    radius: 0.2,
+                    material: Dielectric::refraction_index(1.5).into(),
+                });
+            }
+        }
+    }
+
+    world.add(Sphere {
+        center: Vec3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+        material: Dielectric::refraction_index(1.5).into(),
+    });
+    world.add(Sphere {
+        center: Vec3::new(-4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: Lambertian::linear_rgb(0.4, 0.2, 0.1).into(),
+    });
+    world.add(Sphere {
+        center: Vec3::new(4.0, 1.0, 0.0),
+        radius: 1.0,
+        material: Metal::new(Color::linear_rgb(0.7, 0.6, 0.5), 0.0).into(),
+    });
+
+    world
+}
+
+/// A recursive sphere-flake: every sphere has `branching` smaller spheres sitting on it,
+/// down to `depth` levels below the root.
+///
+/// The root sphere sits at the origin with radius 1 and grows its children upwards (+Y).
+/// Each child is a third the size of its parent.
+/// The material of each sphere is decided by its depth, where the root has depth 0.
+pub fn sphere_flake(
+    depth: usize,
+    branching: usize,
+    material: impl Fn(usize) -> DynMaterial,
+) -> Hittables {
+    let mut world = Hittables::default();
+
+    flake_level(&mut world, Vec3::ZERO, 1.0, Vec3::Y, 0, depth, branching, &material);
+
+    world
+}
+
+#[allow(clippy::too_many_arguments)]
+fn flake_level(
+    world: &mut Hittables,
+    center: Vec3,
+    radius: f32,
+    axis: Vec3,
+    level: usize,
+    depth: usize,
+    branching: usize,
+    material: &impl Fn(usize) -> DynMaterial,
+) {
+    world.add(Sphere {
+        center,
+        radius,
+        material: material(level),
+    });
+
+    if level == depth {
+        return;
+    }
+
+    let child_radius = radius / 3.0;
+
+    // Spread children over the part of the sphere facing away from the parent,
+    // reaching a bit past the equator (cos(100 degrees) is about -0.17)
+    let min_cos = -0.17;
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+
+    let tangent = axis.any_orthonormal_vector();
+    let bitangent = axis.cross(tangent);
+
+    for child in 0..branching {
+        let cos_theta = 1.0 - (1.0 - min_cos) * (child as f32 + 0.5) / branching as f32;
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        // Offset by level so siblings at different depths don't line up
+        let phi = golden_angle * child as f32 + level as f32;
+
+        let direction = (tangent * phi.cos() + bitangent * phi.sin()) * sin_theta + axis * cos_theta;
+
+        flake_level(
+            world,
+            center + direction * (radius + child_radius),
+            child_radius,
+            direction,
+            level + 1,
+            depth,
+            branching,
+            material,
+        );
+    }
+}
+
+/// A Menger sponge made of boxes, centered at the origin with side length `size`.
+///
+/// Each iteration splits every box into 27 and removes the center and the middle of each face,
+/// so there are `20^iterations` boxes.
+pub fn menger_sponge(iterations: usize, size: f32, material: DynMaterial) -> Hittables {
+    let mut world = Hittables::default();
+
+    menger_level(&mut world, Vec3::ZERO, size, iterations, &material);
+
+    world
+}
+
+fn menger_level(
+    world: &mut Hittables,
+    center: Vec3,
+    size: f32,
+    iterations: usize,
+    material: &DynMaterial,
+) {
+    if iterations == 0 {
+        let half = Vec3::splat(size / 2.0);
+        world.add(Cuboid::new(center - half, center + half, material.clone()));
+        return;
+    }
+
+    let step = size / 3.0;
+
+    for x in -1..=1_i32 {
+        for y in -1..=1_i32 {
+            for z in -1..=1_i32 {
+                // Two or more coordinates in the middle means a face center or the very center
+                let middles = [x, y, z].iter().filter(|&&c| c == 0).count();
+                if middles >= 2 {
+                    continue;
+                }
+
+                let offset = Vec3::new(x as f32, y as f32, z as f32) * step;
+                menger_level(world, center + offset, step, iterations - 1, material);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn final_scene_follows_the_seed() {
+        let scene = final_scene(0);
+        // The ground, three big spheres and up to 22 by 22 small ones
+        assert!((400..=488).contains(&scene.objects.len()));
+
+        let placement = |world: &Hittables| {
+            world
+                .objects
+                .iter()
+                .map(|object| object.bounding_box().center())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(placement(&scene), placement(&final_scene(0)));
+        assert_ne!(placement(&scene), placement(&final_scene(1)));
+    }
+}
@@ -0,0 +1,323 @@
+//! Build worlds from [rhai](https://rhai.rs) scripts, so procedural scenes don't need recompiling.
+//!
+//! A script creates a world, adds objects to it and evaluates to it:
+//!
+//! ```rhai
+//! let w = world();
+//! w.sphere(vec3(0, -1000, 0), 1000, lambertian(0.5, 0.5, 0.5));
+//!
+//! for x in -10..10 {
+//!     for z in -10..10 {
+//!         w.sphere(vec3(x, 0.2, z), 0.2, lambertian(rand(), rand(), rand()));
+//!     }
+//! }
+//!
+//! w
+//! ```
+//!
+//! Numbers may be given as integers or floats anywhere.
+//!
+//! To choose the view too, a script evaluates to a world with a camera instead,
+//! with the `position`, `look_at`, `up`, `vfov`, `defocus_angle`, `focus_distance`,
+//! `samples`, `bounces` and `background` of [`CameraSettings`]:
+//!
+//! ```rhai
+//! let w = world();
+//! w.quad(vec3(-1, 3, -1), vec3(2, 0, 0), vec3(0, 0, 2), diffuse_light(4, 4, 4));
+//! w.sphere(vec3(0, 1, 0), 1, dielectric(1.5));
+//!
+//! let cam = camera();
+//! cam.position = vec3(0, 2, 6);
+//! cam.look_at = vec3(0, 1, 0);
+//! cam.vfov = 40;
+//! cam.background = rgb(0, 0, 0);
+//!
+//! w.seen_by(cam)
+//! ```
+
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use bevy_color::Color;
+use bevy_math::Vec3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rhai::{Dynamic, Engine, EvalAltResult};
+
+use crate::{
+    camera::Camera,
+    hittable::Hittables,
+    material::{Dielectric, DiffuseLight, DynMaterial, Lambertian, Metal},
+    objects::{Cuboid, Quad, Sphere, Triangle},
+    scene_file::{BackgroundSettings, CameraSettings},
+};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Scripts mix integers and floats freely, so accept both
+fn num(value: Dynamic) -> ScriptResult<f32> {
+    if let Ok(float) = value.as_float() {
+        Ok(float as f32)
+    } else if let Ok(int) = value.as_int() {
+        Ok(int as f32)
+    } else {
+        Err(format!("expected a number, got {}", value.type_name()).into())
+    }
+}
+
+/// What a script can evaluate to besides a bare world, see [`scene_from_script`].
+#[derive(Debug, Clone)]
+pub struct ScriptScene {
+    pub world: Hittables,
+    pub camera: CameraSettings,
+}
+
+/// An engine with the scene building API registered.
+/// `rand()` draws from a generator seeded by `seed`, so the same script and seed give the same world.
+pub fn engine(seed: u64) -> Engine {
+    let mut engine = Engine::new();
+
+    engine
+        .register_type_with_name::<Hittables>("World")
+        .register_type_with_name::<Vec3>("Vec3")
+        .register_type_with_name::<DynMaterial>("Material")
+        .register_type_with_name::<CameraSettings>("Camera")
+        .register_type_with_name::<ScriptScene>("Scene")
+        .register_type_with_name::<BackgroundSettings>("Background");
+
+    engine.register_fn("world", Hittables::default);
+
+    engine.register_fn(
+        "vec3",
+        |x: Dynamic, y: Dynamic, z: Dynamic| -> ScriptResult<Vec3> {
+            Ok(Vec3::new(num(x)?, num(y)?, num(z)?))
+        },
+    );
+    engine.register_get("x", |v: &mut Vec3| v.x as rhai::FLOAT);
+    engine.register_get("y", |v: &mut Vec3| v.y as rhai::FLOAT);
+    engine.register_get("z", |v: &mut Vec3| v.z as rhai::FLOAT);
+
+    let rng = Rc::new(RefCell::new(StdRng::seed_from_u64(seed)));
+    engine.register_fn("rand", move || rng.borrow_mut().gen::<f32>() as rhai::FLOAT);
+
+    engine.register_fn(
+        "lambertian",
+        |r: Dynamic, g: Dynamic, b: Dynamic| -> ScriptResult<DynMaterial> {
+            Ok(DynMaterial::from(Lambertian::linear_rgb(
+                num(r)?,
+                num(g)?,
+                num(b)?,
+            )))
+        },
+    );
+    engine.register_fn(
+        "metal",
+        |r: Dynamic, g: Dynamic, b: Dynamic, fuzz: Dynamic| -> ScriptResult<DynMaterial> {
+            let color = Color::linear_rgb(num(r)?, num(g)?, num(b)?);
+            Ok(Metal::new(color, num(fuzz)?).into())
+        },
+    );
+    engine.register_fn("dielectric", |ior: Dynamic| -> ScriptResult<DynMaterial> {
+        Ok(Dielectric::refraction_index(num(ior)?).into())
+    });
+    engine.register_fn(
+        "diffuse_light",
+        |r: Dynamic, g: Dynamic, b: Dynamic| -> ScriptResult<DynMaterial> {
+            Ok(DiffuseLight::linear_rgb(num(r)?, num(g)?, num(b)?).into())
+        },
+    );
+
+    engine.register_fn(
+        "sphere",
+        |world: &mut Hittables,
+         center: Vec3,
+         radius: Dynamic,
+         material: DynMaterial|
+         -> ScriptResult<()> {
+            world.add(Sphere {
+                center,
+                radius: num(radius)?,
+                material,
+            });
+            Ok(())
+        },
+    );
+    engine.register_fn(
+        "cuboid",
+        |world: &mut Hittables, a: Vec3, b: Vec3, material: DynMaterial| {
+            world.add(Cuboid::new(a, b, material));
+        },
+    );
+    engine.register_fn(
+        "triangle",
+        |world: &mut Hittables, a: Vec3, b: Vec3, c: Vec3, material: DynMaterial| {
+            world.add(Triangle::new(a, b, c, material));
+        },
+    );
+    engine.register_fn(
+        "quad",
+        |world: &mut Hittables, corner: Vec3, u: Vec3, v: Vec3, material: DynMaterial| {
+            world.add(Quad::new(corner, u, v, material));
+        },
+    );
+
+    register_camera(&mut engine);
+
+    engine
+}
+
+/// `camera()` and its settings, and `seen_by` to pair it with a world.
+fn register_camera(engine: &mut Engine) {
+    engine.register_fn("camera", CameraSettings::default);
+    engine.register_fn(
+        "seen_by",
+        |world: &mut Hittables, camera: CameraSettings| ScriptScene {
+            world: world.clone(),
+            camera,
+        },
+    );
+
+    engine.register_fn(
+        "rgb",
+        |r: Dynamic, g: Dynamic, b: Dynamic| -> ScriptResult<BackgroundSettings> {
+            Ok(BackgroundSettings::Solid([num(r)?, num(g)?, num(b)?]))
+        },
+    );
+    engine.register_set(
+        "background",
+        |camera: &mut CameraSettings, background: BackgroundSettings| {
+            camera.background = background;
+        },
+    );
+
+    engine.register_set("position", |camera: &mut CameraSettings, position: Vec3| {
+        camera.position = position;
+    });
+    engine.register_set("look_at", |camera: &mut CameraSettings, target: Vec3| {
+        camera.look_at = Some(target);
+    });
+    engine.register_set("up", |camera: &mut CameraSettings, up: Vec3| {
+        camera.up = up;
+    });
+
+    engine.register_set(
+        "vfov",
+        |camera: &mut CameraSettings, degrees: Dynamic| -> ScriptResult<()> {
+            camera.vfov = Some(num(degrees)?);
+            Ok(())
+        },
+    );
+    engine.register_set(
+        "defocus_angle",
+        |camera: &mut CameraSettings, degrees: Dynamic| -> ScriptResult<()> {
+            camera.defocus_angle = num(degrees)?;
+            Ok(())
+        },
+    );
+    engine.register_set(
+        "focus_distance",
+        |camera: &mut CameraSettings, distance: Dynamic| -> ScriptResult<()> {
+            camera.focus_distance = num(distance)?;
+            Ok(())
+        },
+    );
+    engine.register_set(
+        "samples",
+        |camera: &mut CameraSettings, samples: rhai::INT| -> ScriptResult<()> {
+            camera.samples = usize::try_from(samples).map_err(|e| e.to_string())?;
+            Ok(())
+        },
+    );
+    engine.register_set(
+        "bounces",
+        |camera: &mut CameraSettings, bounces: rhai::INT| -> ScriptResult<()> {
+            camera.bounces = usize::try_from(bounces).map_err(|e| e.to_string())?;
+            Ok(())
+        },
+    );
+}
+
+/// Run a script and return the world it evaluates to.
+pub fn world_from_script(source: &str, seed: u64) -> anyhow::Result<Hittables> {
+    engine(seed)
+        .eval::<Hittables>(source)
+        .map_err(|e| anyhow::anyhow!("script error: {e}"))
+}
+
+/// Run a script which evaluates to a world, or to a world `seen_by` a camera.
+/// Returns the world and the camera, if the script chose one.
+pub fn scene_from_script(source: &str, seed: u64) -> anyhow::Result<(Hittables, Option<Camera>)> {
+    let result = engine(seed)
+        .eval::<Dynamic>(source)
+        .map_err(|e| anyhow::anyhow!("script error: {e}"))?;
+
+    if result.is::<ScriptScene>() {
+        let ScriptScene { world, camera } = result.cast();
+        Ok((world, Some(camera.build(Path::new(""))?)))
+    } else if result.is::<Hittables>() {
+        Ok((result.cast(), None))
+    } else {
+        anyhow::bail!(
+            "script error: expected a world or a scene, got {}",
+            result.type_name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_of_spheres() -> anyhow::Result<()> {
+        let script = r#"
+            let w = world();
+            for x in 0..20 {
+                for z in 0..20 {
+                    w.sphere(vec3(x, 0.2, z * 1.5), 0.2, lambertian(rand(), 0.5, 1));
+                }
+            }
+            w
+        "#;
+
+        let world = world_from_script(script, 0)?;
+        assert_eq!(world.objects.len(), 400);
+
+        Ok(())
+    }
+
+    #[test]
+    fn scripts_can_place_the_camera() -> anyhow::Result<()> {
+        let script = r#"
+            let w = world();
+            w.quad(vec3(-1, 3, -1), vec3(2, 0, 0), vec3(0, 0, 2), diffuse_light(4, 4, 4));
+            w.sphere(vec3(0, 1, 0), 1, dielectric(1.5));
+
+            let cam = camera();
+            cam.position = vec3(0, 2, 6);
+            cam.look_at = vec3(0, 1, 0);
+            cam.vfov = 40;
+            cam.samples = 8;
+            w.seen_by(cam)
+        "#;
+
+        let (world, camera) = scene_from_script(script, 0)?;
+        assert_eq!(world.objects.len(), 2);
+        let camera = camera.unwrap();
+        assert_eq!(camera.samples_per_pixel, 8);
+        assert_eq!(camera.cam_origin, Vec3::new(0.0, 2.0, 6.0));
+
+        // A bare world leaves the camera to the caller
+        let (_, camera) = scene_from_script("world()", 0)?;
+        assert!(camera.is_none());
+
+        assert!(scene_from_script("42", 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        assert!(
+            world_from_script("world().sphere(vec3(0, 0, 0), \"big\", dielectric(1.5))", 0)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! An analytic sun and sky after Preetham, Shirley and Smits,
+//! "A Practical Analytic Model for Daylight" (1999), for outdoor scenes without an HDRI.
+
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy_color::{ColorToComponents, LinearRgba, Xyza};
+use bevy_math::{Dir3, UVec2, Vec2, Vec3};
+use rayon::prelude::*;
+
+use crate::environment::{direction_to_uv, uv_to_direction, EnvironmentMap};
+
+/// Angular radius of the sun seen from the earth, in radians.
+const SUN_ANGULAR_RADIUS: f32 = 0.004_65;
+
+/// The sun's luminance above the atmosphere, in the sky's kilocandela per square metre.
+const SUN_LUMINANCE: f32 = 1.6e6;
+
+/// The sky as lit by the sun from `sun`, hazier with higher `turbidity`.
+///
+/// Radiance is in kilocandela per square metre times `intensity`,
+/// so a clear midday zenith is around `7.0 * intensity`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalSky {
+    /// Towards the sun. Below the horizon the sky is dark.
+    pub sun: Dir3,
+    /// Haze, from 2 on a very clear day to 10 on a hazy one. The model's fit holds in that range.
+    pub turbidity: f32,
+    /// Scales both sun and sky into the scene's units.
+    pub intensity: f32,
+    /// How much of the sky the ground below the horizon reflects.
+    pub ground_albedo: f32,
+}
+
+impl PhysicalSky {
+    pub fn new(sun: Dir3, turbidity: f32) -> Self {
+        Self {
+            sun,
+            turbidity,
+            intensity: 0.1,
+            ground_albedo: 0.3,
+        }
+    }
+
+    /// The sky seen looking in `direction`, without the sun's disk.
+    pub fn radiance(&self, direction: Dir3) -> LinearRgba {
+        let sun_theta = self.sun.y.clamp(-1.0, 1.0).acos();
+        if sun_theta >= FRAC_PI_2 {
+            return LinearRgba::BLACK;
+        }
+
+        // The ground reflects the sky along the horizon
+        let (direction, scale) = if direction.y < 0.0 {
+            let horizon = Vec3::new(direction.x, 0.0, direction.z);
+            let horizon = Dir3::new(horizon).unwrap_or(Dir3::X);
+            (horizon, self.ground_albedo)
+        } else {
+            (direction, 1.0)
+        };
+
+        // Keeps 1 / cos(theta) finite at the horizon
+        let theta = direction.y.clamp(0.0, 1.0).acos().min(FRAC_PI_2 - 1e-3);
+        let gamma = direction.dot(self.sun.as_vec3()).clamp(-1.0, 1.0).acos();
+
+        let coefficients = Coefficients::new(self.turbidity());
+        let [x, y, luminance] = [
+            (coefficients.x, self.zenith_x()),
+            (coefficients.y, self.zenith_y()),
+            (coefficients.luminance, self.zenith_luminance()),
+        ]
+        .map(|(perez, zenith)| zenith * perez.f(theta, gamma) / perez.f(0.0, sun_theta));
+
+        xyy_to_linear(x, y, luminance * self.intensity * scale)
+    }
+
+    /// The sun's disk, dimmed and reddened by the air it shines through.
+    pub fn sun_radiance(&self) -> LinearRgba {
+        let sun_theta = self.sun.y.clamp(-1.0, 1.0).acos();
+        if sun_theta >= FRAC_PI_2 {
+            return LinearRgba::BLACK;
+        }
+
+        // Relative optical mass of the air, after Kasten
+        let degrees = sun_theta.to_degrees();
+        let mass = 1.0 / (sun_theta.cos() + 0.15 * (93.885 - degrees).powf(-1.253));
+
+        // Rayleigh scattering by air plus Ångström's aerosols at red, green and blue
+        let beta = 0.046_08 * self.turbidity() - 0.045_86;
+        let transmittance = [0.65f32, 0.55, 0.45]
+            .map(|micrometres| {
+                let rayleigh = 0.008_735 * micrometres.powf(-4.08);
+                let aerosol = beta * micrometres.powf(-1.3);
+                (-mass * (rayleigh + aerosol)).exp()
+            })
+            .map(|transmitted| transmitted * SUN_LUMINANCE * self.intensity);
+
+        LinearRgba::rgb(transmittance[0], transmittance[1], transmittance[2])
+    }
+
+    /// Render the sky and sun into a `width` by `height` environment map,
+    /// which the camera can aim rays at.
+    ///
+    /// The sun is much smaller than a pixel, so the pixel it is in gets its light spread out.
+    pub fn bake(&self, width: usize, height: usize) -> EnvironmentMap {
+        let size = Vec2::new(width as f32, height as f32);
+        let mut pixels: Vec<LinearRgba> = (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                let pixel = Vec2::new((index % width) as f32, (index / width) as f32);
+                self.radiance(uv_to_direction((pixel + 0.5) / size))
+            })
+            .collect();
+
+        if width > 0 && height > 0 {
+            let pixel = (direction_to_uv(self.sun) * size)
+                .as_uvec2()
+                .min(UVec2::new(width as u32 - 1, height as u32 - 1));
+            let pixel_solid_angle =
+                (TAU / size.x) * (PI / size.y) * ((pixel.y as f32 + 0.5) / size.y * PI).sin();
+            let sun_solid_angle = TAU * (1.0 - SUN_ANGULAR_RADIUS.cos());
+
+            let index = pixel.y as usize * width + pixel.x as usize;
+            let sun = self.sun_radiance().to_vec3() * sun_solid_angle / pixel_solid_angle;
+            pixels[index] = LinearRgba::from_vec3(pixels[index].to_vec3() + sun);
+        }
+
+        EnvironmentMap::new(width, height, pixels)
+    }
+
+    fn turbidity(&self) -> f32 {
+        self.turbidity.clamp(2.0, 10.0)
+    }
+
+    fn zenith_luminance(&self) -> f32 {
+        let turbidity = self.turbidity();
+        let sun_theta = self.sun.y.clamp(-1.0, 1.0).acos();
+        let chi = (4.0 / 9.0 - turbidity / 120.0) * (PI - 2.0 * sun_theta);
+
+        ((4.0453 * turbidity - 4.9710) * chi.tan() - 0.2155 * turbidity + 2.4192).max(0.0)
+    }
+
+    fn zenith_x(&self) -> f32 {
+        self.zenith_chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ])
+    }
+
+    fn zenith_y(&self) -> f32 {
+        self.zenith_chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ])
+    }
+
+    /// Cubic in the sun's angle from the zenith, quadratic in turbidity.
+    fn zenith_chromaticity(&self, matrix: [[f32; 4]; 3]) -> f32 {
+        let turbidity = self.turbidity();
+        let sun_theta = self.sun.y.clamp(-1.0, 1.0).acos();
+        let angles = [sun_theta.powi(3), sun_theta.powi(2), sun_theta, 1.0];
+
+        let [squared, linear, constant] =
+            matrix.map(|row| row.iter().zip(angles).map(|(a, b)| a * b).sum::<f32>());
+        turbidity * turbidity * squared + turbidity * linear + constant
+    }
+}
+
+impl Default for PhysicalSky {
+    /// A clear afternoon with the sun ahead and to the right.
+    fn default() -> Self {
+        Self::new(
+            Dir3::new_unchecked(Vec3::new(0.5, 0.6, -0.62).normalize()),
+            3.0,
+        )
+    }
+}
+
+/// Perez et al.'s distribution of light over the sky for one quantity.
+#[derive(Debug, Clone, Copy)]
+struct Perez {
+    a: f32,
+    b: f32,
+    c: f32,
+    d: f32,
+    e: f32,
+}
+
+impl Perez {
+    /// At `theta` from the zenith and `gamma` from the sun.
+    fn f(self, theta: f32, gamma: f32) -> f32 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / theta.cos()).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+/// Preetham's fit of the Perez distributions to turbidity.
+struct Coefficients {
+    luminance: Perez,
+    x: Perez,
+    y: Perez,
+}
+
+impl Coefficients {
+    fn new(turbidity: f32) -> Self {
+        let perez = |fit: [[f32; 2]; 5]| {
+            let [a, b, c, d, e] = fit.map(|[slope, offset]| slope * turbidity + offset);
+            Perez { a, b, c, d, e }
+        };
+
+        Self {
+            luminance: perez([
+                [0.1787, -1.4630],
+                [-0.3554, 0.4275],
+                [-0.0227, 5.3251],
+                [0.1206, -2.5771],
+                [-0.0670, 0.3703],
+            ]),
+            x: perez([
+                [-0.0193, -0.2592],
+                [-0.0665, 0.0008],
+                [-0.0004, 0.2125],
+                [-0.0641, -0.8989],
+                [-0.0033, 0.0452],
+            ]),
+            y: perez([
+                [-0.0167, -0.2608],
+                [-0.0950, 0.0092],
+                [-0.0079, 0.2102],
+                [-0.0441, -1.6537],
+                [-0.0109, 0.0529],
+            ]),
+        }
+    }
+}
+
+fn xyy_to_linear(x: f32, y: f32, luminance: f32) -> LinearRgba {
+    if y <= 0.0 || luminance <= 0.0 {
+        return LinearRgba::BLACK;
+    }
+
+    let xyz = Xyza::xyz(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+    let rgb = LinearRgba::from(xyz);
+    // Out of gamut colors would subtract light
+    LinearRgba::rgb(rgb.red.max(0.0), rgb.green.max(0.0), rgb.blue.max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::Pdf;
+
+    #[test]
+    fn daylight() {
+        let noon = PhysicalSky::new(Dir3::new(Vec3::new(0.2, 1.0, 0.0)).unwrap(), 2.5);
+        let zenith = noon.radiance(Dir3::Y);
+        // A clear sky is blue, and a few kilocandela per square metre overhead
+        assert!(zenith.blue > zenith.red, "{zenith:?}");
+        assert!((0.2..2.0).contains(&zenith.green), "{zenith:?}");
+
+        // Brightest around the sun
+        let near_sun = noon.radiance(Dir3::new(Vec3::new(0.3, 1.0, 0.0)).unwrap());
+        let away = noon.radiance(Dir3::new(Vec3::new(-1.0, 0.3, 0.0)).unwrap());
+        assert!(near_sun.green > away.green, "{near_sun:?} {away:?}");
+
+        // Haze washes the blue out
+        let hazy = PhysicalSky {
+            turbidity: 8.0,
+            ..noon
+        }
+        .radiance(Dir3::Y);
+        let blueness = |rgb: LinearRgba| rgb.blue / rgb.red;
+        assert!(blueness(hazy) < blueness(zenith), "{hazy:?} {zenith:?}");
+
+        // The setting sun is dimmer and redder, and gone below the horizon
+        let sunset = PhysicalSky::new(Dir3::new(Vec3::new(1.0, 0.05, 0.0)).unwrap(), 2.5);
+        let (high, low) = (noon.sun_radiance(), sunset.sun_radiance());
+        assert!(low.green < high.green);
+        assert!(low.red / low.blue > high.red / high.blue);
+        let night = PhysicalSky::new(Dir3::NEG_Y, 2.5);
+        assert_eq!(night.radiance(Dir3::Y), LinearRgba::BLACK);
+        assert_eq!(night.sun_radiance(), LinearRgba::BLACK);
+    }
+
+    #[test]
+    fn baking_keeps_the_suns_light() {
+        let sky = PhysicalSky::default();
+        let map = sky.bake(256, 128);
+
+        // Light from the sun pixel, as much as the tiny disk gives
+        let index = map.pixel_index(sky.sun).unwrap();
+        let row = index / 256;
+        let solid_angle = (TAU / 256.0) * (PI / 128.0) * ((row as f32 + 0.5) / 128.0 * PI).sin();
+        let baked = map.color(sky.sun).to_linear().green * solid_angle;
+        let disk = sky.sun_radiance().green * TAU * (1.0 - SUN_ANGULAR_RADIUS.cos());
+        assert!((baked - disk).abs() < 0.05 * disk, "{baked} {disk}");
+
+        // The sun outshines the sky, so aimed rays mostly go towards it
+        let towards_sun = map.value(sky.sun);
+        assert!(towards_sun > 100.0 * map.value(Dir3::Y), "{towards_sun}");
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    time::Duration,
+};
+
+use crate::material::DynMaterial;
+
+/// A summary of what a scene is made of, to get a feel for how expensive it is to render.
+#[derive(Debug, Default)]
+pub struct SceneStats {
+    /// Number of objects per type, e.g. "Sphere"
+    pub objects: BTreeMap<&'static str, usize>,
+
+    /// Number of distinct materials per type, e.g. "Metal"
+    pub materials: BTreeMap<&'static str, usize>,
+
+    pub triangles: usize,
+
+    /// Rough estimate of the heap memory used by objects and materials
+    pub memory_bytes: usize,
+
+    /// The shape of the outermost BVH, if the scene is built into one
+    pub bvh: Option<TreeStats>,
+
+    /// Lights the camera samples directly, see `Camera::lights`
+    pub lights: usize,
+
+    /// Emissive objects scattered rays are aimed at, see `Camera::light_objects`
+    pub light_objects: usize,
+
+    seen_materials: HashSet<usize>,
+}
+
+/// How an acceleration tree came out, e.g. to spot leaves holding too many objects.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TreeStats {
+    /// Levels below the root, zero if the root is a leaf
+    pub depth: usize,
+    pub leaves: usize,
+    /// Objects in the emptiest and the fullest leaf
+    pub leaf_sizes: (usize, usize),
+    /// Object references in all leaves
+    pub leaf_objects: usize,
+}
+
+/// `std::any::type_name` without the module path
+pub(crate) fn short_type_name(name: &'static str) -> &'static str {
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+impl SceneStats {
+    /// Count an object which is `bytes` large.
+    pub fn add_object(&mut self, type_name: &'static str, bytes: usize) {
+        *self.objects.entry(short_type_name(type_name)).or_default() += 1;
+        self.memory_bytes += bytes;
+    }
+
+    /// Count a material, unless this exact material has been counted before.
+    pub fn add_material(&mut self, material: &DynMaterial) {
+        if self.seen_materials.insert(material.id()) {
+            *self.materials.entry(short_type_name(material.type_name())).or_default() += 1;
+            self.memory_bytes += std::mem::size_of_val(&**material);
+        }
+    }
+
+    pub fn add_triangles(&mut self, count: usize) {
+        self.triangles += count;
+    }
+
+    /// Record the shape of a BVH, unless one containing it was recorded already.
+    pub fn add_bvh(&mut self, tree: TreeStats) {
+        self.bvh.get_or_insert(tree);
+    }
+}
+
+impl Display for SceneStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "objects:")?;
+        for (name, count) in &self.objects {
+            writeln!(f, "  {name}: {count}")?;
+        }
+
+        writeln!(f, "materials:")?;
+        for (name, count) in &self.materials {
+            writeln!(f, "  {name}: {count}")?;
+        }
+
+        writeln!(f, "triangles: {}", self.triangles)?;
+        if let Some(bvh) = &self.bvh {
+            let (smallest, largest) = bvh.leaf_sizes;
+            writeln!(
+                f,
+                "bvh: depth {}, {} leaves of {smallest}-{largest} objects ({:.1} on average)",
+                bvh.depth,
+                bvh.leaves,
+                bvh.leaf_objects as f64 / bvh.leaves.max(1) as f64
+            )?;
+        }
+        writeln!(f, "lights: {}", self.lights)?;
+        writeln!(f, "light objects: {}", self.light_objects)?;
+        write!(
+            f,
+            "memory: {:.2} MiB",
+            self.memory_bytes as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+thread_local! {
+    static RAYS_TRACED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Count a ray traced through the scene by this thread.
+pub(crate) fn count_ray() {
+    RAYS_TRACED.with(|rays| rays.set(rays.get() + 1));
+}
+
+/// How many rays this thread has traced so far.
+/// Compare before and after some work to see how many rays it took.
+pub fn rays_traced_on_thread() -> u64 {
+    RAYS_TRACED.with(Cell::get)
+}
+
+/// Time spent in each phase of a render, to see at a glance where a slow render went.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderTimings {
+    /// Building the objects of the scene.
+    pub scene: Duration,
+    /// Building the acceleration structure.
+    pub acceleration: Duration,
+    /// Tracing rays.
+    pub tracing: Duration,
+    /// Quantizing and resizing the traced image.
+    pub post_processing: Duration,
+    /// Encoding and writing the image.
+    pub encoding: Duration,
+}
+
+impl RenderTimings {
+    pub fn total(&self) -> Duration {
+        self.scene + self.acceleration + self.tracing + self.post_processing + self.encoding
+    }
+}
+
+impl Display for RenderTimings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.total().as_secs_f64().max(f64::EPSILON);
+        let phases = [
+            ("scene", self.scene),
+            ("acceleration", self.acceleration),
+            ("tracing", self.tracing),
+            ("post-processing", self.post_processing),
+            ("encoding", self.encoding),
+        ];
+
+        for (name, time) in phases {
+            let percent = 100.0 * time.as_secs_f64() / total;
+            write!(f, "{name} {time:.1?} ({percent:.0}%), ")?;
+        }
+        write!(f, "total {:.1?}", self.total())
+    }
+}
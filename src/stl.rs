@@ -0,0 +1,208 @@
+//! Loading triangle meshes from STL files, the usual export of CAD and 3D printing tools.
+//!
+//! Both the binary and the ASCII flavour are read. STL stores each triangle on its own,
+//! so corners at the same position are merged to share vertices.
+//! The stored facet normals are ignored, since the corner order gives the same facing
+//! and is more often right.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{ensure, Context};
+use bevy_math::Vec3;
+
+use crate::{material::DynMaterial, mesh::Mesh};
+
+const HEADER_BYTES: usize = 80;
+const TRIANGLE_BYTES: usize = 50;
+
+/// Load the STL at `path` as one mesh of the given material.
+pub fn load(path: impl AsRef<Path>, material: impl Into<DynMaterial>) -> anyhow::Result<Mesh> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).with_context(|| format!("reading STL {}", path.display()))?;
+
+    parse(&bytes, material).with_context(|| format!("parsing STL {}", path.display()))
+}
+
+/// Parse the contents of a binary or ASCII STL file.
+pub fn parse(bytes: &[u8], material: impl Into<DynMaterial>) -> anyhow::Result<Mesh> {
+    let corners = if is_binary(bytes) {
+        parse_binary(bytes)?
+    } else {
+        let text = std::str::from_utf8(bytes).context("neither binary nor text")?;
+        parse_ascii(text)?
+    };
+
+    let mut vertex_indices: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut positions = vec![];
+    let triangles = corners
+        .chunks_exact(3)
+        .map(|triangle| {
+            [0, 1, 2].map(|corner| {
+                let position = triangle[corner];
+                *vertex_indices
+                    .entry(position.to_array().map(f32::to_bits))
+                    .or_insert_with(|| {
+                        positions.push(position);
+                        (positions.len() - 1) as u32
+                    })
+            })
+        })
+        .collect();
+
+    Ok(Mesh::new(positions, triangles, material))
+}
+
+/// Binary files may also start with "solid" in their header,
+/// so trust the size matching the triangle count instead.
+fn is_binary(bytes: &[u8]) -> bool {
+    let Some(count) = bytes.get(HEADER_BYTES..HEADER_BYTES + 4) else {
+        return false;
+    };
+    let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+
+    bytes.len() == HEADER_BYTES + 4 + count * TRIANGLE_BYTES || !bytes.starts_with(b"solid")
+}
+
+/// Corners of all triangles, three in a row each.
+fn parse_binary(bytes: &[u8]) -> anyhow::Result<Vec<Vec3>> {
+    ensure!(
+        bytes.len() >= HEADER_BYTES + 4,
+        "{} bytes is too short for the header",
+        bytes.len()
+    );
+    let count = u32::from_le_bytes(bytes[HEADER_BYTES..HEADER_BYTES + 4].try_into()?) as usize;
+    let records = &bytes[HEADER_BYTES + 4..];
+    ensure!(
+        records.len() >= count * TRIANGLE_BYTES,
+        "the file ends before its {count} triangles do"
+    );
+
+    let float = |record: &[u8], index: usize| {
+        let offset = index * 4;
+        f32::from_le_bytes(record[offset..offset + 4].try_into().unwrap())
+    };
+
+    Ok(records
+        .chunks_exact(TRIANGLE_BYTES)
+        .take(count)
+        .flat_map(|record| {
+            // A normal, three corners, then two bytes of attributes
+            (1..4).map(move |corner| {
+                Vec3::new(
+                    float(record, corner * 3),
+                    float(record, corner * 3 + 1),
+                    float(record, corner * 3 + 2),
+                )
+            })
+        })
+        .collect())
+}
+
+fn parse_ascii(text: &str) -> anyhow::Result<Vec<Vec3>> {
+    let mut corners = vec![];
+    let mut in_facet = 0;
+
+    for (number, line) in text.lines().enumerate() {
+        let mut parse_line = || -> anyhow::Result<()> {
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("facet") => in_facet = 0,
+                Some("vertex") => {
+                    let mut position = [0.0; 3];
+                    for value in &mut position {
+                        let field = fields.next().context("too few numbers")?;
+                        *value = field
+                            .parse()
+                            .with_context(|| format!("{field} is not a number"))?;
+                    }
+                    corners.push(Vec3::from_array(position));
+                    in_facet += 1;
+                }
+                Some("endfacet") => {
+                    ensure!(in_facet == 3, "a facet needs 3 vertices, got {in_facet}")
+                }
+                _ => {}
+            }
+            Ok(())
+        };
+        parse_line().with_context(|| format!("line {}: {line}", number + 1))?;
+    }
+
+    ensure!(corners.len() % 3 == 0, "the last facet is unfinished");
+    Ok(corners)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::Color;
+
+    use super::*;
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray};
+
+    /// A unit square facing +Z as two facets.
+    const SQUARE: &str = "solid square
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 1 1 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 1 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid square
+";
+
+    fn binary_square() -> Vec<u8> {
+        // Headers starting with "solid" should not fool the loader
+        let mut bytes = b"solid but actually binary".to_vec();
+        bytes.resize(HEADER_BYTES, 0);
+        bytes.extend(2u32.to_le_bytes());
+        for triangle in [
+            [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0)],
+            [Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0), Vec3::Y],
+        ] {
+            for vector in [Vec3::Z].iter().chain(&triangle) {
+                for value in vector.to_array() {
+                    bytes.extend(value.to_le_bytes());
+                }
+            }
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn ascii_and_binary() -> anyhow::Result<()> {
+        for bytes in [SQUARE.as_bytes().to_vec(), binary_square()] {
+            let mesh = parse(&bytes, Lambertian::new(Color::WHITE))?;
+            assert_eq!(mesh.positions.len(), 4, "shared corners are merged");
+            assert_eq!(mesh.triangles, [[0, 1, 2], [0, 2, 3]]);
+
+            let ray = Ray::new(Vec3::new(0.25, 0.75, 2.0), Vec3::NEG_Z);
+            let hit = mesh.hit(&ray, 0.0..f32::MAX).unwrap();
+            assert_eq!(hit.distance, 2.0);
+            assert_eq!(hit.normal.as_vec3(), Vec3::Z);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let material = || Lambertian::new(Color::WHITE);
+
+        let unfinished = SQUARE.replacen("    vertex 1 0 0\n", "", 1);
+        let error = parse(unfinished.as_bytes(), material()).unwrap_err();
+        assert!(format!("{error:#}").contains("line 7"), "{error:#}");
+
+        let mut truncated = binary_square();
+        truncated.truncate(100);
+        assert!(parse(&truncated, material()).is_err());
+    }
+}
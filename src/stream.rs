@@ -0,0 +1,205 @@
+//! Send a render to another process tile by tile as it completes,
+//! so a viewer can show progress without polling files.
+//!
+//! The protocol is a sequence of frames. Integers are little-endian `u32`,
+//! pixels are linear RGBA as little-endian `f32`.
+//!
+//! - `RTIM` width height: a new image starts.
+//! - `TILE` x y width height, then width * height pixels row-major: a tile is done.
+//! - `DONE`: the image is complete.
+
+use std::io::{self, Read, Write};
+
+use anyhow::Context;
+use bevy_color::LinearRgba;
+
+use crate::{camera::Camera, hittable::Hittable, tile::Tile};
+
+const IMAGE: &[u8; 4] = b"RTIM";
+const TILE: &[u8; 4] = b"TILE";
+const DONE: &[u8; 4] = b"DONE";
+
+/// Writes frames of the streaming protocol.
+pub struct TileStream<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TileStream<W> {
+    /// Announce a new image.
+    pub fn new(mut writer: W, width: usize, height: usize) -> io::Result<Self> {
+        writer.write_all(IMAGE)?;
+        write_u32(&mut writer, width)?;
+        write_u32(&mut writer, height)?;
+
+        Ok(Self { writer })
+    }
+
+    /// Send a finished tile. Pixels are row-major within the tile.
+    pub fn send(&mut self, tile: &Tile, pixels: &[LinearRgba]) -> io::Result<()> {
+        assert_eq!(pixels.len(), tile.width * tile.height);
+
+        self.writer.write_all(TILE)?;
+        for value in [tile.x, tile.y, tile.width, tile.height] {
+            write_u32(&mut self.writer, value)?;
+        }
+
+        let mut bytes = Vec::with_capacity(pixels.len() * 16);
+        for pixel in pixels {
+            for channel in [pixel.red, pixel.green, pixel.blue, pixel.alpha] {
+                bytes.extend(channel.to_le_bytes());
+            }
+        }
+        self.writer.write_all(&bytes)?;
+
+        // Tiles are few and far between, get each one out as soon as it's done
+        self.writer.flush()
+    }
+
+    /// Mark the image as complete.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(DONE)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+fn write_u32(writer: &mut impl Write, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    writer.write_all(&value.to_le_bytes())
+}
+
+/// A decoded frame, for viewers written in Rust.
+#[derive(Debug, PartialEq)]
+pub enum Frame {
+    Image { width: usize, height: usize },
+    Tile(Tile, Vec<LinearRgba>),
+    Done,
+}
+
+/// Read the next frame. Returns `None` if the stream ended between frames.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<Frame>> {
+    let mut tag = [0; 4];
+    match reader.read_exact(&mut tag) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+
+    let frame = match &tag {
+        IMAGE => Frame::Image {
+            width: read_u32(reader)?,
+            height: read_u32(reader)?,
+        },
+        TILE => {
+            let tile = Tile {
+                x: read_u32(reader)?,
+                y: read_u32(reader)?,
+                width: read_u32(reader)?,
+                height: read_u32(reader)?,
+            };
+
+            let mut bytes = vec![0; tile.width * tile.height * 16];
+            reader.read_exact(&mut bytes)?;
+
+            let pixels = bytes
+                .chunks_exact(16)
+                .map(|pixel| {
+                    let channel = |index: usize| {
+                        f32::from_le_bytes(pixel[index * 4..index * 4 + 4].try_into().unwrap())
+                    };
+                    LinearRgba::new(channel(0), channel(1), channel(2), channel(3))
+                })
+                .collect();
+
+            Frame::Tile(tile, pixels)
+        }
+        DONE => Frame::Done,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown frame tag {tag:?}"),
+            ))
+        }
+    };
+
+    Ok(Some(frame))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<usize> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes) as usize)
+}
+
+/// Connect to a listening viewer.
+/// `host:port` connects over TCP, anything else is taken as a Unix socket path.
+pub fn connect(address: &str) -> anyhow::Result<Box<dyn Write>> {
+    if let Ok(stream) = std::net::TcpStream::connect(address) {
+        return Ok(Box::new(stream));
+    }
+
+    #[cfg(unix)]
+    {
+        let stream = std::os::unix::net::UnixStream::connect(address)
+            .with_context(|| format!("connecting to viewer at {address}"))?;
+        Ok(Box::new(stream))
+    }
+
+    #[cfg(not(unix))]
+    anyhow::bail!("could not connect to viewer at {address}")
+}
+
+/// Render tile by tile, sending each one as it completes.
+/// Returns the full image, row-major.
+pub fn render_streamed(
+    camera: &Camera,
+    world: &dyn Hittable,
+    writer: impl Write,
+    tile_size: usize,
+) -> anyhow::Result<Vec<LinearRgba>> {
+    let mut stream = TileStream::new(writer, camera.im_width, camera.im_height)?;
+    let image = camera.render_tiles(world, tile_size, |tile, pixels| stream.send(tile, pixels))?;
+    stream.finish()?;
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittables;
+
+    #[test]
+    fn roundtrip() -> anyhow::Result<()> {
+        let camera = Camera::builder().resolution(5, 3).build().unwrap();
+
+        let mut bytes = vec![];
+        let image = render_streamed(&camera, &Hittables::default(), &mut bytes, 2)?;
+
+        let mut reader = bytes.as_slice();
+        assert_eq!(
+            read_frame(&mut reader)?,
+            Some(Frame::Image {
+                width: 5,
+                height: 3
+            })
+        );
+
+        let mut received = vec![LinearRgba::NONE; 15];
+        let mut tiles = 0;
+        while let Some(Frame::Tile(tile, pixels)) = read_frame(&mut reader)? {
+            for ((row, col), pixel) in tile.pixels().zip(pixels) {
+                received[row * 5 + col] = pixel;
+            }
+            tiles += 1;
+        }
+
+        // 3 columns by 2 rows of tiles, the loop above consumed the final frame
+        assert_eq!(tiles, 6);
+        assert!(reader.is_empty());
+        assert_eq!(received, image);
+
+        Ok(())
+    }
+}
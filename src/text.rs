@@ -0,0 +1,145 @@
+//! A tiny 5x7 bitmap font, for labelling images without any font files.
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+/// Space between characters, in font pixels.
+const SPACING: usize = 1;
+
+/// Rows of 5 bits, most significant bit is the leftmost column.
+/// Letters are uppercase only, lowercase is drawn as uppercase.
+#[rustfmt::skip]
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        ' ' => [0; GLYPH_HEIGHT],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        '=' => [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '%' => [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011],
+        '_' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+/// Width and height in image pixels of `text` drawn at the given scale.
+pub fn size(text: &str, scale: usize) -> (usize, usize) {
+    let chars = text.chars().count();
+    let width = (chars * (GLYPH_WIDTH + SPACING)).saturating_sub(SPACING);
+
+    (width * scale, GLYPH_HEIGHT * scale)
+}
+
+/// Draw `text` into an RGB 8-bit image with its top left corner at (`x`, `y`).
+/// Each font pixel becomes a `scale` by `scale` square. Anything outside the image is clipped.
+pub fn draw(
+    data: &mut [u8],
+    image_width: usize,
+    x: usize,
+    y: usize,
+    text: &str,
+    scale: usize,
+    color: [u8; 3],
+) {
+    let image_height = data.len() / 3 / image_width.max(1);
+
+    for (index, c) in text.chars().enumerate() {
+        let left = x + index * (GLYPH_WIDTH + SPACING) * scale;
+
+        for (glyph_row, bits) in glyph(c).into_iter().enumerate() {
+            for glyph_col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - glyph_col)) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let col = left + glyph_col * scale + dx;
+                        let row = y + glyph_row * scale + dy;
+                        if col >= image_width || row >= image_height {
+                            continue;
+                        }
+
+                        let offset = (row * image_width + col) * 3;
+                        data[offset..offset + 3].copy_from_slice(&color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Background of [`with_footer`].
+const FOOTER_BACKGROUND: [u8; 3] = [16, 16, 16];
+const FOOTER_COLOR: [u8; 3] = [230, 230, 230];
+/// Around the text of a footer, in image pixels.
+const FOOTER_PADDING: usize = 4;
+
+/// Append a dark band below an RGB 8-bit image with `text` written in it.
+/// The text is scaled down for small images and clipped if it still doesn't fit.
+pub fn with_footer(mut data: Vec<u8>, image_width: usize, text: &str) -> Vec<u8> {
+    let fits = |scale| size(text, scale).0 + 2 * FOOTER_PADDING <= image_width;
+    let scale = if fits(2) { 2 } else { 1 };
+
+    let top = data.len() / 3 / image_width.max(1);
+    let band_height = size(text, scale).1 + 2 * FOOTER_PADDING;
+
+    data.extend(
+        FOOTER_BACKGROUND
+            .into_iter()
+            .cycle()
+            .take(image_width * band_height * 3),
+    );
+    draw(
+        &mut data,
+        image_width,
+        FOOTER_PADDING,
+        top + FOOTER_PADDING,
+        text,
+        scale,
+        FOOTER_COLOR,
+    );
+
+    data
+}
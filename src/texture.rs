@@ -0,0 +1,267 @@
+//! Colors which vary over a surface.
+
+use std::{fmt::Debug, ops::Deref, path::Path, sync::Arc};
+
+use anyhow::Context;
+use bevy_color::{Alpha, Color, LinearRgba, Srgba};
+use bevy_math::Vec3;
+
+use crate::perlin::Perlin;
+
+#[derive(Debug, Clone)]
+pub struct DynTexture(Arc<Box<dyn Texture>>);
+
+impl Deref for DynTexture {
+    type Target = dyn Texture;
+
+    fn deref(&self) -> &Self::Target {
+        &**self.0
+    }
+}
+
+impl DynTexture {
+    pub fn new(texture: impl Texture + 'static) -> Self {
+        Self(Arc::new(Box::new(texture)))
+    }
+}
+
+impl From<SolidColor> for DynTexture {
+    fn from(value: SolidColor) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Color> for DynTexture {
+    fn from(value: Color) -> Self {
+        SolidColor { color: value }.into()
+    }
+}
+
+impl From<ImageTexture> for DynTexture {
+    fn from(value: ImageTexture) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<NoiseTexture> for DynTexture {
+    fn from(value: NoiseTexture) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<LinearRgba> for DynTexture {
+    fn from(value: LinearRgba) -> Self {
+        Color::from(value).into()
+    }
+}
+
+pub trait Texture: Debug + Send + Sync {
+    /// The color at texture coordinates `u`, `v` of a surface,
+    /// which is at `point` in world space.
+    fn value(&self, u: f32, v: f32, point: Vec3) -> Color;
+
+    /// Describe any parameters which are out of range, see [`crate::scene::Scene::validate`].
+    fn validate(&self, _problems: &mut Vec<String>) {}
+}
+
+/// The same color everywhere.
+#[derive(Debug)]
+pub struct SolidColor {
+    pub color: Color,
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _point: Vec3) -> Color {
+        self.color
+    }
+}
+
+/// What a [`NoiseTexture`] does with its noise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoisePattern {
+    /// Soft blotches.
+    Smooth,
+    /// Noise over several octaves, like smoke or clouds.
+    Turbulence { octaves: usize },
+    /// Stripes along Z, bent by turbulence.
+    Marble { octaves: usize },
+}
+
+/// Gray patterns from Perlin noise, sampled at the point in space rather than by `u` and `v`,
+/// so they run through objects like the grain of a material.
+#[derive(Debug, Clone)]
+pub struct NoiseTexture {
+    pub noise: Perlin,
+    /// Higher values give finer patterns.
+    pub scale: f32,
+    pub pattern: NoisePattern,
+    /// Brightest color of the pattern.
+    pub color: Color,
+}
+
+impl NoiseTexture {
+    /// White marble.
+    pub fn marble(seed: u64, scale: f32) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            pattern: NoisePattern::Marble { octaves: 7 },
+            color: Color::WHITE,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f32, _v: f32, point: Vec3) -> Color {
+        let point = self.scale * point;
+
+        let brightness = match self.pattern {
+            NoisePattern::Smooth => 0.5 * (1.0 + self.noise.noise(point)),
+            NoisePattern::Turbulence { octaves } => self.noise.turbulence(point, octaves),
+            NoisePattern::Marble { octaves } => {
+                0.5 * (1.0 + (point.z + 10.0 * self.noise.turbulence(point, octaves)).sin())
+            }
+        };
+
+        (LinearRgba::from(self.color) * brightness.clamp(0.0, 1.0))
+            .with_alpha(1.0)
+            .into()
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !(self.scale > 0.0 && self.scale.is_finite()) {
+            problems.push(format!(
+                "noise texture: scale {} is not positive",
+                self.scale
+            ));
+        }
+    }
+}
+
+/// An image stretched over the texture coordinates, with `v` going up from the bottom row.
+#[derive(Debug)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// Row-major from the top left.
+    pixels: Vec<LinearRgba>,
+}
+
+impl ImageTexture {
+    /// Load a PNG or JPEG, assumed to be in sRGB like most images are.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .with_context(|| format!("loading texture {}", path.display()))?
+            .into_rgb8();
+
+        Ok(Self::from_srgb8(
+            image.width() as usize,
+            image.height() as usize,
+            image.as_raw(),
+        ))
+    }
+
+    /// From sRGB 8-bit RGB data, row-major from the top left.
+    pub fn from_srgb8(width: usize, height: usize, data: &[u8]) -> Self {
+        assert_eq!(data.len(), width * height * 3, "data should be RGB");
+
+        Self {
+            width,
+            height,
+            pixels: data
+                .chunks_exact(3)
+                .map(|rgb| Srgba::rgb_u8(rgb[0], rgb[1], rgb[2]).into())
+                .collect(),
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _point: Vec3) -> Color {
+        if self.pixels.is_empty() {
+            // Stands out, like a missing texture in a game
+            return Color::linear_rgb(1.0, 0.0, 1.0);
+        }
+
+        let (u, v) = (u.clamp(0.0, 1.0), 1.0 - v.clamp(0.0, 1.0));
+        let col = ((u * self.width as f32) as usize).min(self.width - 1);
+        let row = ((v * self.height as f32) as usize).min(self.height - 1);
+
+        self.pixels[row * self.width + col].into()
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if self.pixels.is_empty() {
+            problems.push("image texture: the image is empty".to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::{Dir3, Vec2};
+
+    use super::*;
+    use crate::{
+        hittable::Hit,
+        material::{Lambertian, Material},
+        ray::Ray,
+    };
+
+    /// Red increasing along u, green along v.
+    #[derive(Debug)]
+    struct Gradient;
+
+    impl Texture for Gradient {
+        fn value(&self, u: f32, v: f32, _point: Vec3) -> Color {
+            Color::linear_rgb(u, v, 0.0)
+        }
+    }
+
+    #[test]
+    fn materials_sample_at_the_hit() {
+        let material = Lambertian::new(DynTexture::new(Gradient));
+        let hit = |uv: Vec2| Hit {
+            point: Vec3::ZERO,
+            normal: Dir3::Y,
+            front_face: true,
+            distance: 1.0,
+            uv,
+            barycentric: None,
+            material: Lambertian::linear_rgb(0.0, 0.0, 0.0).into(),
+            object_id: 0,
+        };
+        let ray = Ray::new(Vec3::Y, Vec3::NEG_Y);
+
+        for uv in [Vec2::new(0.25, 0.5), Vec2::new(1.0, 0.0)] {
+            let mut rng = crate::random::sample_rng(0, 0, 0);
+            let scattering = material.scatter(&ray, &hit(uv), &mut rng).unwrap();
+            assert_eq!(
+                LinearRgba::from(scattering.attenuation),
+                LinearRgba::rgb(uv.x, uv.y, 0.0)
+            );
+            assert_eq!(material.albedo(&hit(uv)), scattering.attenuation);
+        }
+    }
+
+    #[test]
+    fn image_corners() -> anyhow::Result<()> {
+        // Red, green on top, blue, white below
+        let data = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+        let path = std::env::temp_dir().join("rt_one_texture.png");
+        crate::output::write_pathlike(2, data, &path, None)?;
+
+        let texture = ImageTexture::load(&path)?;
+        let at = |u, v| LinearRgba::from(texture.value(u, v, Vec3::ZERO));
+
+        assert_eq!(at(0.0, 1.0), LinearRgba::RED);
+        assert_eq!(at(1.0, 1.0), LinearRgba::GREEN);
+        assert_eq!(at(0.2, 0.3), LinearRgba::BLUE);
+        assert_eq!(at(1.0, 0.0), LinearRgba::WHITE);
+        // Outside the image is clamped to the edge
+        assert_eq!(at(-1.0, 2.0), LinearRgba::RED);
+
+        Ok(())
+    }
+}
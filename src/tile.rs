@@ -0,0 +1,38 @@
+/// A rectangle of pixels within an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    /// Column of the top left pixel.
+    pub x: usize,
+    /// Row of the top left pixel.
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// Cover an image with tiles of at most `size` by `size` pixels, row by row from the top left.
+    /// Tiles along the right and bottom edges are cut to fit.
+    pub fn grid(image_width: usize, image_height: usize, size: usize) -> Vec<Tile> {
+        let size = size.max(1);
+        let mut tiles = vec![];
+
+        for y in (0..image_height).step_by(size) {
+            for x in (0..image_width).step_by(size) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: size.min(image_width - x),
+                    height: size.min(image_height - y),
+                });
+            }
+        }
+
+        tiles
+    }
+
+    /// Each (row, column) in the tile, row-major.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |row| (self.x..self.x + self.width).map(move |col| (row, col)))
+    }
+}
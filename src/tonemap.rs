@@ -0,0 +1,128 @@
+//! Squeezing high dynamic range radiance into what a display shows,
+//! instead of clipping everything brighter than white.
+
+use std::str::FromStr;
+
+use anyhow::bail;
+use bevy_color::{LinearRgba, Luminance};
+use bevy_math::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// How radiance is brought into the range of a display before it is quantized.
+/// The results are still linear, the transfer function comes after.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tonemap {
+    /// Clip at white, as the book does.
+    #[default]
+    None,
+    /// Divides by one plus the luminance, which keeps hues.
+    /// Grays never quite reach white, very saturated highlights may still clip.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve: contrasty, highlights desaturate to white.
+    Aces,
+    /// Hable's curve from Uncharted 2, with a toe lifting the shadows and a long shoulder.
+    Filmic,
+}
+
+impl FromStr for Tonemap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Tonemap::None),
+            "reinhard" => Ok(Tonemap::Reinhard),
+            "aces" => Ok(Tonemap::Aces),
+            "filmic" => Ok(Tonemap::Filmic),
+            other => bail!("unknown tonemap {other:?}, expected none, reinhard, aces or filmic"),
+        }
+    }
+}
+
+impl Tonemap {
+    /// Map linear radiance to linear display values, mostly within `[0, 1]`.
+    pub fn apply(self, color: LinearRgba) -> LinearRgba {
+        let rgb = Vec3::new(color.red, color.green, color.blue).max(Vec3::ZERO);
+
+        let mapped = match self {
+            Tonemap::None => return color,
+            Tonemap::Reinhard => rgb / (1.0 + color.luminance().max(0.0)),
+            Tonemap::Aces => {
+                // Narkowicz scales by 0.6 to match the reference's exposure
+                let x = rgb * 0.6;
+                (x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14))
+                    .clamp(Vec3::ZERO, Vec3::ONE)
+            }
+            Tonemap::Filmic => {
+                const WHITE: f32 = 11.2;
+                const EXPOSURE_BIAS: f32 = 2.0;
+                (hable(rgb * EXPOSURE_BIAS) / hable(Vec3::splat(WHITE))).min(Vec3::ONE)
+            }
+        };
+
+        LinearRgba::new(mapped.x, mapped.y, mapped.z, color.alpha)
+    }
+}
+
+fn hable(x: Vec3) -> Vec3 {
+    const A: f32 = 0.15; // Shoulder strength
+    const B: f32 = 0.50; // Linear strength
+    const C: f32 = 0.10; // Linear angle
+    const D: f32 = 0.20; // Toe strength
+    const E: f32 = 0.02; // Toe numerator
+    const F: f32 = 0.30; // Toe denominator
+
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+
+    #[test]
+    fn highlights_are_compressed() {
+        let gray = |value| LinearRgba::rgb(value, value, value);
+
+        assert_eq!(Tonemap::None.apply(gray(4.0)), gray(4.0));
+
+        for tonemap in [Tonemap::Reinhard, Tonemap::Aces, Tonemap::Filmic] {
+            assert!(tonemap.apply(gray(0.0)).red.abs() < 1e-3, "{tonemap:?}");
+
+            // Brighter never gets darker, and even the sun fits on the display
+            let values: Vec<f32> = [0.01, 0.1, 0.5, 1.0, 4.0, 16.0, 1000.0]
+                .map(|value| tonemap.apply(gray(value)).red)
+                .to_vec();
+            assert!(
+                values.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{tonemap:?} {values:?}"
+            );
+            assert!(
+                values.iter().all(|&value| value <= 1.0),
+                "{tonemap:?} {values:?}"
+            );
+        }
+
+        // Reinhard keeps the hue
+        let orange = Tonemap::Reinhard.apply(LinearRgba::rgb(8.0, 4.0, 0.0));
+        assert!((orange.red / orange.green - 2.0).abs() < 1e-5, "{orange:?}");
+    }
+
+    #[test]
+    fn quantizing_exposes_then_tonemaps() {
+        let mut camera = Camera::new();
+        let bright = LinearRgba::rgb(3.0, 2.0, 1.0);
+        assert_eq!(camera.to_rgb8(bright), [255, 255, 255]);
+
+        camera.exposure_compensation = -2.0;
+        assert_eq!(camera.to_rgb8(bright), [191, 128, 64]);
+
+        // No longer clipped, and still orange
+        camera.exposure_compensation = 0.0;
+        camera.tonemap = Tonemap::Reinhard;
+        let [red, green, blue] = camera.to_rgb8(bright);
+        assert!(
+            red < 255 && green < red && blue < green,
+            "{red} {green} {blue}"
+        );
+    }
+}
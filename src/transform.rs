@@ -0,0 +1,374 @@
+//! Placing objects by moving the rays instead of the geometry.
+//!
+//! Wrap an `Arc` of an object to place the same geometry several times,
+//! e.g. one mesh as a whole forest of trees.
+
+use std::ops::Range;
+
+use bevy_math::{Affine3A, Dir3, Mat3, Quat, Vec3};
+use rand::RngCore;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{Hit, Hittable},
+    ray::Ray,
+    stats::SceneStats,
+};
+
+/// An object moved by an offset.
+#[derive(Debug)]
+pub struct Translate<H> {
+    pub object: H,
+    pub offset: Vec3,
+}
+
+impl<H: Hittable> Translate<H> {
+    pub fn new(object: H, offset: Vec3) -> Self {
+        Self { object, offset }
+    }
+}
+
+impl<H: Hittable> Hittable for Translate<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        let moved = Ray::new(ray.origin() - self.offset, ray.direction().as_vec3())
+            .with_kind(ray.kind())
+            .with_time(ray.time());
+
+        let mut hit = self.object.hit(&moved, t_range)?;
+        hit.point += self.offset;
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let aabb = self.object.bounding_box();
+        Aabb {
+            min: aabb.min + self.offset,
+            max: aabb.max + self.offset,
+        }
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !self.offset.is_finite() {
+            problems.push(format!("translate: offset {} is not finite", self.offset));
+        }
+        self.object.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        self.object.stats(stats);
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Dir3) -> f32 {
+        self.object.pdf_value(origin - self.offset, direction)
+    }
+
+    fn random_direction(&self, origin: Vec3, rng: &mut dyn RngCore) -> Dir3 {
+        self.object.random_direction(origin - self.offset, rng)
+    }
+}
+
+/// An object turned around the Y axis through the origin.
+/// Combine with [`Translate`] to turn it around its own center.
+#[derive(Debug)]
+pub struct RotateY<H> {
+    pub object: H,
+    rotation: Quat,
+    bounds: Aabb,
+}
+
+impl<H: Hittable> RotateY<H> {
+    /// Turns counter-clockwise seen from above.
+    pub fn new(object: H, degrees: f32) -> Self {
+        let rotation = Quat::from_rotation_y(degrees.to_radians());
+        let bounds = transformed_bounds(object.bounding_box(), Affine3A::from_quat(rotation));
+
+        Self {
+            object,
+            rotation,
+            bounds,
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for RotateY<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        let inverse = self.rotation.inverse();
+        let turned = Ray::new(inverse * ray.origin(), inverse * ray.direction().as_vec3())
+            .with_kind(ray.kind())
+            .with_time(ray.time());
+
+        let mut hit = self.object.hit(&turned, t_range)?;
+        hit.point = self.rotation * hit.point;
+        hit.normal = Dir3::new_unchecked(self.rotation * hit.normal.as_vec3());
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if !self.rotation.is_finite() {
+            problems.push("rotate: the angle is not finite".to_string());
+        }
+        self.object.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        self.object.stats(stats);
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Dir3) -> f32 {
+        let inverse = self.rotation.inverse();
+        self.object.pdf_value(inverse * origin, inverse * direction)
+    }
+
+    fn random_direction(&self, origin: Vec3, rng: &mut dyn RngCore) -> Dir3 {
+        let local = self
+            .object
+            .random_direction(self.rotation.inverse() * origin, rng);
+        self.rotation * local
+    }
+}
+
+/// An object moved, turned, scaled or sheared by any affine transform.
+///
+/// Rays are brought into the object's space by the inverse, which is kept
+/// so it isn't recomputed for every ray.
+#[derive(Debug)]
+pub struct Transformed<H> {
+    pub object: H,
+    transform: Affine3A,
+    inverse: Affine3A,
+    /// Keeps normals perpendicular to the surface under non-uniform scaling.
+    normal_matrix: Mat3,
+    bounds: Aabb,
+}
+
+impl<H: Hittable> Transformed<H> {
+    pub fn new(object: H, transform: Affine3A) -> Self {
+        let inverse = transform.inverse();
+        let normal_matrix = Mat3::from(inverse.matrix3).transpose();
+        let bounds = transformed_bounds(object.bounding_box(), transform);
+
+        Self {
+            object,
+            transform,
+            inverse,
+            normal_matrix,
+            bounds,
+        }
+    }
+
+    pub fn transform(&self) -> Affine3A {
+        self.transform
+    }
+}
+
+impl<H: Hittable> Hittable for Transformed<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        let origin = self.inverse.transform_point3(ray.origin());
+        let direction = self.inverse.transform_vector3(ray.direction().as_vec3());
+
+        // Scaling stretches the direction, so distances along it differ between the spaces
+        let stretch = direction.length();
+        if !(stretch.is_finite() && stretch > 0.0) {
+            return None;
+        }
+        let local = Ray::new(origin, direction)
+            .with_kind(ray.kind())
+            .with_time(ray.time());
+        let local_range = t_range.start * stretch..t_range.end * stretch;
+
+        let mut hit = self.object.hit(&local, local_range)?;
+        hit.point = self.transform.transform_point3(hit.point);
+        hit.normal = Dir3::new(self.normal_matrix * hit.normal.as_vec3()).ok()?;
+        hit.distance /= stretch;
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        let determinant = self.transform.matrix3.determinant();
+        if !self.transform.is_finite() || determinant == 0.0 || !determinant.is_finite() {
+            problems.push(format!(
+                "transformed: {:?} can't be inverted",
+                self.transform
+            ));
+        }
+        self.object.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        self.object.stats(stats);
+    }
+
+    /// The object's density, scaled by how much the transform spreads out directions around
+    /// this one: a linear map `M` of directions changes solid angles by `|det M| / |M d|³`.
+    fn pdf_value(&self, origin: Vec3, direction: Dir3) -> f32 {
+        let local = self.inverse.transform_vector3(direction.as_vec3());
+        let Ok(local_direction) = Dir3::new(local) else {
+            return 0.0;
+        };
+
+        let jacobian = self.inverse.matrix3.determinant().abs() / local.length().powi(3);
+        let pdf = self
+            .object
+            .pdf_value(self.inverse.transform_point3(origin), local_direction);
+        pdf * jacobian
+    }
+
+    fn random_direction(&self, origin: Vec3, rng: &mut dyn RngCore) -> Dir3 {
+        let local = self
+            .object
+            .random_direction(self.inverse.transform_point3(origin), rng);
+        Dir3::new(self.transform.transform_vector3(local.as_vec3())).unwrap_or(local)
+    }
+}
+
+/// A box around all corners of `aabb` once transformed.
+fn transformed_bounds(aabb: Aabb, transform: Affine3A) -> Aabb {
+    if aabb.is_empty() {
+        return aabb;
+    }
+
+    Aabb::from_points((0..8).map(|corner| {
+        let pick = |bit: usize, min: f32, max: f32| if corner & bit == 0 { min } else { max };
+        transform.transform_point3(Vec3::new(
+            pick(1, aabb.min.x, aabb.max.x),
+            pick(2, aabb.min.y, aabb.max.y),
+            pick(4, aabb.min.z, aabb.max.z),
+        ))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        hittable::Hittables,
+        material::Lambertian,
+        objects::{Cuboid, Sphere},
+    };
+
+    #[test]
+    fn instances_share_geometry() {
+        let unit_box = Arc::new(Cuboid::new(
+            Vec3::ZERO,
+            Vec3::ONE,
+            Lambertian::linear_rgb(0.5, 0.5, 0.5),
+        ));
+
+        let mut world = Hittables::default();
+        world.add(Translate::new(
+            Arc::clone(&unit_box),
+            Vec3::new(5.0, 0.0, 0.0),
+        ));
+        world.add(Translate::new(
+            RotateY::new(Arc::clone(&unit_box), 45.0),
+            Vec3::new(-5.0, 0.0, 0.0),
+        ));
+        assert_eq!(Arc::strong_count(&unit_box), 3);
+
+        // The moved box, hit on its near face
+        let ray = Ray::new(Vec3::new(5.5, 0.5, 10.0), Vec3::NEG_Z);
+        let hit = world.hit(&ray, 0.0..f32::MAX).unwrap();
+        assert!((hit.distance - 9.0).abs() < 1e-5, "{}", hit.distance);
+        assert!(hit.point.abs_diff_eq(Vec3::new(5.5, 0.5, 1.0), 1e-5));
+        assert!(hit.normal.abs_diff_eq(Vec3::Z, 1e-5));
+
+        // Turned, seen from above the box is a diamond with its face once at x = 0
+        // now running from its corner at the origin up to (0.71, 0.71)
+        let ray = Ray::new(Vec3::new(-4.5, 0.5, 10.0), Vec3::NEG_Z);
+        let hit = world.hit(&ray, 0.0..f32::MAX).unwrap();
+        assert!((hit.distance - 9.5).abs() < 1e-4, "{}", hit.distance);
+        let expected = Vec3::new(-1.0, 0.0, 1.0).normalize();
+        assert!(hit.normal.abs_diff_eq(expected, 1e-4), "{:?}", hit.normal);
+
+        let bounds = world.objects[1].bounding_box();
+        let half_diagonal = 2f32.sqrt() / 2.0;
+        assert!((bounds.max.z - half_diagonal).abs() < 1e-5);
+        assert!((bounds.max.x - (-5.0 + 2.0 * half_diagonal)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn stretched_sphere() {
+        // x^2 / 4 + y^2 + z^2 = 1, moved up by 3
+        let ellipsoid = Transformed::new(
+            Sphere {
+                center: Vec3::ZERO,
+                radius: 1.0,
+                material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+            },
+            Affine3A::from_translation(Vec3::new(0.0, 3.0, 0.0))
+                * Affine3A::from_scale(Vec3::new(2.0, 1.0, 1.0)),
+        );
+
+        let ray = Ray::new(Vec3::new(10.0, 3.0, 0.0), Vec3::NEG_X);
+        let hit = ellipsoid.hit(&ray, 0.0..f32::MAX).unwrap();
+        assert!((hit.distance - 8.0).abs() < 1e-4, "{}", hit.distance);
+        assert!(hit.point.abs_diff_eq(Vec3::new(2.0, 3.0, 0.0), 1e-4));
+        assert!(hit.normal.abs_diff_eq(Vec3::X, 1e-4));
+
+        // Ranges are in world distances too
+        assert!(ellipsoid.hit(&ray, 0.0..7.9).is_none());
+
+        // Off the axes the normal follows the squashed surface, not the stretched sphere's
+        let x = 2f32.sqrt();
+        let y = 0.5f32.sqrt();
+        let ray = Ray::new(Vec3::new(x, 10.0, 0.0), Vec3::NEG_Y);
+        let hit = ellipsoid.hit(&ray, 0.0..f32::MAX).unwrap();
+        assert!((hit.distance - (7.0 - y)).abs() < 1e-4, "{}", hit.distance);
+        let expected = Vec3::new(x / 4.0, y, 0.0).normalize();
+        assert!(hit.normal.abs_diff_eq(expected, 1e-4), "{:?}", hit.normal);
+
+        let bounds = ellipsoid.bounding_box();
+        assert!(bounds.min.abs_diff_eq(Vec3::new(-2.0, 2.0, -1.0), 1e-5));
+        assert!(bounds.max.abs_diff_eq(Vec3::new(2.0, 4.0, 1.0), 1e-5));
+
+        let mut problems = vec![];
+        Transformed::new(ellipsoid, Affine3A::from_scale(Vec3::ZERO)).validate(&mut problems);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+    }
+    #[test]
+    fn stretched_light_sampling() {
+        use rand::{rngs::SmallRng, SeedableRng};
+
+        // Stretched, turned and moved, so the density changes differently in every direction
+        let ellipsoid = Transformed::new(
+            Sphere {
+                center: Vec3::ZERO,
+                radius: 1.0,
+                material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+            },
+            Affine3A::from_translation(Vec3::new(0.0, 3.0, 0.0))
+                * Affine3A::from_rotation_z(0.5)
+                * Affine3A::from_scale(Vec3::new(2.0, 1.0, 0.5)),
+        );
+        let origin = Vec3::new(1.0, 0.0, 1.0);
+        let mut rng = SmallRng::seed_from_u64(5);
+
+        // Uniform directions over the whole sphere have a density of 1 / 4π
+        let count = 200_000;
+        let integral = (0..count)
+            .map(|_| ellipsoid.pdf_value(origin, crate::random::random_on_sphere(&mut rng)))
+            .sum::<f32>()
+            * 2.0
+            * std::f32::consts::TAU
+            / count as f32;
+        assert!((integral - 1.0).abs() < 0.03, "{integral}");
+
+        for _ in 0..100 {
+            let direction = ellipsoid.random_direction(origin, &mut rng);
+            assert!(ellipsoid
+                .hit(&Ray::new(origin, *direction), 0.0..f32::MAX)
+                .is_some());
+            assert!(ellipsoid.pdf_value(origin, direction) > 0.0);
+        }
+    }
+}
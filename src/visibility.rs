@@ -0,0 +1,87 @@
+//! Hiding objects from some kinds of rays, e.g. a light blocker the camera can't see.
+
+use std::ops::Range;
+
+use bevy_math::{Dir3, Vec3};
+use rand::RngCore;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{Hit, Hittable},
+    ray::{Ray, RayMask},
+    stats::SceneStats,
+};
+
+/// An object which only some kinds of rays can hit.
+#[derive(Debug)]
+pub struct Visible<H> {
+    pub object: H,
+    /// The kinds of rays which see the object.
+    /// E.g. [`RayMask::SHADOW`] alone makes a light blocker the camera can't see.
+    pub visibility: RayMask,
+}
+
+impl<H: Hittable> Visible<H> {
+    pub fn new(object: H, visibility: RayMask) -> Self {
+        Self { object, visibility }
+    }
+}
+
+impl<H: Hittable> Hittable for Visible<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<f32>) -> Option<Hit> {
+        if !self.visibility.contains(ray.kind()) {
+            return None;
+        }
+        self.object.hit(ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if self.visibility == RayMask::NONE {
+            problems.push("object: invisible to every kind of ray".into());
+        }
+        self.object.validate(problems);
+    }
+
+    fn stats(&self, stats: &mut SceneStats) {
+        self.object.stats(stats);
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Dir3) -> f32 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random_direction(&self, origin: Vec3, rng: &mut dyn RngCore) -> Dir3 {
+        self.object.random_direction(origin, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, objects::Sphere, ray::RayKind};
+
+    #[test]
+    fn hidden_from_some_rays() {
+        let blocker = Visible::new(
+            Sphere {
+                center: Vec3::new(0.0, 0.0, -2.0),
+                radius: 0.5,
+                material: Lambertian::linear_rgb(0.5, 0.5, 0.5).into(),
+            },
+            RayMask::ALL.without(RayMask::CAMERA),
+        );
+
+        let ray = || Ray::new(Vec3::ZERO, Vec3::NEG_Z);
+        assert!(blocker.hit(&ray(), 0.0..10.0).is_none());
+        assert!(blocker
+            .hit(&ray().with_kind(RayKind::Shadow), 0.0..10.0)
+            .is_some());
+        assert!(blocker
+            .hit(&ray().with_kind(RayKind::Specular), 0.0..10.0)
+            .is_some());
+    }
+}